@@ -52,23 +52,42 @@ async function getBalance(tokenAccount) {
 
 // ----------------------------------------------------------------------------- escrow acc
 
+// the first byte of every account the program owns says what's in it, see AccountType in state.rs
+const ESCROW_ACCOUNT_TYPE = 2;
+
 //lhs names can be anything we want - but rhs have to exactly match names given in rust, or will deserialize as "undefined"
 class EscrowAccount {
   // the reason you need these fields here is so that you're able to calc ESCROW_ACC_SIZE. For serialization, in other words.
   // they won't have any impact on deserialization, as deserialization overwrites them.
-  is_initialized = 1;
+  account_type = ESCROW_ACCOUNT_TYPE;
   initializer_pubkey = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
   temp_token_account_pubkey = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
   initializer_token_to_receive_account_pubkey = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
   expected_amount = 0;
+  mode = 0; // 0 = Vault (temp account handed to the PDA), 1 = Approve (PDA is a delegate), 2 = Htlc, 3 = CoveredCall, 4 = DutchAuction, 5 = EnglishAuction, 6 = SealedAuction, 7 = FlashSettling
+  offered_amount = 0;
+  rent_payer = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+  recipient_token_account_pubkey = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+  request = 0; // 0 = Tokens (expected_amount of Y), 1 = Mint (that one NFT), 2 = Collection (any NFT of a verified collection)
+  request_key = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+  royalties = 0;
+  authority_pubkey = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]; // a multisig account whose signers act for the initializer, all zeroes if there's none
 
   constructor(fields) {
     if (fields) {
-      this.is_initialized = fields.is_initialized;
+      this.account_type = fields.account_type;
       this.initializer_pubkey = fields.initializer_pubkey;
       this.temp_token_account_pubkey = fields.temp_token_account_pubkey;
       this.initializer_token_to_receive_account_pubkey = fields.initializer_token_to_receive_account_pubkey;
       this.expected_amount = fields.expected_amount;
+      this.mode = fields.mode;
+      this.offered_amount = fields.offered_amount;
+      this.rent_payer = fields.rent_payer;
+      this.recipient_token_account_pubkey = fields.recipient_token_account_pubkey;
+      this.request = fields.request;
+      this.request_key = fields.request_key;
+      this.royalties = fields.royalties;
+      this.authority_pubkey = fields.authority_pubkey;
     }
   }
 }
@@ -77,15 +96,24 @@ const EscrowSchema = new Map([
   [EscrowAccount, {
     kind: 'struct',
     fields: [
-      ['is_initialized', 'u8'], //important: borsh doesn't have the concept of a boolean, so use a u8
+      ['account_type', 'u8'], // 0 until it's initialized, then ESCROW_ACCOUNT_TYPE
       ['initializer_pubkey', [32]], //important: Pubkey in rust = array of 32 u8 bytes here. NOT string.
       ['temp_token_account_pubkey', [32]],
       ['initializer_token_to_receive_account_pubkey', [32]],
       ['expected_amount', 'u64'],
+      ['mode', 'u8'],
+      ['offered_amount', 'u64'],
+      ['rent_payer', [32]],
+      ['recipient_token_account_pubkey', [32]],
+      ['request', 'u8'],
+      ['request_key', [32]],
+      ['royalties', 'u8'],
+      ['authority_pubkey', [32]],
     ]
   }]
 ]);
 
+// just the header, which is all a Vault mode escrow has. Other modes have their terms after it
 const ESCROW_ACC_SIZE = borsh.serialize(
   EscrowSchema,
   new EscrowAccount(),
@@ -101,12 +129,18 @@ async function getEscrowInfo(escrow_account) {
   const deserEscrow = borsh.deserialize(
     EscrowSchema,
     EscrowAccount,
-    programInfo.data,
+    programInfo.data.slice(0, ESCROW_ACC_SIZE),
   )
   // console.log(deserEscrow);
   return deserEscrow;
 }
 
+// the mint of a token account
+async function getMint(tokenAccountPubkey) {
+  const tokenAccountInfo = await CONNECTION.getParsedAccountInfo(tokenAccountPubkey);
+  return new PublicKey(tokenAccountInfo.value.data.parsed.info.mint);
+}
+
 // ----------------------------------------------------------------------------- init escrow
 
 async function initEscrow(
@@ -168,6 +202,7 @@ async function initEscrow(
 
   // 5. initialize empty account as escrow state and transfer temporary X token account ownership to PDA
   const initializerYTokenAccountPubkey = new PublicKey(initializerYTokenAccountPubkeyString);
+  const YTokenMintAccountPubkey = await getMint(initializerYTokenAccountPubkey);
   const InitEscrowIx = new TransactionInstruction({
     programId: escrowProgramId,
     keys: [
@@ -177,6 +212,8 @@ async function initEscrow(
       {pubkey: escrowAccount.publicKey, isSigner: false, isWritable: true},
       {pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false},
       {pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false},
+      {pubkey: XTokenMintAccountPubkey, isSigner: false, isWritable: false},
+      {pubkey: YTokenMintAccountPubkey, isSigner: false, isWritable: false},
     ],
 
     // we specify what will arrive as instruction data
@@ -233,6 +270,10 @@ async function takeTrade(
   const programId = new PublicKey(escrowProgamIdString);
   const pdaAccount = await PublicKey.findProgramAddress([Buffer.from("escrow")], programId);
 
+  // transfers are checked against the mints, so the program needs both
+  const XTokenMintPubkey = await getMint(tempXTokenPubKey);
+  const YTokenMintPubkey = await getMint(initializerYTokenPubkey);
+
   const takeTradeIx = new TransactionInstruction({
     programId, //escrow program id - what's interesting is that when we "build" a tx we don't actually send it to one place - instead this acts as a guide as to which program should execute which instruction
     keys: [
@@ -254,6 +295,12 @@ async function takeTrade(
       {pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false},
       // 8. `[]` The PDA account
       {pubkey: pdaAccount[0], isSigner: false, isWritable: false},
+      // 9. `[writable]` The X mint
+      {pubkey: XTokenMintPubkey, isSigner: false, isWritable: true},
+      // 10. `[]` The Y mint
+      {pubkey: YTokenMintPubkey, isSigner: false, isWritable: false},
+      // 11. `[]` The token program of the Y leg
+      {pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false},
     ],
     data: Buffer.from(Uint8Array.of(1, ...new BN(takerExpectedXAmount).toArray("le", 8)))
   })
@@ -312,6 +359,8 @@ async function cancelEscrow(
       {pubkey: escrowAcc, isSigner: false, isWritable: true},
       /// 5 [] pda acc
       {pubkey: pdaAccount[0], isSigner: false, isWritable: false},
+      /// 6 [writable] X mint
+      {pubkey: await getMint(tempXAcc), isSigner: false, isWritable: true},
     ],
     data: Buffer.from(finalBuffer)
     // data: Buffer.from(Uint8Array.of(1, ...new BN(123).toArray("le", 8)))
@@ -368,7 +417,7 @@ async function getTokenAccount(
 // ----------------------------------------------------------------------------- exports
 
 module.exports = {
-  CONNECTION, connect, getTokenBalance, getBalance, getEscrowInfo,
+  CONNECTION, ESCROW_ACCOUNT_TYPE, connect, getTokenBalance, getBalance, getEscrowInfo,
   initEscrow, takeTrade, cancelEscrow, getTokenAccount
 }
 
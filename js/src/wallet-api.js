@@ -45,6 +45,10 @@ async function takeTradeSigned(
   const programId = new PublicKey(escrowProgramIdString);
   const pdaAccount = await PublicKey.findProgramAddress([Buffer.from("escrow")], programId);
 
+  // transfers are checked against the mints, so the program needs both
+  const XTokenMintPubkey = new PublicKey((await CONNECTION.getParsedAccountInfo(tempXTokenPubKey)).value.data.parsed.info.mint);
+  const YTokenMintPubkey = new PublicKey((await CONNECTION.getParsedAccountInfo(initializerYTokenPubkey)).value.data.parsed.info.mint);
+
   const takeTradeIx = new TransactionInstruction({
     programId,
     keys: [
@@ -66,6 +70,12 @@ async function takeTradeSigned(
       {pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false},
       // 8. `[]` The PDA account
       {pubkey: pdaAccount[0], isSigner: false, isWritable: false},
+      // 9. `[writable]` The X mint
+      {pubkey: XTokenMintPubkey, isSigner: false, isWritable: true},
+      // 10. `[]` The Y mint
+      {pubkey: YTokenMintPubkey, isSigner: false, isWritable: false},
+      // 11. `[]` The token program of the Y leg
+      {pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false},
     ],
     data: Buffer.from(Uint8Array.of(1, ...new BN(takerExpectedXAmount).toArray("le", 8)))
   })
@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// the scenarios live next to the integration tests so `cargo test` runs them too
+#[path = "../../tests/common/mod.rs"]
+mod common;
+
+fuzz_target!(|data: &[u8]| {
+    common::fuzz::unpack_instruction(data);
+});
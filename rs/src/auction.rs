@@ -0,0 +1,82 @@
+// price curves for the auction modes. All the math is done in u128 so the intermediate products can't overflow,
+// and checked anyway - a price the program can't compute is an error, not a wrapped number
+use solana_program::{
+    entrypoint::ProgramResult, hash::hashv, program_error::ProgramError, pubkey::Pubkey,
+};
+
+use crate::{
+    error::EscrowError,
+    state::{DutchTerms, SealedTerms},
+};
+
+// past 64 halvings a u64 excess is gone anyway
+pub const MAX_DECAY: u8 = 64;
+
+/// Rejects a curve that goes up, ends before it starts or decays further than a u64 can
+pub fn check_dutch_curve(
+    start_price: u64,
+    floor_price: u64,
+    start: i64,
+    end: i64,
+    decay: u8,
+) -> ProgramResult {
+    if start_price < floor_price || end <= start || decay > MAX_DECAY {
+        return Err(EscrowError::InvalidPriceCurve.into());
+    }
+    Ok(())
+}
+
+/// What a Dutch auction asks at `now`: `start_price` until `auction_start`, the floor (the escrow's `expected_amount`)
+/// from `auction_end` on, and in between either a straight line (decay 0) or the excess over the floor halving at
+/// `decay` even intervals, with a straight line within each interval and the last one ending on the floor
+pub fn dutch_price(terms: &DutchTerms, floor: u64, now: i64) -> Result<u64, ProgramError> {
+    if now <= terms.auction_start {
+        return Ok(terms.start_price);
+    }
+    if now >= terms.auction_end {
+        return Ok(floor);
+    }
+
+    let overflow = || ProgramError::from(EscrowError::AmountOverflow);
+    let excess = terms.start_price.checked_sub(floor).ok_or(overflow())? as u128;
+    // both positive and at most 2^64 apart, given the checks above
+    let elapsed = (now as i128 - terms.auction_start as i128) as u128;
+    let duration = (terms.auction_end as i128 - terms.auction_start as i128) as u128;
+
+    let remaining = if terms.decay == 0 {
+        // excess * (duration - elapsed) / duration
+        excess.checked_mul(duration - elapsed).ok_or(overflow())? / duration
+    } else {
+        // how many halvings are behind us, and how far we are into the next one (as a fraction of the duration)
+        let scaled = elapsed.checked_mul(terms.decay as u128).ok_or(overflow())?;
+        let halvings = (scaled / duration) as u32;
+        let into_next = scaled % duration;
+        let high = excess.checked_shr(halvings).unwrap_or(0);
+        // the last interval runs all the way down to the floor, so there's no jump at the end
+        let low = if halvings + 1 >= terms.decay as u32 {
+            0
+        } else {
+            excess.checked_shr(halvings + 1).unwrap_or(0)
+        };
+        high - (high - low).checked_mul(into_next).ok_or(overflow())? / duration
+    };
+
+    // remaining <= excess, so this fits back into a u64
+    Ok(floor + remaining as u64)
+}
+
+/// What a sealed bid commits to. The bidder is part of it so nobody can copy someone else's commitment
+/// and reveal it alongside them
+pub fn bid_commitment(amount: u64, salt: &[u8; 32], bidder: &Pubkey) -> [u8; 32] {
+    hashv(&[&amount.to_le_bytes(), salt, bidder.as_ref()]).to_bytes()
+}
+
+/// What the winner of a sealed-bid auction pays: their own bid, or in a second-price auction the best bid they beat,
+/// but never less than the reserve (the escrow's `expected_amount`)
+pub fn sealed_price(terms: &SealedTerms, reserve: u64) -> u64 {
+    if terms.second_price {
+        terms.second_bid.max(reserve)
+    } else {
+        terms.auction.highest_bid
+    }
+}
@@ -15,7 +15,148 @@ pub enum EscrowError {
     ExpectedAmountMismatch,
     /// AmountOverflow
     #[error("AmountOverflow")]
-    AmountOverflow
+    AmountOverflow,
+    /// UnsupportedMintExtension
+    #[error("UnsupportedMintExtension")]
+    UnsupportedMintExtension,
+    /// TempAccountHasDelegate
+    #[error("TempAccountHasDelegate")]
+    TempAccountHasDelegate,
+    /// TempAccountHasCloseAuthority
+    #[error("TempAccountHasCloseAuthority")]
+    TempAccountHasCloseAuthority,
+    /// TempAccountFrozen
+    #[error("TempAccountFrozen")]
+    TempAccountFrozen,
+    /// TempAccountIsNative
+    #[error("TempAccountIsNative")]
+    TempAccountIsNative,
+    /// TempAccountEmpty
+    #[error("TempAccountEmpty")]
+    TempAccountEmpty,
+    /// WrongEscrowMode
+    #[error("WrongEscrowMode")]
+    WrongEscrowMode,
+    /// HashlockMismatch
+    #[error("HashlockMismatch")]
+    HashlockMismatch,
+    /// TimelockNotExpired
+    #[error("TimelockNotExpired")]
+    TimelockNotExpired,
+    /// TimelockInThePast
+    #[error("TimelockInThePast")]
+    TimelockInThePast,
+    /// OptionExpired
+    #[error("OptionExpired")]
+    OptionExpired,
+    /// OptionAlreadySold
+    #[error("OptionAlreadySold")]
+    OptionAlreadySold,
+    /// NotOptionHolder
+    #[error("NotOptionHolder")]
+    NotOptionHolder,
+    /// LoanAlreadyFunded
+    #[error("LoanAlreadyFunded")]
+    LoanAlreadyFunded,
+    /// LoanNotFunded
+    #[error("LoanNotFunded")]
+    LoanNotFunded,
+    /// LoanOverdue
+    #[error("LoanOverdue")]
+    LoanOverdue,
+    /// InvalidPriceCurve
+    #[error("InvalidPriceCurve")]
+    InvalidPriceCurve,
+    /// PriceAboveMaximum
+    #[error("PriceAboveMaximum")]
+    PriceAboveMaximum,
+    /// BidTooLow
+    #[error("BidTooLow")]
+    BidTooLow,
+    /// AuctionEnded
+    #[error("AuctionEnded")]
+    AuctionEnded,
+    /// WrongAuctionPhase
+    #[error("WrongAuctionPhase")]
+    WrongAuctionPhase,
+    /// CommitmentMismatch
+    #[error("CommitmentMismatch")]
+    CommitmentMismatch,
+    /// EscrowsDontCross
+    #[error("EscrowsDontCross")]
+    EscrowsDontCross,
+    /// TargetNotFilled
+    #[error("TargetNotFilled")]
+    TargetNotFilled,
+    /// InvalidSignature
+    #[error("InvalidSignature")]
+    InvalidSignature,
+    /// OrderExpired
+    #[error("OrderExpired")]
+    OrderExpired,
+    /// NonceAlreadyUsed
+    #[error("NonceAlreadyUsed")]
+    NonceAlreadyUsed,
+    /// FlashNotSettled
+    #[error("FlashNotSettled")]
+    FlashNotSettled,
+    /// InvalidProof
+    #[error("InvalidProof")]
+    InvalidProof,
+    /// AlreadyClaimed
+    #[error("AlreadyClaimed")]
+    AlreadyClaimed,
+    /// DistributionEnded
+    #[error("DistributionEnded")]
+    DistributionEnded,
+    /// NotAnNft
+    #[error("NotAnNft")]
+    NotAnNft,
+    /// NftNotRequested
+    #[error("NftNotRequested")]
+    NftNotRequested,
+    /// CreatorAccountMismatch
+    #[error("CreatorAccountMismatch")]
+    CreatorAccountMismatch,
+    /// InvalidSchedule
+    #[error("InvalidSchedule")]
+    InvalidSchedule,
+    /// NothingToWithdraw
+    #[error("NothingToWithdraw")]
+    NothingToWithdraw,
+    /// NotRevocable
+    #[error("NotRevocable")]
+    NotRevocable,
+    /// WrongMilestone
+    #[error("WrongMilestone")]
+    WrongMilestone,
+    /// NotDisputed
+    #[error("NotDisputed")]
+    NotDisputed,
+    /// AlreadyDisputed
+    #[error("AlreadyDisputed")]
+    AlreadyDisputed,
+    /// PoolClosed
+    #[error("PoolClosed")]
+    PoolClosed,
+    /// PoolStillOpen
+    #[error("PoolStillOpen")]
+    PoolStillOpen,
+    /// TrancheNotDue
+    #[error("TrancheNotDue")]
+    TrancheNotDue,
+    /// TrancheTooLarge
+    #[error("TrancheTooLarge")]
+    TrancheTooLarge,
+    /// BudgetExhausted
+    #[error("BudgetExhausted")]
+    BudgetExhausted,
+    /// UnsupportedAccountExtension
+    #[error("UnsupportedAccountExtension")]
+    UnsupportedAccountExtension,
+    /// OrderNotApproved
+    #[error("OrderNotApproved")]
+    OrderNotApproved,
 }
 
 impl From<EscrowError> for ProgramError {
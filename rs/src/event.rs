@@ -0,0 +1,106 @@
+// events are plain log lines with a fixed prefix, so off-chain watchers (eg the other chain's side of a swap)
+// can pick them out of the transaction logs without knowing anything else about the program
+use solana_program::{msg, pubkey::Pubkey};
+
+pub const EVENT_PREFIX: &str = "EscrowEvent";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EscrowEvent {
+    /// An HTLC vault was released - `preimage` unlocks the counterparty's leg on the other chain
+    HtlcClaimed {
+        escrow: Pubkey,
+        hashlock: [u8; 32],
+        preimage: [u8; 32],
+    },
+    /// A milestone escrow was funded with `total` for its milestones
+    MilestoneEscrowOpened {
+        escrow: Pubkey,
+        payer: Pubkey,
+        payee: Pubkey,
+        arbiter: Pubkey,
+        total: u64,
+    },
+    /// The payer released milestone `milestone` to the payee
+    MilestoneReleased {
+        escrow: Pubkey,
+        milestone: u8,
+        amount: u64,
+    },
+    /// Milestone `milestone` is disputed, `by` is the payer or the payee
+    MilestoneDisputed {
+        escrow: Pubkey,
+        milestone: u8,
+        by: Pubkey,
+    },
+    /// The arbiter split a disputed milestone
+    DisputeResolved {
+        escrow: Pubkey,
+        milestone: u8,
+        payee_amount: u64,
+        payer_amount: u64,
+    },
+    /// The last milestone was paid out and the escrow closed
+    MilestoneEscrowClosed { escrow: Pubkey },
+}
+
+impl EscrowEvent {
+    /// Logs the event as `EscrowEvent <Name> key=value ...`, byte strings hex encoded
+    pub fn emit(&self) {
+        match self {
+            Self::HtlcClaimed {
+                escrow,
+                hashlock,
+                preimage,
+            } => msg!(&format!(
+                "{} HtlcClaimed escrow={} hashlock={} preimage={}",
+                EVENT_PREFIX,
+                escrow,
+                hex(hashlock),
+                hex(preimage)
+            )),
+            Self::MilestoneEscrowOpened {
+                escrow,
+                payer,
+                payee,
+                arbiter,
+                total,
+            } => msg!(&format!(
+                "{} MilestoneEscrowOpened escrow={} payer={} payee={} arbiter={} total={}",
+                EVENT_PREFIX, escrow, payer, payee, arbiter, total
+            )),
+            Self::MilestoneReleased {
+                escrow,
+                milestone,
+                amount,
+            } => msg!(&format!(
+                "{} MilestoneReleased escrow={} milestone={} amount={}",
+                EVENT_PREFIX, escrow, milestone, amount
+            )),
+            Self::MilestoneDisputed {
+                escrow,
+                milestone,
+                by,
+            } => msg!(&format!(
+                "{} MilestoneDisputed escrow={} milestone={} by={}",
+                EVENT_PREFIX, escrow, milestone, by
+            )),
+            Self::DisputeResolved {
+                escrow,
+                milestone,
+                payee_amount,
+                payer_amount,
+            } => msg!(&format!(
+                "{} DisputeResolved escrow={} milestone={} payee_amount={} payer_amount={}",
+                EVENT_PREFIX, escrow, milestone, payee_amount, payer_amount
+            )),
+            Self::MilestoneEscrowClosed { escrow } => msg!(&format!(
+                "{} MilestoneEscrowClosed escrow={}",
+                EVENT_PREFIX, escrow
+            )),
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
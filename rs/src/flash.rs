@@ -0,0 +1,40 @@
+// a flash exchange hands X to the taker before Y is paid, on the promise of a SettleFlash later in the same transaction.
+// We check the promise through the instructions sysvar, and since SettleFlash failing fails the whole transaction,
+// the initializer either gets paid or keeps their X
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    pubkey::Pubkey, sysvar,
+};
+
+use crate::{error::EscrowError, instruction::EscrowInstruction};
+
+/// Where the escrow account sits in SettleFlash's accounts
+pub const SETTLE_FLASH_ESCROW: usize = 4;
+
+/// Checks that a top-level SettleFlash for `escrow` comes somewhere after the current instruction
+pub fn check_settled_later(
+    instructions_acc: &AccountInfo,
+    escrow: &Pubkey,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    if *instructions_acc.key != sysvar::instructions::id() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let data = instructions_acc.try_borrow_data()?;
+    if data.len() < 2 {
+        return Err(EscrowError::FlashNotSettled.into());
+    }
+    let current = sysvar::instructions::load_current_index(&data) as usize;
+    // runs out at the end of the transaction
+    let mut index = current + 1;
+    while let Ok(ix) = sysvar::instructions::load_instruction_at(index, &data) {
+        if ix.program_id == *program_id
+            && EscrowInstruction::unpack(&ix.data) == Ok(EscrowInstruction::SettleFlash)
+            && ix.accounts.get(SETTLE_FLASH_ESCROW).map(|a| a.pubkey) == Some(*escrow)
+        {
+            return Ok(());
+        }
+        index += 1;
+    }
+    Err(EscrowError::FlashNotSettled.into())
+}
@@ -1,8 +1,12 @@
-use solana_program::program_error::ProgramError;
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
 use std::convert::TryInto;
+use std::mem::size_of;
 
 use crate::error::EscrowError::InvalidInstruction;
+use crate::order::Order;
+use crate::state::Request;
 
+#[derive(Clone, Debug, PartialEq)]
 pub enum EscrowInstruction {
     /// Starts the trade by creating and populating an escrow account and transferring ownership of the given temp token account to the PDA
     ///
@@ -12,10 +16,19 @@ pub enum EscrowInstruction {
     /// 0. `[signer]` The account of the person initializing the escrow
     /// 1. `[writable]` Temporary token account that should be created prior to this instruction and owned by the initializer
     /// 2. `[]` The initializer's token account for the token they will receive should the trade go through
-    /// 3. `[writable]` The escrow account, it will hold all necessary info about the trade.
+    /// 3. `[writable]` The escrow account, it will hold all necessary info about the trade. Created with `Escrow::len(mode)` bytes for the mode it lists in, Vault here
     /// 4. `[]` The rent sysvar
-    /// 5. `[]` The token program - the thing in the middle of his diagram. This would tbe "the smart contract" that we'd be interacting with in ethereum
-    InitEscrow { amount: u64 },
+    /// 5. `[]` The token program of the temp account - the thing in the middle of his diagram. This would tbe "the smart contract" that we'd be interacting with in ethereum
+    /// 6. `[]` The X mint
+    /// 7. `[]` The Y mint
+    /// 8. `[signer]` (with `rent_payer` only) Whoever pays the rent for the escrow and temp accounts, gets it back on close. Defaults to the initializer
+    ///
+    /// Either leg can use the original token program or Token-2022.
+    InitEscrow {
+        amount: u64,
+        /// whether a rent payer comes last in the accounts, it has to sign. Packed as a trailing byte, left out when false
+        rent_payer: bool,
+    },
 
     /// Accepts a trade
     ///
@@ -25,26 +38,1015 @@ pub enum EscrowInstruction {
     /// 0. `[signer]` The account of the person taking the trade
     /// 1. `[writable]` The taker's token account for the token they send (Y)
     /// 2. `[writable]` The taker's token account for the token they will receive should the trade go through (X)
-    /// 3. `[writable]` The PDA's temp token account to get tokens from and eventually close
+    /// 3. `[writable]` The PDA's temp token account to get tokens from and eventually close (Approve mode: the initializer's X account)
     /// 4. `[writable]` The initializer's main account to send their rent fees to
     /// 5. `[writable]` The initializer's token account that will receive tokens (Y)
     /// 6. `[writable]` The escrow account holding the escrow info
-    /// 7. `[]` The token program
+    /// 7. `[]` The token program of the X leg
     /// 8. `[]` The PDA account
+    /// 9. `[writable]` The X mint
+    /// 10. `[]` The Y mint
+    /// 11. `[]` The token program of the Y leg
+    /// 12. `[writable]` (optional) The rent payer recorded in the escrow, only needed if it isn't the initializer
+    ///
+    /// Royalty escrows take the metadata and creator accounts listed under InitRoyaltyEscrow before the rent payer
     Exchange {
         /// the amount the taker expects to be paid in the other token, as a u64 because that's the max possible supply of a token
+        /// net of any Token-2022 transfer fee
         amount: u64,
     },
 
     /// 0 [signer] initializer's main account
     /// 1 [] token program account
-    /// 2 [writable] temp x account
+    /// 2 [writable] temp x account (Approve mode: the initializer's X account, the delegation gets revoked)
     /// 3 [writable] initializer's x account (writable coz we'll update their balance with new coins)
-    /// 4 [writable] escrow account
+    /// 4 [writable] escrow account, or one of the 105 byte escrows from before the account type (see LegacyEscrow)
     /// 5 [] pda acc
+    /// 6 [writable] X mint
+    /// 7 [writable] (optional) rent payer recorded in the escrow, only needed if it isn't the initializer
+    /// then, if the escrow has a multisig authority, the multisig account and at least M of its signers - the
+    /// initializer needn't sign then, except for an Approve mode escrow since only they can revoke its delegation
     Cancel { bump_seed: u8 },
+
+    /// Same as InitEscrow, but the initializer keeps their X tokens and approves the PDA as delegate for `offered_amount` instead
+    /// No temp account needed, so listing costs nothing but the escrow account's rent. Exchange pulls through the delegation, Cancel revokes it.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person initializing the escrow
+    /// 1. `[writable]` The initializer's own token account holding the X tokens
+    /// 2. `[]` The initializer's token account for the token they will receive should the trade go through
+    /// 3. `[writable]` The escrow account, it will hold all necessary info about the trade, `Escrow::len(EscrowMode::Approve)` bytes
+    /// 4. `[]` The rent sysvar
+    /// 5. `[]` The token program of the X account
+    /// 6. `[]` The X mint
+    /// 7. `[]` The Y mint
+    /// 8. `[]` The PDA account, it becomes the delegate
+    /// 9. `[signer]` (with `rent_payer` only) Whoever pays the rent for the escrow account, gets it back on close. Defaults to the initializer
+    InitApproveEscrow {
+        /// the amount of Y the initializer expects
+        amount: u64,
+        /// the amount of X the taker gets
+        offered_amount: u64,
+        /// whether a rent payer comes last in the accounts, it has to sign. Packed as a trailing byte, left out when false
+        rent_payer: bool,
+    },
+
+    /// Hashed-timelock mode, for the Solana leg of a cross-chain atomic swap. The vault goes to the PDA like in InitEscrow,
+    /// but it's released by revealing the preimage of `hashlock` (Claim) rather than by paying for it,
+    /// or goes back to the initializer once `timelock` has passed (Refund)
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person initializing the escrow
+    /// 1. `[writable]` Temporary token account holding the X tokens, owned by the initializer
+    /// 2. `[]` The recipient's token account for X
+    /// 3. `[writable]` The escrow account, it will hold all necessary info about the trade, `Escrow::len(EscrowMode::Htlc)` bytes
+    /// 4. `[]` The rent sysvar
+    /// 5. `[]` The token program of the X accounts
+    /// 6. `[]` The X mint
+    /// 7. `[signer]` (with `rent_payer` only) Whoever pays the rent for the escrow and temp accounts, gets it back on close. Defaults to the initializer
+    InitHtlcEscrow {
+        /// SHA-256 of the secret
+        hashlock: [u8; 32],
+        /// unix timestamp after which the initializer can get the tokens back
+        timelock: i64,
+        /// whether a rent payer comes last in the accounts, it has to sign. Packed as a trailing byte, left out when false
+        rent_payer: bool,
+    },
+
+    /// Releases an HTLC vault to the recipient. Anyone who knows the preimage can send it, the tokens only go to the recipient.
+    /// The preimage is logged as an `HtlcClaimed` event for the other chain to pick up.
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The PDA's temp token account
+    /// 1. `[writable]` The recipient's token account for X
+    /// 2. `[writable]` The initializer's main account to send their rent fees to
+    /// 3. `[writable]` The escrow account
+    /// 4. `[]` The token program of the X accounts
+    /// 5. `[]` The PDA account
+    /// 6. `[writable]` The X mint
+    /// 7. `[writable]` (optional) The rent payer recorded in the escrow, only needed if it isn't the initializer
+    Claim {
+        /// the secret, fixed at 32 bytes so a preimage that's valid here is valid on the other chain too
+        preimage: [u8; 32],
+    },
+
+    /// Returns an HTLC vault to the initializer after the timelock
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The initializer's main account
+    /// 1. `[writable]` The PDA's temp token account
+    /// 2. `[writable]` The initializer's token account for X
+    /// 3. `[writable]` The escrow account
+    /// 4. `[]` The token program of the X accounts
+    /// 5. `[]` The PDA account
+    /// 6. `[writable]` The X mint
+    /// 7. `[writable]` (optional) The rent payer recorded in the escrow, only needed if it isn't the initializer
+    ///
+    /// then, if the escrow has a multisig authority, the multisig account and at least M of its signers - the initializer
+    /// needn't sign then
+    Refund,
+
+    /// Writes a covered call: the writer locks X in a vault like InitEscrow does, and sells the right to buy it for
+    /// `strike` Y until `expiry`
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The writer
+    /// 1. `[writable]` Temporary token account holding the X tokens, owned by the writer
+    /// 2. `[]` The writer's token account for Y, gets the premium and the strike
+    /// 3. `[writable]` The escrow account, it will hold all necessary info about the option, `Escrow::len(EscrowMode::CoveredCall)` bytes
+    /// 4. `[]` The rent sysvar
+    /// 5. `[]` The token program of the X leg
+    /// 6. `[]` The X mint
+    /// 7. `[]` The Y mint
+    /// 8. `[signer]` (with `rent_payer` only) Whoever pays the rent for the escrow and temp accounts, gets it back on close. Defaults to the writer
+    WriteOption {
+        /// Y the holder pays for the X on exercise
+        strike: u64,
+        /// Y the buyer pays for the option
+        premium: u64,
+        /// unix timestamp, the option can be exercised before it and the writer gets X back from it on
+        expiry: i64,
+        /// whether a rent payer comes last in the accounts, it has to sign. Packed as a trailing byte, left out when false
+        rent_payer: bool,
+    },
+
+    /// Buys an option that's for sale, the buyer becomes its holder
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The buyer
+    /// 1. `[writable]` The buyer's token account for Y
+    /// 2. `[writable]` The writer's token account for Y
+    /// 3. `[writable]` The escrow account
+    /// 4. `[]` The Y mint
+    /// 5. `[]` The token program of the Y leg
+    BuyOption {
+        /// the premium the buyer expects to pay, so the writer can't change it under them
+        premium: u64,
+    },
+
+    /// Exercises an option: the holder pays the strike and gets the vault. Same accounts as Exchange, with the holder as the taker
+    ExerciseOption {
+        /// the amount of X the holder expects, same as in Exchange
+        amount: u64,
+    },
+
+    /// Gives the writer their X back, once the option has expired or if nobody bought it yet
+    ///
+    ///
+    /// Accounts expected: same as Refund, with the writer as the initializer
+    ReclaimOption,
+
+    /// Offers a loan: the borrower locks collateral in a vault like InitEscrow does, and asks for `principal` Y
+    /// to be paid back with `interest` within `duration` seconds of being funded
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The borrower
+    /// 1. `[writable]` Temporary token account holding the collateral, owned by the borrower
+    /// 2. `[]` The borrower's token account for the principal, it gets paid out to it and repaid from it
+    /// 3. `[writable]` The loan account, it will hold all necessary info about the loan.
+    /// 4. `[]` The rent sysvar
+    /// 5. `[]` The token program of the collateral
+    /// 6. `[]` The collateral mint
+    /// 7. `[]` The principal mint
+    /// 8. `[signer]` (with `rent_payer` only) Whoever pays the rent for the loan and temp accounts, gets it back on close. Defaults to the borrower
+    OfferLoan {
+        /// Y the borrower wants
+        principal: u64,
+        /// Y the borrower pays on top when repaying
+        interest: u64,
+        /// seconds from funding until the loan is due
+        duration: i64,
+        /// whether a rent payer comes last in the accounts, it has to sign. Packed as a trailing byte, left out when false
+        rent_payer: bool,
+    },
+
+    /// Funds an offered loan, the principal goes straight to the borrower and the clock starts
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The lender
+    /// 1. `[writable]` The lender's token account for the principal, the repayment comes back to it
+    /// 2. `[writable]` The borrower's token account for the principal
+    /// 3. `[writable]` The loan account
+    /// 4. `[]` The principal mint
+    /// 5. `[]` The token program of the principal
+    FundLoan {
+        /// the principal the lender expects to send, so the borrower can't change it under them
+        principal: u64,
+    },
+
+    /// Repays a funded loan before its deadline and gives the borrower their collateral back
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The borrower
+    /// 1. `[writable]` The borrower's token account for the principal
+    /// 2. `[writable]` The lender's token account for the principal
+    /// 3. `[writable]` The PDA's collateral vault
+    /// 4. `[writable]` The borrower's token account for the collateral
+    /// 5. `[writable]` The loan account
+    /// 6. `[]` The token program of the collateral
+    /// 7. `[]` The PDA account
+    /// 8. `[writable]` The collateral mint
+    /// 9. `[]` The principal mint
+    /// 10. `[]` The token program of the principal
+    /// 11. `[writable]` (optional) The rent payer recorded in the loan, only needed if it isn't the borrower
+    RepayLoan,
+
+    /// Hands the collateral of a loan that wasn't repaid in time to the lender
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The lender
+    /// 1. `[writable]` The PDA's collateral vault
+    /// 2. `[writable]` The lender's token account for the collateral
+    /// 3. `[writable]` The borrower's main account to send their rent fees to
+    /// 4. `[writable]` The loan account
+    /// 5. `[]` The token program of the collateral
+    /// 6. `[]` The PDA account
+    /// 7. `[writable]` The collateral mint
+    /// 8. `[writable]` (optional) The rent payer recorded in the loan, only needed if it isn't the borrower
+    ForecloseLoan,
+
+    /// Takes back a loan offer nobody funded yet, the collateral goes back to the borrower
+    ///
+    ///
+    /// Accounts expected: same as Refund, with the borrower as the initializer and the loan account as the escrow
+    CancelLoan,
+
+    /// Lists X like InitEscrow, but the price falls from `start_price` at `start_time` to `floor_price` at `end_time`
+    /// and stays there. Cancel works as for any vault. Same accounts as InitEscrow
+    InitDutchAuction {
+        start_price: u64,
+        floor_price: u64,
+        /// unix timestamps
+        start_time: i64,
+        end_time: i64,
+        /// 0 for a straight line, n to halve the price's excess over the floor at n even intervals instead (max 64)
+        decay: u8,
+        /// whether a rent payer comes last in the accounts, it has to sign. Packed as a trailing byte, left out when false
+        rent_payer: bool,
+    },
+
+    /// Buys a Dutch auction at whatever it asks right now. Same accounts as Exchange
+    TakeDutchAuction {
+        /// the amount of X the taker expects, same as in Exchange
+        amount: u64,
+        /// the most Y the taker is willing to pay, the price may have moved since they looked
+        max_price: u64,
+    },
+
+    /// Auctions X to the highest bidder. The vault side works like InitEscrow, and a second, empty Y account owned by
+    /// the seller gets handed to the PDA to hold the bids
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0-7. Same as InitEscrow, with the seller as the initializer
+    /// 8. `[writable]` Empty token account for Y owned by the seller, becomes the bid vault
+    /// 9. `[]` The token program of the Y leg
+    /// 10. `[signer]` (with `rent_payer` only) Whoever pays the rent for the escrow, temp and bid vault accounts, gets it back on close. Defaults to the seller
+    InitEnglishAuction {
+        /// the lowest first bid
+        reserve_price: u64,
+        /// how much each bid has to beat the last one by
+        min_increment: u64,
+        /// unix timestamp
+        end_time: i64,
+        /// seconds - a bid closer than this to the end pushes the end back to this far from the bid
+        extension: i64,
+        /// whether a rent payer comes last in the accounts, it has to sign. Packed as a trailing byte, left out when false
+        rent_payer: bool,
+    },
+
+    /// Outbids the highest bidder. The Y goes into the bid vault and the previous bid goes back in the same instruction
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The bidder
+    /// 1. `[writable]` The bidder's token account for Y
+    /// 2. `[writable]` The escrow account
+    /// 3. `[writable]` The bid vault
+    /// 4. `[]` The Y mint
+    /// 5. `[]` The token program of the Y leg
+    /// 6. `[]` The PDA account
+    /// 7. `[writable]` (once someone has bid) Any token account for Y owned by the previous highest bidder, gets their bid back
+    Bid {
+        /// all of it, not on top of an earlier bid
+        amount: u64,
+    },
+
+    /// Ends an English or sealed-bid auction once its end time has passed. Anyone can send it: X goes to the highest
+    /// bidder (back to the seller if nobody bid) and the bids to the seller. In a sealed-bid auction that includes the
+    /// bonds of bids nobody revealed, and in a second-price one the winner gets back what they bid above the price
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The PDA's temp token account
+    /// 1. `[writable]` Any token account for X owned by the highest bidder (the seller if nobody bid)
+    /// 2. `[writable]` The bid vault
+    /// 3. `[writable]` Any token account for Y owned by the seller
+    /// 4. `[writable]` The seller's main account to send their rent fees to
+    /// 5. `[writable]` The escrow account
+    /// 6. `[]` The token program of the X leg
+    /// 7. `[]` The token program of the Y leg
+    /// 8. `[]` The PDA account
+    /// 9. `[writable]` The X mint
+    /// 10. `[writable]` The Y mint
+    /// 11. `[writable]` (sealed-bid only) Any token account for Y owned by the highest bidder (the seller if nobody bid)
+    /// 11. `[writable]` (optional, 12. in a sealed-bid auction) The rent payer recorded in the escrow, only needed if it isn't the seller
+    SettleAuction,
+
+    /// Auctions X in sealed bids: bidders commit to a hash of their bid until `commit_end`, paying `bond` each time,
+    /// and open them until `reveal_end`. Accounts are the same as InitEnglishAuction
+    InitSealedAuction {
+        /// the lowest bid that can win
+        reserve_price: u64,
+        /// Y paid in with each commitment, counts towards the bid once it's revealed. At most the reserve
+        bond: u64,
+        /// unix timestamps
+        commit_end: i64,
+        reveal_end: i64,
+        /// the winner pays the second highest bid (or the reserve, if that's more) instead of their own
+        second_price: bool,
+        /// whether a rent payer comes last in the accounts, it has to sign. Packed as a trailing byte, left out when false
+        rent_payer: bool,
+    },
+
+    /// Commits to a sealed bid and pays the bond into the bid vault
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The bidder
+    /// 1. `[writable]` The bidder's token account for Y
+    /// 2. `[]` The escrow account
+    /// 3. `[writable]` The bid account, created beforehand and owned by the program, it will hold the commitment
+    /// 4. `[writable]` The bid vault
+    /// 5. `[]` The Y mint
+    /// 6. `[]` The token program of the Y leg
+    CommitBid {
+        /// sha256 of the amount (8 bytes LE), a 32 byte salt and the bidder's pubkey, see `auction::bid_commitment`
+        commitment: [u8; 32],
+    },
+
+    /// Opens a sealed bid. If it's the highest so far the bidder pays the rest of it into the bid vault and the bid it
+    /// beats is refunded, otherwise the bond goes back. Either way the bid account is closed
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The bidder, gets the bid account's rent back
+    /// 1. `[writable]` The bidder's token account for Y
+    /// 2. `[writable]` The escrow account
+    /// 3. `[writable]` The bid account
+    /// 4. `[writable]` The bid vault
+    /// 5. `[]` The Y mint
+    /// 6. `[]` The token program of the Y leg
+    /// 7. `[]` The PDA account
+    /// 8. `[writable]` (if it beats an earlier revealed bid) Any token account for Y owned by that bid's bidder
+    RevealBid { amount: u64, salt: [u8; 32] },
+
+    /// Closes a bid account that was never revealed, once it's too late to. The bond stays with the auction
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The bidder, gets the rent back
+    /// 1. `[writable]` The bid account
+    CloseBid,
+
+    /// Settles two Vault mode escrows against each other, when each one's vault covers what the other asks for (after
+    /// any transfer fees). Anyone can send it: both makers get exactly what they asked for, the cranker gets
+    /// `matching::CRANK_FEE_BPS` of whatever is left in each vault and the rest goes to that escrow's match recipient
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The first escrow account, selling X for Y
+    /// 1. `[writable]` Its vault (X)
+    /// 2. `[writable]` Its initializer's main account to send their rent fees to
+    /// 3. `[writable]` Its initializer's token account that will receive Y
+    /// 4. `[writable]` The match recipient set with SetMatchRecipient, or else any token account for X owned by its initializer
+    /// 5. `[writable]` The second escrow account, selling Y for X
+    /// 6. `[writable]` Its vault (Y)
+    /// 7. `[writable]` Its initializer's main account to send their rent fees to
+    /// 8. `[writable]` Its initializer's token account that will receive X
+    /// 9. `[writable]` The match recipient set with SetMatchRecipient, or else any token account for Y owned by its initializer
+    /// 10. `[writable]` The cranker's token account for X
+    /// 11. `[writable]` The cranker's token account for Y
+    /// 12. `[writable]` The X mint
+    /// 13. `[writable]` The Y mint
+    /// 14. `[]` The token program of the X leg
+    /// 15. `[]` The token program of the Y leg
+    /// 16. `[]` The PDA account
+    /// 17. `[writable]` (optional) The first escrow's rent payer, only needed if it isn't its initializer
+    /// 18. `[writable]` (optional) The second escrow's rent payer, same
+    Match,
+
+    /// Sends the price improvement of a Vault mode escrow to someone other than its initializer when it's matched
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The initializer
+    /// 1. `[writable]` The escrow account
+    /// 2. `[]` The escrow's vault
+    /// 3. `[]` The token account to get the improvement, same mint as the vault
+    /// 4. `[]` (if the escrow has a multisig authority) The multisig account, the initializer needn't sign then
+    /// 5. ..5+M `[signer]` (same) At least M of its signers
+    SetMatchRecipient,
+
+    /// Takes several Vault or Approve mode escrows selling the same X for the same Y in one go, cheapest Y per X first,
+    /// until the taker has at least `target_x`. Escrows are only ever taken whole, so the last one can overshoot the
+    /// target, and the ones not needed to reach it are left alone. Fails as a whole if the target isn't reached or
+    /// the total comes to more than `max_y`
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person taking the trades
+    /// 1. `[writable]` The taker's token account for the token they send (Y)
+    /// 2. `[writable]` The taker's token account for the token they will receive (X)
+    /// 3. `[]` The token program of the X leg
+    /// 4. `[]` The token program of the Y leg
+    /// 5. `[]` The PDA account
+    /// 6. `[writable]` The X mint
+    /// 7. `[]` The Y mint
+    ///
+    /// Then `EXCHANGE_MANY_ACCOUNTS` per escrow, in any order:
+    ///
+    /// 0. `[writable]` The escrow account
+    /// 1. `[writable]` The PDA's temp token account (Approve mode: the initializer's X account)
+    /// 2. `[writable]` The initializer's main account to send their rent fees to
+    /// 3. `[writable]` The initializer's token account that will receive Y
+    /// 4. `[writable]` The rent payer recorded in the escrow, the initializer's main account again if that's who it is
+    ExchangeMany {
+        /// the least X the taker wants to end up with, net of any transfer fee
+        target_x: u64,
+        /// the most Y the taker is willing to send for all of it, fees included
+        max_y: u64,
+    },
+
+    /// Fills an order its maker signed off-chain, see order.rs. The instruction right before this one in the transaction
+    /// has to be the Ed25519 program verifying the maker's signature of `order.message(program_id)`. The maker's X moves
+    /// with their order delegate (`order::delegate_address`) signing, so they approve it for at least `amount_x`
+    /// beforehand
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The taker, pays the rent of the nonce bitmap if it has to be created
+    /// 1. `[writable]` The taker's token account for the token they send (Y)
+    /// 2. `[writable]` The taker's token account for the token they will receive (X)
+    /// 3. `[writable]` Any token account for X owned by the maker, with the maker's order delegate as its delegate
+    /// 4. `[writable]` Any token account for Y owned by the maker
+    /// 5. `[writable]` The maker's nonce bitmap for the order's nonce, `order::nonce_bitmap_address`
+    /// 6. `[writable]` The X mint
+    /// 7. `[]` The Y mint
+    /// 8. `[]` The token program of the X leg
+    /// 9. `[]` The token program of the Y leg
+    /// 10. `[]` The maker's order delegate, `order::delegate_address`
+    /// 11. `[]` The instructions sysvar
+    /// 12. `[]` The system program
+    FillSignedOrder { order: Order },
+
+    /// Uses up a nonce without a fill, so any order signed with it can't be filled anymore
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The maker, pays the rent of the nonce bitmap if it has to be created
+    /// 1. `[writable]` The maker's nonce bitmap for the nonce, `order::nonce_bitmap_address`
+    /// 2. `[]` The system program
+    CancelOrderNonce { nonce: u64 },
+
+    /// Takes a Vault or Approve mode escrow without paying for it yet: X goes to the taker right away, and Y is paid by
+    /// the SettleFlash for the same escrow that has to come later in the same transaction. In between, the taker can
+    /// sell the X elsewhere to raise the Y. Without that SettleFlash, or if it fails, the whole transaction fails
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person taking the trade
+    /// 1. `[writable]` The taker's token account for the token they will receive (X)
+    /// 2. `[writable]` The PDA's temp token account to get tokens from and eventually close (Approve mode: the initializer's X account)
+    /// 3. `[writable]` The initializer's main account to send their rent fees to
+    /// 4. `[writable]` The escrow account holding the escrow info
+    /// 5. `[]` The token program of the X leg
+    /// 6. `[]` The PDA account
+    /// 7. `[writable]` The X mint
+    /// 8. `[]` The instructions sysvar
+    /// 9. `[writable]` (optional) The rent payer, if it isn't the initializer
+    FlashExchange {
+        /// the amount of X the taker expects to receive, net of any Token-2022 transfer fee
+        amount: u64,
+    },
+
+    /// Pays for a FlashExchange earlier in the same transaction: sends the initializer the expected amount of Y and
+    /// closes the escrow. Anyone can pay
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account paying, usually the taker
+    /// 1. `[writable]` Its token account for Y
+    /// 2. `[writable]` The initializer's token account that will receive Y
+    /// 3. `[writable]` The initializer's main account to send their rent fees to
+    /// 4. `[writable]` The escrow account holding the escrow info
+    /// 5. `[]` The Y mint
+    /// 6. `[]` The token program of the Y leg
+    /// 7. `[writable]` (optional) The rent payer, if it isn't the initializer
+    SettleFlash,
+
+    /// Hands a vault to the PDA to be paid out to many recipients, each of whom proves their share against
+    /// `merkle_root` (see merkle.rs) with ClaimDistribution. Both state accounts are created by the client beforehand,
+    /// the claimed bitmap with `ClaimedBitmap::len(num_recipients)` bytes
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person paying out
+    /// 1. `[writable]` The token account holding everything to pay out, handed to the PDA as the vault
+    /// 2. `[writable]` The distribution account, empty and rent exempt
+    /// 3. `[writable]` The claimed bitmap account, empty and rent exempt
+    /// 4. `[]` The token program
+    /// 5. `[]` The mint
+    InitDistribution {
+        merkle_root: [u8; 32],
+        num_recipients: u64,
+        /// claims stop at this unix timestamp, and the initializer can take the rest back
+        deadline: i64,
+    },
+
+    /// Pays leaf `index` of a distribution out of the vault, once. Anyone can send it, the tokens only ever go to an
+    /// account owned by the recipient in the leaf
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` Any token account owned by the recipient
+    /// 1. `[]` The distribution account
+    /// 2. `[writable]` The claimed bitmap account
+    /// 3. `[writable]` The vault
+    /// 4. `[writable]` The mint
+    /// 5. `[]` The token program
+    /// 6. `[]` The PDA account
+    ClaimDistribution {
+        index: u64,
+        /// leaves out of the vault, the recipient gets less if the mint charges a transfer fee
+        amount: u64,
+        /// the sibling hashes from the leaf up to the root
+        proof: Vec<[u8; 32]>,
+    },
+
+    /// Once the deadline has passed, sends whatever is left in a distribution's vault back to its initializer and
+    /// closes all three accounts
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The initializer, gets the rent back
+    /// 1. `[writable]` The initializer's token account to get the rest
+    /// 2. `[writable]` The distribution account
+    /// 3. `[writable]` The claimed bitmap account
+    /// 4. `[writable]` The vault
+    /// 5. `[writable]` The mint
+    /// 6. `[]` The token program
+    /// 7. `[]` The PDA account
+    ReclaimDistribution,
+
+    /// Same as InitEscrow in Vault mode, but asks for an NFT instead of an amount of Y: either one particular mint, or
+    /// any NFT of a verified Metaplex collection (a collection offer). Taken with ExchangeNft, cancelled with Cancel
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person initializing the escrow
+    /// 1. `[writable]` Temporary token account holding what's on offer, handed to the PDA
+    /// 2. `[writable]` The escrow account, it will hold all necessary info about the trade, `Escrow::len(EscrowMode::Vault)` bytes
+    /// 3. `[]` The token program of the temp account
+    /// 4. `[]` The X mint
+    /// 5. `[signer]` (with `rent_payer` only) Whoever pays the rent for the escrow and temp accounts, gets it back on close. Defaults to the initializer
+    InitNftEscrow {
+        /// Mint or Collection, Tokens is what InitEscrow is for
+        request: Request,
+        /// the NFT's mint, or the collection's
+        request_key: Pubkey,
+        /// whether a rent payer comes last in the accounts, it has to sign. Packed as a trailing byte, left out when false
+        rent_payer: bool,
+    },
+
+    /// Takes an InitNftEscrow escrow by sending alice one NFT that fits her request. The NFT's mint has to have
+    /// 0 decimals and a supply of 1
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person taking the trade
+    /// 1. `[writable]` The taker's token account holding the NFT
+    /// 2. `[writable]` A token account of the initializer's for the NFT, eg their associated token account
+    /// 3. `[writable]` The taker's token account for the token they will receive (X)
+    /// 4. `[writable]` The PDA's temp token account to get tokens from and eventually close
+    /// 5. `[writable]` The initializer's main account to send their rent fees to
+    /// 6. `[writable]` The escrow account holding the escrow info
+    /// 7. `[]` The token program of the X leg
+    /// 8. `[]` The PDA account
+    /// 9. `[writable]` The X mint
+    /// 10. `[]` The NFT's mint
+    /// 11. `[]` The NFT's Metaplex metadata account, only read for collection requests
+    /// 12. `[]` The token program of the NFT
+    /// 13. `[writable]` (optional) The rent payer recorded in the escrow, only needed if it isn't the initializer
+    ExchangeNft {
+        /// same as Exchange, what the taker expects to receive in X
+        amount: u64,
+    },
+
+    /// Same as InitEscrow, for an NFT (0 decimals, supply of 1) whose creators are owed royalties. Exchange pays them
+    /// out of the price going by the NFT's Metaplex metadata at the time of the sale, and needs these extra accounts
+    /// right after the Y token program:
+    ///
+    /// 12. `[]` The X mint's metadata account
+    /// 13. `[writable]` The Y token account of each creator with a share, owned by the creator, in metadata order
+    ///
+    /// with the optional rent payer after them. It can't be taken any other way
+    ///
+    ///
+    /// Accounts expected: same as InitEscrow
+    InitRoyaltyEscrow {
+        /// the amount of Y the initializer expects, before royalties
+        amount: u64,
+        /// whether a rent payer comes last in the accounts, it has to sign. Packed as a trailing byte, left out when false
+        rent_payer: bool,
+    },
+
+    /// Locks the temp account's tokens in a vault for a beneficiary, unlocking over time: nothing before `cliff`, then
+    /// in a straight line from `start` to `end` (see vesting.rs). The vesting account is created by the client
+    /// beforehand, with Vesting::LEN bytes and owned by the program
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person funding the vesting
+    /// 1. `[writable]` Temporary token account holding all of it, handed to the PDA as the vault
+    /// 2. `[writable]` The vesting account, empty and rent exempt
+    /// 3. `[]` The beneficiary
+    /// 4. `[]` The token program
+    /// 5. `[]` The mint
+    /// 6. `[]` (optional) Whoever may revoke what hasn't vested yet. Irrevocable without it
+    InitVesting { start: i64, cliff: i64, end: i64 },
+
+    /// Sends the beneficiary of a vesting everything that vested and they haven't withdrawn yet, or the recipient of a
+    /// stream everything that accrued. The withdrawal that takes the last of it closes the vault and the vesting or
+    /// stream account
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The beneficiary, or the stream's recipient
+    /// 1. `[writable]` The token account to withdraw into
+    /// 2. `[writable]` The vesting or stream account
+    /// 3. `[writable]` The vault
+    /// 4. `[writable]` The mint
+    /// 5. `[]` The token program
+    /// 6. `[]` The PDA account
+    /// 7. `[writable]` The initializer's main account, or the stream's payer. Gets the rent back at the end
+    Withdraw,
+
+    /// Claws back whatever hasn't vested yet. The beneficiary keeps what has, and can still withdraw it
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The revocation authority
+    /// 1. `[writable]` The token account to get the unvested tokens
+    /// 2. `[writable]` The vesting account
+    /// 3. `[writable]` The vault
+    /// 4. `[writable]` The mint
+    /// 5. `[]` The token program
+    /// 6. `[]` The PDA account
+    /// 7. `[writable]` The initializer's main account, gets the rent back if nothing is left for the beneficiary
+    RevokeVesting,
+
+    /// Streams the temp account's tokens to a recipient at `rate` per second for `duration` seconds from now. The
+    /// temp account has to hold exactly rate * duration, the recipient takes what has accrued with Withdraw. The stream
+    /// account is created by the client beforehand, with Stream::LEN bytes and owned by the program
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The account of the person paying
+    /// 1. `[writable]` Temporary token account holding the whole payment, handed to the PDA as the vault
+    /// 2. `[writable]` The stream account, empty and rent exempt
+    /// 3. `[]` The recipient
+    /// 4. `[]` The token program
+    /// 5. `[]` The mint
+    InitStream { rate: u64, duration: i64 },
+
+    /// Stops a stream: what has accrued and wasn't withdrawn goes to the recipient, the rest back to the payer, and
+    /// the vault and stream account are closed
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The payer, gets the rent back
+    /// 1. `[writable]` The payer's token account to get the rest
+    /// 2. `[writable]` A token account of the recipient's for what has accrued
+    /// 3. `[writable]` The stream account
+    /// 4. `[writable]` The vault
+    /// 5. `[writable]` The mint
+    /// 6. `[]` The token program
+    /// 7. `[]` The PDA account
+    CancelStream,
+
+    /// Funds an arbitrated escrow for freelance work: the temp account's tokens are paid out to the payee milestone by
+    /// milestone, in order. It has to hold exactly the sum of `amounts`. The milestone account is created by the client
+    /// beforehand, with MilestoneEscrow::LEN bytes and owned by the program
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The payer
+    /// 1. `[writable]` Temporary token account holding all milestones, handed to the PDA as the vault
+    /// 2. `[writable]` The milestone account, empty and rent exempt
+    /// 3. `[]` The payee
+    /// 4. `[]` The arbiter, settles disputes
+    /// 5. `[]` The token program
+    /// 6. `[]` The mint
+    InitMilestoneEscrow {
+        /// what each milestone pays, at most MilestoneEscrow::MAX_MILESTONES of them
+        amounts: Vec<u64>,
+    },
+
+    /// The payer pays the current milestone to the payee, disputed or not. Paying the last one closes the vault and the
+    /// milestone account
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The payer, gets the rent back at the end
+    /// 1. `[writable]` A token account of the payee's
+    /// 2. `[writable]` The milestone account
+    /// 3. `[writable]` The vault
+    /// 4. `[writable]` The mint
+    /// 5. `[]` The token program
+    /// 6. `[]` The PDA account
+    ReleaseMilestone {
+        /// the current milestone, so a release can't land on the one after it
+        milestone: u8,
+    },
+
+    /// The payer or the payee disputes the current milestone. From then on the arbiter can split it, the payer can
+    /// still release it in full
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The payer or the payee
+    /// 1. `[writable]` The milestone account
+    DisputeMilestone { milestone: u8 },
+
+    /// The arbiter splits the disputed current milestone, `payee_amount` of it to the payee and the rest back to the
+    /// payer. Resolving the last one closes the vault and the milestone account
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The arbiter
+    /// 1. `[writable]` A token account of the payee's
+    /// 2. `[writable]` A token account of the payer's
+    /// 3. `[writable]` The milestone account
+    /// 4. `[writable]` The vault
+    /// 5. `[writable]` The mint
+    /// 6. `[]` The token program
+    /// 7. `[]` The PDA account
+    /// 8. `[writable]` The payer's main account, gets the rent back at the end
+    ResolveDispute { milestone: u8, payee_amount: u64 },
+
+    /// Sets up a multisig: `m` of the signer accounts passed in will have to sign for it. The multisig account is
+    /// created by the client beforehand, with Multisig::LEN bytes and owned by the program
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The multisig account, empty and rent exempt
+    /// 1. ..1+N `[]` The signers, at most Multisig::MAX_SIGNERS and no repeats
+    InitMultisig {
+        /// how many of the signers have to sign, at least 1
+        m: u8,
+    },
+
+    /// Hands an escrow over to a multisig, or back to its initializer. From then on Cancel, Refund, ReclaimOption,
+    /// SetMatchRecipient and SetEscrowAuthority take M of the multisig's signers instead of the initializer's signature
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The initializer, needn't sign if the escrow already has a multisig authority
+    /// 1. `[writable]` The escrow account
+    /// 2. `[]` The new authority, a multisig account - or the initializer to go back to them signing alone
+    /// 3. `[]` (if the escrow has a multisig authority) The current multisig account
+    /// 4. ..4+M `[signer]` (same) At least M of its signers
+    SetEscrowAuthority,
+
+    /// Opens a pool to take a Vault mode escrow together: contributors put Y in until there's the escrow's
+    /// expected_amount, then ExecutePool takes it. The pool account is created by the client beforehand, with Pool::LEN
+    /// bytes and owned by the program. Mints with transfer fees aren't supported
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The creator, gets the rent of the pool and its vaults back at the end
+    /// 1. `[writable]` The pool account, empty and rent exempt
+    /// 2. `[]` The escrow account
+    /// 3. `[writable]` An empty token account for Y owned by the creator, handed to the PDA as the Y vault
+    /// 4. `[writable]` An empty token account for X owned by the creator, handed to the PDA as the X vault
+    /// 5. `[]` The escrow's vault
+    /// 6. `[]` The initializer's token account for Y recorded in the escrow
+    /// 7. `[]` The X mint
+    /// 8. `[]` The Y mint
+    /// 9. `[]` The token program of the X leg
+    /// 10. `[]` The token program of the Y leg
+    InitPool {
+        /// unix timestamp, the pool takes no contributions from then on and refunds them if it isn't filled
+        deadline: i64,
+    },
+
+    /// Puts Y into a pool, at most what it still needs. Contributing again adds to the same contribution account, which
+    /// is created by the client beforehand, with Contribution::LEN bytes and owned by the program
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The contributor
+    /// 1. `[writable]` The contributor's token account for Y
+    /// 2. `[writable]` The pool account
+    /// 3. `[writable]` The contributor's contribution account, empty and rent exempt the first time
+    /// 4. `[writable]` The pool's Y vault
+    /// 5. `[]` The Y mint
+    /// 6. `[]` The token program of the Y leg
+    Contribute {
+        /// the most the contributor puts in
+        amount: u64,
+    },
+
+    /// Takes the escrow with a filled pool's Y. Anyone can send it. The X goes into the pool's X vault, the Y vault is
+    /// closed
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` The pool account
+    /// 1. `[writable]` The escrow account
+    /// 2. `[writable]` The escrow's vault
+    /// 3. `[writable]` The pool's X vault
+    /// 4. `[writable]` The X mint
+    /// 5. `[]` The token program of the X leg
+    /// 6. `[writable]` The pool's Y vault
+    /// 7. `[writable]` The initializer's token account for Y
+    /// 8. `[]` The Y mint
+    /// 9. `[]` The token program of the Y leg
+    /// 10. `[]` The PDA account
+    /// 11. `[writable]` The pool's creator, gets the Y vault's rent back
+    /// 12. `[writable]` The escrow's initializer
+    /// 13. `[writable]` (optional) The rent payer recorded in the escrow, only needed if it isn't the initializer
+    ExecutePool,
+
+    /// Takes a contributor out of a pool and closes their contribution account: their share of the X once the pool was
+    /// executed, or their Y back if the escrow is gone or the deadline passed before the pool was filled. The last one
+    /// out closes the pool and its vaults
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The contributor, gets the contribution account's rent back
+    /// 1. `[writable]` The contributor's token account for X once the pool was executed, for Y otherwise
+    /// 2. `[writable]` The pool account
+    /// 3. `[writable]` The contribution account
+    /// 4. `[writable]` The pool's X vault
+    /// 5. `[writable]` The X mint
+    /// 6. `[]` The token program of the X leg
+    /// 7. `[writable]` The pool's Y vault, already closed once the pool was executed
+    /// 8. `[writable]` The Y mint
+    /// 9. `[]` The token program of the Y leg
+    /// 10. `[]` The PDA account
+    /// 11. `[writable]` The pool's creator, gets the rent of the pool and its vaults back at the end
+    /// 12. `[]` The escrow account, a refund before the deadline needs it gone
+    LeavePool,
+
+    /// Sets up a DCA standing order: the temp account's Y is a budget spent on X one tranche at a time, no more often
+    /// than every `interval` seconds. Any keeper can trigger a tranche, against an escrow with ExecuteTranche or a signed
+    /// maker order with ExecuteTrancheWithOrder, and gets `keeper_reward` out of the budget for it. The standing order
+    /// account is created by the client beforehand, with StandingOrder::LEN bytes and owned by the program. Y mints with
+    /// transfer fees aren't supported
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The owner, gets the X and the rent back at the end
+    /// 1. `[writable]` Temporary token account holding the budget, handed to the PDA as the vault
+    /// 2. `[writable]` The standing order account, empty and rent exempt
+    /// 3. `[]` The owner's token account for X, where every tranche goes
+    /// 4. `[]` The Y mint
+    /// 5. `[]` The token program of the Y leg
+    /// 6. `[]` The token program of the X leg
+    InitStandingOrder {
+        /// the most Y one tranche spends, not counting the keeper's reward
+        amount_per_interval: u64,
+        /// the price limit: the least X a tranche of amount_per_interval has to buy, pro rata for a smaller one
+        min_amount_x: u64,
+        /// seconds from one tranche to the next
+        interval: i64,
+        /// Y paid to the keeper per tranche
+        keeper_reward: u64,
+    },
+
+    /// Spends one tranche of a standing order taking an escrow, the way Exchange would. Only plain Vault and Approve
+    /// mode escrows asking for Y can be taken, and only for at most amount_per_interval within the price limit.
+    /// Anyone can send it once the interval has passed
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` The keeper
+    /// 1. `[writable]` The keeper's token account for Y, gets the reward
+    /// 2. `[writable]` The standing order account
+    /// 3. `[writable]` The standing order's vault
+    /// 4. `[writable]` The owner's token account for X recorded in the standing order
+    /// 5. `[writable]` The escrow account
+    /// 6. `[writable]` The PDA's temp token account (Approve mode: the initializer's X account)
+    /// 7. `[writable]` The initializer's main account to send their rent fees to
+    /// 8. `[writable]` The initializer's token account that will receive Y
+    /// 9. `[writable]` The X mint
+    /// 10. `[]` The Y mint
+    /// 11. `[]` The token program of the X leg
+    /// 12. `[]` The token program of the Y leg
+    /// 13. `[]` The PDA account
+    /// 14. `[writable]` (optional) The rent payer recorded in the escrow, only needed if it isn't the initializer
+    ExecuteTranche,
+
+    /// Same as ExecuteTranche, filling a maker's signed order instead (see FillSignedOrder): the instruction right before
+    /// this one has to be the Ed25519 program verifying the maker's signature
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The keeper, pays the rent of the nonce bitmap if it has to be created
+    /// 1. `[writable]` The keeper's token account for Y, gets the reward
+    /// 2. `[writable]` The standing order account
+    /// 3. `[writable]` The standing order's vault
+    /// 4. `[writable]` The owner's token account for X recorded in the standing order
+    /// 5. `[writable]` Any token account for X owned by the maker, with the maker's order delegate as its delegate
+    /// 6. `[writable]` Any token account for Y owned by the maker
+    /// 7. `[writable]` The maker's nonce bitmap for the order's nonce, `order::nonce_bitmap_address`
+    /// 8. `[writable]` The X mint
+    /// 9. `[]` The Y mint
+    /// 10. `[]` The token program of the X leg
+    /// 11. `[]` The token program of the Y leg
+    /// 12. `[]` The PDA account
+    /// 13. `[]` The maker's order delegate, `order::delegate_address`
+    /// 14. `[]` The instructions sysvar
+    /// 15. `[]` The system program
+    ExecuteTrancheWithOrder { order: Order },
+
+    /// Stops a standing order: whatever is left of the budget goes back to the owner, and the vault and standing order
+    /// account are closed
+    ///
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` The owner, gets the rent back
+    /// 1. `[writable]` The owner's token account for Y to get the rest
+    /// 2. `[writable]` The standing order account
+    /// 3. `[writable]` The vault
+    /// 4. `[]` The Y mint
+    /// 5. `[]` The token program of the Y leg
+    /// 6. `[]` The PDA account
+    CancelStandingOrder,
 }
 
+/// How many accounts each escrow takes up in ExchangeMany
+pub const EXCHANGE_MANY_ACCOUNTS: usize = 5;
+
 impl EscrowInstruction {
     //takes the input, which is a bytearray
     //splits and gets the first byte - if it's 0 that means we move into the first state, which is InitEscrow, else we throw an error
@@ -55,17 +1057,502 @@ impl EscrowInstruction {
         Ok(match tag {
             0 => Self::InitEscrow {
                 amount: Self::unpack_amount(rest)?,
+                rent_payer: Self::unpack_rent_payer(rest, 8)?,
             },
             1 => Self::Exchange {
                 amount: Self::unpack_amount(rest)?,
             },
             2 => Self::Cancel {
-                bump_seed: rest[0], //can do it directly coz we only have one byte to unpack
+                bump_seed: *rest.first().ok_or(InvalidInstruction)?, //only one byte to unpack, but it still has to be there
+            },
+            3 => Self::InitApproveEscrow {
+                amount: Self::unpack_amount(rest)?,
+                offered_amount: Self::unpack_amount(rest.get(8..).ok_or(InvalidInstruction)?)?,
+                rent_payer: Self::unpack_rent_payer(rest, 16)?,
+            },
+            4 => Self::InitHtlcEscrow {
+                hashlock: Self::unpack_bytes32(rest)?,
+                timelock: Self::unpack_amount(rest.get(32..).ok_or(InvalidInstruction)?)? as i64,
+                rent_payer: Self::unpack_rent_payer(rest, 40)?,
+            },
+            5 => Self::Claim {
+                preimage: Self::unpack_bytes32(rest)?,
+            },
+            6 => Self::Refund,
+            7 => Self::WriteOption {
+                strike: Self::unpack_amount(rest)?,
+                premium: Self::unpack_amount(rest.get(8..).ok_or(InvalidInstruction)?)?,
+                expiry: Self::unpack_amount(rest.get(16..).ok_or(InvalidInstruction)?)? as i64,
+                rent_payer: Self::unpack_rent_payer(rest, 24)?,
+            },
+            8 => Self::BuyOption {
+                premium: Self::unpack_amount(rest)?,
+            },
+            9 => Self::ExerciseOption {
+                amount: Self::unpack_amount(rest)?,
+            },
+            10 => Self::ReclaimOption,
+            11 => Self::OfferLoan {
+                principal: Self::unpack_amount(rest)?,
+                interest: Self::unpack_amount(rest.get(8..).ok_or(InvalidInstruction)?)?,
+                duration: Self::unpack_amount(rest.get(16..).ok_or(InvalidInstruction)?)? as i64,
+                rent_payer: Self::unpack_rent_payer(rest, 24)?,
+            },
+            12 => Self::FundLoan {
+                principal: Self::unpack_amount(rest)?,
+            },
+            13 => Self::RepayLoan,
+            14 => Self::ForecloseLoan,
+            15 => Self::CancelLoan,
+            16 => Self::InitDutchAuction {
+                start_price: Self::unpack_amount(rest)?,
+                floor_price: Self::unpack_amount(rest.get(8..).ok_or(InvalidInstruction)?)?,
+                start_time: Self::unpack_amount(rest.get(16..).ok_or(InvalidInstruction)?)? as i64,
+                end_time: Self::unpack_amount(rest.get(24..).ok_or(InvalidInstruction)?)? as i64,
+                decay: *rest.get(32).ok_or(InvalidInstruction)?,
+                rent_payer: Self::unpack_rent_payer(rest, 33)?,
+            },
+            17 => Self::TakeDutchAuction {
+                amount: Self::unpack_amount(rest)?,
+                max_price: Self::unpack_amount(rest.get(8..).ok_or(InvalidInstruction)?)?,
             },
+            18 => Self::InitEnglishAuction {
+                reserve_price: Self::unpack_amount(rest)?,
+                min_increment: Self::unpack_amount(rest.get(8..).ok_or(InvalidInstruction)?)?,
+                end_time: Self::unpack_amount(rest.get(16..).ok_or(InvalidInstruction)?)? as i64,
+                extension: Self::unpack_amount(rest.get(24..).ok_or(InvalidInstruction)?)? as i64,
+                rent_payer: Self::unpack_rent_payer(rest, 32)?,
+            },
+            19 => Self::Bid {
+                amount: Self::unpack_amount(rest)?,
+            },
+            20 => Self::SettleAuction,
+            21 => Self::InitSealedAuction {
+                reserve_price: Self::unpack_amount(rest)?,
+                bond: Self::unpack_amount(rest.get(8..).ok_or(InvalidInstruction)?)?,
+                commit_end: Self::unpack_amount(rest.get(16..).ok_or(InvalidInstruction)?)? as i64,
+                reveal_end: Self::unpack_amount(rest.get(24..).ok_or(InvalidInstruction)?)? as i64,
+                second_price: match rest.get(32) {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(InvalidInstruction.into()),
+                },
+                rent_payer: Self::unpack_rent_payer(rest, 33)?,
+            },
+            22 => Self::CommitBid {
+                commitment: Self::unpack_bytes32(rest)?,
+            },
+            23 => Self::RevealBid {
+                amount: Self::unpack_amount(rest)?,
+                salt: Self::unpack_bytes32(rest.get(8..).ok_or(InvalidInstruction)?)?,
+            },
+            24 => Self::CloseBid,
+            25 => Self::Match,
+            26 => Self::SetMatchRecipient,
+            27 => Self::ExchangeMany {
+                target_x: Self::unpack_amount(rest)?,
+                max_y: Self::unpack_amount(rest.get(8..).ok_or(InvalidInstruction)?)?,
+            },
+            28 => Self::FillSignedOrder {
+                order: Order::unpack_from_slice(rest.get(..Order::LEN).ok_or(InvalidInstruction)?)?,
+            },
+            29 => Self::CancelOrderNonce {
+                nonce: Self::unpack_amount(rest)?,
+            },
+            30 => Self::FlashExchange {
+                amount: Self::unpack_amount(rest)?,
+            },
+            31 => Self::SettleFlash,
+            32 => Self::InitDistribution {
+                merkle_root: Self::unpack_bytes32(rest)?,
+                num_recipients: Self::unpack_amount(rest.get(32..).ok_or(InvalidInstruction)?)?,
+                deadline: Self::unpack_amount(rest.get(40..).ok_or(InvalidInstruction)?)? as i64,
+            },
+            33 => {
+                let (&proof_len, proof) = rest
+                    .get(16..)
+                    .and_then(|r| r.split_first())
+                    .ok_or(InvalidInstruction)?;
+                Self::ClaimDistribution {
+                    index: Self::unpack_amount(rest)?,
+                    amount: Self::unpack_amount(rest.get(8..).ok_or(InvalidInstruction)?)?,
+                    proof: (0..proof_len as usize)
+                        .map(|i| {
+                            Self::unpack_bytes32(proof.get(32 * i..).ok_or(InvalidInstruction)?)
+                        })
+                        .collect::<Result<_, _>>()?,
+                }
+            }
+            34 => Self::ReclaimDistribution,
+            35 => Self::InitNftEscrow {
+                request: match rest.first().ok_or(InvalidInstruction)? {
+                    0 => Request::Tokens,
+                    1 => Request::Mint,
+                    2 => Request::Collection,
+                    _ => return Err(InvalidInstruction.into()),
+                },
+                request_key: Pubkey::new_from_array(Self::unpack_bytes32(
+                    rest.get(1..).ok_or(InvalidInstruction)?,
+                )?),
+                rent_payer: Self::unpack_rent_payer(rest, 33)?,
+            },
+            36 => Self::ExchangeNft {
+                amount: Self::unpack_amount(rest)?,
+            },
+            37 => Self::InitRoyaltyEscrow {
+                amount: Self::unpack_amount(rest)?,
+                rent_payer: Self::unpack_rent_payer(rest, 8)?,
+            },
+            38 => Self::InitVesting {
+                start: Self::unpack_amount(rest)? as i64,
+                cliff: Self::unpack_amount(rest.get(8..).ok_or(InvalidInstruction)?)? as i64,
+                end: Self::unpack_amount(rest.get(16..).ok_or(InvalidInstruction)?)? as i64,
+            },
+            39 => Self::Withdraw,
+            40 => Self::RevokeVesting,
+            41 => Self::InitStream {
+                rate: Self::unpack_amount(rest)?,
+                duration: Self::unpack_amount(rest.get(8..).ok_or(InvalidInstruction)?)? as i64,
+            },
+            42 => Self::CancelStream,
+            43 => {
+                let (&count, amounts) = rest.split_first().ok_or(InvalidInstruction)?;
+                Self::InitMilestoneEscrow {
+                    amounts: (0..count as usize)
+                        .map(|i| {
+                            Self::unpack_amount(amounts.get(8 * i..).ok_or(InvalidInstruction)?)
+                        })
+                        .collect::<Result<_, _>>()?,
+                }
+            }
+            44 => Self::ReleaseMilestone {
+                milestone: *rest.first().ok_or(InvalidInstruction)?,
+            },
+            45 => Self::DisputeMilestone {
+                milestone: *rest.first().ok_or(InvalidInstruction)?,
+            },
+            46 => Self::ResolveDispute {
+                milestone: *rest.first().ok_or(InvalidInstruction)?,
+                payee_amount: Self::unpack_amount(rest.get(1..).ok_or(InvalidInstruction)?)?,
+            },
+            47 => Self::InitMultisig {
+                m: *rest.first().ok_or(InvalidInstruction)?,
+            },
+            48 => Self::SetEscrowAuthority,
+            49 => Self::InitPool {
+                deadline: Self::unpack_amount(rest)? as i64,
+            },
+            50 => Self::Contribute {
+                amount: Self::unpack_amount(rest)?,
+            },
+            51 => Self::ExecutePool,
+            52 => Self::LeavePool,
+            53 => Self::InitStandingOrder {
+                amount_per_interval: Self::unpack_amount(rest)?,
+                min_amount_x: Self::unpack_amount(rest.get(8..).ok_or(InvalidInstruction)?)?,
+                interval: Self::unpack_amount(rest.get(16..).ok_or(InvalidInstruction)?)? as i64,
+                keeper_reward: Self::unpack_amount(rest.get(24..).ok_or(InvalidInstruction)?)?,
+            },
+            54 => Self::ExecuteTranche,
+            55 => Self::ExecuteTrancheWithOrder {
+                order: Order::unpack_from_slice(rest.get(..Order::LEN).ok_or(InvalidInstruction)?)?,
+            },
+            56 => Self::CancelStandingOrder,
             _ => return Err(InvalidInstruction.into()),
         })
     }
 
+    /// Packs a [EscrowInstruction](enum.EscrowInstruction.html) into a byte buffer - the inverse of `unpack`, handy for clients and tests.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(size_of::<Self>());
+        match self {
+            Self::InitEscrow { amount, rent_payer } => {
+                buf.push(0);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                Self::pack_rent_payer(&mut buf, *rent_payer);
+            }
+            Self::Exchange { amount } => {
+                buf.push(1);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::Cancel { bump_seed } => {
+                buf.push(2);
+                buf.push(*bump_seed);
+            }
+            Self::InitApproveEscrow {
+                amount,
+                offered_amount,
+                rent_payer,
+            } => {
+                buf.push(3);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&offered_amount.to_le_bytes());
+                Self::pack_rent_payer(&mut buf, *rent_payer);
+            }
+            Self::InitHtlcEscrow {
+                hashlock,
+                timelock,
+                rent_payer,
+            } => {
+                buf.push(4);
+                buf.extend_from_slice(hashlock);
+                buf.extend_from_slice(&timelock.to_le_bytes());
+                Self::pack_rent_payer(&mut buf, *rent_payer);
+            }
+            Self::Claim { preimage } => {
+                buf.push(5);
+                buf.extend_from_slice(preimage);
+            }
+            Self::Refund => buf.push(6),
+            Self::WriteOption {
+                strike,
+                premium,
+                expiry,
+                rent_payer,
+            } => {
+                buf.push(7);
+                buf.extend_from_slice(&strike.to_le_bytes());
+                buf.extend_from_slice(&premium.to_le_bytes());
+                buf.extend_from_slice(&expiry.to_le_bytes());
+                Self::pack_rent_payer(&mut buf, *rent_payer);
+            }
+            Self::BuyOption { premium } => {
+                buf.push(8);
+                buf.extend_from_slice(&premium.to_le_bytes());
+            }
+            Self::ExerciseOption { amount } => {
+                buf.push(9);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::ReclaimOption => buf.push(10),
+            Self::OfferLoan {
+                principal,
+                interest,
+                duration,
+                rent_payer,
+            } => {
+                buf.push(11);
+                buf.extend_from_slice(&principal.to_le_bytes());
+                buf.extend_from_slice(&interest.to_le_bytes());
+                buf.extend_from_slice(&duration.to_le_bytes());
+                Self::pack_rent_payer(&mut buf, *rent_payer);
+            }
+            Self::FundLoan { principal } => {
+                buf.push(12);
+                buf.extend_from_slice(&principal.to_le_bytes());
+            }
+            Self::RepayLoan => buf.push(13),
+            Self::ForecloseLoan => buf.push(14),
+            Self::CancelLoan => buf.push(15),
+            Self::InitDutchAuction {
+                start_price,
+                floor_price,
+                start_time,
+                end_time,
+                decay,
+                rent_payer,
+            } => {
+                buf.push(16);
+                buf.extend_from_slice(&start_price.to_le_bytes());
+                buf.extend_from_slice(&floor_price.to_le_bytes());
+                buf.extend_from_slice(&start_time.to_le_bytes());
+                buf.extend_from_slice(&end_time.to_le_bytes());
+                buf.push(*decay);
+                Self::pack_rent_payer(&mut buf, *rent_payer);
+            }
+            Self::TakeDutchAuction { amount, max_price } => {
+                buf.push(17);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&max_price.to_le_bytes());
+            }
+            Self::InitEnglishAuction {
+                reserve_price,
+                min_increment,
+                end_time,
+                extension,
+                rent_payer,
+            } => {
+                buf.push(18);
+                buf.extend_from_slice(&reserve_price.to_le_bytes());
+                buf.extend_from_slice(&min_increment.to_le_bytes());
+                buf.extend_from_slice(&end_time.to_le_bytes());
+                buf.extend_from_slice(&extension.to_le_bytes());
+                Self::pack_rent_payer(&mut buf, *rent_payer);
+            }
+            Self::Bid { amount } => {
+                buf.push(19);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::SettleAuction => buf.push(20),
+            Self::InitSealedAuction {
+                reserve_price,
+                bond,
+                commit_end,
+                reveal_end,
+                second_price,
+                rent_payer,
+            } => {
+                buf.push(21);
+                buf.extend_from_slice(&reserve_price.to_le_bytes());
+                buf.extend_from_slice(&bond.to_le_bytes());
+                buf.extend_from_slice(&commit_end.to_le_bytes());
+                buf.extend_from_slice(&reveal_end.to_le_bytes());
+                buf.push(*second_price as u8);
+                Self::pack_rent_payer(&mut buf, *rent_payer);
+            }
+            Self::CommitBid { commitment } => {
+                buf.push(22);
+                buf.extend_from_slice(commitment);
+            }
+            Self::RevealBid { amount, salt } => {
+                buf.push(23);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(salt);
+            }
+            Self::CloseBid => buf.push(24),
+            Self::Match => buf.push(25),
+            Self::SetMatchRecipient => buf.push(26),
+            Self::ExchangeMany { target_x, max_y } => {
+                buf.push(27);
+                buf.extend_from_slice(&target_x.to_le_bytes());
+                buf.extend_from_slice(&max_y.to_le_bytes());
+            }
+            Self::FillSignedOrder { order } => {
+                buf.push(28);
+                let mut packed = [0; Order::LEN];
+                order.pack_into_slice(&mut packed);
+                buf.extend_from_slice(&packed);
+            }
+            Self::CancelOrderNonce { nonce } => {
+                buf.push(29);
+                buf.extend_from_slice(&nonce.to_le_bytes());
+            }
+            Self::FlashExchange { amount } => {
+                buf.push(30);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::SettleFlash => buf.push(31),
+            Self::InitDistribution {
+                merkle_root,
+                num_recipients,
+                deadline,
+            } => {
+                buf.push(32);
+                buf.extend_from_slice(merkle_root);
+                buf.extend_from_slice(&num_recipients.to_le_bytes());
+                buf.extend_from_slice(&deadline.to_le_bytes());
+            }
+            Self::ClaimDistribution {
+                index,
+                amount,
+                proof,
+            } => {
+                buf.push(33);
+                buf.extend_from_slice(&index.to_le_bytes());
+                buf.extend_from_slice(&amount.to_le_bytes());
+                // a u8 count, a proof that long covers more leaves than a bitmap account could ever track
+                buf.push(proof.len() as u8);
+                for hash in proof {
+                    buf.extend_from_slice(hash);
+                }
+            }
+            Self::ReclaimDistribution => buf.push(34),
+            Self::InitNftEscrow {
+                request,
+                request_key,
+                rent_payer,
+            } => {
+                buf.push(35);
+                buf.push(*request as u8);
+                buf.extend_from_slice(request_key.as_ref());
+                Self::pack_rent_payer(&mut buf, *rent_payer);
+            }
+            Self::ExchangeNft { amount } => {
+                buf.push(36);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::InitRoyaltyEscrow { amount, rent_payer } => {
+                buf.push(37);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                Self::pack_rent_payer(&mut buf, *rent_payer);
+            }
+            Self::InitVesting { start, cliff, end } => {
+                buf.push(38);
+                buf.extend_from_slice(&start.to_le_bytes());
+                buf.extend_from_slice(&cliff.to_le_bytes());
+                buf.extend_from_slice(&end.to_le_bytes());
+            }
+            Self::Withdraw => buf.push(39),
+            Self::RevokeVesting => buf.push(40),
+            Self::InitStream { rate, duration } => {
+                buf.push(41);
+                buf.extend_from_slice(&rate.to_le_bytes());
+                buf.extend_from_slice(&duration.to_le_bytes());
+            }
+            Self::CancelStream => buf.push(42),
+            Self::InitMilestoneEscrow { amounts } => {
+                buf.push(43);
+                // a u8 count, the processor takes at most MilestoneEscrow::MAX_MILESTONES anyway
+                buf.push(amounts.len() as u8);
+                for amount in amounts {
+                    buf.extend_from_slice(&amount.to_le_bytes());
+                }
+            }
+            Self::ReleaseMilestone { milestone } => {
+                buf.push(44);
+                buf.push(*milestone);
+            }
+            Self::DisputeMilestone { milestone } => {
+                buf.push(45);
+                buf.push(*milestone);
+            }
+            Self::ResolveDispute {
+                milestone,
+                payee_amount,
+            } => {
+                buf.push(46);
+                buf.push(*milestone);
+                buf.extend_from_slice(&payee_amount.to_le_bytes());
+            }
+            Self::InitMultisig { m } => {
+                buf.push(47);
+                buf.push(*m);
+            }
+            Self::SetEscrowAuthority => buf.push(48),
+            Self::InitPool { deadline } => {
+                buf.push(49);
+                buf.extend_from_slice(&deadline.to_le_bytes());
+            }
+            Self::Contribute { amount } => {
+                buf.push(50);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::ExecutePool => buf.push(51),
+            Self::LeavePool => buf.push(52),
+            Self::InitStandingOrder {
+                amount_per_interval,
+                min_amount_x,
+                interval,
+                keeper_reward,
+            } => {
+                buf.push(53);
+                buf.extend_from_slice(&amount_per_interval.to_le_bytes());
+                buf.extend_from_slice(&min_amount_x.to_le_bytes());
+                buf.extend_from_slice(&interval.to_le_bytes());
+                buf.extend_from_slice(&keeper_reward.to_le_bytes());
+            }
+            Self::ExecuteTranche => buf.push(54),
+            Self::ExecuteTrancheWithOrder { order } => {
+                buf.push(55);
+                let mut packed = [0; Order::LEN];
+                order.pack_into_slice(&mut packed);
+                buf.extend_from_slice(&packed);
+            }
+            Self::CancelStandingOrder => buf.push(56),
+        }
+        buf
+    }
+
     //takes the rest of the array and try to conver to a u64
     fn unpack_amount(input: &[u8]) -> Result<u64, ProgramError> {
         let amount = input
@@ -75,4 +1562,29 @@ impl EscrowInstruction {
             .ok_or(InvalidInstruction)?;
         Ok(amount)
     }
+
+    // the flag init instructions can end with, absent means no rent payer
+    fn unpack_rent_payer(input: &[u8], at: usize) -> Result<bool, ProgramError> {
+        match input.get(at) {
+            None | Some(0) => Ok(false),
+            Some(1) => Ok(true),
+            _ => Err(InvalidInstruction.into()),
+        }
+    }
+
+    // only written when set, so encodings from before the flag stay what they were
+    fn pack_rent_payer(buf: &mut Vec<u8>, rent_payer: bool) {
+        if rent_payer {
+            buf.push(1);
+        }
+    }
+
+    //same for a 32 byte hash
+    fn unpack_bytes32(input: &[u8]) -> Result<[u8; 32], ProgramError> {
+        let bytes = input
+            .get(..32)
+            .and_then(|slice| slice.try_into().ok())
+            .ok_or(InvalidInstruction)?;
+        Ok(bytes)
+    }
 }
@@ -1,6 +1,15 @@
 #[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
+pub mod auction;
 pub mod instruction;
+pub mod matching;
+pub mod merkle;
+pub mod metadata;
+pub mod order;
 pub mod error;
+pub mod event;
+pub mod flash;
 pub mod processor;
-pub mod state;
\ No newline at end of file
+pub mod state;
+pub mod token;
+pub mod vesting;
\ No newline at end of file
@@ -0,0 +1,17 @@
+// matching two escrows against each other. Each maker gets exactly what they asked for, whatever is left in the vaults
+// after that is the price improvement, and the cranker gets a cut of it for sending the transaction
+use crate::error::EscrowError;
+
+// 10% of the improvement. A match with no improvement pays the cranker nothing, the makers never pay for it
+pub const CRANK_FEE_BPS: u64 = 1_000;
+
+/// What's left in a vault holding `vault` once `owed` is paid out of it, or EscrowsDontCross if it can't cover that
+pub fn improvement(vault: u64, owed: u64) -> Result<u64, EscrowError> {
+    vault.checked_sub(owed).ok_or(EscrowError::EscrowsDontCross)
+}
+
+/// The cranker's cut of an improvement, rounded down
+pub fn crank_fee(improvement: u64) -> u64 {
+    // fits back into a u64, CRANK_FEE_BPS is below 10_000
+    (improvement as u128 * CRANK_FEE_BPS as u128 / 10_000) as u64
+}
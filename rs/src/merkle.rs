@@ -0,0 +1,24 @@
+// distributions commit to their recipients with a merkle root. The leaves are (index, recipient, amount), pairs are
+// hashed smallest first so a proof is just the sibling hashes on the way up, and leaves and nodes are hashed with
+// different prefixes so one can't be passed off as the other
+use solana_program::{hash::hashv, pubkey::Pubkey};
+
+pub fn leaf(index: u64, recipient: &Pubkey, amount: u64) -> [u8; 32] {
+    hashv(&[
+        &[0],
+        &index.to_le_bytes(),
+        recipient.as_ref(),
+        &amount.to_le_bytes(),
+    ])
+    .to_bytes()
+}
+
+pub fn node(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[&[1], left, right]).to_bytes()
+}
+
+/// Whether `proof` leads from `leaf` up to `root`
+pub fn verify(root: &[u8; 32], leaf: [u8; 32], proof: &[[u8; 32]]) -> bool {
+    proof.iter().fold(leaf, |hash, sibling| node(&hash, sibling)) == *root
+}
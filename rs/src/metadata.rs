@@ -0,0 +1,167 @@
+// reads Metaplex token metadata accounts, for NFT escrows that ask for "any NFT from collection C" and for paying
+// creators their royalties.
+// The metadata program is borsh all the way down, so fields after the first string move around with the data in
+// front of them - we walk the layout up to the collection and stop there. Accounts created before collections
+// existed simply end early, which reads as no collection.
+use std::convert::TryInto;
+
+use solana_program::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+
+pub mod metadata_program {
+    solana_program::declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+}
+
+// the metadata program's Key::MetadataV1
+const KEY_METADATA_V1: u8 = 4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Creator {
+    pub address: Pubkey,
+    pub verified: bool,
+    pub share: u8, //percent of the royalties
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Collection {
+    pub verified: bool, //signed off by the collection's authority, an unverified one is just a claim
+    pub key: Pubkey,    //the collection NFT's mint
+}
+
+/// The parts of a metadata account the escrow cares about
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+    pub mint: Pubkey,
+    pub seller_fee_basis_points: u16,
+    pub creators: Vec<Creator>,
+    pub collection: Option<Collection>,
+}
+
+/// The metadata account of `mint`
+pub fn metadata_address(mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"metadata", metadata_program::id().as_ref(), mint.as_ref()],
+        &metadata_program::id(),
+    )
+}
+
+/// Reads `mint`'s metadata out of `metadata_acc`, which has to be the real one: owned by the metadata program and at
+/// the address it derives for the mint
+pub fn unpack_metadata(
+    metadata_acc: &AccountInfo,
+    mint: &Pubkey,
+) -> Result<Metadata, ProgramError> {
+    if *metadata_acc.owner != metadata_program::id()
+        || *metadata_acc.key != metadata_address(mint).0
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let metadata = parse(&metadata_acc.try_borrow_data()?)?;
+    if metadata.mint != *mint {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(metadata)
+}
+
+/// Parses the bytes of a MetadataV1 account
+pub fn parse(data: &[u8]) -> Result<Metadata, ProgramError> {
+    let mut reader = Reader { data };
+    if reader.u8()? != KEY_METADATA_V1 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let _update_authority = reader.pubkey()?;
+    let mint = reader.pubkey()?;
+    // name, symbol, uri
+    for _ in 0..3 {
+        let len = reader.u32()? as usize;
+        reader.take(len)?;
+    }
+    let seller_fee_basis_points = u16::from_le_bytes(reader.take(2)?.try_into().unwrap());
+    let creators = if reader.bool()? {
+        (0..reader.u32()?)
+            .map(|_| {
+                Ok(Creator {
+                    address: reader.pubkey()?,
+                    verified: reader.bool()?,
+                    share: reader.u8()?,
+                })
+            })
+            .collect::<Result<_, ProgramError>>()?
+    } else {
+        vec![]
+    };
+    let _primary_sale_happened = reader.bool()?;
+    let _is_mutable = reader.bool()?;
+
+    // everything from here on was added in later versions of the metadata program
+    let mut collection = None;
+    if !reader.data.is_empty() {
+        // edition nonce and token standard, both Option<u8>
+        for _ in 0..2 {
+            if reader.bool()? {
+                reader.u8()?;
+            }
+        }
+        if reader.bool()? {
+            collection = Some(Collection {
+                verified: reader.bool()?,
+                key: reader.pubkey()?,
+            });
+        }
+    }
+
+    Ok(Metadata {
+        mint,
+        seller_fee_basis_points,
+        creators,
+        collection,
+    })
+}
+
+/// What each creator with a share gets out of a sale at `price`, in the order of `metadata.creators`. Rounds down,
+/// whatever is left over stays with the seller
+pub fn royalties(price: u64, metadata: &Metadata) -> Vec<u64> {
+    // the metadata program caps it at 100%, but we don't take its word for it
+    let bps = metadata.seller_fee_basis_points.min(10_000) as u128;
+    let royalty = price as u128 * bps / 10_000;
+    metadata
+        .creators
+        .iter()
+        .filter(|creator| creator.share > 0)
+        .map(|creator| (royalty * creator.share.min(100) as u128 / 100) as u64)
+        .collect()
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ProgramError> {
+        if self.data.len() < len {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, ProgramError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, ProgramError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32, ProgramError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn pubkey(&mut self) -> Result<Pubkey, ProgramError> {
+        Ok(Pubkey::new_from_array(self.take(32)?.try_into().unwrap()))
+    }
+}
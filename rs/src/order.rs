@@ -0,0 +1,152 @@
+// off-chain signed orders. A maker signs an Order with their wallet key and hands it out, and the taker sends it on-chain
+// right after an Ed25519 program instruction verifying that signature. The runtime fails the whole transaction on a bad
+// signature before we ever run, so all that's left to us is to check the instruction is there and covers the right key
+// and message
+use std::convert::TryInto;
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    program_error::ProgramError,
+    program_pack::{Pack, Sealed},
+    pubkey::Pubkey,
+    sysvar,
+};
+
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+
+use crate::error::EscrowError;
+
+pub mod ed25519_program {
+    solana_program::declare_id!("Ed25519SigVerify111111111111111111111111111");
+}
+
+/// Every signed message starts with this, so an order signature can't be passed off as one over anything else
+pub const ORDER_PREFIX: &[u8] = b"EscrowOrder";
+
+// the Ed25519 program's data: a signature count and a padding byte, then 7 u16 offsets per signature
+const OFFSETS_START: usize = 2;
+const OFFSETS_LEN: usize = 14;
+
+/// The maker sells `amount_x` of `mint_x` for `amount_y` of `mint_y`, to whoever fills it first before `expiry`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Order {
+    pub maker: Pubkey,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub amount_x: u64, //leaves the maker's account, the taker gets less if X charges a fee
+    pub amount_y: u64, //what the maker receives, the taker pays any fee on top
+    pub expiry: i64,   //unix timestamp, the order can't be filled from then on
+    pub nonce: u64,    //each one can be used once, by a fill or by CancelOrderNonce
+}
+
+impl Order {
+    /// The bytes the maker signs: the prefix, the program id (so an order is only good on one deployment) and the order
+    pub fn message(&self, program_id: &Pubkey) -> Vec<u8> {
+        let mut message = Vec::with_capacity(ORDER_PREFIX.len() + 32 + Order::LEN);
+        message.extend_from_slice(ORDER_PREFIX);
+        message.extend_from_slice(program_id.as_ref());
+        let mut order = [0; Order::LEN];
+        self.pack_into_slice(&mut order);
+        message.extend_from_slice(&order);
+        message
+    }
+}
+
+impl Sealed for Order {}
+
+impl Pack for Order {
+    // 3x32 (pubkeys) + 2x8 (amounts) + 8 (expiry i64) + 8 (nonce)
+    const LEN: usize = 128;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Order::LEN];
+        let (maker, mint_x, mint_y, amount_x, amount_y, expiry, nonce) =
+            array_refs![src, 32, 32, 32, 8, 8, 8, 8];
+
+        Ok(Order {
+            maker: Pubkey::new_from_array(*maker),
+            mint_x: Pubkey::new_from_array(*mint_x),
+            mint_y: Pubkey::new_from_array(*mint_y),
+            amount_x: u64::from_le_bytes(*amount_x),
+            amount_y: u64::from_le_bytes(*amount_y),
+            expiry: i64::from_le_bytes(*expiry),
+            nonce: u64::from_le_bytes(*nonce),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Order::LEN];
+        let (maker_dst, mint_x_dst, mint_y_dst, amount_x_dst, amount_y_dst, expiry_dst, nonce_dst) =
+            mut_array_refs![dst, 32, 32, 32, 8, 8, 8, 8];
+
+        maker_dst.copy_from_slice(self.maker.as_ref());
+        mint_x_dst.copy_from_slice(self.mint_x.as_ref());
+        mint_y_dst.copy_from_slice(self.mint_y.as_ref());
+        *amount_x_dst = self.amount_x.to_le_bytes();
+        *amount_y_dst = self.amount_y.to_le_bytes();
+        *expiry_dst = self.expiry.to_le_bytes();
+        *nonce_dst = self.nonce.to_le_bytes();
+    }
+}
+
+/// The bitmap account holding `maker`'s nonces of one window, see `NonceBitmap`
+pub fn nonce_bitmap_address(maker: &Pubkey, window: u64, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"nonces", maker.as_ref(), &window.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// The delegate `maker` approves to move the X of their signed orders. It's theirs alone, so an order can only spend
+/// what the maker approved for orders, and not an Approve mode escrow's allowance
+pub fn delegate_address(maker: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"order", maker.as_ref()], program_id)
+}
+
+/// Checks that the instruction right before the current one has the Ed25519 program verify `signer`'s signature of
+/// `message`, with both of them inside that instruction's own data
+pub fn check_ed25519(
+    instructions_acc: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> ProgramResult {
+    if *instructions_acc.key != sysvar::instructions::id() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let data = instructions_acc.try_borrow_data()?;
+    if data.len() < 2 {
+        return Err(EscrowError::InvalidSignature.into());
+    }
+    let current = sysvar::instructions::load_current_index(&data);
+    let index = current
+        .checked_sub(1)
+        .ok_or(EscrowError::InvalidSignature)?;
+    let ix = sysvar::instructions::load_instruction_at(index as usize, &data)
+        .map_err(|_| EscrowError::InvalidSignature)?;
+    if ix.program_id != ed25519_program::id() || ix.data.first() != Some(&1) {
+        return Err(EscrowError::InvalidSignature.into());
+    }
+
+    let offsets = ix
+        .data
+        .get(OFFSETS_START..OFFSETS_START + OFFSETS_LEN)
+        .ok_or(EscrowError::InvalidSignature)?;
+    let offset =
+        |i: usize| u16::from_le_bytes(offsets[2 * i..2 * i + 2].try_into().unwrap()) as usize;
+    let (signature_ix, pubkey_offset, pubkey_ix) = (offset(1), offset(2), offset(3));
+    let (message_offset, message_size, message_ix) = (offset(4), offset(5), offset(6));
+    // u16::MAX is "this instruction" - anything else would have the key or message read from somewhere we don't look
+    if signature_ix != u16::MAX as usize
+        || pubkey_ix != u16::MAX as usize
+        || message_ix != u16::MAX as usize
+    {
+        return Err(EscrowError::InvalidSignature.into());
+    }
+    let signed_by = ix.data.get(pubkey_offset..pubkey_offset + 32);
+    let signed = ix.data.get(message_offset..message_offset + message_size);
+    if signed_by != Some(signer.as_ref()) || signed != Some(message) {
+        return Err(EscrowError::InvalidSignature.into());
+    }
+    Ok(())
+}
@@ -4,16 +4,29 @@ use solana_program::{
     msg,
     program::{invoke, invoke_signed},
     program_error::ProgramError,
+    program_option::COption,
     program_pack::{IsInitialized, Pack},
     pubkey::Pubkey,
+    system_instruction, system_program,
     sysvar::{rent::Rent, Sysvar},
 };
 
-use spl_token::state::Account as TokenAccount;
-
-use crate::state::Escrow;
-use crate::{error::EscrowError, instruction::EscrowInstruction};
+use crate::order::{self, Order};
+use crate::state::{
+    AccountType, AuctionTerms, ClaimedBitmap, Contribution, Distribution, DutchTerms, EnglishTerms,
+    Escrow, EscrowMode, HtlcTerms, LegacyEscrow, Loan, MilestoneEscrow, Multisig, NonceBitmap,
+    OptionTerms, Pool, Request, SealedBid, SealedTerms, StandingOrder, Stream, Terms, Vesting,
+};
+use crate::{auction, flash, matching, merkle, metadata, token, vesting};
+use crate::{
+    error::EscrowError,
+    event::EscrowEvent,
+    instruction::{EscrowInstruction, EXCHANGE_MANY_ACCOUNTS},
+};
+use solana_program::clock::Clock;
+use solana_program::hash::hash;
 use solana_program::log::sol_log_compute_units;
+use spl_token::state::Account as TokenAccount;
 
 pub struct Processor;
 
@@ -27,30 +40,370 @@ impl Processor {
         let instruction = EscrowInstruction::unpack(instruction_data)?;
 
         match instruction {
-            EscrowInstruction::InitEscrow { amount } => {
+            EscrowInstruction::InitEscrow { amount, rent_payer } => {
                 msg!("Instruction: InitEscrow"); // this is how you do logging in solana! cool!
-                Self::process_init_escrow(accounts, amount, program_id)
+                Self::process_init_escrow(
+                    &mut accounts.iter(),
+                    amount,
+                    EscrowMode::Vault,
+                    0,
+                    rent_payer,
+                    program_id,
+                )
+            }
+            EscrowInstruction::InitApproveEscrow {
+                amount,
+                offered_amount,
+                rent_payer,
+            } => {
+                msg!("Instruction: InitApproveEscrow");
+                Self::process_init_escrow(
+                    &mut accounts.iter(),
+                    amount,
+                    EscrowMode::Approve,
+                    offered_amount,
+                    rent_payer,
+                    program_id,
+                )
             }
             EscrowInstruction::Exchange { amount } => {
                 msg!("Instruction: Exchange");
-                Self::process_exchange(accounts, amount, program_id)
+                Self::process_exchange(accounts, amount, None, false, program_id)
             }
             EscrowInstruction::Cancel { bump_seed } => {
                 msg!("Instruction: Cancel");
                 Self::cancel_exchange(accounts, bump_seed, program_id)
             }
+            EscrowInstruction::InitHtlcEscrow {
+                hashlock,
+                timelock,
+                rent_payer,
+            } => {
+                msg!("Instruction: InitHtlcEscrow");
+                Self::process_init_htlc(accounts, hashlock, timelock, rent_payer, program_id)
+            }
+            EscrowInstruction::Claim { preimage } => {
+                msg!("Instruction: Claim");
+                Self::process_claim(accounts, preimage, program_id)
+            }
+            EscrowInstruction::Refund => {
+                msg!("Instruction: Refund");
+                Self::process_reclaim(accounts, EscrowMode::Htlc, program_id)
+            }
+            EscrowInstruction::WriteOption {
+                strike,
+                premium,
+                expiry,
+                rent_payer,
+            } => {
+                msg!("Instruction: WriteOption");
+                Self::process_write_option(
+                    accounts, strike, premium, expiry, rent_payer, program_id,
+                )
+            }
+            EscrowInstruction::BuyOption { premium } => {
+                msg!("Instruction: BuyOption");
+                Self::process_buy_option(accounts, premium)
+            }
+            EscrowInstruction::ExerciseOption { amount } => {
+                msg!("Instruction: ExerciseOption");
+                Self::process_exchange(accounts, amount, None, true, program_id)
+            }
+            EscrowInstruction::ReclaimOption => {
+                msg!("Instruction: ReclaimOption");
+                Self::process_reclaim(accounts, EscrowMode::CoveredCall, program_id)
+            }
+            EscrowInstruction::OfferLoan {
+                principal,
+                interest,
+                duration,
+                rent_payer,
+            } => {
+                msg!("Instruction: OfferLoan");
+                Self::process_offer_loan(
+                    accounts, principal, interest, duration, rent_payer, program_id,
+                )
+            }
+            EscrowInstruction::FundLoan { principal } => {
+                msg!("Instruction: FundLoan");
+                Self::process_fund_loan(accounts, principal)
+            }
+            EscrowInstruction::RepayLoan => {
+                msg!("Instruction: RepayLoan");
+                Self::process_repay_loan(accounts, program_id)
+            }
+            EscrowInstruction::ForecloseLoan => {
+                msg!("Instruction: ForecloseLoan");
+                Self::process_foreclose_loan(accounts, program_id)
+            }
+            EscrowInstruction::CancelLoan => {
+                msg!("Instruction: CancelLoan");
+                Self::process_cancel_loan(accounts, program_id)
+            }
+            EscrowInstruction::InitDutchAuction {
+                start_price,
+                floor_price,
+                start_time,
+                end_time,
+                decay,
+                rent_payer,
+            } => {
+                msg!("Instruction: InitDutchAuction");
+                Self::process_init_dutch_auction(
+                    accounts,
+                    start_price,
+                    floor_price,
+                    start_time,
+                    end_time,
+                    decay,
+                    rent_payer,
+                    program_id,
+                )
+            }
+            EscrowInstruction::TakeDutchAuction { amount, max_price } => {
+                msg!("Instruction: TakeDutchAuction");
+                Self::process_exchange(accounts, amount, Some(max_price), false, program_id)
+            }
+            EscrowInstruction::InitEnglishAuction {
+                reserve_price,
+                min_increment,
+                end_time,
+                extension,
+                rent_payer,
+            } => {
+                msg!("Instruction: InitEnglishAuction");
+                Self::process_init_english_auction(
+                    accounts,
+                    reserve_price,
+                    min_increment,
+                    end_time,
+                    extension,
+                    rent_payer,
+                    program_id,
+                )
+            }
+            EscrowInstruction::Bid { amount } => {
+                msg!("Instruction: Bid");
+                Self::process_bid(accounts, amount, program_id)
+            }
+            EscrowInstruction::SettleAuction => {
+                msg!("Instruction: SettleAuction");
+                Self::process_settle_auction(accounts, program_id)
+            }
+            EscrowInstruction::InitSealedAuction {
+                reserve_price,
+                bond,
+                commit_end,
+                reveal_end,
+                second_price,
+                rent_payer,
+            } => {
+                msg!("Instruction: InitSealedAuction");
+                Self::process_init_sealed_auction(
+                    accounts,
+                    reserve_price,
+                    bond,
+                    commit_end,
+                    reveal_end,
+                    second_price,
+                    rent_payer,
+                    program_id,
+                )
+            }
+            EscrowInstruction::CommitBid { commitment } => {
+                msg!("Instruction: CommitBid");
+                Self::process_commit_bid(accounts, commitment, program_id)
+            }
+            EscrowInstruction::RevealBid { amount, salt } => {
+                msg!("Instruction: RevealBid");
+                Self::process_reveal_bid(accounts, amount, salt, program_id)
+            }
+            EscrowInstruction::CloseBid => {
+                msg!("Instruction: CloseBid");
+                Self::process_close_bid(accounts, program_id)
+            }
+            EscrowInstruction::Match => {
+                msg!("Instruction: Match");
+                Self::process_match(accounts, program_id)
+            }
+            EscrowInstruction::SetMatchRecipient => {
+                msg!("Instruction: SetMatchRecipient");
+                Self::process_set_match_recipient(accounts, program_id)
+            }
+            EscrowInstruction::ExchangeMany { target_x, max_y } => {
+                msg!("Instruction: ExchangeMany");
+                Self::process_exchange_many(accounts, target_x, max_y, program_id)
+            }
+            EscrowInstruction::FillSignedOrder { order } => {
+                msg!("Instruction: FillSignedOrder");
+                Self::process_fill_signed_order(accounts, &order, program_id)
+            }
+            EscrowInstruction::CancelOrderNonce { nonce } => {
+                msg!("Instruction: CancelOrderNonce");
+                Self::process_cancel_order_nonce(accounts, nonce, program_id)
+            }
+            EscrowInstruction::FlashExchange { amount } => {
+                msg!("Instruction: FlashExchange");
+                Self::process_flash_exchange(accounts, amount, program_id)
+            }
+            EscrowInstruction::SettleFlash => {
+                msg!("Instruction: SettleFlash");
+                Self::process_settle_flash(accounts)
+            }
+            EscrowInstruction::InitDistribution {
+                merkle_root,
+                num_recipients,
+                deadline,
+            } => {
+                msg!("Instruction: InitDistribution");
+                Self::process_init_distribution(
+                    accounts,
+                    merkle_root,
+                    num_recipients,
+                    deadline,
+                    program_id,
+                )
+            }
+            EscrowInstruction::ClaimDistribution {
+                index,
+                amount,
+                proof,
+            } => {
+                msg!("Instruction: ClaimDistribution");
+                Self::process_claim_distribution(accounts, index, amount, &proof, program_id)
+            }
+            EscrowInstruction::ReclaimDistribution => {
+                msg!("Instruction: ReclaimDistribution");
+                Self::process_reclaim_distribution(accounts, program_id)
+            }
+            EscrowInstruction::InitNftEscrow {
+                request,
+                request_key,
+                rent_payer,
+            } => {
+                msg!("Instruction: InitNftEscrow");
+                Self::process_init_nft_escrow(
+                    accounts,
+                    request,
+                    request_key,
+                    rent_payer,
+                    program_id,
+                )
+            }
+            EscrowInstruction::ExchangeNft { amount } => {
+                msg!("Instruction: ExchangeNft");
+                Self::process_exchange_nft(accounts, amount, program_id)
+            }
+            EscrowInstruction::InitRoyaltyEscrow { amount, rent_payer } => {
+                msg!("Instruction: InitRoyaltyEscrow");
+                Self::process_init_royalty_escrow(accounts, amount, rent_payer, program_id)
+            }
+            EscrowInstruction::InitVesting { start, cliff, end } => {
+                msg!("Instruction: InitVesting");
+                Self::process_init_vesting(accounts, start, cliff, end, program_id)
+            }
+            EscrowInstruction::Withdraw => {
+                msg!("Instruction: Withdraw");
+                Self::process_withdraw(accounts, program_id)
+            }
+            EscrowInstruction::RevokeVesting => {
+                msg!("Instruction: RevokeVesting");
+                Self::process_revoke_vesting(accounts, program_id)
+            }
+            EscrowInstruction::InitStream { rate, duration } => {
+                msg!("Instruction: InitStream");
+                Self::process_init_stream(accounts, rate, duration, program_id)
+            }
+            EscrowInstruction::CancelStream => {
+                msg!("Instruction: CancelStream");
+                Self::process_cancel_stream(accounts, program_id)
+            }
+            EscrowInstruction::InitMilestoneEscrow { amounts } => {
+                msg!("Instruction: InitMilestoneEscrow");
+                Self::process_init_milestone_escrow(accounts, &amounts, program_id)
+            }
+            EscrowInstruction::ReleaseMilestone { milestone } => {
+                msg!("Instruction: ReleaseMilestone");
+                Self::process_release_milestone(accounts, milestone, program_id)
+            }
+            EscrowInstruction::DisputeMilestone { milestone } => {
+                msg!("Instruction: DisputeMilestone");
+                Self::process_dispute_milestone(accounts, milestone, program_id)
+            }
+            EscrowInstruction::ResolveDispute {
+                milestone,
+                payee_amount,
+            } => {
+                msg!("Instruction: ResolveDispute");
+                Self::process_resolve_dispute(accounts, milestone, payee_amount, program_id)
+            }
+            EscrowInstruction::InitMultisig { m } => {
+                msg!("Instruction: InitMultisig");
+                Self::process_init_multisig(accounts, m, program_id)
+            }
+            EscrowInstruction::SetEscrowAuthority => {
+                msg!("Instruction: SetEscrowAuthority");
+                Self::process_set_escrow_authority(accounts, program_id)
+            }
+            EscrowInstruction::InitPool { deadline } => {
+                msg!("Instruction: InitPool");
+                Self::process_init_pool(accounts, deadline, program_id)
+            }
+            EscrowInstruction::Contribute { amount } => {
+                msg!("Instruction: Contribute");
+                Self::process_contribute(accounts, amount, program_id)
+            }
+            EscrowInstruction::ExecutePool => {
+                msg!("Instruction: ExecutePool");
+                Self::process_execute_pool(accounts, program_id)
+            }
+            EscrowInstruction::LeavePool => {
+                msg!("Instruction: LeavePool");
+                Self::process_leave_pool(accounts, program_id)
+            }
+            EscrowInstruction::InitStandingOrder {
+                amount_per_interval,
+                min_amount_x,
+                interval,
+                keeper_reward,
+            } => {
+                msg!("Instruction: InitStandingOrder");
+                Self::process_init_standing_order(
+                    accounts,
+                    amount_per_interval,
+                    min_amount_x,
+                    interval,
+                    keeper_reward,
+                    program_id,
+                )
+            }
+            EscrowInstruction::ExecuteTranche => {
+                msg!("Instruction: ExecuteTranche");
+                Self::process_execute_tranche(accounts, program_id)
+            }
+            EscrowInstruction::ExecuteTrancheWithOrder { order } => {
+                msg!("Instruction: ExecuteTrancheWithOrder");
+                Self::process_execute_tranche_with_order(accounts, &order, program_id)
+            }
+            EscrowInstruction::CancelStandingOrder => {
+                msg!("Instruction: CancelStandingOrder");
+                Self::process_cancel_standing_order(accounts, program_id)
+            }
         }
     }
 
-    fn process_init_escrow(
-        accounts: &[AccountInfo],
+    // both ways of listing share this. Vault mode hands the whole temp account to the PDA,
+    // Approve mode leaves the tokens where they are and only lets the PDA pull `offered_amount` out of them
+    // takes the accounts as an iterator, so the auctions can go on with their own accounts after ours
+    fn process_init_escrow<'a, 'b>(
+        account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
         amount: u64,
+        mode: EscrowMode,
+        offered_amount: u64,
+        rent_payer: bool,
         program_id: &Pubkey,
     ) -> ProgramResult {
         // ----------------------------------------------------------------------------- get the accs
-        //turn accounts into an iterator
-        let account_info_iter = &mut accounts.iter();
-
         // [0] first account = signer, Alice. This is by convention
         let initializer = next_account_info(account_info_iter)?;
 
@@ -64,9 +417,9 @@ impl Processor {
         // needs to be owned by the token program as we're going to be transfering to a PDA, but again no need to check for the same reason
         let temp_token_account = next_account_info(account_info_iter)?;
 
-        // [2] next = receiver for the Y token for alice
+        // [2] next = receiver for the Y token for alice. Can belong to either token program, independently of the X leg
         let token_to_receive_account = next_account_info(account_info_iter)?;
-        if *token_to_receive_account.owner != spl_token::id() {
+        if !token::is_token_program(token_to_receive_account.owner) {
             return Err(ProgramError::IncorrectProgramId);
         }
 
@@ -103,9 +456,10 @@ impl Processor {
         escrow_info.temp_token_account_pubkey = *temp_token_account.key; //alice's X account
         escrow_info.initializer_token_to_receive_account_pubkey = *token_to_receive_account.key; //alice's Y account
         escrow_info.expected_amount = amount;
-
-        //now pack back into bytes. Note we're taking a mutable reference so it's in place.
-        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())?;
+        escrow_info.mode = mode;
+        escrow_info.offered_amount = offered_amount;
+        escrow_info.terms = Terms::new(mode);
+        // the rent payer is the last account, we'll get to it below and pack the state then
 
         // ----------------------------------------------------------------------------- pda
         // we're going to assign ownership of the temp account to a PDA
@@ -117,22 +471,147 @@ impl Processor {
         // ----------------------------------------------------------------------------- cross-program invocation (CPI)
         // 2) do the cross program call (done using invoke / invoke_signed)
 
-        // [5] next - get token_program account
-        // in theory we'd need to check that token_program is truly the account we're expecting it to be, but spl-token below does it for us already
+        // [5] next - get token_program account, the one that owns the temp account (X leg)
+        // either the original token program or Token-2022, so we have to check which one we got
         let token_program = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program)?;
+
+        // [6] + [7] the X and Y mints. Token-2022 mints can carry extensions the escrow can't work with (eg a permanent delegate
+        // could pull X straight out of our temp account), better to find out now than when someone tries to take the trade
+        let mint_x = next_account_info(account_info_iter)?;
+        let mint_y = next_account_info(account_info_iter)?;
+        let temp_token_account_info =
+            token::unpack_token_account(temp_token_account, token_program.key)?;
+        let (token_to_receive_account_info, token_to_receive_extensions) =
+            token::unpack_token_account_with_extensions(
+                token_to_receive_account,
+                token_to_receive_account.owner,
+            )?;
+        if temp_token_account_info.mint != *mint_x.key
+            || token_to_receive_account_info.mint != *mint_y.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        token::unpack_mint(mint_x, token_program.key)?;
+        token::unpack_mint(mint_y, token_to_receive_account.owner)?;
+        Self::check_temp_account(&temp_token_account_info, mode)?;
+        // Y gets to alice through a CPI, which can't put the memo in front of it
+        if token_to_receive_extensions.memo_transfer {
+            return Err(EscrowError::UnsupportedAccountExtension.into());
+        }
+
+        // [8] Approve mode only: the PDA itself, the token program wants to see the delegate's account
+        let pda_acc = match mode {
+            EscrowMode::Approve => Some(next_account_info(account_info_iter)?),
+            _ => None,
+        };
+
+        // [8] (Approve mode: [9]) if the instruction says so - a marketplace can sponsor the rent and get it back when
+        // the escrow closes. The auctions pass false and record theirs after their own accounts
+        escrow_info.rent_payer = Self::rent_payer(rent_payer, initializer, account_info_iter)?;
+
+        //now pack back into bytes. Note we're taking a mutable reference so it's in place.
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())?;
+
+        if let Some(pda_acc) = pda_acc {
+            // the account stays alice's, so it really has to be hers - otherwise anyone could list somebody else's
+            // tokens that happen to be delegated. And there has to be something to pull.
+            if temp_token_account_info.owner != *initializer.key {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if offered_amount == 0 || temp_token_account_info.amount < offered_amount {
+                return Err(ProgramError::InsufficientFunds);
+            }
+            // a cpi guard refuses the approve below, alice would have to approve outside of a CPI
+            let (_, extensions) =
+                token::unpack_token_account_with_extensions(temp_token_account, token_program.key)?;
+            if extensions.cpi_guard {
+                return Err(EscrowError::UnsupportedAccountExtension.into());
+            }
+
+            if *pda_acc.key != pda {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            // approve the PDA as delegate for exactly what's on offer. Same signature extension as set_owner below
+            let approve_ix = token::approve(
+                token_program.key,
+                temp_token_account.key,
+                &pda,
+                initializer.key,
+                offered_amount,
+            )?;
+
+            msg!("Calling the token program to approve the escrow as delegate...");
+
+            invoke(
+                &approve_ix,
+                &[
+                    temp_token_account.clone(),
+                    pda_acc.clone(),
+                    initializer.clone(),
+                    token_program.clone(),
+                ],
+            )?;
+            return Ok(());
+        }
+
+        Self::hand_to_pda(token_program, temp_token_account, initializer, &pda)
+    }
+
+    // the temp account has to be a plain one. A frozen account can't be traded out of, and wrapped SOL has its lamports
+    // and amount tied together in ways the escrow doesn't track
+    fn check_temp_account(
+        temp_token_account_info: &TokenAccount,
+        mode: EscrowMode,
+    ) -> ProgramResult {
+        if temp_token_account_info.is_frozen() {
+            return Err(EscrowError::TempAccountFrozen.into());
+        }
+        if temp_token_account_info.is_native() {
+            return Err(EscrowError::TempAccountIsNative.into());
+        }
+        // a vault is handed to the PDA as is, so nobody else may keep a hand on it: a delegate could pull X out of it and a
+        // close authority outlives the owner change. And an empty vault isn't much of an offer.
+        if mode != EscrowMode::Approve {
+            if temp_token_account_info.delegate.is_some() {
+                return Err(EscrowError::TempAccountHasDelegate.into());
+            }
+            if temp_token_account_info.close_authority.is_some() {
+                return Err(EscrowError::TempAccountHasCloseAuthority.into());
+            }
+            if temp_token_account_info.amount == 0 {
+                return Err(EscrowError::TempAccountEmpty.into());
+            }
+        }
+        Ok(())
+    }
+
+    // transfers ownership of the temp account to the PDA, which makes it the vault
+    fn hand_to_pda<'a>(
+        token_program: &AccountInfo<'a>,
+        temp_token_account: &AccountInfo<'a>,
+        initializer: &AccountInfo<'a>,
+        pda: &Pubkey,
+    ) -> ProgramResult {
+        // Token-2022 accounts can be locked against exactly this: an immutable owner never changes, and a cpi guard
+        // wants the owner to sign set_authority themselves, outside of a CPI
+        let (_, extensions) =
+            token::unpack_token_account_with_extensions(temp_token_account, token_program.key)?;
+        if extensions.immutable_owner || extensions.cpi_guard {
+            return Err(EscrowError::UnsupportedAccountExtension.into());
+        }
 
         // build the instruction.
-        // set_authority = helper function that allows us to use a builder pattern to create an ix that we'll pass on later
-        // https://docs.rs/spl-token/3.1.1/spl_token/instruction/fn.set_authority.html
-        let owner_change_ix = spl_token::instruction::set_authority(
+        // set_owner = helper function that allows us to use a builder pattern to create an ix that we'll pass on later
+        // same encoding as https://docs.rs/spl-token/3.1.1/spl_token/instruction/fn.set_authority.html, but works for Token-2022 too
+        let owner_change_ix = token::set_owner(
             token_program.key,      // this is the id of the token_program
             temp_token_account.key, //this is the account whose authority we'd like to change
-            Some(&pda),             // the account's new authority
-            spl_token::instruction::AuthorityType::AccountOwner, // the type of authority change (diff types exist)
-            initializer.key, // the current account owner's pubkey
+            pda,                    // the account's new authority
             // (!) this is key - When including a signed account in a program call, in all CPIs including that account made by that program inside the current instruction, the account will also be signed, i.e. the signature is extended to the CPIs.
             // basically because alice signed InitEscrow tx, the program can now include her signature in this CPI call
-            &[&initializer.key], // the public keys signing the CPI
+            initializer.key, // the current account owner's pubkey
         )?;
 
         msg!("Calling the token program to transfer token account ownership...");
@@ -150,9 +629,13 @@ impl Processor {
         Ok(())
     }
 
+    // also exercises options - that's an Exchange only the option's holder can do, and only before it expires.
+    // And takes Dutch auctions, which come with the most the taker will pay since the price depends on when they land
     fn process_exchange(
         accounts: &[AccountInfo],
         amount_expected_by_taker: u64,
+        max_price: Option<u64>,
+        exercise: bool,
         program_id: &Pubkey,
     ) -> ProgramResult {
         // ----------------------------------------------------------------------------- get all the accounts
@@ -173,7 +656,6 @@ impl Processor {
 
         // 3. `[writable]` The PDA's temp token account to get tokens from and eventually close
         let pda_temp_x_acc = next_account_info(account_info_iter)?;
-        let pda_temp_x_info = TokenAccount::unpack(&pda_temp_x_acc.data.borrow())?;
 
         // 4. `[writable]` The initializer's main account to send their rent fees to
         let initializer_main_acc = next_account_info(account_info_iter)?;
@@ -184,6 +666,28 @@ impl Processor {
         // 6. `[writable]` The escrow account holding the escrow info
         let escrow_acc = next_account_info(account_info_iter)?;
         let escrow_info = Escrow::unpack(&escrow_acc.data.borrow())?;
+        // an HTLC isn't for sale, its vault only moves by Claim or Refund. And an option can only be exercised, not taken,
+        // same for a Dutch auction - a plain Exchange would get it for the floor price. English auctions go by Bid and Settle
+        let is_option = escrow_info.mode == EscrowMode::CoveredCall;
+        let is_auction = escrow_info.mode == EscrowMode::DutchAuction;
+        if escrow_info.mode == EscrowMode::Htlc
+            || escrow_info.mode == EscrowMode::EnglishAuction
+            || escrow_info.mode == EscrowMode::SealedAuction
+            || escrow_info.mode == EscrowMode::FlashSettling
+            || escrow_info.request != Request::Tokens
+            || exercise != is_option
+            || max_price.is_some() != is_auction
+        {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        if let Terms::CoveredCall(option) = escrow_info.terms {
+            if option.holder_pubkey != *taker_main_acc.key {
+                return Err(EscrowError::NotOptionHolder.into());
+            }
+            if Clock::get()?.unix_timestamp >= option.expiry {
+                return Err(EscrowError::OptionExpired.into());
+            }
+        }
         // check that the passed temp account matches what's saved in escrow state
         if escrow_info.temp_token_account_pubkey != *pda_temp_x_acc.key {
             return Err(ProgramError::InvalidAccountData);
@@ -197,53 +701,164 @@ impl Processor {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        // 7. `[]` The token program
+        // 7. `[]` The token program of the X leg
         let token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        let pda_temp_x_info = token::unpack_token_account(pda_temp_x_acc, token_program_acc.key)?;
 
         // 8. `[]` The PDA account
         let pda_acc = next_account_info(account_info_iter)?;
 
+        // 9. `[writable]` The X mint - writable because Token-2022 may have to harvest withheld fees into it
+        let mint_x_acc = next_account_info(account_info_iter)?;
+        if pda_temp_x_info.mint != *mint_x_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mint_x = token::unpack_mint(mint_x_acc, token_program_acc.key)?;
+
+        // 10. `[]` The Y mint
+        let mint_y_acc = next_account_info(account_info_iter)?;
+
+        // 11. `[]` The token program of the Y leg, can be the same as 7.
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(y_token_program_acc)?;
+        let initializer_y_info =
+            token::unpack_token_account(initializer_y_acc, y_token_program_acc.key)?;
+        if initializer_y_info.mint != *mint_y_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mint_y = token::unpack_mint(mint_y_acc, y_token_program_acc.key)?;
+
+        // 12. + 13. royalty escrows: X's metadata, then the Y account of each creator with a share
+        let metadata = if escrow_info.royalties {
+            let metadata_acc = next_account_info(account_info_iter)?;
+            Some(metadata::unpack_metadata(metadata_acc, mint_x_acc.key)?)
+        } else {
+            None
+        };
+        let mut creator_y_accs = Vec::new();
+        for creator in metadata
+            .iter()
+            .flat_map(|metadata| metadata.creators.iter())
+            .filter(|creator| creator.share > 0)
+        {
+            let creator_y_acc = next_account_info(account_info_iter)?;
+            let creator_y_info =
+                token::unpack_token_account(creator_y_acc, y_token_program_acc.key)?;
+            if creator_y_info.owner != creator.address || creator_y_info.mint != *mint_y_acc.key {
+                return Err(EscrowError::CreatorAccountMismatch.into());
+            }
+            creator_y_accs.push(creator_y_acc);
+        }
+
+        // 12. (royalty escrows: after the creators) `[writable]` (optional) The rent payer, if it isn't alice
+        let rent_recipient_acc = Self::rent_recipient(
+            &escrow_info.rent_payer,
+            initializer_main_acc,
+            account_info_iter,
+        )?;
+
         // ------------------------------------------------------------------------------ do quant checks
 
         let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
 
-        if amount_expected_by_taker != pda_temp_x_info.amount {
+        // Token-2022 transfer fees depend on the epoch, and auction prices on the time. Only ask for the clock when we need it.
+        let clock = if mint_x.transfer_fee.is_some() || mint_y.transfer_fee.is_some() || is_auction
+        {
+            Clock::get()?
+        } else {
+            Clock::default()
+        };
+        let epoch = clock.epoch;
+
+        // what alice gets: her fixed price, or whatever the auction asks right now
+        let price = match max_price {
+            Some(max_price) => {
+                let terms = match &escrow_info.terms {
+                    Terms::DutchAuction(terms) => terms,
+                    _ => return Err(EscrowError::WrongEscrowMode.into()),
+                };
+                let price =
+                    auction::dutch_price(terms, escrow_info.expected_amount, clock.unix_timestamp)?;
+                if price > max_price {
+                    return Err(EscrowError::PriceAboveMaximum.into());
+                }
+                price
+            }
+            None => escrow_info.expected_amount,
+        };
+
+        // a vault hands over everything in it, a delegation only what was offered - the rest of the account is still alice's
+        let amount_x = match escrow_info.mode {
+            EscrowMode::Vault
+            | EscrowMode::Htlc
+            | EscrowMode::CoveredCall
+            | EscrowMode::DutchAuction
+            | EscrowMode::EnglishAuction
+            | EscrowMode::SealedAuction
+            | EscrowMode::FlashSettling => pda_temp_x_info.amount,
+            EscrowMode::Approve => escrow_info.offered_amount,
+        };
+
+        // the taker states what they expect to end up with, which is less than what's in the vault if X charges a fee
+        if amount_expected_by_taker != mint_x.received(amount_x, epoch)? {
             return Err(EscrowError::ExpectedAmountMismatch.into());
         }
 
         // ----------------------------------------------------------------------------- move Y from bob to alice
 
-        let transfer_to_initializer_ix = spl_token::instruction::transfer(
-            token_program_acc.key,
-            taker_y_acc.key,
-            initializer_y_acc.key,
-            taker_main_acc.key,
-            &[&taker_main_acc.key],
-            escrow_info.expected_amount,
-        )?;
+        // the creators' royalties come out of alice's price
+        let royalties = metadata
+            .as_ref()
+            .map_or_else(Vec::new, |metadata| metadata::royalties(price, metadata));
+        let price = price
+            .checked_sub(royalties.iter().sum())
+            .ok_or(EscrowError::AmountOverflow)?;
 
-        msg!("Calling the token program to transfer tokens to the escrow's initializer...");
+        for (recipient_acc, amount) in std::iter::once((initializer_y_acc, price))
+            .chain(creator_y_accs.into_iter().zip(royalties))
+        {
+            // alice asked to receive the price, so if Y charges a fee bob has to send a bit more. Same for the creators
+            let amount_to_recipient = mint_y.pre_fee_amount(amount, epoch)?;
+            let transfer_to_recipient_ix = token::transfer_checked(
+                y_token_program_acc.key,
+                taker_y_acc.key,
+                mint_y_acc.key,
+                recipient_acc.key,
+                taker_main_acc.key,
+                amount_to_recipient,
+                mint_y.decimals,
+            )?;
 
-        // use signature extension to make the token transfer to Alice's Y token account on Bob's behalf.
-        invoke(
-            &transfer_to_initializer_ix,
-            &[
-                taker_y_acc.clone(),
-                initializer_y_acc.clone(),
-                taker_main_acc.clone(),
-                token_program_acc.clone(),
-            ],
-        )?;
+            if recipient_acc.key == initializer_y_acc.key {
+                msg!("Calling the token program to transfer tokens to the escrow's initializer...");
+            } else {
+                msg!("Calling the token program to pay a creator their royalty...");
+            }
+
+            // use signature extension to make the token transfer to Alice's Y token account on Bob's behalf.
+            invoke(
+                &transfer_to_recipient_ix,
+                &[
+                    taker_y_acc.clone(),
+                    mint_y_acc.clone(),
+                    recipient_acc.clone(),
+                    taker_main_acc.clone(),
+                    y_token_program_acc.clone(),
+                ],
+            )?;
+        }
 
         // ----------------------------------------------------------------------------- move X from alice to bob
 
-        let transfer_to_taker_ix = spl_token::instruction::transfer(
+        let transfer_to_taker_ix = token::transfer_checked(
             token_program_acc.key, //always first
             pda_temp_x_acc.key,
+            mint_x_acc.key,
             taker_x_acc.key,
-            &pda,
-            &[&pda],
-            pda_temp_x_info.amount,
+            &pda, // owner in Vault mode, delegate in Approve mode - either way the PDA signs
+            amount_x,
+            mint_x.decimals,
         )?;
 
         msg!("Calling the token program to transfer tokens to the taker...");
@@ -259,6 +874,7 @@ impl Processor {
             &[
                 //the order DOES NOT MATTER
                 pda_temp_x_acc.clone(),
+                mint_x_acc.clone(),
                 taker_x_acc.clone(),
                 pda_acc.clone(), //has to be passed into the instruction to prevent preimage attacks
                 token_program_acc.clone(),
@@ -268,49 +884,285 @@ impl Processor {
 
         // ----------------------------------------------------------------------------- clean up
 
-        // rm [3 ]temp X acc
+        // rm [3 ]temp X acc (not in Approve mode, there it's alice's own account)
         // rm [6] escrow acc
 
-        // we close the account by transferring its "rent-exempt" balance out of it
-        let close_pdas_temp_acc_ix = spl_token::instruction::close_account(
+        if escrow_info.mode != EscrowMode::Approve {
+            Self::close_temp_account(
+                token_program_acc,
+                pda_temp_x_acc,
+                mint_x_acc,
+                rent_recipient_acc, //rent goes back to whoever paid it
+                pda_acc,
+                &pda,
+                bump_seed,
+            )?;
+        }
+
+        msg!("Closing the escrow account...");
+
+        Self::close_escrow_account(escrow_acc, rent_recipient_acc)
+    }
+
+    fn process_exchange_many(
+        accounts: &[AccountInfo],
+        target_x: u64,
+        max_y: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        // ----------------------------------------------------------------------------- get accs
+        let account_info_iter = &mut accounts.iter();
+
+        let taker_main_acc = next_account_info(account_info_iter)?;
+        if !taker_main_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let taker_y_acc = next_account_info(account_info_iter)?;
+        let taker_x_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        let mint_x_acc = next_account_info(account_info_iter)?;
+        let mint_y_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        token::check_token_program(y_token_program_acc)?;
+
+        // the rest are the escrows, a fixed number of accounts each
+        let listings = account_info_iter.as_slice();
+        if listings.is_empty() || !listings.len().is_multiple_of(EXCHANGE_MANY_ACCOUNTS) {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        if target_x == 0 {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+
+        // ----------------------------------------------------------------------------- price every escrow
+        let mint_x = token::unpack_mint(mint_x_acc, token_program_acc.key)?;
+        let mint_y = token::unpack_mint(mint_y_acc, y_token_program_acc.key)?;
+        let epoch = if mint_x.transfer_fee.is_some() || mint_y.transfer_fee.is_some() {
+            Clock::get()?.epoch
+        } else {
+            0
+        };
+
+        // (accounts, X the taker gets, Y the taker sends, whether Exchange needs the rent payer passed in)
+        let mut fills = Vec::with_capacity(listings.len() / EXCHANGE_MANY_ACCOUNTS);
+        for (i, listing) in listings.chunks(EXCHANGE_MANY_ACCOUNTS).enumerate() {
+            // the same escrow twice would count its X twice
+            if listings
+                .chunks(EXCHANGE_MANY_ACCOUNTS)
+                .take(i)
+                .any(|other| other[0].key == listing[0].key)
+            {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let escrow_info = Escrow::unpack(&listing[0].data.borrow())?;
+            // only fixed prices can be compared up front
+            if escrow_info.request != Request::Tokens || escrow_info.royalties {
+                return Err(EscrowError::WrongEscrowMode.into());
+            }
+            let amount_x = match escrow_info.mode {
+                EscrowMode::Vault => {
+                    token::unpack_token_account(&listing[1], token_program_acc.key)?.amount
+                }
+                EscrowMode::Approve => escrow_info.offered_amount,
+                _ => return Err(EscrowError::WrongEscrowMode.into()),
+            };
+            fills.push((
+                listing,
+                mint_x.received(amount_x, epoch)?,
+                mint_y.pre_fee_amount(escrow_info.expected_amount, epoch)?,
+                escrow_info.rent_payer != escrow_info.initializer_pubkey,
+            ));
+        }
+
+        // cheapest Y per X first. The sort is stable, so equal prices are taken in the order they were passed in
+        fills.sort_by(|a, b| (a.2 as u128 * b.1 as u128).cmp(&(b.2 as u128 * a.1 as u128)));
+
+        // ----------------------------------------------------------------------------- check the totals
+        let mut filled_x: u64 = 0;
+        let mut paid_y: u64 = 0;
+        let mut taken = 0;
+        for (_, received_x, sent_y, _) in fills.iter() {
+            if filled_x >= target_x {
+                break;
+            }
+            filled_x = filled_x
+                .checked_add(*received_x)
+                .ok_or(EscrowError::AmountOverflow)?;
+            paid_y = paid_y
+                .checked_add(*sent_y)
+                .ok_or(EscrowError::AmountOverflow)?;
+            taken += 1;
+        }
+        if filled_x < target_x {
+            return Err(EscrowError::TargetNotFilled.into());
+        }
+        if paid_y > max_y {
+            return Err(EscrowError::PriceAboveMaximum.into());
+        }
+
+        // ----------------------------------------------------------------------------- take them
+        // each one the same way a single Exchange would, checks included
+        for (listing, received_x, _, pass_rent_payer) in fills.iter().take(taken) {
+            let mut exchange_accounts = vec![
+                taker_main_acc.clone(),
+                taker_y_acc.clone(),
+                taker_x_acc.clone(),
+                listing[1].clone(),
+                listing[2].clone(),
+                listing[3].clone(),
+                listing[0].clone(),
+                token_program_acc.clone(),
+                pda_acc.clone(),
+                mint_x_acc.clone(),
+                mint_y_acc.clone(),
+                y_token_program_acc.clone(),
+            ];
+            if *pass_rent_payer {
+                exchange_accounts.push(listing[4].clone());
+            }
+            Self::process_exchange(&exchange_accounts, *received_x, None, false, program_id)?;
+        }
+        Ok(())
+    }
+
+    fn close_escrow_account(
+        escrow_acc: &AccountInfo,
+        rent_recipient_acc: &AccountInfo,
+    ) -> ProgramResult {
+        **rent_recipient_acc.lamports.borrow_mut() = rent_recipient_acc
+            .lamports()
+            .checked_add(escrow_acc.lamports())
+            .ok_or(EscrowError::AmountOverflow)?; //add the balance to the rent payer's acc
+
+        **escrow_acc.lamports.borrow_mut() = 0; //empty the balance
+        *escrow_acc.data.borrow_mut() = &mut []; //AND zero out its data
+
+        Ok(())
+    }
+
+    // who gets the rent back when the account closes: whoever initializes it, unless the instruction's rent_payer flag
+    // says the next account pays. That one has to sign, it can't be signed up for it by someone else
+    fn rent_payer<'a, 'b>(
+        rent_payer: bool,
+        initializer: &AccountInfo<'b>,
+        account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    ) -> Result<Pubkey, ProgramError> {
+        if !rent_payer {
+            return Ok(*initializer.key);
+        }
+        let rent_payer_acc = next_account_info(account_info_iter)?;
+        if !rent_payer_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(*rent_payer_acc.key)
+    }
+
+    // the rent goes back to whoever paid it. That's alice unless a rent payer was recorded at init,
+    // then it has to be passed in too, as the next account
+    fn rent_recipient<'a, 'b>(
+        rent_payer: &Pubkey,
+        initializer_main_acc: &'a AccountInfo<'b>,
+        account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    ) -> Result<&'a AccountInfo<'b>, ProgramError> {
+        if *rent_payer == *initializer_main_acc.key {
+            return Ok(initializer_main_acc);
+        }
+        let rent_payer_acc = next_account_info(account_info_iter)?;
+        if *rent_payer != *rent_payer_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(rent_payer_acc)
+    }
+
+    // the initializer's say-so on an escrow: their signature, or if the escrow has a multisig authority, the multisig
+    // account followed by at least M of its signers. Takes the rest of the accounts
+    fn check_initializer_approval<'a, 'b>(
+        escrow_info: &Escrow,
+        initializer_main_acc: &'a AccountInfo<'b>,
+        account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if escrow_info.authority_pubkey == Pubkey::default() {
+            if !initializer_main_acc.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            return Ok(());
+        }
+        let multisig_acc = next_account_info(account_info_iter)?;
+        if *multisig_acc.key != escrow_info.authority_pubkey || multisig_acc.owner != program_id {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let multisig_info = Multisig::unpack(&multisig_acc.data.borrow())?;
+
+        // each listed signer counts once, however many times it's passed in
+        let mut signed = [false; Multisig::MAX_SIGNERS];
+        for signer_acc in account_info_iter.filter(|acc| acc.is_signer) {
+            if let Some(i) = multisig_info.signers[..multisig_info.n as usize]
+                .iter()
+                .position(|signer| signer == signer_acc.key)
+            {
+                signed[i] = true;
+            }
+        }
+        if signed.iter().filter(|signed| **signed).count() < multisig_info.m as usize {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(())
+    }
+
+    // we close the account by transferring its "rent-exempt" balance out of it
+    // Token-2022 refuses to close an account that still holds withheld transfer fees, so harvest them into the mint first
+    fn close_temp_account<'a>(
+        token_program_acc: &AccountInfo<'a>,
+        temp_acc: &AccountInfo<'a>,
+        mint_acc: &AccountInfo<'a>,
+        destination_acc: &AccountInfo<'a>,
+        pda_acc: &AccountInfo<'a>,
+        pda: &Pubkey,
+        bump_seed: u8,
+    ) -> ProgramResult {
+        if *token_program_acc.key == token::token_2022::id()
+            && token::withheld_amount(temp_acc)? > 0
+        {
+            msg!("Calling the token program to harvest withheld fees from the temp account...");
+            invoke(
+                &token::harvest_withheld_tokens_to_mint(mint_acc.key, &[temp_acc.key]),
+                &[
+                    mint_acc.clone(),
+                    temp_acc.clone(),
+                    token_program_acc.clone(),
+                ],
+            )?;
+        }
+
+        let close_temp_acc_ix = token::close_account(
             token_program_acc.key,
-            pda_temp_x_acc.key,       //from temp account
-            initializer_main_acc.key, //to initializer main account
-            &pda,
-            &[&pda],
+            temp_acc.key,        //from temp account
+            destination_acc.key, //to whoever gets the rent
+            pda,
         )?;
 
         msg!("Calling the token program to close pda's temp account...");
 
         // same story as above - since we're moving out of a PDA account, we use invoke_signed
         invoke_signed(
-            &close_pdas_temp_acc_ix,
+            &close_temp_acc_ix,
             &[
-                pda_temp_x_acc.clone(),
-                initializer_main_acc.clone(),
+                temp_acc.clone(),
+                destination_acc.clone(),
                 pda_acc.clone(),
                 token_program_acc.clone(),
             ],
             &[&[&b"escrow"[..], &[bump_seed]]],
-        )?;
-
-        msg!("Closing the escrow account...");
-
-        **initializer_main_acc.lamports.borrow_mut() = initializer_main_acc
-            .lamports()
-            .checked_add(escrow_acc.lamports())
-            .ok_or(EscrowError::AmountOverflow)?; //add the balance to initializer's acc
-
-        **escrow_acc.lamports.borrow_mut() = 0; //empty the balance
-        *escrow_acc.data.borrow_mut() = &mut []; //AND zero out its data
-
-        Ok(())
+        )
     }
 
     fn cancel_exchange(
         accounts: &[AccountInfo],
         bump_seed: u8,
-        _program_id: &Pubkey,
+        program_id: &Pubkey,
     ) -> ProgramResult {
         // ----------------------------------------------------------------------------- get accs
         let accounts_info_iter = &mut accounts.iter();
@@ -321,21 +1173,52 @@ impl Processor {
         let initializer_x_acc = next_account_info(accounts_info_iter)?;
         let escrow_acc = next_account_info(accounts_info_iter)?;
         let pda_acc = next_account_info(accounts_info_iter)?;
+        let mint_x_acc = next_account_info(accounts_info_iter)?;
+        token::check_token_program(token_program_acc)?;
 
         // ----------------------------------------------------------------------------- checks
-        // deserialize the escrow account
-        let escrow_info = Escrow::unpack(&escrow_acc.data.borrow())?;
+        // deserialize the escrow account. The ones from before the account type are still around, they can be cancelled
+        // like the Vault mode escrows they were
+        let escrow_info = match AccountType::of(&escrow_acc.data.borrow()) {
+            Some(AccountType::Legacy) => LegacyEscrow::unpack(&escrow_acc.data.borrow())?.into(),
+            _ => Escrow::unpack(&escrow_acc.data.borrow())?,
+        };
+        let rent_recipient_acc = Self::rent_recipient(
+            &escrow_info.rent_payer,
+            initializer_main_acc,
+            accounts_info_iter,
+        )?;
 
         // check that the sender is indeed the initializer who created the escrow
         if escrow_info.initializer_pubkey != *initializer_main_acc.key {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        // check that initializer is listed as signer
-        if !initializer_main_acc.is_signer {
+        // check that initializer is listed as signer - or enough of the multisig that stands in for them
+        Self::check_initializer_approval(
+            &escrow_info,
+            initializer_main_acc,
+            accounts_info_iter,
+            program_id,
+        )?;
+        // an Approve mode escrow's delegation to the PDA is alice's to revoke, so she signs along with her multisig.
+        // Without her the PDA would be left able to move her tokens
+        if escrow_info.mode == EscrowMode::Approve && !initializer_main_acc.is_signer {
             return Err(ProgramError::MissingRequiredSignature);
         }
 
+        // cancelling an HTLC early would break the swap for the other side, that's what Refund and the timelock are for.
+        // same for an option someone paid for - ReclaimOption knows when that's ok. And an auction may have bids
+        // in it, if nobody bid SettleAuction gives the seller X back. A flash exchange's X is already gone
+        if escrow_info.mode == EscrowMode::Htlc
+            || escrow_info.mode == EscrowMode::CoveredCall
+            || escrow_info.mode == EscrowMode::EnglishAuction
+            || escrow_info.mode == EscrowMode::SealedAuction
+            || escrow_info.mode == EscrowMode::FlashSettling
+        {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+
         // check that temp_x_acc is what we're expecting
         if escrow_info.temp_token_account_pubkey != *temp_x_acc.key {
             return Err(ProgramError::InvalidAccountData);
@@ -377,63 +1260,4114 @@ impl Processor {
         // ----------------------------------------------------------------------------- send x token back
 
         // similarly to our Escrow, pack/unpack turns a slice into an actual account info
-        let temp_x_info = TokenAccount::unpack(&temp_x_acc.data.borrow())?;
+        let temp_x_info = token::unpack_token_account(temp_x_acc, token_program_acc.key)?;
+        if temp_x_info.mint != *mint_x_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mint_x = token::unpack_mint(mint_x_acc, token_program_acc.key)?;
 
-        let transfer_x_tokens_back_ix = spl_token::instruction::transfer(
-            token_program_acc.key,
-            temp_x_acc.key,
-            initializer_x_acc.key,
-            &pda,
-            &[&pda],
-            temp_x_info.amount, //get the amount in x tokens programmatically
-        )?;
+        if escrow_info.mode == EscrowMode::Approve {
+            // nothing to send back, the tokens never left. Just take the delegation away again - unless alice already
+            // handed it to someone else, then it's not ours to revoke
+            if temp_x_info.delegate == COption::Some(pda) {
+                let revoke_ix = token::revoke(
+                    token_program_acc.key,
+                    temp_x_acc.key,
+                    initializer_main_acc.key,
+                )?;
 
-        //invoke here because we're asking the token program to do something for us
-        invoke_signed(
-            &transfer_x_tokens_back_ix,
-            &[
-                temp_x_acc.clone(),
-                initializer_x_acc.clone(),
-                pda_acc.clone(),
-                token_program_acc.clone(),
-            ],
-            &[&[&b"escrow"[..], &[bump_seed]]],
-        )?;
+                msg!("Calling the token program to revoke the escrow's delegation...");
 
-        // ----------------------------------------------------------------------------- clean up
+                invoke(
+                    &revoke_ix,
+                    &[
+                        temp_x_acc.clone(),
+                        initializer_main_acc.clone(),
+                        token_program_acc.clone(),
+                    ],
+                )?;
+            }
+        } else {
+            let transfer_x_tokens_back_ix = token::transfer_checked(
+                token_program_acc.key,
+                temp_x_acc.key,
+                mint_x_acc.key,
+                initializer_x_acc.key,
+                &pda,
+                temp_x_info.amount, //get the amount in x tokens programmatically
+                mint_x.decimals,
+            )?;
+
+            //invoke here because we're asking the token program to do something for us
+            invoke_signed(
+                &transfer_x_tokens_back_ix,
+                &[
+                    temp_x_acc.clone(),
+                    mint_x_acc.clone(),
+                    initializer_x_acc.clone(),
+                    pda_acc.clone(),
+                    token_program_acc.clone(),
+                ],
+                &[&[&b"escrow"[..], &[bump_seed]]],
+            )?;
+
+            // ----------------------------------------------------------------------------- clean up
+
+            sol_log_compute_units();
+
+            //1) close the temp acc by transferring rent out of it
+            Self::close_temp_account(
+                token_program_acc,
+                temp_x_acc,
+                mint_x_acc,
+                rent_recipient_acc,
+                pda_acc,
+                &pda,
+                bump_seed,
+            )?;
+        }
+
+        //2) close the escrow acc by transferring rent out of it AND zeroing out the data
+        Self::close_escrow_account(escrow_acc, rent_recipient_acc)?;
 
         sol_log_compute_units();
 
-        //1) close the temp acc by transferring rent out of it
-        let close_temp_x_acc_ix = spl_token::instruction::close_account(
-            token_program_acc.key,
-            temp_x_acc.key,
-            initializer_x_acc.key,
+        Ok(())
+    }
+
+    // like a Vault mode InitEscrow, except nothing gets paid for the vault - it's locked to a hash and a point in time instead
+    fn process_init_htlc(
+        accounts: &[AccountInfo],
+        hashlock: [u8; 32],
+        timelock: i64,
+        rent_payer: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        // ----------------------------------------------------------------------------- get the accs
+        let account_info_iter = &mut accounts.iter();
+
+        // [0] alice, she has to sign so the vault can be handed over
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // [1] the temp account that becomes the vault, [2] where it goes on Claim
+        let temp_token_account = next_account_info(account_info_iter)?;
+        let recipient_token_account = next_account_info(account_info_iter)?;
+
+        // [3] the escrow state
+        let escrow_account = next_account_info(account_info_iter)?;
+
+        // [4] rent sysvar, same story as in process_init_escrow
+        let _manual_rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let rent = &Rent::get()?;
+        if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+
+        // [5] + [6] the token program and mint of both token accounts
+        let token_program = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program)?;
+        let mint_x = next_account_info(account_info_iter)?;
+
+        // [7] the rent payer, if there is one
+        let rent_payer = Self::rent_payer(rent_payer, initializer, account_info_iter)?;
+
+        // ----------------------------------------------------------------------------- checks
+        let temp_token_account_info =
+            token::unpack_token_account(temp_token_account, token_program.key)?;
+        let (recipient_token_account_info, recipient_extensions) =
+            token::unpack_token_account_with_extensions(
+                recipient_token_account,
+                token_program.key,
+            )?;
+        if temp_token_account_info.mint != *mint_x.key
+            || recipient_token_account_info.mint != *mint_x.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if recipient_extensions.memo_transfer {
+            return Err(EscrowError::UnsupportedAccountExtension.into());
+        }
+        token::unpack_mint(mint_x, token_program.key)?;
+        Self::check_temp_account(&temp_token_account_info, EscrowMode::Htlc)?;
+
+        // a timelock that already passed would let alice refund right away, after the other side has locked theirs
+        if timelock <= Clock::get()?.unix_timestamp {
+            return Err(EscrowError::TimelockInThePast.into());
+        }
+
+        // ----------------------------------------------------------------------------- data
+        let escrow_info = Escrow::unpack_unchecked(&escrow_account.data.borrow())?;
+        if escrow_info.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        let escrow_info = Escrow {
+            is_initialized: true,
+            initializer_pubkey: *initializer.key,
+            temp_token_account_pubkey: *temp_token_account.key,
+            mode: EscrowMode::Htlc,
+            rent_payer,
+            recipient_token_account_pubkey: *recipient_token_account.key,
+            terms: Terms::Htlc(HtlcTerms { hashlock, timelock }),
+            ..Escrow::default()
+        };
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())?;
+
+        // ----------------------------------------------------------------------------- lock the vault
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::hand_to_pda(token_program, temp_token_account, initializer, &pda)
+    }
+
+    fn process_claim(
+        accounts: &[AccountInfo],
+        preimage: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        // ----------------------------------------------------------------------------- get accs
+        let account_info_iter = &mut accounts.iter();
+
+        // no signer needed - knowing the preimage is the authorization, and the tokens can only go to the recipient
+        let pda_temp_x_acc = next_account_info(account_info_iter)?;
+        let recipient_acc = next_account_info(account_info_iter)?;
+        let initializer_main_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        let mint_x_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+
+        // ----------------------------------------------------------------------------- checks
+        let escrow_info = Escrow::unpack(&escrow_acc.data.borrow())?;
+        let htlc = match escrow_info.terms {
+            Terms::Htlc(htlc) => htlc,
+            _ => return Err(EscrowError::WrongEscrowMode.into()),
+        };
+        if escrow_info.temp_token_account_pubkey != *pda_temp_x_acc.key
+            || escrow_info.recipient_token_account_pubkey != *recipient_acc.key
+            || escrow_info.initializer_pubkey != *initializer_main_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let rent_recipient_acc = Self::rent_recipient(
+            &escrow_info.rent_payer,
+            initializer_main_acc,
+            account_info_iter,
+        )?;
+
+        if hash(&preimage).to_bytes() != htlc.hashlock {
+            return Err(EscrowError::HashlockMismatch.into());
+        }
+
+        // ----------------------------------------------------------------------------- release
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::empty_vault(
+            token_program_acc,
+            pda_temp_x_acc,
+            mint_x_acc,
+            recipient_acc,
+            rent_recipient_acc,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )?;
+        Self::close_escrow_account(escrow_acc, rent_recipient_acc)?;
+
+        // the whole point: now the preimage is public, the other side can claim their leg with it
+        EscrowEvent::HtlcClaimed {
+            escrow: *escrow_acc.key,
+            hashlock: htlc.hashlock,
+            preimage,
+        }
+        .emit();
+        Ok(())
+    }
+
+    // Refund for HTLCs, ReclaimOption for options - the initializer takes the vault back once the timelock is up
+    fn process_reclaim(
+        accounts: &[AccountInfo],
+        mode: EscrowMode,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        // ----------------------------------------------------------------------------- get accs
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer_main_acc = next_account_info(account_info_iter)?;
+        let pda_temp_x_acc = next_account_info(account_info_iter)?;
+        let initializer_x_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        let mint_x_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+
+        // ----------------------------------------------------------------------------- checks
+        let escrow_info = Escrow::unpack(&escrow_acc.data.borrow())?;
+        if escrow_info.mode != mode {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        if escrow_info.initializer_pubkey != *initializer_main_acc.key
+            || escrow_info.temp_token_account_pubkey != *pda_temp_x_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let rent_recipient_acc = Self::rent_recipient(
+            &escrow_info.rent_payer,
+            initializer_main_acc,
+            account_info_iter,
+        )?;
+        Self::check_initializer_approval(
+            &escrow_info,
+            initializer_main_acc,
+            account_info_iter,
+            program_id,
+        )?;
+
+        // an option nobody bought yet can come off the market any time
+        let timelock = match escrow_info.terms {
+            Terms::Htlc(htlc) => htlc.timelock,
+            Terms::CoveredCall(option) if option.holder_pubkey == Pubkey::default() => i64::MIN,
+            Terms::CoveredCall(option) => option.expiry,
+            _ => return Err(EscrowError::WrongEscrowMode.into()),
+        };
+        if Clock::get()?.unix_timestamp < timelock {
+            return Err(EscrowError::TimelockNotExpired.into());
+        }
+
+        // ----------------------------------------------------------------------------- send x back
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::empty_vault(
+            token_program_acc,
+            pda_temp_x_acc,
+            mint_x_acc,
+            initializer_x_acc,
+            rent_recipient_acc,
+            pda_acc,
             &pda,
-            &[&pda],
+            bump_seed,
+        )?;
+        Self::close_escrow_account(escrow_acc, rent_recipient_acc)
+    }
+
+    // sends everything in a vault to `destination_acc`, then closes the vault
+    #[allow(clippy::too_many_arguments)]
+    fn empty_vault<'a>(
+        token_program_acc: &AccountInfo<'a>,
+        vault_acc: &AccountInfo<'a>,
+        mint_acc: &AccountInfo<'a>,
+        destination_acc: &AccountInfo<'a>,
+        rent_recipient_acc: &AccountInfo<'a>,
+        pda_acc: &AccountInfo<'a>,
+        pda: &Pubkey,
+        bump_seed: u8,
+    ) -> ProgramResult {
+        let vault_info = token::unpack_token_account(vault_acc, token_program_acc.key)?;
+        if vault_info.mint != *mint_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mint = token::unpack_mint(mint_acc, token_program_acc.key)?;
+
+        let transfer_ix = token::transfer_checked(
+            token_program_acc.key,
+            vault_acc.key,
+            mint_acc.key,
+            destination_acc.key,
+            pda,
+            vault_info.amount,
+            mint.decimals,
         )?;
 
+        msg!("Calling the token program to empty the vault...");
+
         invoke_signed(
-            &close_temp_x_acc_ix,
+            &transfer_ix,
             &[
-                temp_x_acc.clone(),
-                initializer_x_acc.clone(),
+                vault_acc.clone(),
+                mint_acc.clone(),
+                destination_acc.clone(),
                 pda_acc.clone(),
                 token_program_acc.clone(),
             ],
             &[&[&b"escrow"[..], &[bump_seed]]],
         )?;
 
-        //2) close the escrow acc by transferring rent out of it AND zeroing out the data
-        **initializer_main_acc.lamports.borrow_mut() = initializer_main_acc
-            .lamports()
-            .checked_add(escrow_acc.lamports())
+        Self::close_temp_account(
+            token_program_acc,
+            vault_acc,
+            mint_acc,
+            rent_recipient_acc,
+            pda_acc,
+            pda,
+            bump_seed,
+        )
+    }
+
+    // a Vault mode listing with the floor as its price, and the curve on top
+    #[allow(clippy::too_many_arguments)]
+    fn process_init_dutch_auction(
+        accounts: &[AccountInfo],
+        start_price: u64,
+        floor_price: u64,
+        start_time: i64,
+        end_time: i64,
+        decay: u8,
+        rent_payer: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        auction::check_dutch_curve(start_price, floor_price, start_time, end_time, decay)?;
+        Self::process_init_escrow(
+            &mut accounts.iter(),
+            floor_price,
+            EscrowMode::DutchAuction,
+            0,
+            rent_payer,
+            program_id,
+        )?;
+
+        // [3] the escrow account process_init_escrow just wrote
+        let escrow_account = accounts.get(3).ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let mut escrow_info = Escrow::unpack(&escrow_account.data.borrow())?;
+        escrow_info.terms = Terms::DutchAuction(DutchTerms {
+            start_price,
+            auction_start: start_time,
+            auction_end: end_time,
+            decay,
+        });
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    // the vault side works like InitEscrow, the option's terms go into the escrow state
+    fn process_write_option(
+        accounts: &[AccountInfo],
+        strike: u64,
+        premium: u64,
+        expiry: i64,
+        rent_payer: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        // ----------------------------------------------------------------------------- get the accs
+        let account_info_iter = &mut accounts.iter();
+
+        // [0] the writer
+        let writer = next_account_info(account_info_iter)?;
+        if !writer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // [1] the temp account that becomes the vault, [2] where premium and strike go
+        let temp_token_account = next_account_info(account_info_iter)?;
+        let writer_y_account = next_account_info(account_info_iter)?;
+        if !token::is_token_program(writer_y_account.owner) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        // [3] the escrow state
+        let escrow_account = next_account_info(account_info_iter)?;
+
+        // [4] rent sysvar
+        let _manual_rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let rent = &Rent::get()?;
+        if !rent.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+
+        // [5] X token program, [6] + [7] the mints
+        let token_program = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program)?;
+        let mint_x = next_account_info(account_info_iter)?;
+        let mint_y = next_account_info(account_info_iter)?;
+
+        // [8] the rent payer, if there is one
+        let rent_payer = Self::rent_payer(rent_payer, writer, account_info_iter)?;
+
+        // ----------------------------------------------------------------------------- checks
+        let temp_token_account_info =
+            token::unpack_token_account(temp_token_account, token_program.key)?;
+        let writer_y_account_info =
+            token::unpack_token_account(writer_y_account, writer_y_account.owner)?;
+        if temp_token_account_info.mint != *mint_x.key || writer_y_account_info.mint != *mint_y.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        token::unpack_mint(mint_x, token_program.key)?;
+        token::unpack_mint(mint_y, writer_y_account.owner)?;
+        Self::check_temp_account(&temp_token_account_info, EscrowMode::CoveredCall)?;
+
+        // nobody would buy an option that's already expired, but better not to list it at all
+        if expiry <= Clock::get()?.unix_timestamp {
+            return Err(EscrowError::TimelockInThePast.into());
+        }
+
+        // ----------------------------------------------------------------------------- data
+        let escrow_info = Escrow::unpack_unchecked(&escrow_account.data.borrow())?;
+        if escrow_info.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        let escrow_info = Escrow {
+            is_initialized: true,
+            initializer_pubkey: *writer.key,
+            temp_token_account_pubkey: *temp_token_account.key,
+            initializer_token_to_receive_account_pubkey: *writer_y_account.key,
+            expected_amount: strike,
+            mode: EscrowMode::CoveredCall,
+            rent_payer,
+            terms: Terms::CoveredCall(OptionTerms {
+                expiry,
+                premium,
+                holder_pubkey: Pubkey::default(),
+            }),
+            ..Escrow::default()
+        };
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())?;
+
+        // ----------------------------------------------------------------------------- lock the vault
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::hand_to_pda(token_program, temp_token_account, writer, &pda)
+    }
+
+    fn process_buy_option(accounts: &[AccountInfo], premium: u64) -> ProgramResult {
+        // ----------------------------------------------------------------------------- get accs
+        let account_info_iter = &mut accounts.iter();
+
+        let buyer_acc = next_account_info(account_info_iter)?;
+        if !buyer_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let buyer_y_acc = next_account_info(account_info_iter)?;
+        let writer_y_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let mint_y_acc = next_account_info(account_info_iter)?;
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(y_token_program_acc)?;
+
+        // ----------------------------------------------------------------------------- checks
+        let mut escrow_info = Escrow::unpack(&escrow_acc.data.borrow())?;
+        let option = match &mut escrow_info.terms {
+            Terms::CoveredCall(option) => option,
+            _ => return Err(EscrowError::WrongEscrowMode.into()),
+        };
+        if escrow_info.initializer_token_to_receive_account_pubkey != *writer_y_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if option.holder_pubkey != Pubkey::default() {
+            return Err(EscrowError::OptionAlreadySold.into());
+        }
+        let clock = Clock::get()?;
+        if clock.unix_timestamp >= option.expiry {
+            return Err(EscrowError::OptionExpired.into());
+        }
+        if premium != option.premium {
+            return Err(EscrowError::ExpectedAmountMismatch.into());
+        }
+
+        let writer_y_info = token::unpack_token_account(writer_y_acc, y_token_program_acc.key)?;
+        if writer_y_info.mint != *mint_y_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mint_y = token::unpack_mint(mint_y_acc, y_token_program_acc.key)?;
+
+        // ----------------------------------------------------------------------------- pay the premium
+        // like in Exchange, the writer gets the premium in full and the buyer covers any transfer fee
+        let transfer_premium_ix = token::transfer_checked(
+            y_token_program_acc.key,
+            buyer_y_acc.key,
+            mint_y_acc.key,
+            writer_y_acc.key,
+            buyer_acc.key,
+            mint_y.pre_fee_amount(premium, clock.epoch)?,
+            mint_y.decimals,
+        )?;
+
+        msg!("Calling the token program to pay the premium...");
+
+        invoke(
+            &transfer_premium_ix,
+            &[
+                buyer_y_acc.clone(),
+                mint_y_acc.clone(),
+                writer_y_acc.clone(),
+                buyer_acc.clone(),
+                y_token_program_acc.clone(),
+            ],
+        )?;
+
+        // ----------------------------------------------------------------------------- the buyer holds the option now
+        option.holder_pubkey = *buyer_acc.key;
+        Escrow::pack(escrow_info, &mut escrow_acc.data.borrow_mut())?;
+        Ok(())
+    }
+
+    // the collateral side works like InitEscrow, the loan's terms go into a Loan account
+    fn process_offer_loan(
+        accounts: &[AccountInfo],
+        principal: u64,
+        interest: u64,
+        duration: i64,
+        rent_payer: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        // ----------------------------------------------------------------------------- get the accs
+        let account_info_iter = &mut accounts.iter();
+
+        // [0] the borrower
+        let borrower = next_account_info(account_info_iter)?;
+        if !borrower.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // [1] the temp account that becomes the collateral vault, [2] where the principal goes
+        let temp_token_account = next_account_info(account_info_iter)?;
+        let borrower_principal_account = next_account_info(account_info_iter)?;
+        if !token::is_token_program(borrower_principal_account.owner) {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        // [3] the loan state
+        let loan_account = next_account_info(account_info_iter)?;
+
+        // [4] rent sysvar
+        let _manual_rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let rent = &Rent::get()?;
+        if !rent.is_exempt(loan_account.lamports(), loan_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+
+        // [5] collateral token program, [6] + [7] the mints
+        let token_program = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program)?;
+        let collateral_mint = next_account_info(account_info_iter)?;
+        let principal_mint = next_account_info(account_info_iter)?;
+
+        // [8] the rent payer, if there is one
+        let rent_payer = Self::rent_payer(rent_payer, borrower, account_info_iter)?;
+
+        // ----------------------------------------------------------------------------- checks
+        let temp_token_account_info =
+            token::unpack_token_account(temp_token_account, token_program.key)?;
+        let borrower_principal_account_info = token::unpack_token_account(
+            borrower_principal_account,
+            borrower_principal_account.owner,
+        )?;
+        if temp_token_account_info.mint != *collateral_mint.key
+            || borrower_principal_account_info.mint != *principal_mint.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        token::unpack_mint(collateral_mint, token_program.key)?;
+        token::unpack_mint(principal_mint, borrower_principal_account.owner)?;
+        Self::check_temp_account(&temp_token_account_info, EscrowMode::Vault)?;
+
+        // a loan that's due the moment it's funded could be foreclosed right away
+        if duration <= 0 {
+            return Err(EscrowError::TimelockInThePast.into());
+        }
+
+        // ----------------------------------------------------------------------------- data
+        let loan_info = Loan::unpack_unchecked(&loan_account.data.borrow())?;
+        if loan_info.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        let loan_info = Loan {
+            is_initialized: true,
+            borrower_pubkey: *borrower.key,
+            collateral_account_pubkey: *temp_token_account.key,
+            borrower_principal_account_pubkey: *borrower_principal_account.key,
+            principal,
+            interest,
+            duration,
+            rent_payer,
+            ..Loan::default()
+        };
+        Loan::pack(loan_info, &mut loan_account.data.borrow_mut())?;
+
+        // ----------------------------------------------------------------------------- lock the collateral
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::hand_to_pda(token_program, temp_token_account, borrower, &pda)
+    }
+
+    fn process_fund_loan(accounts: &[AccountInfo], principal: u64) -> ProgramResult {
+        // ----------------------------------------------------------------------------- get accs
+        let account_info_iter = &mut accounts.iter();
+
+        let lender_acc = next_account_info(account_info_iter)?;
+        if !lender_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let lender_principal_acc = next_account_info(account_info_iter)?;
+        let borrower_principal_acc = next_account_info(account_info_iter)?;
+        let loan_acc = next_account_info(account_info_iter)?;
+        let principal_mint_acc = next_account_info(account_info_iter)?;
+        let principal_token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(principal_token_program_acc)?;
+
+        // ----------------------------------------------------------------------------- checks
+        let mut loan_info = Loan::unpack(&loan_acc.data.borrow())?;
+        if loan_info.borrower_principal_account_pubkey != *borrower_principal_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if loan_info.is_funded() {
+            return Err(EscrowError::LoanAlreadyFunded.into());
+        }
+        if principal != loan_info.principal {
+            return Err(EscrowError::ExpectedAmountMismatch.into());
+        }
+
+        let borrower_principal_info =
+            token::unpack_token_account(borrower_principal_acc, principal_token_program_acc.key)?;
+        if borrower_principal_info.mint != *principal_mint_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let principal_mint =
+            token::unpack_mint(principal_mint_acc, principal_token_program_acc.key)?;
+
+        // the clock starts now
+        let clock = Clock::get()?;
+        loan_info.deadline = clock
+            .unix_timestamp
+            .checked_add(loan_info.duration)
             .ok_or(EscrowError::AmountOverflow)?;
-        **escrow_acc.lamports.borrow_mut() = 0;
-        *escrow_acc.data.borrow_mut() = &mut [];
 
-        sol_log_compute_units();
+        // ----------------------------------------------------------------------------- pay out the principal
+        // the borrower gets the principal in full, the lender covers any transfer fee
+        let transfer_principal_ix = token::transfer_checked(
+            principal_token_program_acc.key,
+            lender_principal_acc.key,
+            principal_mint_acc.key,
+            borrower_principal_acc.key,
+            lender_acc.key,
+            principal_mint.pre_fee_amount(principal, clock.epoch)?,
+            principal_mint.decimals,
+        )?;
 
+        msg!("Calling the token program to pay out the principal...");
+
+        invoke(
+            &transfer_principal_ix,
+            &[
+                lender_principal_acc.clone(),
+                principal_mint_acc.clone(),
+                borrower_principal_acc.clone(),
+                lender_acc.clone(),
+                principal_token_program_acc.clone(),
+            ],
+        )?;
+
+        // ----------------------------------------------------------------------------- the loan is running
+        loan_info.lender_pubkey = *lender_acc.key;
+        loan_info.lender_principal_account_pubkey = *lender_principal_acc.key;
+        Loan::pack(loan_info, &mut loan_acc.data.borrow_mut())?;
         Ok(())
     }
+
+    fn process_repay_loan(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        // ----------------------------------------------------------------------------- get accs
+        let account_info_iter = &mut accounts.iter();
+
+        let borrower_acc = next_account_info(account_info_iter)?;
+        if !borrower_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let borrower_principal_acc = next_account_info(account_info_iter)?;
+        let lender_principal_acc = next_account_info(account_info_iter)?;
+        let pda_collateral_acc = next_account_info(account_info_iter)?;
+        let borrower_collateral_acc = next_account_info(account_info_iter)?;
+        let loan_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        let collateral_mint_acc = next_account_info(account_info_iter)?;
+        let principal_mint_acc = next_account_info(account_info_iter)?;
+        let principal_token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        token::check_token_program(principal_token_program_acc)?;
+
+        // ----------------------------------------------------------------------------- checks
+        if loan_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let loan_info = Loan::unpack(&loan_acc.data.borrow())?;
+        if loan_info.borrower_pubkey != *borrower_acc.key
+            || loan_info.collateral_account_pubkey != *pda_collateral_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !loan_info.is_funded() {
+            return Err(EscrowError::LoanNotFunded.into());
+        }
+        if loan_info.lender_principal_account_pubkey != *lender_principal_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let rent_recipient_acc =
+            Self::rent_recipient(&loan_info.rent_payer, borrower_acc, account_info_iter)?;
+
+        // once the deadline has passed the collateral is the lender's to take
+        let clock = Clock::get()?;
+        if clock.unix_timestamp >= loan_info.deadline {
+            return Err(EscrowError::LoanOverdue.into());
+        }
+
+        let lender_principal_info =
+            token::unpack_token_account(lender_principal_acc, principal_token_program_acc.key)?;
+        if lender_principal_info.mint != *principal_mint_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let principal_mint =
+            token::unpack_mint(principal_mint_acc, principal_token_program_acc.key)?;
+        let owed = loan_info
+            .principal
+            .checked_add(loan_info.interest)
+            .ok_or(EscrowError::AmountOverflow)?;
+
+        // ----------------------------------------------------------------------------- pay the lender back
+        // same as funding, the lender gets what they're owed in full
+        let repay_ix = token::transfer_checked(
+            principal_token_program_acc.key,
+            borrower_principal_acc.key,
+            principal_mint_acc.key,
+            lender_principal_acc.key,
+            borrower_acc.key,
+            principal_mint.pre_fee_amount(owed, clock.epoch)?,
+            principal_mint.decimals,
+        )?;
+
+        msg!("Calling the token program to repay the lender...");
+
+        invoke(
+            &repay_ix,
+            &[
+                borrower_principal_acc.clone(),
+                principal_mint_acc.clone(),
+                lender_principal_acc.clone(),
+                borrower_acc.clone(),
+                principal_token_program_acc.clone(),
+            ],
+        )?;
+
+        // ----------------------------------------------------------------------------- release the collateral
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::empty_vault(
+            token_program_acc,
+            pda_collateral_acc,
+            collateral_mint_acc,
+            borrower_collateral_acc,
+            rent_recipient_acc,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )?;
+        Self::close_escrow_account(loan_acc, rent_recipient_acc)
+    }
+
+    fn process_foreclose_loan(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        // ----------------------------------------------------------------------------- get accs
+        let account_info_iter = &mut accounts.iter();
+
+        let lender_acc = next_account_info(account_info_iter)?;
+        if !lender_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let pda_collateral_acc = next_account_info(account_info_iter)?;
+        let lender_collateral_acc = next_account_info(account_info_iter)?;
+        let borrower_acc = next_account_info(account_info_iter)?;
+        let loan_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        let collateral_mint_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+
+        // ----------------------------------------------------------------------------- checks
+        if loan_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let loan_info = Loan::unpack(&loan_acc.data.borrow())?;
+        if !loan_info.is_funded() {
+            return Err(EscrowError::LoanNotFunded.into());
+        }
+        if loan_info.lender_pubkey != *lender_acc.key
+            || loan_info.borrower_pubkey != *borrower_acc.key
+            || loan_info.collateral_account_pubkey != *pda_collateral_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let rent_recipient_acc =
+            Self::rent_recipient(&loan_info.rent_payer, borrower_acc, account_info_iter)?;
+
+        if Clock::get()?.unix_timestamp < loan_info.deadline {
+            return Err(EscrowError::TimelockNotExpired.into());
+        }
+
+        // ----------------------------------------------------------------------------- the collateral is the lender's now
+        // the rent still goes back to whoever paid it, not to the lender
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::empty_vault(
+            token_program_acc,
+            pda_collateral_acc,
+            collateral_mint_acc,
+            lender_collateral_acc,
+            rent_recipient_acc,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )?;
+        Self::close_escrow_account(loan_acc, rent_recipient_acc)
+    }
+
+    // nothing's owed yet, so the borrower can walk away - otherwise an offer nobody wants would lock the collateral for good
+    fn process_cancel_loan(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        // ----------------------------------------------------------------------------- get accs
+        let account_info_iter = &mut accounts.iter();
+
+        let borrower_acc = next_account_info(account_info_iter)?;
+        let pda_collateral_acc = next_account_info(account_info_iter)?;
+        let borrower_collateral_acc = next_account_info(account_info_iter)?;
+        let loan_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        let collateral_mint_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+
+        // ----------------------------------------------------------------------------- checks
+        if loan_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let loan_info = Loan::unpack(&loan_acc.data.borrow())?;
+        if loan_info.borrower_pubkey != *borrower_acc.key
+            || loan_info.collateral_account_pubkey != *pda_collateral_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !borrower_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if loan_info.is_funded() {
+            return Err(EscrowError::LoanAlreadyFunded.into());
+        }
+        let rent_recipient_acc =
+            Self::rent_recipient(&loan_info.rent_payer, borrower_acc, account_info_iter)?;
+
+        // ----------------------------------------------------------------------------- send the collateral back
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::empty_vault(
+            token_program_acc,
+            pda_collateral_acc,
+            collateral_mint_acc,
+            borrower_collateral_acc,
+            rent_recipient_acc,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )?;
+        Self::close_escrow_account(loan_acc, rent_recipient_acc)
+    }
+
+    fn process_init_english_auction(
+        accounts: &[AccountInfo],
+        reserve_price: u64,
+        min_increment: u64,
+        end_time: i64,
+        extension: i64,
+        rent_payer: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if extension < 0 {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        if end_time <= Clock::get()?.unix_timestamp {
+            return Err(EscrowError::TimelockInThePast.into());
+        }
+        Self::init_auction(
+            accounts,
+            reserve_price,
+            end_time,
+            EscrowMode::EnglishAuction,
+            rent_payer,
+            program_id,
+            |auction| {
+                Terms::EnglishAuction(EnglishTerms {
+                    auction,
+                    min_increment,
+                    extension,
+                })
+            },
+        )
+    }
+
+    // both kinds of auction are a Vault mode listing with the reserve as its price, plus the bid vault.
+    // `terms` turns what they have in common into the mode's terms
+    fn init_auction(
+        accounts: &[AccountInfo],
+        reserve_price: u64,
+        end_time: i64,
+        mode: EscrowMode,
+        rent_payer: bool,
+        program_id: &Pubkey,
+        terms: impl FnOnce(AuctionTerms) -> Terms,
+    ) -> ProgramResult {
+        // the rent payer comes after the bid vault, we record it ourselves
+        let account_info_iter = &mut accounts.iter();
+        Self::process_init_escrow(account_info_iter, reserve_price, mode, 0, false, program_id)?;
+
+        // ----------------------------------------------------------------------------- get the accs
+        // process_init_escrow already went through [0] to [7]
+        let account = |i: usize| accounts.get(i).ok_or(ProgramError::NotEnoughAccountKeys);
+        let seller = account(0)?;
+        let escrow_account = account(3)?;
+        let mint_y = account(7)?;
+        // [8] the bid vault, [9] its token program
+        let bid_vault = next_account_info(account_info_iter)?;
+        let y_token_program = next_account_info(account_info_iter)?;
+        token::check_token_program(y_token_program)?;
+        // [10] the rent payer, if there is one
+        let rent_payer = Self::rent_payer(rent_payer, seller, account_info_iter)?;
+
+        // ----------------------------------------------------------------------------- checks
+        // the vault starts out empty and nobody but the PDA may touch it once it's handed over
+        let bid_vault_info = token::unpack_token_account(bid_vault, y_token_program.key)?;
+        if bid_vault_info.mint != *mint_y.key
+            || bid_vault_info.amount != 0
+            || bid_vault_info.delegate.is_some()
+            || bid_vault_info.close_authority.is_some()
+            || bid_vault_info.is_frozen()
+            || bid_vault_info.is_native()
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // ----------------------------------------------------------------------------- data
+        let mut escrow_info = Escrow::unpack(&escrow_account.data.borrow())?;
+        escrow_info.rent_payer = rent_payer;
+        escrow_info.terms = terms(AuctionTerms {
+            end: end_time,
+            bid_vault_pubkey: *bid_vault.key,
+            ..AuctionTerms::default()
+        });
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())?;
+
+        // ----------------------------------------------------------------------------- lock the bid vault
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::hand_to_pda(y_token_program, bid_vault, seller, &pda)
+    }
+
+    fn process_bid(accounts: &[AccountInfo], amount: u64, program_id: &Pubkey) -> ProgramResult {
+        // ----------------------------------------------------------------------------- get accs
+        let account_info_iter = &mut accounts.iter();
+
+        let bidder_acc = next_account_info(account_info_iter)?;
+        if !bidder_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let bidder_y_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let bid_vault_acc = next_account_info(account_info_iter)?;
+        let mint_y_acc = next_account_info(account_info_iter)?;
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(y_token_program_acc)?;
+
+        // ----------------------------------------------------------------------------- checks
+        let mut escrow_info = Escrow::unpack(&escrow_acc.data.borrow())?;
+        let english = match &mut escrow_info.terms {
+            Terms::EnglishAuction(english) => english,
+            _ => return Err(EscrowError::WrongEscrowMode.into()),
+        };
+        if english.auction.bid_vault_pubkey != *bid_vault_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let clock = Clock::get()?;
+        if clock.unix_timestamp >= english.auction.end {
+            return Err(EscrowError::AuctionEnded.into());
+        }
+
+        // the first bid has to meet the reserve, every one after it has to beat the last by the increment
+        let has_bids = english.auction.bidder_pubkey != Pubkey::default();
+        let minimum = if has_bids {
+            english
+                .auction
+                .highest_bid
+                .checked_add(english.min_increment.max(1))
+                .ok_or(EscrowError::AmountOverflow)?
+        } else {
+            escrow_info.expected_amount.max(1)
+        };
+        if amount < minimum {
+            return Err(EscrowError::BidTooLow.into());
+        }
+
+        let bid_vault_info = token::unpack_token_account(bid_vault_acc, y_token_program_acc.key)?;
+        if bid_vault_info.mint != *mint_y_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mint_y = token::unpack_mint(mint_y_acc, y_token_program_acc.key)?;
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+
+        // ----------------------------------------------------------------------------- refund the last bid
+        // to any of the previous bidder's Y accounts - if it had to be the one they bid from, closing that would make
+        // them impossible to outbid
+        if has_bids {
+            let previous_y_acc = next_account_info(account_info_iter)?;
+            let previous_y_info =
+                token::unpack_token_account(previous_y_acc, y_token_program_acc.key)?;
+            if previous_y_info.owner != english.auction.bidder_pubkey {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            let refund_ix = token::transfer_checked(
+                y_token_program_acc.key,
+                bid_vault_acc.key,
+                mint_y_acc.key,
+                previous_y_acc.key,
+                &pda,
+                english.auction.highest_bid,
+                mint_y.decimals,
+            )?;
+
+            msg!("Calling the token program to refund the previous bid...");
+
+            invoke_signed(
+                &refund_ix,
+                &[
+                    bid_vault_acc.clone(),
+                    mint_y_acc.clone(),
+                    previous_y_acc.clone(),
+                    pda_acc.clone(),
+                    y_token_program_acc.clone(),
+                ],
+                &[&[&b"escrow"[..], &[bump_seed]]],
+            )?;
+        }
+
+        // ----------------------------------------------------------------------------- lock the new bid
+        // the vault has to end up holding the whole bid, so the bidder covers any transfer fee
+        let deposit_ix = token::transfer_checked(
+            y_token_program_acc.key,
+            bidder_y_acc.key,
+            mint_y_acc.key,
+            bid_vault_acc.key,
+            bidder_acc.key,
+            mint_y.pre_fee_amount(amount, clock.epoch)?,
+            mint_y.decimals,
+        )?;
+
+        msg!("Calling the token program to deposit the bid...");
+
+        invoke(
+            &deposit_ix,
+            &[
+                bidder_y_acc.clone(),
+                mint_y_acc.clone(),
+                bid_vault_acc.clone(),
+                bidder_acc.clone(),
+                y_token_program_acc.clone(),
+            ],
+        )?;
+
+        // ----------------------------------------------------------------------------- data
+        // a bid in the last moments gives everyone else time to answer
+        let extended_end = clock
+            .unix_timestamp
+            .checked_add(english.extension)
+            .ok_or(EscrowError::AmountOverflow)?;
+        english.auction.end = english.auction.end.max(extended_end);
+        english.auction.highest_bid = amount;
+        english.auction.bidder_pubkey = *bidder_acc.key;
+        Escrow::pack(escrow_info, &mut escrow_acc.data.borrow_mut())?;
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn process_init_sealed_auction(
+        accounts: &[AccountInfo],
+        reserve_price: u64,
+        bond: u64,
+        commit_end: i64,
+        reveal_end: i64,
+        second_price: bool,
+        rent_payer: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if commit_end <= Clock::get()?.unix_timestamp {
+            return Err(EscrowError::TimelockInThePast.into());
+        }
+        // a bond above the reserve would keep out bids that could win
+        if reveal_end <= commit_end || bond > reserve_price {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        Self::init_auction(
+            accounts,
+            reserve_price,
+            reveal_end,
+            EscrowMode::SealedAuction,
+            rent_payer,
+            program_id,
+            |auction| {
+                Terms::SealedAuction(SealedTerms {
+                    auction,
+                    commit_end,
+                    bond,
+                    second_bid: 0,
+                    second_price,
+                })
+            },
+        )
+    }
+
+    fn process_commit_bid(
+        accounts: &[AccountInfo],
+        commitment: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        // ----------------------------------------------------------------------------- get accs
+        let account_info_iter = &mut accounts.iter();
+
+        let bidder_acc = next_account_info(account_info_iter)?;
+        if !bidder_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let bidder_y_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let bid_acc = next_account_info(account_info_iter)?;
+        let bid_vault_acc = next_account_info(account_info_iter)?;
+        let mint_y_acc = next_account_info(account_info_iter)?;
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(y_token_program_acc)?;
+
+        // ----------------------------------------------------------------------------- checks
+        let escrow_info = Escrow::unpack(&escrow_acc.data.borrow())?;
+        let sealed = match escrow_info.terms {
+            Terms::SealedAuction(sealed) => sealed,
+            _ => return Err(EscrowError::WrongEscrowMode.into()),
+        };
+        if sealed.auction.bid_vault_pubkey != *bid_vault_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let clock = Clock::get()?;
+        if clock.unix_timestamp >= sealed.commit_end {
+            return Err(EscrowError::WrongAuctionPhase.into());
+        }
+
+        // reveal trusts what's in the bid account, so it has to be ours
+        if bid_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !Rent::get()?.is_exempt(bid_acc.lamports(), bid_acc.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+        if SealedBid::unpack_unchecked(&bid_acc.data.borrow())?.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        let bid_vault_info = token::unpack_token_account(bid_vault_acc, y_token_program_acc.key)?;
+        if bid_vault_info.mint != *mint_y_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mint_y = token::unpack_mint(mint_y_acc, y_token_program_acc.key)?;
+
+        // ----------------------------------------------------------------------------- pay the bond
+        // same as a bid in an English auction, the vault has to get all of it
+        let bond_ix = token::transfer_checked(
+            y_token_program_acc.key,
+            bidder_y_acc.key,
+            mint_y_acc.key,
+            bid_vault_acc.key,
+            bidder_acc.key,
+            mint_y.pre_fee_amount(sealed.bond, clock.epoch)?,
+            mint_y.decimals,
+        )?;
+
+        msg!("Calling the token program to pay the bond...");
+
+        invoke(
+            &bond_ix,
+            &[
+                bidder_y_acc.clone(),
+                mint_y_acc.clone(),
+                bid_vault_acc.clone(),
+                bidder_acc.clone(),
+                y_token_program_acc.clone(),
+            ],
+        )?;
+
+        // ----------------------------------------------------------------------------- data
+        let bid_info = SealedBid {
+            is_initialized: true,
+            escrow_pubkey: *escrow_acc.key,
+            bidder_pubkey: *bidder_acc.key,
+            commitment,
+            bond: sealed.bond,
+            reveal_end: sealed.auction.end,
+        };
+        SealedBid::pack(bid_info, &mut bid_acc.data.borrow_mut())?;
+        Ok(())
+    }
+
+    fn process_reveal_bid(
+        accounts: &[AccountInfo],
+        amount: u64,
+        salt: [u8; 32],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        // ----------------------------------------------------------------------------- get accs
+        let account_info_iter = &mut accounts.iter();
+
+        let bidder_acc = next_account_info(account_info_iter)?;
+        if !bidder_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let bidder_y_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let bid_acc = next_account_info(account_info_iter)?;
+        let bid_vault_acc = next_account_info(account_info_iter)?;
+        let mint_y_acc = next_account_info(account_info_iter)?;
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(y_token_program_acc)?;
+
+        // ----------------------------------------------------------------------------- checks
+        let mut escrow_info = Escrow::unpack(&escrow_acc.data.borrow())?;
+        let sealed = match &mut escrow_info.terms {
+            Terms::SealedAuction(sealed) => sealed,
+            _ => return Err(EscrowError::WrongEscrowMode.into()),
+        };
+        if sealed.auction.bid_vault_pubkey != *bid_vault_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let clock = Clock::get()?;
+        if clock.unix_timestamp < sealed.commit_end || clock.unix_timestamp >= sealed.auction.end {
+            return Err(EscrowError::WrongAuctionPhase.into());
+        }
+
+        if bid_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let bid_info = SealedBid::unpack(&bid_acc.data.borrow())?;
+        if bid_info.escrow_pubkey != *escrow_acc.key || bid_info.bidder_pubkey != *bidder_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if auction::bid_commitment(amount, &salt, bidder_acc.key) != bid_info.commitment {
+            return Err(EscrowError::CommitmentMismatch.into());
+        }
+
+        let bid_vault_info = token::unpack_token_account(bid_vault_acc, y_token_program_acc.key)?;
+        if bid_vault_info.mint != *mint_y_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mint_y = token::unpack_mint(mint_y_acc, y_token_program_acc.key)?;
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+
+        // ----------------------------------------------------------------------------- move the Y
+        // ties go to whoever revealed first
+        let has_bids = sealed.auction.bidder_pubkey != Pubkey::default();
+        let wins = amount >= escrow_info.expected_amount
+            && (!has_bids || amount > sealed.auction.highest_bid);
+        // whatever the highest bid turns out to be, the best one it beat sets the second price
+        let beaten = if wins {
+            sealed.auction.highest_bid
+        } else {
+            amount
+        };
+        sealed.second_bid = sealed.second_bid.max(beaten);
+
+        // the bid it beats gets refunded, to any of that bidder's Y accounts like in Bid. A losing bid only gets its bond
+        // back
+        let refund = if !wins {
+            Some((bidder_y_acc, bid_info.bond))
+        } else if has_bids {
+            let previous_y_acc = next_account_info(account_info_iter)?;
+            let previous_y_info =
+                token::unpack_token_account(previous_y_acc, y_token_program_acc.key)?;
+            if previous_y_info.owner != sealed.auction.bidder_pubkey {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            Some((previous_y_acc, sealed.auction.highest_bid))
+        } else {
+            None
+        };
+        if let Some((refund_acc, refund_amount)) = refund {
+            let refund_ix = token::transfer_checked(
+                y_token_program_acc.key,
+                bid_vault_acc.key,
+                mint_y_acc.key,
+                refund_acc.key,
+                &pda,
+                refund_amount,
+                mint_y.decimals,
+            )?;
+
+            msg!("Calling the token program to refund the bid...");
+
+            invoke_signed(
+                &refund_ix,
+                &[
+                    bid_vault_acc.clone(),
+                    mint_y_acc.clone(),
+                    refund_acc.clone(),
+                    pda_acc.clone(),
+                    y_token_program_acc.clone(),
+                ],
+                &[&[&b"escrow"[..], &[bump_seed]]],
+            )?;
+        }
+
+        if wins {
+            // the bond is already in, the bidder covers the rest plus any transfer fee
+            let rest = amount
+                .checked_sub(bid_info.bond)
+                .ok_or(EscrowError::AmountOverflow)?;
+            let deposit_ix = token::transfer_checked(
+                y_token_program_acc.key,
+                bidder_y_acc.key,
+                mint_y_acc.key,
+                bid_vault_acc.key,
+                bidder_acc.key,
+                mint_y.pre_fee_amount(rest, clock.epoch)?,
+                mint_y.decimals,
+            )?;
+
+            msg!("Calling the token program to deposit the rest of the bid...");
+
+            invoke(
+                &deposit_ix,
+                &[
+                    bidder_y_acc.clone(),
+                    mint_y_acc.clone(),
+                    bid_vault_acc.clone(),
+                    bidder_acc.clone(),
+                    y_token_program_acc.clone(),
+                ],
+            )?;
+
+            sealed.auction.highest_bid = amount;
+            sealed.auction.bidder_pubkey = *bidder_acc.key;
+        }
+
+        // ----------------------------------------------------------------------------- data
+        Escrow::pack(escrow_info, &mut escrow_acc.data.borrow_mut())?;
+        Self::close_escrow_account(bid_acc, bidder_acc)
+    }
+
+    fn process_close_bid(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let bidder_acc = next_account_info(account_info_iter)?;
+        let bid_acc = next_account_info(account_info_iter)?;
+
+        // no signer needed, the rent can only go back to the bidder
+        if bid_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let bid_info = SealedBid::unpack(&bid_acc.data.borrow())?;
+        if bid_info.bidder_pubkey != *bidder_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if Clock::get()?.unix_timestamp < bid_info.reveal_end {
+            return Err(EscrowError::TimelockNotExpired.into());
+        }
+        Self::close_escrow_account(bid_acc, bidder_acc)
+    }
+
+    fn process_settle_auction(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        // ----------------------------------------------------------------------------- get accs
+        let account_info_iter = &mut accounts.iter();
+
+        // no signer needed - once it's over there's only one way the tokens can go
+        let pda_temp_x_acc = next_account_info(account_info_iter)?;
+        let winner_x_acc = next_account_info(account_info_iter)?;
+        let bid_vault_acc = next_account_info(account_info_iter)?;
+        let seller_y_acc = next_account_info(account_info_iter)?;
+        let seller_main_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        let mint_x_acc = next_account_info(account_info_iter)?;
+        let mint_y_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        token::check_token_program(y_token_program_acc)?;
+
+        // ----------------------------------------------------------------------------- checks
+        let escrow_info = Escrow::unpack(&escrow_acc.data.borrow())?;
+        let auction_terms = escrow_info
+            .terms
+            .auction()
+            .ok_or(EscrowError::WrongEscrowMode)?;
+        let sealed = match escrow_info.terms {
+            Terms::SealedAuction(sealed) => Some(sealed),
+            _ => None,
+        };
+        if escrow_info.temp_token_account_pubkey != *pda_temp_x_acc.key
+            || auction_terms.bid_vault_pubkey != *bid_vault_acc.key
+            || escrow_info.initializer_pubkey != *seller_main_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let winner_y_acc = match sealed {
+            Some(sealed) => Some((next_account_info(account_info_iter)?, sealed)),
+            None => None,
+        };
+        let rent_recipient_acc =
+            Self::rent_recipient(&escrow_info.rent_payer, seller_main_acc, account_info_iter)?;
+
+        if Clock::get()?.unix_timestamp < auction_terms.end {
+            return Err(EscrowError::TimelockNotExpired.into());
+        }
+
+        // any account of the right owner will do, same reason as for the refunds in Bid
+        let winner = if auction_terms.bidder_pubkey == Pubkey::default() {
+            escrow_info.initializer_pubkey
+        } else {
+            auction_terms.bidder_pubkey
+        };
+        let winner_x_info = token::unpack_token_account(winner_x_acc, token_program_acc.key)?;
+        let seller_y_info = token::unpack_token_account(seller_y_acc, y_token_program_acc.key)?;
+        if winner_x_info.owner != winner || seller_y_info.owner != escrow_info.initializer_pubkey {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // ----------------------------------------------------------------------------- X to the winner, Y to the seller
+        // the seller gets the bid minus whatever fee Y charges on the way out of the vault
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        if let Some((winner_y_acc, sealed)) = winner_y_acc {
+            let winner_y_info = token::unpack_token_account(winner_y_acc, y_token_program_acc.key)?;
+            if winner_y_info.owner != winner {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            // a second-price winner gets back what they bid above the price. Whatever's left after that is the seller's,
+            // the bonds nobody revealed included
+            let overpaid = if auction_terms.bidder_pubkey == Pubkey::default() {
+                0
+            } else {
+                auction_terms
+                    .highest_bid
+                    .checked_sub(auction::sealed_price(&sealed, escrow_info.expected_amount))
+                    .ok_or(EscrowError::AmountOverflow)?
+            };
+            if overpaid > 0 {
+                let mint_y = token::unpack_mint(mint_y_acc, y_token_program_acc.key)?;
+                let refund_ix = token::transfer_checked(
+                    y_token_program_acc.key,
+                    bid_vault_acc.key,
+                    mint_y_acc.key,
+                    winner_y_acc.key,
+                    &pda,
+                    overpaid,
+                    mint_y.decimals,
+                )?;
+
+                msg!("Calling the token program to refund the winner above the second price...");
+
+                invoke_signed(
+                    &refund_ix,
+                    &[
+                        bid_vault_acc.clone(),
+                        mint_y_acc.clone(),
+                        winner_y_acc.clone(),
+                        pda_acc.clone(),
+                        y_token_program_acc.clone(),
+                    ],
+                    &[&[&b"escrow"[..], &[bump_seed]]],
+                )?;
+            }
+        }
+        Self::empty_vault(
+            token_program_acc,
+            pda_temp_x_acc,
+            mint_x_acc,
+            winner_x_acc,
+            rent_recipient_acc,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )?;
+        Self::empty_vault(
+            y_token_program_acc,
+            bid_vault_acc,
+            mint_y_acc,
+            seller_y_acc,
+            rent_recipient_acc,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )?;
+        Self::close_escrow_account(escrow_acc, rent_recipient_acc)
+    }
+    fn process_match(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        // ----------------------------------------------------------------------------- get accs
+        let account_info_iter = &mut accounts.iter();
+
+        // no signer needed - both makers already agreed to their prices
+        let escrow_a_acc = next_account_info(account_info_iter)?;
+        let vault_a_acc = next_account_info(account_info_iter)?;
+        let main_a_acc = next_account_info(account_info_iter)?;
+        let receive_a_acc = next_account_info(account_info_iter)?;
+        let recipient_a_acc = next_account_info(account_info_iter)?;
+        let escrow_b_acc = next_account_info(account_info_iter)?;
+        let vault_b_acc = next_account_info(account_info_iter)?;
+        let main_b_acc = next_account_info(account_info_iter)?;
+        let receive_b_acc = next_account_info(account_info_iter)?;
+        let recipient_b_acc = next_account_info(account_info_iter)?;
+        let cranker_x_acc = next_account_info(account_info_iter)?;
+        let cranker_y_acc = next_account_info(account_info_iter)?;
+        let mint_x_acc = next_account_info(account_info_iter)?;
+        let mint_y_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        token::check_token_program(y_token_program_acc)?;
+
+        // ----------------------------------------------------------------------------- checks
+        if escrow_a_acc.key == escrow_b_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let escrow_a = Escrow::unpack(&escrow_a_acc.data.borrow())?;
+        let escrow_b = Escrow::unpack(&escrow_b_acc.data.borrow())?;
+        // only plain vaults have a fixed price for everything in them
+        if escrow_a.mode != EscrowMode::Vault
+            || escrow_b.mode != EscrowMode::Vault
+            || escrow_a.request != Request::Tokens
+            || escrow_b.request != Request::Tokens
+            || escrow_a.royalties
+            || escrow_b.royalties
+        {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        for (escrow_info, vault_acc, main_acc, receive_acc) in [
+            (&escrow_a, vault_a_acc, main_a_acc, receive_a_acc),
+            (&escrow_b, vault_b_acc, main_b_acc, receive_b_acc),
+        ]
+        .iter()
+        {
+            if escrow_info.temp_token_account_pubkey != *vault_acc.key
+                || escrow_info.initializer_pubkey != *main_acc.key
+                || escrow_info.initializer_token_to_receive_account_pubkey != *receive_acc.key
+            {
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
+        let rent_recipient_a_acc =
+            Self::rent_recipient(&escrow_a.rent_payer, main_a_acc, account_info_iter)?;
+        let rent_recipient_b_acc =
+            Self::rent_recipient(&escrow_b.rent_payer, main_b_acc, account_info_iter)?;
+
+        // the two escrows have to be mirror images: A's vault is what B wants and the other way round
+        let vault_a_info = token::unpack_token_account(vault_a_acc, token_program_acc.key)?;
+        let vault_b_info = token::unpack_token_account(vault_b_acc, y_token_program_acc.key)?;
+        let receive_a_info = token::unpack_token_account(receive_a_acc, y_token_program_acc.key)?;
+        let receive_b_info = token::unpack_token_account(receive_b_acc, token_program_acc.key)?;
+        if vault_a_info.mint != *mint_x_acc.key
+            || receive_b_info.mint != *mint_x_acc.key
+            || vault_b_info.mint != *mint_y_acc.key
+            || receive_a_info.mint != *mint_y_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::check_match_recipient(&escrow_a, recipient_a_acc, token_program_acc.key)?;
+        Self::check_match_recipient(&escrow_b, recipient_b_acc, y_token_program_acc.key)?;
+        let mint_x = token::unpack_mint(mint_x_acc, token_program_acc.key)?;
+        let mint_y = token::unpack_mint(mint_y_acc, y_token_program_acc.key)?;
+
+        // ----------------------------------------------------------------------------- quant checks
+        let epoch = if mint_x.transfer_fee.is_some() || mint_y.transfer_fee.is_some() {
+            Clock::get()?.epoch
+        } else {
+            0
+        };
+
+        // each maker asked for an amount net of fees, same as in Exchange
+        let owed_to_a = mint_y.pre_fee_amount(escrow_a.expected_amount, epoch)?;
+        let owed_to_b = mint_x.pre_fee_amount(escrow_b.expected_amount, epoch)?;
+        let improvement_x = matching::improvement(vault_a_info.amount, owed_to_b)?;
+        let improvement_y = matching::improvement(vault_b_info.amount, owed_to_a)?;
+
+        // ----------------------------------------------------------------------------- settle both
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        let x_payouts = [
+            (receive_b_acc, owed_to_b),
+            (cranker_x_acc, matching::crank_fee(improvement_x)),
+        ];
+        for (destination_acc, amount) in x_payouts.iter() {
+            Self::pay_from_vault(
+                token_program_acc,
+                vault_a_acc,
+                mint_x_acc,
+                mint_x.decimals,
+                destination_acc,
+                *amount,
+                pda_acc,
+                &pda,
+                bump_seed,
+            )?;
+        }
+        let y_payouts = [
+            (receive_a_acc, owed_to_a),
+            (cranker_y_acc, matching::crank_fee(improvement_y)),
+        ];
+        for (destination_acc, amount) in y_payouts.iter() {
+            Self::pay_from_vault(
+                y_token_program_acc,
+                vault_b_acc,
+                mint_y_acc,
+                mint_y.decimals,
+                destination_acc,
+                *amount,
+                pda_acc,
+                &pda,
+                bump_seed,
+            )?;
+        }
+
+        // whatever is left is the improvement net of the cranker's cut
+        Self::empty_vault(
+            token_program_acc,
+            vault_a_acc,
+            mint_x_acc,
+            recipient_a_acc,
+            rent_recipient_a_acc,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )?;
+        Self::empty_vault(
+            y_token_program_acc,
+            vault_b_acc,
+            mint_y_acc,
+            recipient_b_acc,
+            rent_recipient_b_acc,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )?;
+
+        msg!("Closing the escrow accounts...");
+
+        Self::close_escrow_account(escrow_a_acc, rent_recipient_a_acc)?;
+        Self::close_escrow_account(escrow_b_acc, rent_recipient_b_acc)
+    }
+
+    // the recipient the initializer set, or any of their own accounts if they didn't, like the refunds in Bid
+    fn check_match_recipient(
+        escrow_info: &Escrow,
+        recipient_acc: &AccountInfo,
+        token_program_id: &Pubkey,
+    ) -> ProgramResult {
+        if escrow_info.recipient_token_account_pubkey != Pubkey::default() {
+            if escrow_info.recipient_token_account_pubkey != *recipient_acc.key {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            return Ok(());
+        }
+        let recipient_info = token::unpack_token_account(recipient_acc, token_program_id)?;
+        if recipient_info.owner != escrow_info.initializer_pubkey {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn pay_from_vault<'a>(
+        token_program_acc: &AccountInfo<'a>,
+        vault_acc: &AccountInfo<'a>,
+        mint_acc: &AccountInfo<'a>,
+        decimals: u8,
+        destination_acc: &AccountInfo<'a>,
+        amount: u64,
+        pda_acc: &AccountInfo<'a>,
+        pda: &Pubkey,
+        bump_seed: u8,
+    ) -> ProgramResult {
+        if amount == 0 {
+            return Ok(());
+        }
+        let transfer_ix = token::transfer_checked(
+            token_program_acc.key,
+            vault_acc.key,
+            mint_acc.key,
+            destination_acc.key,
+            pda,
+            amount,
+            decimals,
+        )?;
+
+        msg!("Calling the token program to pay out of the vault...");
+
+        invoke_signed(
+            &transfer_ix,
+            &[
+                vault_acc.clone(),
+                mint_acc.clone(),
+                destination_acc.clone(),
+                pda_acc.clone(),
+                token_program_acc.clone(),
+            ],
+            &[&[&b"escrow"[..], &[bump_seed]]],
+        )
+    }
+
+    fn process_set_match_recipient(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let vault_acc = next_account_info(account_info_iter)?;
+        let recipient_acc = next_account_info(account_info_iter)?;
+
+        let mut escrow_info = Escrow::unpack(&escrow_acc.data.borrow())?;
+        if escrow_info.mode != EscrowMode::Vault {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        if escrow_info.initializer_pubkey != *initializer_acc.key
+            || escrow_info.temp_token_account_pubkey != *vault_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::check_initializer_approval(
+            &escrow_info,
+            initializer_acc,
+            account_info_iter,
+            program_id,
+        )?;
+        // the improvement is paid in whatever the vault holds
+        let vault_info = token::unpack_token_account(vault_acc, vault_acc.owner)?;
+        let recipient_info = token::unpack_token_account(recipient_acc, vault_acc.owner)?;
+        if recipient_info.mint != vault_info.mint {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        escrow_info.recipient_token_account_pubkey = *recipient_acc.key;
+        Escrow::pack(escrow_info, &mut escrow_acc.data.borrow_mut())?;
+        Ok(())
+    }
+
+    fn process_fill_signed_order(
+        accounts: &[AccountInfo],
+        order: &Order,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let taker_main_acc = next_account_info(account_info_iter)?;
+        if !taker_main_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let taker_y_acc = next_account_info(account_info_iter)?;
+        let taker_x_acc = next_account_info(account_info_iter)?;
+        let maker_x_acc = next_account_info(account_info_iter)?;
+        let maker_y_acc = next_account_info(account_info_iter)?;
+        let bitmap_acc = next_account_info(account_info_iter)?;
+        let mint_x_acc = next_account_info(account_info_iter)?;
+        let mint_y_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(y_token_program_acc)?;
+        let delegate_acc = next_account_info(account_info_iter)?;
+        let instructions_acc = next_account_info(account_info_iter)?;
+        let system_program_acc = next_account_info(account_info_iter)?;
+
+        let clock = Clock::get()?;
+        if clock.unix_timestamp >= order.expiry {
+            return Err(EscrowError::OrderExpired.into());
+        }
+        order::check_ed25519(instructions_acc, &order.maker, &order.message(program_id))?;
+
+        // the maker signed for the mints, and can be paid into any of their accounts for them
+        if *mint_x_acc.key != order.mint_x || *mint_y_acc.key != order.mint_y {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let maker_x_info = token::unpack_token_account(maker_x_acc, token_program_acc.key)?;
+        let maker_y_info = token::unpack_token_account(maker_y_acc, y_token_program_acc.key)?;
+        if maker_x_info.owner != order.maker
+            || maker_x_info.mint != order.mint_x
+            || maker_y_info.owner != order.maker
+            || maker_y_info.mint != order.mint_y
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mint_x = token::unpack_mint(mint_x_acc, token_program_acc.key)?;
+        let mint_y = token::unpack_mint(mint_y_acc, y_token_program_acc.key)?;
+
+        Self::use_nonce(
+            taker_main_acc,
+            bitmap_acc,
+            system_program_acc,
+            &order.maker,
+            order.nonce,
+            program_id,
+        )?;
+
+        // the maker asked to receive amount_y, so if Y charges a fee the taker has to send a bit more
+        let amount_to_maker = mint_y.pre_fee_amount(order.amount_y, clock.epoch)?;
+        let transfer_to_maker_ix = token::transfer_checked(
+            y_token_program_acc.key,
+            taker_y_acc.key,
+            mint_y_acc.key,
+            maker_y_acc.key,
+            taker_main_acc.key,
+            amount_to_maker,
+            mint_y.decimals,
+        )?;
+
+        msg!("Calling the token program to transfer tokens to the maker...");
+
+        invoke(
+            &transfer_to_maker_ix,
+            &[
+                taker_y_acc.clone(),
+                mint_y_acc.clone(),
+                maker_y_acc.clone(),
+                taker_main_acc.clone(),
+                y_token_program_acc.clone(),
+            ],
+        )?;
+
+        Self::pay_from_maker(
+            token_program_acc,
+            maker_x_acc,
+            &maker_x_info,
+            mint_x_acc,
+            mint_x.decimals,
+            taker_x_acc,
+            delegate_acc,
+            order,
+            program_id,
+        )
+    }
+
+    // moves the order's X out of the maker's account, with the maker's order delegate signing for it
+    #[allow(clippy::too_many_arguments)]
+    fn pay_from_maker<'a>(
+        token_program_acc: &AccountInfo<'a>,
+        maker_x_acc: &AccountInfo<'a>,
+        maker_x_info: &TokenAccount,
+        mint_x_acc: &AccountInfo<'a>,
+        decimals: u8,
+        destination_acc: &AccountInfo<'a>,
+        delegate_acc: &AccountInfo<'a>,
+        order: &Order,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let (delegate, bump_seed) = order::delegate_address(&order.maker, program_id);
+        if *delegate_acc.key != delegate {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if maker_x_info.delegate != COption::Some(delegate)
+            || maker_x_info.delegated_amount < order.amount_x
+        {
+            return Err(EscrowError::OrderNotApproved.into());
+        }
+        let transfer_ix = token::transfer_checked(
+            token_program_acc.key,
+            maker_x_acc.key,
+            mint_x_acc.key,
+            destination_acc.key,
+            &delegate,
+            order.amount_x,
+            decimals,
+        )?;
+
+        msg!("Calling the token program to transfer the maker's tokens...");
+
+        invoke_signed(
+            &transfer_ix,
+            &[
+                maker_x_acc.clone(),
+                mint_x_acc.clone(),
+                destination_acc.clone(),
+                delegate_acc.clone(),
+                token_program_acc.clone(),
+            ],
+            &[&[&b"order"[..], order.maker.as_ref(), &[bump_seed]]],
+        )
+    }
+
+    fn process_cancel_order_nonce(
+        accounts: &[AccountInfo],
+        nonce: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let maker_acc = next_account_info(account_info_iter)?;
+        if !maker_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let bitmap_acc = next_account_info(account_info_iter)?;
+        let system_program_acc = next_account_info(account_info_iter)?;
+
+        Self::use_nonce(
+            maker_acc,
+            bitmap_acc,
+            system_program_acc,
+            maker_acc.key,
+            nonce,
+            program_id,
+        )
+    }
+
+    // marks `nonce` of `maker` as used, creating its bitmap account first if it's the first one of its window
+    fn use_nonce<'a>(
+        payer_acc: &AccountInfo<'a>,
+        bitmap_acc: &AccountInfo<'a>,
+        system_program_acc: &AccountInfo<'a>,
+        maker: &Pubkey,
+        nonce: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let window = NonceBitmap::window(nonce);
+        let (address, bump_seed) = order::nonce_bitmap_address(maker, window, program_id);
+        if address != *bitmap_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let mut bitmap = if bitmap_acc.data_is_empty() {
+            Self::create_pda_account(
+                payer_acc,
+                bitmap_acc,
+                system_program_acc,
+                NonceBitmap::LEN,
+                &[
+                    b"nonces",
+                    maker.as_ref(),
+                    &window.to_le_bytes(),
+                    &[bump_seed],
+                ],
+                program_id,
+            )?;
+            NonceBitmap::new(*maker, window)
+        } else {
+            if bitmap_acc.owner != program_id {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            NonceBitmap::unpack(&bitmap_acc.data.borrow())?
+        };
+        if bitmap.is_used(nonce) {
+            return Err(EscrowError::NonceAlreadyUsed.into());
+        }
+        bitmap.set_used(nonce);
+        NonceBitmap::pack(bitmap, &mut bitmap_acc.data.borrow_mut())
+    }
+
+    // creates a rent-exempt account of ours at a PDA. Anyone can send lamports to the address beforehand, which would
+    // make create_account fail, so if there are some we top them up, allocate and assign instead
+    fn create_pda_account<'a>(
+        payer_acc: &AccountInfo<'a>,
+        new_acc: &AccountInfo<'a>,
+        system_program_acc: &AccountInfo<'a>,
+        space: usize,
+        seeds: &[&[u8]],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        if *system_program_acc.key != system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let lamports = Rent::get()?.minimum_balance(space);
+
+        msg!("Calling the system program to create a PDA account...");
+
+        if new_acc.lamports() == 0 {
+            return invoke_signed(
+                &system_instruction::create_account(
+                    payer_acc.key,
+                    new_acc.key,
+                    lamports,
+                    space as u64,
+                    program_id,
+                ),
+                &[
+                    payer_acc.clone(),
+                    new_acc.clone(),
+                    system_program_acc.clone(),
+                ],
+                &[seeds],
+            );
+        }
+        let top_up = lamports.saturating_sub(new_acc.lamports());
+        if top_up > 0 {
+            invoke(
+                &system_instruction::transfer(payer_acc.key, new_acc.key, top_up),
+                &[
+                    payer_acc.clone(),
+                    new_acc.clone(),
+                    system_program_acc.clone(),
+                ],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(new_acc.key, space as u64),
+            &[new_acc.clone(), system_program_acc.clone()],
+            &[seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(new_acc.key, program_id),
+            &[new_acc.clone(), system_program_acc.clone()],
+            &[seeds],
+        )
+    }
+
+    fn process_flash_exchange(
+        accounts: &[AccountInfo],
+        amount_expected_by_taker: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let taker_main_acc = next_account_info(account_info_iter)?;
+        if !taker_main_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let taker_x_acc = next_account_info(account_info_iter)?;
+        let pda_temp_x_acc = next_account_info(account_info_iter)?;
+        let initializer_main_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let mut escrow_info = Escrow::unpack(&escrow_acc.data.borrow())?;
+        if (escrow_info.mode != EscrowMode::Vault && escrow_info.mode != EscrowMode::Approve)
+            || escrow_info.request != Request::Tokens
+            || escrow_info.royalties
+        {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        if escrow_info.temp_token_account_pubkey != *pda_temp_x_acc.key
+            || escrow_info.initializer_pubkey != *initializer_main_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        let pda_temp_x_info = token::unpack_token_account(pda_temp_x_acc, token_program_acc.key)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        let mint_x_acc = next_account_info(account_info_iter)?;
+        if pda_temp_x_info.mint != *mint_x_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mint_x = token::unpack_mint(mint_x_acc, token_program_acc.key)?;
+
+        // the whole point: no X leaves unless the transaction goes on to pay for it
+        let instructions_acc = next_account_info(account_info_iter)?;
+        flash::check_settled_later(instructions_acc, escrow_acc.key, program_id)?;
+
+        let rent_recipient_acc = Self::rent_recipient(
+            &escrow_info.rent_payer,
+            initializer_main_acc,
+            account_info_iter,
+        )?;
+
+        let epoch = if mint_x.transfer_fee.is_some() {
+            Clock::get()?.epoch
+        } else {
+            0
+        };
+        let amount_x = match escrow_info.mode {
+            EscrowMode::Approve => escrow_info.offered_amount,
+            _ => pda_temp_x_info.amount,
+        };
+        if amount_expected_by_taker != mint_x.received(amount_x, epoch)? {
+            return Err(EscrowError::ExpectedAmountMismatch.into());
+        }
+
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::pay_from_vault(
+            token_program_acc,
+            pda_temp_x_acc,
+            mint_x_acc,
+            mint_x.decimals,
+            taker_x_acc,
+            amount_x,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )?;
+        if escrow_info.mode == EscrowMode::Vault {
+            Self::close_temp_account(
+                token_program_acc,
+                pda_temp_x_acc,
+                mint_x_acc,
+                rent_recipient_acc,
+                pda_acc,
+                &pda,
+                bump_seed,
+            )?;
+        }
+
+        // the escrow stays around for SettleFlash, and can't be taken or cancelled again in the meantime
+        escrow_info.mode = EscrowMode::FlashSettling;
+        Escrow::pack(escrow_info, &mut escrow_acc.data.borrow_mut())?;
+        Ok(())
+    }
+
+    fn process_settle_flash(accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let payer_acc = next_account_info(account_info_iter)?;
+        if !payer_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let payer_y_acc = next_account_info(account_info_iter)?;
+        let initializer_y_acc = next_account_info(account_info_iter)?;
+        let initializer_main_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let escrow_info = Escrow::unpack(&escrow_acc.data.borrow())?;
+        if escrow_info.mode != EscrowMode::FlashSettling {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        if escrow_info.initializer_token_to_receive_account_pubkey != *initializer_y_acc.key
+            || escrow_info.initializer_pubkey != *initializer_main_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let mint_y_acc = next_account_info(account_info_iter)?;
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(y_token_program_acc)?;
+        let initializer_y_info =
+            token::unpack_token_account(initializer_y_acc, y_token_program_acc.key)?;
+        if initializer_y_info.mint != *mint_y_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mint_y = token::unpack_mint(mint_y_acc, y_token_program_acc.key)?;
+        let rent_recipient_acc = Self::rent_recipient(
+            &escrow_info.rent_payer,
+            initializer_main_acc,
+            account_info_iter,
+        )?;
+
+        let epoch = if mint_y.transfer_fee.is_some() {
+            Clock::get()?.epoch
+        } else {
+            0
+        };
+        // same as Exchange, the initializer gets exactly their price
+        let amount_to_initializer = mint_y.pre_fee_amount(escrow_info.expected_amount, epoch)?;
+        let transfer_to_initializer_ix = token::transfer_checked(
+            y_token_program_acc.key,
+            payer_y_acc.key,
+            mint_y_acc.key,
+            initializer_y_acc.key,
+            payer_acc.key,
+            amount_to_initializer,
+            mint_y.decimals,
+        )?;
+
+        msg!("Calling the token program to transfer tokens to the escrow's initializer...");
+
+        invoke(
+            &transfer_to_initializer_ix,
+            &[
+                payer_y_acc.clone(),
+                mint_y_acc.clone(),
+                initializer_y_acc.clone(),
+                payer_acc.clone(),
+                y_token_program_acc.clone(),
+            ],
+        )?;
+
+        Self::close_escrow_account(escrow_acc, rent_recipient_acc)
+    }
+
+    fn process_init_distribution(
+        accounts: &[AccountInfo],
+        merkle_root: [u8; 32],
+        num_recipients: u64,
+        deadline: i64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let temp_token_account = next_account_info(account_info_iter)?;
+        let distribution_acc = next_account_info(account_info_iter)?;
+        let bitmap_acc = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program)?;
+        let mint = next_account_info(account_info_iter)?;
+
+        let rent = &Rent::get()?;
+        if !rent.is_exempt(distribution_acc.lamports(), distribution_acc.data_len())
+            || !rent.is_exempt(bitmap_acc.lamports(), bitmap_acc.data_len())
+        {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+
+        let temp_token_account_info =
+            token::unpack_token_account(temp_token_account, token_program.key)?;
+        if temp_token_account_info.mint != *mint.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        token::unpack_mint(mint, token_program.key)?;
+        Self::check_temp_account(&temp_token_account_info, EscrowMode::Vault)?;
+
+        if num_recipients == 0 {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        if deadline <= Clock::get()?.unix_timestamp {
+            return Err(EscrowError::TimelockInThePast.into());
+        }
+
+        let distribution_info = Distribution::unpack_unchecked(&distribution_acc.data.borrow())?;
+        if distribution_info.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        // the bitmap is written to on every claim, so it has to be ours too, and not the distribution itself
+        if bitmap_acc.owner != program_id
+            || bitmap_acc.key == distribution_acc.key
+            || bitmap_acc.data_len() != ClaimedBitmap::len(num_recipients)
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if ClaimedBitmap::is_initialized(&bitmap_acc.data.borrow()) {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        ClaimedBitmap::init(&mut bitmap_acc.data.borrow_mut(), distribution_acc.key);
+
+        let distribution_info = Distribution {
+            is_initialized: true,
+            initializer_pubkey: *initializer.key,
+            vault_pubkey: *temp_token_account.key,
+            claimed_bitmap_pubkey: *bitmap_acc.key,
+            merkle_root,
+            num_recipients,
+            deadline,
+        };
+        Distribution::pack(distribution_info, &mut distribution_acc.data.borrow_mut())?;
+
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::hand_to_pda(token_program, temp_token_account, initializer, &pda)
+    }
+
+    fn process_claim_distribution(
+        accounts: &[AccountInfo],
+        index: u64,
+        amount: u64,
+        proof: &[[u8; 32]],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let recipient_token_acc = next_account_info(account_info_iter)?;
+        let distribution_acc = next_account_info(account_info_iter)?;
+        let bitmap_acc = next_account_info(account_info_iter)?;
+        let vault_acc = next_account_info(account_info_iter)?;
+        let mint_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+
+        // a claim never writes to the distribution, so nothing else would stop anyone passing in one of their own, with
+        // their own root and somebody else's vault
+        if distribution_acc.owner != program_id || bitmap_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let distribution_info = Distribution::unpack(&distribution_acc.data.borrow())?;
+        if distribution_info.claimed_bitmap_pubkey != *bitmap_acc.key
+            || distribution_info.vault_pubkey != *vault_acc.key
+            || !ClaimedBitmap::belongs_to(
+                &bitmap_acc.data.borrow(),
+                distribution_acc.key,
+                distribution_info.num_recipients,
+            )
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if Clock::get()?.unix_timestamp >= distribution_info.deadline {
+            return Err(EscrowError::DistributionEnded.into());
+        }
+        if index >= distribution_info.num_recipients {
+            return Err(EscrowError::InvalidProof.into());
+        }
+
+        // the leaf names the recipient, so whoever sends this can only pay them
+        let recipient_info =
+            token::unpack_token_account(recipient_token_acc, token_program_acc.key)?;
+        let leaf = merkle::leaf(index, &recipient_info.owner, amount);
+        if !merkle::verify(&distribution_info.merkle_root, leaf, proof) {
+            return Err(EscrowError::InvalidProof.into());
+        }
+        if ClaimedBitmap::is_claimed(&bitmap_acc.data.borrow(), index) {
+            return Err(EscrowError::AlreadyClaimed.into());
+        }
+        ClaimedBitmap::set_claimed(&mut bitmap_acc.data.borrow_mut(), index);
+
+        let mint = token::unpack_mint(mint_acc, token_program_acc.key)?;
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::pay_from_vault(
+            token_program_acc,
+            vault_acc,
+            mint_acc,
+            mint.decimals,
+            recipient_token_acc,
+            amount,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )
+    }
+
+    fn process_reclaim_distribution(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer_acc = next_account_info(account_info_iter)?;
+        if !initializer_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let initializer_token_acc = next_account_info(account_info_iter)?;
+        let distribution_acc = next_account_info(account_info_iter)?;
+        let bitmap_acc = next_account_info(account_info_iter)?;
+        let vault_acc = next_account_info(account_info_iter)?;
+        let mint_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+
+        if distribution_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let distribution_info = Distribution::unpack(&distribution_acc.data.borrow())?;
+        if distribution_info.initializer_pubkey != *initializer_acc.key
+            || distribution_info.claimed_bitmap_pubkey != *bitmap_acc.key
+            || distribution_info.vault_pubkey != *vault_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if Clock::get()?.unix_timestamp < distribution_info.deadline {
+            return Err(EscrowError::TimelockNotExpired.into());
+        }
+
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::empty_vault(
+            token_program_acc,
+            vault_acc,
+            mint_acc,
+            initializer_token_acc,
+            initializer_acc,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )?;
+        Self::close_escrow_account(bitmap_acc, initializer_acc)?;
+        Self::close_escrow_account(distribution_acc, initializer_acc)
+    }
+
+    fn process_init_nft_escrow(
+        accounts: &[AccountInfo],
+        request: Request,
+        request_key: Pubkey,
+        rent_payer: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let temp_token_account = next_account_info(account_info_iter)?;
+        let escrow_account = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program)?;
+        let mint_x = next_account_info(account_info_iter)?;
+        // [5] the rent payer, if there is one, same as InitEscrow's
+        let rent_payer = Self::rent_payer(rent_payer, initializer, account_info_iter)?;
+
+        if request == Request::Tokens {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        if !Rent::get()?.is_exempt(escrow_account.lamports(), escrow_account.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+        let escrow_info = Escrow::unpack_unchecked(&escrow_account.data.borrow())?;
+        if escrow_info.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        let temp_token_account_info =
+            token::unpack_token_account(temp_token_account, token_program.key)?;
+        if temp_token_account_info.mint != *mint_x.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        token::unpack_mint(mint_x, token_program.key)?;
+        Self::check_temp_account(&temp_token_account_info, EscrowMode::Vault)?;
+
+        let escrow_info = Escrow {
+            is_initialized: true,
+            initializer_pubkey: *initializer.key,
+            temp_token_account_pubkey: *temp_token_account.key,
+            expected_amount: 1,
+            mode: EscrowMode::Vault,
+            rent_payer,
+            request,
+            request_key,
+            ..Escrow::default()
+        };
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())?;
+
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::hand_to_pda(token_program, temp_token_account, initializer, &pda)
+    }
+
+    fn process_exchange_nft(
+        accounts: &[AccountInfo],
+        amount_expected_by_taker: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let taker_main_acc = next_account_info(account_info_iter)?;
+        if !taker_main_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let taker_nft_acc = next_account_info(account_info_iter)?;
+        let initializer_nft_acc = next_account_info(account_info_iter)?;
+        let taker_x_acc = next_account_info(account_info_iter)?;
+        let pda_temp_x_acc = next_account_info(account_info_iter)?;
+        let initializer_main_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        let mint_x_acc = next_account_info(account_info_iter)?;
+        let nft_mint_acc = next_account_info(account_info_iter)?;
+        let metadata_acc = next_account_info(account_info_iter)?;
+        let nft_token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(nft_token_program_acc)?;
+
+        let escrow_info = Escrow::unpack(&escrow_acc.data.borrow())?;
+        if escrow_info.mode != EscrowMode::Vault || escrow_info.request == Request::Tokens {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        if escrow_info.temp_token_account_pubkey != *pda_temp_x_acc.key
+            || escrow_info.initializer_pubkey != *initializer_main_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let rent_recipient_acc = Self::rent_recipient(
+            &escrow_info.rent_payer,
+            initializer_main_acc,
+            account_info_iter,
+        )?;
+
+        // ----------------------------------------------------------------------------- is it the NFT alice wants
+
+        // one of one, so the single token sent is the whole thing
+        let nft_mint = token::unpack_mint(nft_mint_acc, nft_token_program_acc.key)?;
+        if nft_mint.decimals != 0 || nft_mint.supply != 1 {
+            return Err(EscrowError::NotAnNft.into());
+        }
+        match escrow_info.request {
+            Request::Mint => {
+                if *nft_mint_acc.key != escrow_info.request_key {
+                    return Err(EscrowError::NftNotRequested.into());
+                }
+            }
+            Request::Collection => {
+                // only a verified collection counts, anyone can mint an NFT that claims to be in one
+                let metadata = metadata::unpack_metadata(metadata_acc, nft_mint_acc.key)?;
+                match metadata.collection {
+                    Some(collection)
+                        if collection.verified && collection.key == escrow_info.request_key => {}
+                    _ => return Err(EscrowError::NftNotRequested.into()),
+                }
+            }
+            Request::Tokens => unreachable!(),
+        }
+        // whatever account it goes into, it has to be alice's
+        let initializer_nft_info =
+            token::unpack_token_account(initializer_nft_acc, nft_token_program_acc.key)?;
+        if initializer_nft_info.owner != *initializer_main_acc.key
+            || initializer_nft_info.mint != *nft_mint_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let pda_temp_x_info = token::unpack_token_account(pda_temp_x_acc, token_program_acc.key)?;
+        if pda_temp_x_info.mint != *mint_x_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mint_x = token::unpack_mint(mint_x_acc, token_program_acc.key)?;
+        let epoch = if mint_x.transfer_fee.is_some() {
+            Clock::get()?.epoch
+        } else {
+            0
+        };
+        if amount_expected_by_taker != mint_x.received(pda_temp_x_info.amount, epoch)? {
+            return Err(EscrowError::ExpectedAmountMismatch.into());
+        }
+
+        // ----------------------------------------------------------------------------- swap
+
+        let transfer_nft_ix = token::transfer_checked(
+            nft_token_program_acc.key,
+            taker_nft_acc.key,
+            nft_mint_acc.key,
+            initializer_nft_acc.key,
+            taker_main_acc.key,
+            1,
+            0,
+        )?;
+        msg!("Calling the token program to transfer the NFT to the escrow's initializer...");
+        invoke(
+            &transfer_nft_ix,
+            &[
+                taker_nft_acc.clone(),
+                nft_mint_acc.clone(),
+                initializer_nft_acc.clone(),
+                taker_main_acc.clone(),
+                nft_token_program_acc.clone(),
+            ],
+        )?;
+
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::empty_vault(
+            token_program_acc,
+            pda_temp_x_acc,
+            mint_x_acc,
+            taker_x_acc,
+            rent_recipient_acc,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )?;
+
+        msg!("Closing the escrow account...");
+        Self::close_escrow_account(escrow_acc, rent_recipient_acc)
+    }
+
+    // an InitEscrow for an NFT, flagged so Exchange pays its creators
+    fn process_init_royalty_escrow(
+        accounts: &[AccountInfo],
+        amount: u64,
+        rent_payer: bool,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        Self::process_init_escrow(
+            &mut accounts.iter(),
+            amount,
+            EscrowMode::Vault,
+            0,
+            rent_payer,
+            program_id,
+        )?;
+
+        // [3] the escrow account, [5] the X token program and [6] the X mint process_init_escrow already checked
+        let account = |i: usize| accounts.get(i).ok_or(ProgramError::NotEnoughAccountKeys);
+        let escrow_account = account(3)?;
+        let mint_x = token::unpack_mint(account(6)?, account(5)?.key)?;
+        if mint_x.decimals != 0 || mint_x.supply != 1 {
+            return Err(EscrowError::NotAnNft.into());
+        }
+
+        let mut escrow_info = Escrow::unpack(&escrow_account.data.borrow())?;
+        escrow_info.royalties = true;
+        Escrow::pack(escrow_info, &mut escrow_account.data.borrow_mut())
+    }
+
+    fn process_init_vesting(
+        accounts: &[AccountInfo],
+        start: i64,
+        cliff: i64,
+        end: i64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer = next_account_info(account_info_iter)?;
+        if !initializer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let temp_token_account = next_account_info(account_info_iter)?;
+        let vesting_acc = next_account_info(account_info_iter)?;
+        let beneficiary = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program)?;
+        let mint = next_account_info(account_info_iter)?;
+        // [6] optional, all zeroes makes it irrevocable
+        let revoker =
+            next_account_info(account_info_iter).map_or(Pubkey::default(), |acc| *acc.key);
+
+        if !Rent::get()?.is_exempt(vesting_acc.lamports(), vesting_acc.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+
+        let temp_token_account_info =
+            token::unpack_token_account(temp_token_account, token_program.key)?;
+        if temp_token_account_info.mint != *mint.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        token::unpack_mint(mint, token_program.key)?;
+        Self::check_temp_account(&temp_token_account_info, EscrowMode::Vault)?;
+        vesting::check_schedule(start, cliff, end)?;
+
+        let vesting_info = Vesting::unpack_unchecked(&vesting_acc.data.borrow())?;
+        if vesting_info.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        let vesting_info = Vesting {
+            is_initialized: true,
+            initializer_pubkey: *initializer.key,
+            beneficiary_pubkey: *beneficiary.key,
+            vault_pubkey: *temp_token_account.key,
+            revoker_pubkey: revoker,
+            start,
+            cliff,
+            end,
+            total_amount: temp_token_account_info.amount,
+            withdrawn: 0,
+        };
+        Vesting::pack(vesting_info, &mut vesting_acc.data.borrow_mut())?;
+
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::hand_to_pda(token_program, temp_token_account, initializer, &pda)
+    }
+
+    fn process_withdraw(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let beneficiary_acc = next_account_info(account_info_iter)?;
+        if !beneficiary_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let destination_acc = next_account_info(account_info_iter)?;
+        let state_acc = next_account_info(account_info_iter)?;
+        let vault_acc = next_account_info(account_info_iter)?;
+        let mint_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        let initializer_main_acc = next_account_info(account_info_iter)?;
+
+        // the payout goes by what's in the state account, so it has to be ours
+        if state_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let now = Clock::get()?.unix_timestamp;
+        // vestings and streams are withdrawn from the same way, the account type says which one it is
+        let account_type = AccountType::of(&state_acc.data.borrow());
+        let (unlocked, withdrawn, total) = match account_type {
+            Some(AccountType::Vesting) => {
+                let mut vesting_info = Vesting::unpack(&state_acc.data.borrow())?;
+                if vesting_info.beneficiary_pubkey != *beneficiary_acc.key
+                    || vesting_info.vault_pubkey != *vault_acc.key
+                    || vesting_info.initializer_pubkey != *initializer_main_acc.key
+                {
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                let unlocked = vesting::vested(&vesting_info, now);
+                let withdrawn = vesting_info.withdrawn;
+                vesting_info.withdrawn = unlocked;
+                Vesting::pack(vesting_info, &mut state_acc.data.borrow_mut())?;
+                (unlocked, withdrawn, vesting_info.total_amount)
+            }
+            Some(AccountType::Stream) => {
+                let mut stream_info = Stream::unpack(&state_acc.data.borrow())?;
+                if stream_info.recipient_pubkey != *beneficiary_acc.key
+                    || stream_info.vault_pubkey != *vault_acc.key
+                    || stream_info.payer_pubkey != *initializer_main_acc.key
+                {
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                let unlocked = stream_info.accrued(now);
+                let withdrawn = stream_info.withdrawn;
+                stream_info.withdrawn = unlocked;
+                Stream::pack(stream_info, &mut state_acc.data.borrow_mut())?;
+                (unlocked, withdrawn, stream_info.total())
+            }
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        let amount = unlocked
+            .checked_sub(withdrawn)
+            .ok_or(EscrowError::AmountOverflow)?;
+        if amount == 0 {
+            return Err(EscrowError::NothingToWithdraw.into());
+        }
+        msg!("withdrawing {} unlocked tokens", amount);
+
+        Self::pay_out_over_time(
+            unlocked == total,
+            amount,
+            state_acc,
+            vault_acc,
+            mint_acc,
+            destination_acc,
+            token_program_acc,
+            pda_acc,
+            initializer_main_acc,
+            program_id,
+        )
+    }
+
+    fn process_revoke_vesting(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let revoker_acc = next_account_info(account_info_iter)?;
+        if !revoker_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let destination_acc = next_account_info(account_info_iter)?;
+        let vesting_acc = next_account_info(account_info_iter)?;
+        let vault_acc = next_account_info(account_info_iter)?;
+        let mint_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        let initializer_main_acc = next_account_info(account_info_iter)?;
+
+        if vesting_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut vesting_info = Vesting::unpack(&vesting_acc.data.borrow())?;
+        if vesting_info.revoker_pubkey == Pubkey::default() {
+            return Err(EscrowError::NotRevocable.into());
+        }
+        if vesting_info.revoker_pubkey != *revoker_acc.key
+            || vesting_info.vault_pubkey != *vault_acc.key
+            || vesting_info.initializer_pubkey != *initializer_main_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // what has vested by now stays the beneficiary's, and all of it is unlocked from here on
+        let now = Clock::get()?.unix_timestamp;
+        let vested = vesting::vested(&vesting_info, now);
+        let unvested = vesting_info.total_amount - vested;
+        vesting_info.total_amount = vested;
+        vesting_info.start = vesting_info.start.min(now);
+        vesting_info.cliff = vesting_info.cliff.min(now);
+        vesting_info.end = vesting_info.end.min(now);
+        vesting_info.revoker_pubkey = Pubkey::default();
+        Vesting::pack(vesting_info, &mut vesting_acc.data.borrow_mut())?;
+        msg!("revoking {} unvested tokens", unvested);
+
+        Self::pay_out_over_time(
+            vesting_info.withdrawn == vesting_info.total_amount,
+            unvested,
+            vesting_acc,
+            vault_acc,
+            mint_acc,
+            destination_acc,
+            token_program_acc,
+            pda_acc,
+            initializer_main_acc,
+            program_id,
+        )
+    }
+
+    fn process_init_stream(
+        accounts: &[AccountInfo],
+        rate: u64,
+        duration: i64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let payer = next_account_info(account_info_iter)?;
+        if !payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let temp_token_account = next_account_info(account_info_iter)?;
+        let stream_acc = next_account_info(account_info_iter)?;
+        let recipient = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program)?;
+        let mint = next_account_info(account_info_iter)?;
+
+        if !Rent::get()?.is_exempt(stream_acc.lamports(), stream_acc.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+
+        let temp_token_account_info =
+            token::unpack_token_account(temp_token_account, token_program.key)?;
+        if temp_token_account_info.mint != *mint.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        token::unpack_mint(mint, token_program.key)?;
+        Self::check_temp_account(&temp_token_account_info, EscrowMode::Vault)?;
+
+        if rate == 0 || duration <= 0 {
+            return Err(EscrowError::InvalidSchedule.into());
+        }
+        let start = Clock::get()?.unix_timestamp;
+        let stream_info = Stream {
+            is_initialized: true,
+            payer_pubkey: *payer.key,
+            recipient_pubkey: *recipient.key,
+            vault_pubkey: *temp_token_account.key,
+            rate,
+            start,
+            end: start
+                .checked_add(duration)
+                .ok_or(EscrowError::AmountOverflow)?,
+            withdrawn: 0,
+        };
+        // the vault holds exactly what the stream pays out, so the recipient is never owed more than is in it
+        let total = rate
+            .checked_mul(duration as u64)
+            .ok_or(EscrowError::AmountOverflow)?;
+        if temp_token_account_info.amount != total {
+            return Err(EscrowError::ExpectedAmountMismatch.into());
+        }
+
+        if Stream::unpack_unchecked(&stream_acc.data.borrow())?.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        Stream::pack(stream_info, &mut stream_acc.data.borrow_mut())?;
+
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::hand_to_pda(token_program, temp_token_account, payer, &pda)
+    }
+
+    // the stream's Cancel: the recipient gets what has accrued, the payer the rest and the rent
+    fn process_cancel_stream(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let payer_acc = next_account_info(account_info_iter)?;
+        if !payer_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let payer_token_acc = next_account_info(account_info_iter)?;
+        let recipient_token_acc = next_account_info(account_info_iter)?;
+        let stream_acc = next_account_info(account_info_iter)?;
+        let vault_acc = next_account_info(account_info_iter)?;
+        let mint_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+
+        if stream_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let stream_info = Stream::unpack(&stream_acc.data.borrow())?;
+        if stream_info.payer_pubkey != *payer_acc.key || stream_info.vault_pubkey != *vault_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // the payer picks the account, so it has to be the recipient's
+        let recipient_token_info =
+            token::unpack_token_account(recipient_token_acc, token_program_acc.key)?;
+        if recipient_token_info.owner != stream_info.recipient_pubkey {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let accrued = stream_info
+            .accrued(Clock::get()?.unix_timestamp)
+            .checked_sub(stream_info.withdrawn)
+            .ok_or(EscrowError::AmountOverflow)?;
+        msg!("paying out {} accrued tokens", accrued);
+        Self::pay_out_over_time(
+            false,
+            accrued,
+            stream_acc,
+            vault_acc,
+            mint_acc,
+            recipient_token_acc,
+            token_program_acc,
+            pda_acc,
+            payer_acc,
+            program_id,
+        )?;
+        Self::pay_out_over_time(
+            true,
+            0,
+            stream_acc,
+            vault_acc,
+            mint_acc,
+            payer_token_acc,
+            token_program_acc,
+            pda_acc,
+            payer_acc,
+            program_id,
+        )
+    }
+
+    // pays `amount` out of a vesting, stream or milestone vault, or once `finished` whatever is left in it, closing the
+    // vault and the account describing it
+    #[allow(clippy::too_many_arguments)]
+    fn pay_out_over_time<'a>(
+        finished: bool,
+        amount: u64,
+        state_acc: &AccountInfo<'a>,
+        vault_acc: &AccountInfo<'a>,
+        mint_acc: &AccountInfo<'a>,
+        destination_acc: &AccountInfo<'a>,
+        token_program_acc: &AccountInfo<'a>,
+        pda_acc: &AccountInfo<'a>,
+        initializer_main_acc: &AccountInfo<'a>,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        if finished {
+            Self::empty_vault(
+                token_program_acc,
+                vault_acc,
+                mint_acc,
+                destination_acc,
+                initializer_main_acc,
+                pda_acc,
+                &pda,
+                bump_seed,
+            )?;
+            msg!("Closing the vault's state account...");
+            return Self::close_escrow_account(state_acc, initializer_main_acc);
+        }
+
+        let mint = token::unpack_mint(mint_acc, token_program_acc.key)?;
+        Self::pay_from_vault(
+            token_program_acc,
+            vault_acc,
+            mint_acc,
+            mint.decimals,
+            destination_acc,
+            amount,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )
+    }
+
+    fn process_init_milestone_escrow(
+        accounts: &[AccountInfo],
+        amounts: &[u64],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let payer = next_account_info(account_info_iter)?;
+        if !payer.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let temp_token_account = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let payee = next_account_info(account_info_iter)?;
+        let arbiter = next_account_info(account_info_iter)?;
+        let token_program = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program)?;
+        let mint = next_account_info(account_info_iter)?;
+
+        if amounts.is_empty() || amounts.len() > MilestoneEscrow::MAX_MILESTONES {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        if !Rent::get()?.is_exempt(escrow_acc.lamports(), escrow_acc.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+
+        let temp_token_account_info =
+            token::unpack_token_account(temp_token_account, token_program.key)?;
+        if temp_token_account_info.mint != *mint.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        token::unpack_mint(mint, token_program.key)?;
+        Self::check_temp_account(&temp_token_account_info, EscrowMode::Vault)?;
+        // the vault holds exactly what the milestones pay
+        let total = amounts
+            .iter()
+            .try_fold(0u64, |total, amount| total.checked_add(*amount))
+            .ok_or(EscrowError::AmountOverflow)?;
+        if temp_token_account_info.amount != total {
+            return Err(EscrowError::ExpectedAmountMismatch.into());
+        }
+
+        if MilestoneEscrow::unpack_unchecked(&escrow_acc.data.borrow())?.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        let mut escrow_info = MilestoneEscrow {
+            is_initialized: true,
+            payer_pubkey: *payer.key,
+            payee_pubkey: *payee.key,
+            arbiter_pubkey: *arbiter.key,
+            vault_pubkey: *temp_token_account.key,
+            num_milestones: amounts.len() as u8,
+            ..MilestoneEscrow::default()
+        };
+        escrow_info.amounts[..amounts.len()].copy_from_slice(amounts);
+        MilestoneEscrow::pack(escrow_info, &mut escrow_acc.data.borrow_mut())?;
+
+        EscrowEvent::MilestoneEscrowOpened {
+            escrow: *escrow_acc.key,
+            payer: *payer.key,
+            payee: *payee.key,
+            arbiter: *arbiter.key,
+            total,
+        }
+        .emit();
+
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::hand_to_pda(token_program, temp_token_account, payer, &pda)
+    }
+
+    fn process_release_milestone(
+        accounts: &[AccountInfo],
+        milestone: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let payer_acc = next_account_info(account_info_iter)?;
+        if !payer_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let payee_token_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let vault_acc = next_account_info(account_info_iter)?;
+        let mint_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+
+        if escrow_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut escrow_info = MilestoneEscrow::unpack(&escrow_acc.data.borrow())?;
+        if escrow_info.payer_pubkey != *payer_acc.key || escrow_info.vault_pubkey != *vault_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let amount = Self::current_milestone(&escrow_info, milestone)?;
+        if token::unpack_token_account(payee_token_acc, token_program_acc.key)?.owner
+            != escrow_info.payee_pubkey
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        escrow_info.current += 1;
+        escrow_info.disputed = false;
+        MilestoneEscrow::pack(escrow_info, &mut escrow_acc.data.borrow_mut())?;
+        EscrowEvent::MilestoneReleased {
+            escrow: *escrow_acc.key,
+            milestone,
+            amount,
+        }
+        .emit();
+
+        let finished = Self::milestones_finished(&escrow_info, escrow_acc);
+        Self::pay_out_over_time(
+            finished,
+            amount,
+            escrow_acc,
+            vault_acc,
+            mint_acc,
+            payee_token_acc,
+            token_program_acc,
+            pda_acc,
+            payer_acc,
+            program_id,
+        )
+    }
+
+    fn process_dispute_milestone(
+        accounts: &[AccountInfo],
+        milestone: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let party_acc = next_account_info(account_info_iter)?;
+        if !party_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let escrow_acc = next_account_info(account_info_iter)?;
+
+        if escrow_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut escrow_info = MilestoneEscrow::unpack(&escrow_acc.data.borrow())?;
+        if *party_acc.key != escrow_info.payer_pubkey && *party_acc.key != escrow_info.payee_pubkey
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::current_milestone(&escrow_info, milestone)?;
+        if escrow_info.disputed {
+            return Err(EscrowError::AlreadyDisputed.into());
+        }
+
+        escrow_info.disputed = true;
+        MilestoneEscrow::pack(escrow_info, &mut escrow_acc.data.borrow_mut())?;
+        EscrowEvent::MilestoneDisputed {
+            escrow: *escrow_acc.key,
+            milestone,
+            by: *party_acc.key,
+        }
+        .emit();
+        Ok(())
+    }
+
+    fn process_resolve_dispute(
+        accounts: &[AccountInfo],
+        milestone: u8,
+        payee_amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let arbiter_acc = next_account_info(account_info_iter)?;
+        if !arbiter_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let payee_token_acc = next_account_info(account_info_iter)?;
+        let payer_token_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let vault_acc = next_account_info(account_info_iter)?;
+        let mint_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        let payer_main_acc = next_account_info(account_info_iter)?;
+
+        if escrow_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut escrow_info = MilestoneEscrow::unpack(&escrow_acc.data.borrow())?;
+        if escrow_info.arbiter_pubkey != *arbiter_acc.key
+            || escrow_info.vault_pubkey != *vault_acc.key
+            || escrow_info.payer_pubkey != *payer_main_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let amount = Self::current_milestone(&escrow_info, milestone)?;
+        if !escrow_info.disputed {
+            return Err(EscrowError::NotDisputed.into());
+        }
+        let payer_amount = amount
+            .checked_sub(payee_amount)
+            .ok_or(EscrowError::AmountOverflow)?;
+        // the arbiter picks both accounts, so they have to belong to the two parties
+        if token::unpack_token_account(payee_token_acc, token_program_acc.key)?.owner
+            != escrow_info.payee_pubkey
+            || token::unpack_token_account(payer_token_acc, token_program_acc.key)?.owner
+                != escrow_info.payer_pubkey
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        escrow_info.current += 1;
+        escrow_info.disputed = false;
+        MilestoneEscrow::pack(escrow_info, &mut escrow_acc.data.borrow_mut())?;
+        EscrowEvent::DisputeResolved {
+            escrow: *escrow_acc.key,
+            milestone,
+            payee_amount,
+            payer_amount,
+        }
+        .emit();
+
+        Self::pay_out_over_time(
+            false,
+            payee_amount,
+            escrow_acc,
+            vault_acc,
+            mint_acc,
+            payee_token_acc,
+            token_program_acc,
+            pda_acc,
+            payer_main_acc,
+            program_id,
+        )?;
+        let finished = Self::milestones_finished(&escrow_info, escrow_acc);
+        Self::pay_out_over_time(
+            finished,
+            payer_amount,
+            escrow_acc,
+            vault_acc,
+            mint_acc,
+            payer_token_acc,
+            token_program_acc,
+            pda_acc,
+            payer_main_acc,
+            program_id,
+        )
+    }
+
+    // what the current milestone pays, as long as it's the one the caller expects
+    fn current_milestone(
+        escrow_info: &MilestoneEscrow,
+        milestone: u8,
+    ) -> Result<u64, ProgramError> {
+        escrow_info
+            .current_amount()
+            .filter(|_| escrow_info.current == milestone)
+            .ok_or_else(|| EscrowError::WrongMilestone.into())
+    }
+
+    // after the last milestone, the escrow closes
+    fn milestones_finished(escrow_info: &MilestoneEscrow, escrow_acc: &AccountInfo) -> bool {
+        let finished = escrow_info.current == escrow_info.num_milestones;
+        if finished {
+            EscrowEvent::MilestoneEscrowClosed {
+                escrow: *escrow_acc.key,
+            }
+            .emit();
+        }
+        finished
+    }
+
+    fn process_init_multisig(
+        accounts: &[AccountInfo],
+        m: u8,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let multisig_acc = next_account_info(account_info_iter)?;
+        let signer_accs = account_info_iter.as_slice();
+        if multisig_acc.owner != program_id {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !Rent::get()?.is_exempt(multisig_acc.lamports(), multisig_acc.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+        if signer_accs.is_empty()
+            || signer_accs.len() > Multisig::MAX_SIGNERS
+            || m == 0
+            || m as usize > signer_accs.len()
+        {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        // a signer listed twice would count twice towards M
+        for (i, signer_acc) in signer_accs.iter().enumerate() {
+            if signer_accs[..i].iter().any(|acc| acc.key == signer_acc.key) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
+
+        if Multisig::unpack_unchecked(&multisig_acc.data.borrow())?.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        let mut multisig_info = Multisig {
+            is_initialized: true,
+            m,
+            n: signer_accs.len() as u8,
+            ..Multisig::default()
+        };
+        for (signer, signer_acc) in multisig_info.signers.iter_mut().zip(signer_accs) {
+            *signer = *signer_acc.key;
+        }
+        Multisig::pack(multisig_info, &mut multisig_acc.data.borrow_mut())?;
+        Ok(())
+    }
+
+    fn process_set_escrow_authority(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let initializer_main_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let new_authority_acc = next_account_info(account_info_iter)?;
+
+        let mut escrow_info = Escrow::unpack(&escrow_acc.data.borrow())?;
+        if escrow_info.initializer_pubkey != *initializer_main_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::check_initializer_approval(
+            &escrow_info,
+            initializer_main_acc,
+            account_info_iter,
+            program_id,
+        )?;
+
+        escrow_info.authority_pubkey = if new_authority_acc.key == initializer_main_acc.key {
+            Pubkey::default()
+        } else {
+            // has to be a working multisig, or nobody could ever act on the escrow again
+            if new_authority_acc.owner != program_id {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            Multisig::unpack(&new_authority_acc.data.borrow())?;
+            *new_authority_acc.key
+        };
+        Escrow::pack(escrow_info, &mut escrow_acc.data.borrow_mut())?;
+        Ok(())
+    }
+
+    fn process_init_pool(
+        accounts: &[AccountInfo],
+        deadline: i64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let creator_acc = next_account_info(account_info_iter)?;
+        if !creator_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let pool_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let y_vault_acc = next_account_info(account_info_iter)?;
+        let x_vault_acc = next_account_info(account_info_iter)?;
+        let escrow_vault_acc = next_account_info(account_info_iter)?;
+        let initializer_y_acc = next_account_info(account_info_iter)?;
+        let mint_x_acc = next_account_info(account_info_iter)?;
+        let mint_y_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(y_token_program_acc)?;
+
+        // ----------------------------------------------------------------------------- checks
+        // the pool takes it the way a plain Exchange would, so it has to be a plain listing
+        let escrow_info = Escrow::unpack(&escrow_acc.data.borrow())?;
+        if escrow_info.mode != EscrowMode::Vault
+            || escrow_info.request != Request::Tokens
+            || escrow_info.royalties
+        {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        if escrow_info.temp_token_account_pubkey != *escrow_vault_acc.key
+            || escrow_info.initializer_token_to_receive_account_pubkey != *initializer_y_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if deadline <= Clock::get()?.unix_timestamp {
+            return Err(EscrowError::TimelockInThePast.into());
+        }
+
+        if pool_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !Rent::get()?.is_exempt(pool_acc.lamports(), pool_acc.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+        if Pool::unpack_unchecked(&pool_acc.data.borrow())?.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        let escrow_vault_info =
+            token::unpack_token_account(escrow_vault_acc, token_program_acc.key)?;
+        let x_vault_info = token::unpack_token_account(x_vault_acc, token_program_acc.key)?;
+        let initializer_y_info =
+            token::unpack_token_account(initializer_y_acc, y_token_program_acc.key)?;
+        let y_vault_info = token::unpack_token_account(y_vault_acc, y_token_program_acc.key)?;
+        if escrow_vault_info.mint != *mint_x_acc.key
+            || x_vault_info.mint != *mint_x_acc.key
+            || initializer_y_info.mint != *mint_y_acc.key
+            || y_vault_info.mint != *mint_y_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // alice gets exactly what the pool raised and the X is split by what arrived, a transfer fee would throw both off
+        if token::unpack_mint(mint_x_acc, token_program_acc.key)?
+            .transfer_fee
+            .is_some()
+            || token::unpack_mint(mint_y_acc, y_token_program_acc.key)?
+                .transfer_fee
+                .is_some()
+        {
+            return Err(EscrowError::UnsupportedMintExtension.into());
+        }
+        // both vaults start out empty, anything in them would be counted as raised or received
+        for vault_info in [&x_vault_info, &y_vault_info].iter() {
+            if vault_info.delegate.is_some() {
+                return Err(EscrowError::TempAccountHasDelegate.into());
+            }
+            if vault_info.close_authority.is_some() {
+                return Err(EscrowError::TempAccountHasCloseAuthority.into());
+            }
+            if vault_info.amount != 0 {
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
+
+        // ----------------------------------------------------------------------------- data
+        let pool_info = Pool {
+            is_initialized: true,
+            escrow_pubkey: *escrow_acc.key,
+            creator_pubkey: *creator_acc.key,
+            y_vault_pubkey: *y_vault_acc.key,
+            x_vault_pubkey: *x_vault_acc.key,
+            target: escrow_info.expected_amount,
+            deadline,
+            ..Pool::default()
+        };
+        Pool::pack(pool_info, &mut pool_acc.data.borrow_mut())?;
+
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::hand_to_pda(y_token_program_acc, y_vault_acc, creator_acc, &pda)?;
+        Self::hand_to_pda(token_program_acc, x_vault_acc, creator_acc, &pda)
+    }
+
+    fn process_contribute(
+        accounts: &[AccountInfo],
+        amount: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let contributor_acc = next_account_info(account_info_iter)?;
+        if !contributor_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let contributor_y_acc = next_account_info(account_info_iter)?;
+        let pool_acc = next_account_info(account_info_iter)?;
+        let contribution_acc = next_account_info(account_info_iter)?;
+        let y_vault_acc = next_account_info(account_info_iter)?;
+        let mint_y_acc = next_account_info(account_info_iter)?;
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(y_token_program_acc)?;
+
+        // the raise is counted in the pool account, so it has to be ours
+        if pool_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut pool_info = Pool::unpack(&pool_acc.data.borrow())?;
+        if pool_info.y_vault_pubkey != *y_vault_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if pool_info.executed
+            || pool_info.raised == pool_info.target
+            || Clock::get()?.unix_timestamp >= pool_info.deadline
+        {
+            return Err(EscrowError::PoolClosed.into());
+        }
+
+        // refunds and shares go by what's in the contribution account, so it has to be ours
+        if contribution_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut contribution_info =
+            Contribution::unpack_unchecked(&contribution_acc.data.borrow())?;
+        if contribution_info.is_initialized() {
+            if contribution_info.pool_pubkey != *pool_acc.key
+                || contribution_info.contributor_pubkey != *contributor_acc.key
+            {
+                return Err(ProgramError::InvalidAccountData);
+            }
+        } else {
+            if !Rent::get()?.is_exempt(contribution_acc.lamports(), contribution_acc.data_len()) {
+                return Err(EscrowError::NotRentExempt.into());
+            }
+            contribution_info = Contribution {
+                is_initialized: true,
+                pool_pubkey: *pool_acc.key,
+                contributor_pubkey: *contributor_acc.key,
+                amount: 0,
+            };
+            pool_info.contributors += 1;
+        }
+
+        // whatever goes past the target stays with the contributor
+        let amount = amount.min(pool_info.target - pool_info.raised);
+        if amount == 0 {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+        let mint_y = token::unpack_mint(mint_y_acc, y_token_program_acc.key)?;
+        let transfer_ix = token::transfer_checked(
+            y_token_program_acc.key,
+            contributor_y_acc.key,
+            mint_y_acc.key,
+            y_vault_acc.key,
+            contributor_acc.key,
+            amount,
+            mint_y.decimals,
+        )?;
+
+        msg!("Calling the token program to transfer the contribution to the pool...");
+
+        invoke(
+            &transfer_ix,
+            &[
+                contributor_y_acc.clone(),
+                mint_y_acc.clone(),
+                y_vault_acc.clone(),
+                contributor_acc.clone(),
+                y_token_program_acc.clone(),
+            ],
+        )?;
+
+        contribution_info.amount += amount;
+        pool_info.raised += amount;
+        Contribution::pack(contribution_info, &mut contribution_acc.data.borrow_mut())?;
+        Pool::pack(pool_info, &mut pool_acc.data.borrow_mut())?;
+        Ok(())
+    }
+
+    fn process_execute_pool(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let pool_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let escrow_vault_acc = next_account_info(account_info_iter)?;
+        let x_vault_acc = next_account_info(account_info_iter)?;
+        let mint_x_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        let y_vault_acc = next_account_info(account_info_iter)?;
+        let initializer_y_acc = next_account_info(account_info_iter)?;
+        let mint_y_acc = next_account_info(account_info_iter)?;
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(y_token_program_acc)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        let creator_acc = next_account_info(account_info_iter)?;
+        let initializer_main_acc = next_account_info(account_info_iter)?;
+
+        // ----------------------------------------------------------------------------- checks
+        if pool_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut pool_info = Pool::unpack(&pool_acc.data.borrow())?;
+        if pool_info.escrow_pubkey != *escrow_acc.key
+            || pool_info.x_vault_pubkey != *x_vault_acc.key
+            || pool_info.y_vault_pubkey != *y_vault_acc.key
+            || pool_info.creator_pubkey != *creator_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if pool_info.executed {
+            return Err(EscrowError::PoolClosed.into());
+        }
+        if pool_info.raised < pool_info.target {
+            return Err(EscrowError::TargetNotFilled.into());
+        }
+
+        // same as Exchange checks for the taker
+        let escrow_info = Escrow::unpack(&escrow_acc.data.borrow())?;
+        if escrow_info.mode != EscrowMode::Vault {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        if escrow_info.temp_token_account_pubkey != *escrow_vault_acc.key
+            || escrow_info.initializer_pubkey != *initializer_main_acc.key
+            || escrow_info.initializer_token_to_receive_account_pubkey != *initializer_y_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if escrow_info.expected_amount != pool_info.target {
+            return Err(EscrowError::ExpectedAmountMismatch.into());
+        }
+        let rent_recipient_acc = Self::rent_recipient(
+            &escrow_info.rent_payer,
+            initializer_main_acc,
+            account_info_iter,
+        )?;
+
+        // ----------------------------------------------------------------------------- swap
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+
+        // alice's price is everything in the Y vault, which is done with after that
+        Self::empty_vault(
+            y_token_program_acc,
+            y_vault_acc,
+            mint_y_acc,
+            initializer_y_acc,
+            creator_acc,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )?;
+        // and the pool gets the escrow's whole vault, like a taker would
+        pool_info.received_x =
+            token::unpack_token_account(escrow_vault_acc, token_program_acc.key)?.amount;
+        Self::empty_vault(
+            token_program_acc,
+            escrow_vault_acc,
+            mint_x_acc,
+            x_vault_acc,
+            rent_recipient_acc,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )?;
+
+        msg!("Closing the escrow account...");
+        Self::close_escrow_account(escrow_acc, rent_recipient_acc)?;
+
+        pool_info.executed = true;
+        Pool::pack(pool_info, &mut pool_acc.data.borrow_mut())?;
+        Ok(())
+    }
+
+    fn process_leave_pool(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let contributor_acc = next_account_info(account_info_iter)?;
+        if !contributor_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let contributor_token_acc = next_account_info(account_info_iter)?;
+        let pool_acc = next_account_info(account_info_iter)?;
+        let contribution_acc = next_account_info(account_info_iter)?;
+        let x_vault_acc = next_account_info(account_info_iter)?;
+        let mint_x_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        let y_vault_acc = next_account_info(account_info_iter)?;
+        let mint_y_acc = next_account_info(account_info_iter)?;
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(y_token_program_acc)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        let creator_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+
+        // ----------------------------------------------------------------------------- checks
+        if pool_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut pool_info = Pool::unpack(&pool_acc.data.borrow())?;
+        if contribution_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let contribution_info = Contribution::unpack(&contribution_acc.data.borrow())?;
+        if contribution_info.pool_pubkey != *pool_acc.key
+            || contribution_info.contributor_pubkey != *contributor_acc.key
+            || pool_info.x_vault_pubkey != *x_vault_acc.key
+            || pool_info.y_vault_pubkey != *y_vault_acc.key
+            || pool_info.creator_pubkey != *creator_acc.key
+            || pool_info.escrow_pubkey != *escrow_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let amount = if pool_info.executed {
+            // pro rata and rounded down, the last one out gets whatever is left
+            (pool_info.received_x as u128 * contribution_info.amount as u128
+                / pool_info.raised as u128) as u64
+        } else {
+            // a refund once the pool can't be filled any more: the escrow was cancelled or taken by someone else, or
+            // time ran out first
+            let escrow_gone = escrow_acc.owner != program_id
+                || Escrow::unpack(&escrow_acc.data.borrow()).is_err();
+            let expired = pool_info.raised < pool_info.target
+                && Clock::get()?.unix_timestamp >= pool_info.deadline;
+            if !escrow_gone && !expired {
+                return Err(EscrowError::PoolStillOpen.into());
+            }
+            contribution_info.amount
+        };
+
+        pool_info.contributors -= 1;
+        let finished = pool_info.contributors == 0;
+        Pool::pack(pool_info, &mut pool_acc.data.borrow_mut())?;
+        msg!("Closing the contribution account...");
+        Self::close_escrow_account(contribution_acc, contributor_acc)?;
+
+        if !pool_info.executed {
+            if finished {
+                // nobody is going to put X in there any more. Anything sent to it anyway keeps it open
+                let x_vault_info = token::unpack_token_account(x_vault_acc, token_program_acc.key)?;
+                if x_vault_info.amount == 0 {
+                    let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+                    Self::close_temp_account(
+                        token_program_acc,
+                        x_vault_acc,
+                        mint_x_acc,
+                        creator_acc,
+                        pda_acc,
+                        &pda,
+                        bump_seed,
+                    )?;
+                }
+            }
+            return Self::pay_out_over_time(
+                finished,
+                amount,
+                pool_acc,
+                y_vault_acc,
+                mint_y_acc,
+                contributor_token_acc,
+                y_token_program_acc,
+                pda_acc,
+                creator_acc,
+                program_id,
+            );
+        }
+        Self::pay_out_over_time(
+            finished,
+            amount,
+            pool_acc,
+            x_vault_acc,
+            mint_x_acc,
+            contributor_token_acc,
+            token_program_acc,
+            pda_acc,
+            creator_acc,
+            program_id,
+        )
+    }
+
+    fn process_init_standing_order(
+        accounts: &[AccountInfo],
+        amount_per_interval: u64,
+        min_amount_x: u64,
+        interval: i64,
+        keeper_reward: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let owner_acc = next_account_info(account_info_iter)?;
+        if !owner_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let temp_token_account = next_account_info(account_info_iter)?;
+        let standing_order_acc = next_account_info(account_info_iter)?;
+        let owner_x_acc = next_account_info(account_info_iter)?;
+        let mint_y_acc = next_account_info(account_info_iter)?;
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(y_token_program_acc)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+
+        if standing_order_acc.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !Rent::get()?.is_exempt(standing_order_acc.lamports(), standing_order_acc.data_len()) {
+            return Err(EscrowError::NotRentExempt.into());
+        }
+        if StandingOrder::unpack_unchecked(&standing_order_acc.data.borrow())?.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        let temp_token_account_info =
+            token::unpack_token_account(temp_token_account, y_token_program_acc.key)?;
+        if temp_token_account_info.mint != *mint_y_acc.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::check_temp_account(&temp_token_account_info, EscrowMode::Vault)?;
+        // the budget is booked by what leaves the vault, a transfer fee would have sellers get less than their price
+        if token::unpack_mint(mint_y_acc, y_token_program_acc.key)?
+            .transfer_fee
+            .is_some()
+        {
+            return Err(EscrowError::UnsupportedMintExtension.into());
+        }
+        token::unpack_token_account(owner_x_acc, token_program_acc.key)?;
+
+        if amount_per_interval == 0 || interval <= 0 {
+            return Err(EscrowError::InvalidSchedule.into());
+        }
+        // without a price limit a keeper could have it buy at any price
+        if min_amount_x == 0 {
+            return Err(EscrowError::InvalidInstruction.into());
+        }
+
+        let standing_order_info = StandingOrder {
+            is_initialized: true,
+            owner_pubkey: *owner_acc.key,
+            vault_pubkey: *temp_token_account.key,
+            x_account_pubkey: *owner_x_acc.key,
+            amount_per_interval,
+            min_amount_x,
+            interval,
+            keeper_reward,
+            last_execution: 0,
+            remaining: temp_token_account_info.amount,
+            tranches: 0,
+        };
+        StandingOrder::pack(
+            standing_order_info,
+            &mut standing_order_acc.data.borrow_mut(),
+        )?;
+
+        let (pda, _bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::hand_to_pda(y_token_program_acc, temp_token_account, owner_acc, &pda)
+    }
+
+    // what every tranche does whatever it takes: checks it against the standing order, pays the seller and the keeper
+    // out of the budget and books it. The caller moves the X
+    #[allow(clippy::too_many_arguments)]
+    fn spend_tranche<'a>(
+        standing_order_acc: &AccountInfo<'a>,
+        vault_acc: &AccountInfo<'a>,
+        owner_x_acc: &AccountInfo<'a>,
+        seller_y_acc: &AccountInfo<'a>,
+        keeper_y_acc: &AccountInfo<'a>,
+        mint_y_acc: &AccountInfo<'a>,
+        y_token_program_acc: &AccountInfo<'a>,
+        pda_acc: &AccountInfo<'a>,
+        amount_x: u64,
+        amount_y: u64,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let mut standing_order_info = StandingOrder::unpack(&standing_order_acc.data.borrow())?;
+        if standing_order_info.vault_pubkey != *vault_acc.key
+            || standing_order_info.x_account_pubkey != *owner_x_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let now = Clock::get()?.unix_timestamp;
+        if !standing_order_info.due(now) {
+            return Err(EscrowError::TrancheNotDue.into());
+        }
+        if amount_y > standing_order_info.amount_per_interval {
+            return Err(EscrowError::TrancheTooLarge.into());
+        }
+        // amount_x is what the owner ends up with, so a fee on X counts against the price
+        if !standing_order_info.within_limit(amount_x, amount_y) {
+            return Err(EscrowError::PriceAboveMaximum.into());
+        }
+        let spent = amount_y
+            .checked_add(standing_order_info.keeper_reward)
+            .ok_or(EscrowError::AmountOverflow)?;
+        standing_order_info.remaining = standing_order_info
+            .remaining
+            .checked_sub(spent)
+            .ok_or(EscrowError::BudgetExhausted)?;
+        standing_order_info.last_execution = now;
+        standing_order_info.tranches += 1;
+        StandingOrder::pack(
+            standing_order_info,
+            &mut standing_order_acc.data.borrow_mut(),
+        )?;
+        msg!(
+            "tranche {}: {} Y for {} X",
+            standing_order_info.tranches,
+            amount_y,
+            amount_x
+        );
+
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        let mint_y = token::unpack_mint(mint_y_acc, y_token_program_acc.key)?;
+        Self::pay_from_vault(
+            y_token_program_acc,
+            vault_acc,
+            mint_y_acc,
+            mint_y.decimals,
+            seller_y_acc,
+            amount_y,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )?;
+        Self::pay_from_vault(
+            y_token_program_acc,
+            vault_acc,
+            mint_y_acc,
+            mint_y.decimals,
+            keeper_y_acc,
+            standing_order_info.keeper_reward,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )
+    }
+
+    fn process_execute_tranche(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let keeper_acc = next_account_info(account_info_iter)?;
+        if !keeper_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let keeper_y_acc = next_account_info(account_info_iter)?;
+        let standing_order_acc = next_account_info(account_info_iter)?;
+        let vault_acc = next_account_info(account_info_iter)?;
+        let owner_x_acc = next_account_info(account_info_iter)?;
+        let escrow_acc = next_account_info(account_info_iter)?;
+        let escrow_vault_acc = next_account_info(account_info_iter)?;
+        let initializer_main_acc = next_account_info(account_info_iter)?;
+        let initializer_y_acc = next_account_info(account_info_iter)?;
+        let mint_x_acc = next_account_info(account_info_iter)?;
+        let mint_y_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(y_token_program_acc)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+
+        // ----------------------------------------------------------------------------- checks
+        // same as ExchangeMany, only fixed prices
+        let escrow_info = Escrow::unpack(&escrow_acc.data.borrow())?;
+        if (escrow_info.mode != EscrowMode::Vault && escrow_info.mode != EscrowMode::Approve)
+            || escrow_info.request != Request::Tokens
+            || escrow_info.royalties
+        {
+            return Err(EscrowError::WrongEscrowMode.into());
+        }
+        if escrow_info.temp_token_account_pubkey != *escrow_vault_acc.key
+            || escrow_info.initializer_pubkey != *initializer_main_acc.key
+            || escrow_info.initializer_token_to_receive_account_pubkey != *initializer_y_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // a vault hands over everything in it, a delegation only what was offered
+        let amount_x = if escrow_info.mode == EscrowMode::Vault {
+            token::unpack_token_account(escrow_vault_acc, token_program_acc.key)?.amount
+        } else {
+            escrow_info.offered_amount
+        };
+        let rent_recipient_acc = Self::rent_recipient(
+            &escrow_info.rent_payer,
+            initializer_main_acc,
+            account_info_iter,
+        )?;
+        let mint_x = token::unpack_mint(mint_x_acc, token_program_acc.key)?;
+        let received_x = mint_x.received(amount_x, Clock::get()?.epoch)?;
+
+        // ----------------------------------------------------------------------------- swap
+        Self::spend_tranche(
+            standing_order_acc,
+            vault_acc,
+            owner_x_acc,
+            initializer_y_acc,
+            keeper_y_acc,
+            mint_y_acc,
+            y_token_program_acc,
+            pda_acc,
+            received_x,
+            escrow_info.expected_amount,
+            program_id,
+        )?;
+
+        let (pda, bump_seed) = Pubkey::find_program_address(&[b"escrow"], program_id);
+        Self::pay_from_vault(
+            token_program_acc,
+            escrow_vault_acc,
+            mint_x_acc,
+            mint_x.decimals,
+            owner_x_acc,
+            amount_x,
+            pda_acc,
+            &pda,
+            bump_seed,
+        )?;
+
+        // ----------------------------------------------------------------------------- clean up
+        if escrow_info.mode == EscrowMode::Vault {
+            Self::close_temp_account(
+                token_program_acc,
+                escrow_vault_acc,
+                mint_x_acc,
+                rent_recipient_acc,
+                pda_acc,
+                &pda,
+                bump_seed,
+            )?;
+        }
+
+        msg!("Closing the escrow account...");
+        Self::close_escrow_account(escrow_acc, rent_recipient_acc)
+    }
+
+    fn process_execute_tranche_with_order(
+        accounts: &[AccountInfo],
+        order: &Order,
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let keeper_acc = next_account_info(account_info_iter)?;
+        if !keeper_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let keeper_y_acc = next_account_info(account_info_iter)?;
+        let standing_order_acc = next_account_info(account_info_iter)?;
+        let vault_acc = next_account_info(account_info_iter)?;
+        let owner_x_acc = next_account_info(account_info_iter)?;
+        let maker_x_acc = next_account_info(account_info_iter)?;
+        let maker_y_acc = next_account_info(account_info_iter)?;
+        let bitmap_acc = next_account_info(account_info_iter)?;
+        let mint_x_acc = next_account_info(account_info_iter)?;
+        let mint_y_acc = next_account_info(account_info_iter)?;
+        let token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(token_program_acc)?;
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(y_token_program_acc)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+        let delegate_acc = next_account_info(account_info_iter)?;
+        let instructions_acc = next_account_info(account_info_iter)?;
+        let system_program_acc = next_account_info(account_info_iter)?;
+
+        // ----------------------------------------------------------------------------- checks
+        // same as FillSignedOrder
+        let clock = Clock::get()?;
+        if clock.unix_timestamp >= order.expiry {
+            return Err(EscrowError::OrderExpired.into());
+        }
+        order::check_ed25519(instructions_acc, &order.maker, &order.message(program_id))?;
+
+        if *mint_x_acc.key != order.mint_x || *mint_y_acc.key != order.mint_y {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let maker_x_info = token::unpack_token_account(maker_x_acc, token_program_acc.key)?;
+        let maker_y_info = token::unpack_token_account(maker_y_acc, y_token_program_acc.key)?;
+        if maker_x_info.owner != order.maker
+            || maker_x_info.mint != order.mint_x
+            || maker_y_info.owner != order.maker
+            || maker_y_info.mint != order.mint_y
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mint_x = token::unpack_mint(mint_x_acc, token_program_acc.key)?;
+
+        Self::use_nonce(
+            keeper_acc,
+            bitmap_acc,
+            system_program_acc,
+            &order.maker,
+            order.nonce,
+            program_id,
+        )?;
+
+        // ----------------------------------------------------------------------------- swap
+        // Y can't charge a fee, so the maker gets exactly amount_y
+        Self::spend_tranche(
+            standing_order_acc,
+            vault_acc,
+            owner_x_acc,
+            maker_y_acc,
+            keeper_y_acc,
+            mint_y_acc,
+            y_token_program_acc,
+            pda_acc,
+            mint_x.received(order.amount_x, clock.epoch)?,
+            order.amount_y,
+            program_id,
+        )?;
+
+        Self::pay_from_maker(
+            token_program_acc,
+            maker_x_acc,
+            &maker_x_info,
+            mint_x_acc,
+            mint_x.decimals,
+            owner_x_acc,
+            delegate_acc,
+            order,
+            program_id,
+        )
+    }
+
+    fn process_cancel_standing_order(
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let owner_acc = next_account_info(account_info_iter)?;
+        if !owner_acc.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let owner_y_acc = next_account_info(account_info_iter)?;
+        let standing_order_acc = next_account_info(account_info_iter)?;
+        let vault_acc = next_account_info(account_info_iter)?;
+        let mint_y_acc = next_account_info(account_info_iter)?;
+        let y_token_program_acc = next_account_info(account_info_iter)?;
+        token::check_token_program(y_token_program_acc)?;
+        let pda_acc = next_account_info(account_info_iter)?;
+
+        let standing_order_info = StandingOrder::unpack(&standing_order_acc.data.borrow())?;
+        if standing_order_info.owner_pubkey != *owner_acc.key
+            || standing_order_info.vault_pubkey != *vault_acc.key
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        msg!("returning {} of the budget", standing_order_info.remaining);
+        Self::pay_out_over_time(
+            true,
+            0,
+            standing_order_acc,
+            vault_acc,
+            mint_y_acc,
+            owner_y_acc,
+            y_token_program_acc,
+            pda_acc,
+            owner_acc,
+            program_id,
+        )
+    }
 }
@@ -6,88 +6,1670 @@ use solana_program::{
 
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 
-// 105 bytes of data
+// the first byte of every account the program owns says what's in it, 0 until it's initialized. That's what keeps one
+// kind of account from being passed off as another, whatever their lengths
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AccountType {
+    Uninitialized,
+    // the is_initialized flag accounts had before there was a type. Only the first escrows are still read, see
+    // LegacyEscrow
+    Legacy,
+    Escrow,
+    Loan,
+    SealedBid,
+    NonceBitmap,
+    Distribution,
+    ClaimedBitmap,
+    Vesting,
+    Stream,
+    MilestoneEscrow,
+    Multisig,
+    Pool,
+    Contribution,
+    StandingOrder,
+}
+
+impl AccountType {
+    /// What `data` holds, going by its first byte
+    pub fn of(data: &[u8]) -> Option<Self> {
+        use AccountType::*;
+        [
+            Uninitialized,
+            Legacy,
+            Escrow,
+            Loan,
+            SealedBid,
+            NonceBitmap,
+            Distribution,
+            ClaimedBitmap,
+            Vesting,
+            Stream,
+            MilestoneEscrow,
+            Multisig,
+            Pool,
+            Contribution,
+            StandingOrder,
+        ]
+        .get(*data.first()? as usize)
+        .copied()
+    }
+
+    /// The first byte of an account of this type
+    pub fn pack_initialized(self, is_initialized: bool) -> u8 {
+        if is_initialized {
+            self as u8
+        } else {
+            AccountType::Uninitialized as u8
+        }
+    }
+
+    /// Whether an account of this type is initialized. Any other type isn't one of these, initialized or not
+    pub fn unpack_initialized(self, src: &[u8; 1]) -> Result<bool, ProgramError> {
+        match src[0] {
+            0 => Ok(false),
+            tag if tag == self as u8 => Ok(true),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+// how the X tokens are held while the escrow is open
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum EscrowMode {
+    // alice moves X into a temp account and hands its ownership to the PDA
+    #[default]
+    Vault,
+    // alice keeps X in her own account and only approves the PDA as delegate for the offered amount
+    Approve,
+    // a vault like above, released by a hash preimage or refunded after a timelock instead of traded
+    Htlc,
+    // a vault that backs a call option: whoever bought it may pay the strike (expected_amount) for X until it expires
+    CoveredCall,
+    // a vault whose price falls from a start price down to expected_amount, see auction.rs
+    DutchAuction,
+    // a vault that goes to the highest bidder at the end. Bids sit in a second vault, in Y, and expected_amount is the reserve
+    EnglishAuction,
+    // like an English auction, but bids are committed as hashes first and only opened once bidding has closed
+    SealedAuction,
+    // a Vault or Approve escrow whose X went out in a FlashExchange, waiting for the SettleFlash later in the same
+    // transaction to pay Y and close it. Never outlives the transaction
+    FlashSettling,
+}
+
+// what alice asks for in return
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Request {
+    // expected_amount of whatever goes into initializer_token_to_receive_account_pubkey, like it always was
+    #[default]
+    Tokens,
+    // the NFT whose mint is request_key
+    Mint,
+    // any NFT in the verified collection request_key, going by its Metaplex metadata
+    Collection,
+}
+
+// the header every escrow has, whatever its mode. The mode's own terms follow it, see Terms
+// Escrow::len(mode) bytes of data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Escrow {
     pub is_initialized: bool, //determine if escrow program is already in use
     pub initializer_pubkey: Pubkey,
     // storing here for 1)convenience (alice doesn't dhave to send to bob), 2)security
-    pub temp_token_account_pubkey: Pubkey, //addr of Alice's X tokens that will eventually go to Bob
+    pub temp_token_account_pubkey: Pubkey, //addr of Alice's X tokens that will eventually go to Bob. In Approve mode that's her own account
     pub initializer_token_to_receive_account_pubkey: Pubkey, //addr where Bob will send tokens
     pub expected_amount: u64, //and their amount - use u64 coz that's the max supply of a token - https://github.com/solana-labs/solana-program-library/blob/123a3dc1e43dbc6c90c503b2c27a0d9b264e9ede/token/program/src/state.rs#L22
+    pub mode: EscrowMode,
+    pub offered_amount: u64, //Approve mode only - how much X the PDA may pull. In Vault mode it's whatever is in the temp account
+    pub rent_payer: Pubkey, //whoever funded the escrow (and temp) account, gets the rent back on close. Usually the initializer
+    pub recipient_token_account_pubkey: Pubkey, //Htlc mode: where the vault goes on Claim. Vault mode: where the price improvement goes on Match, default = the initializer
+    // Vault mode, taken with ExchangeNft. For anything but Tokens, initializer_token_to_receive_account_pubkey is unset -
+    // the taker brings an account of alice's for whichever NFT they send
+    pub request: Request,
+    pub request_key: Pubkey,
+    // X is an NFT whose creators get their royalties out of the price on Exchange, see InitRoyaltyEscrow
+    pub royalties: bool,
+    // a Multisig account whose signers act for the initializer on Cancel, Refund, ReclaimOption, SetMatchRecipient and
+    // SetEscrowAuthority, all zeroes if the initializer signs alone
+    pub authority_pubkey: Pubkey,
+    pub terms: Terms,
 }
 
-// -----------------------------------------------------------------------------
-// Solana's program_pack module requires 3 traits: Sealed, IsInitialized, and Pack
-
-// Solana's version of Rust's Sized trait, there doesn't seem to be a difference
-impl Sealed for Escrow {}
-
 impl IsInitialized for Escrow {
     fn is_initialized(&self) -> bool {
         self.is_initialized
     }
 }
 
-impl Pack for Escrow {
-    // len = len of our state struct above
-    // 1 byte (for the bool) + 3x32 (for pubkeys) + 8 (for u64)
-    const LEN: usize = 105;
+// not a Pack, its length depends on the mode. These work like Pack's
+impl Escrow {
+    // 1 (account type) + 3x32 (pubkeys) + 8 (for u64) + 1 (mode) + 8 (offered u64) + 32 (rent payer) + 32 (recipient)
+    // + 1 (request) + 32 (request key) + 1 (royalties bool) + 32 (authority)
+    pub const HEADER_LEN: usize = 244;
 
-    // turns an array of u8s into an instance of the Escrow trait above
-    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let src = array_ref![src, 0, Escrow::LEN];
+    /// The account size for an escrow in `mode`: the header, then the mode's terms
+    pub fn len(mode: EscrowMode) -> usize {
+        Self::HEADER_LEN + Terms::len(mode)
+    }
+
+    pub fn unpack(src: &[u8]) -> Result<Self, ProgramError> {
+        let escrow = Self::unpack_unchecked(src)?;
+        if !escrow.is_initialized {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        Ok(escrow)
+    }
+
+    /// An initialized escrow has to be exactly Escrow::len of its mode. An uninitialized one only has to fit the header
+    /// and comes without terms - it gets its mode at init, and pack checks it was created the right size for it
+    pub fn unpack_unchecked(src: &[u8]) -> Result<Self, ProgramError> {
+        if src.len() < Self::HEADER_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let header = array_ref![src, 0, Escrow::HEADER_LEN];
         let (
-            is_initialized,
+            account_type,
             initializer_pubkey,
             temp_token_account_pubkey,
             initializer_token_to_receive_account_pubkey,
             expected_amount,
-        ) = array_refs![src, 1, 32, 32, 32, 8]; // library for getting references to sections of arrays
+            mode,
+            offered_amount,
+            rent_payer,
+            recipient_token_account_pubkey,
+            request,
+            request_key,
+            royalties,
+            authority_pubkey,
+        ) = array_refs![header, 1, 32, 32, 32, 8, 1, 8, 32, 32, 1, 32, 1, 32]; // library for getting references to sections of arrays
         // first arg = array reference, the rest are sizes of slices to be extracted
 
-        let is_initialized = match is_initialized {
+        let is_initialized = AccountType::Escrow.unpack_initialized(account_type)?;
+
+        let mode = match mode {
+            [0] => EscrowMode::Vault,
+            [1] => EscrowMode::Approve,
+            [2] => EscrowMode::Htlc,
+            [3] => EscrowMode::CoveredCall,
+            [4] => EscrowMode::DutchAuction,
+            [5] => EscrowMode::EnglishAuction,
+            [6] => EscrowMode::SealedAuction,
+            [7] => EscrowMode::FlashSettling,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        let royalties = match royalties {
             [0] => false,
             [1] => true,
             _ => return Err(ProgramError::InvalidAccountData),
         };
 
+        let request = match request {
+            [0] => Request::Tokens,
+            [1] => Request::Mint,
+            [2] => Request::Collection,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        let terms = if is_initialized {
+            if src.len() != Self::len(mode) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            Terms::unpack(mode, &src[Self::HEADER_LEN..])?
+        } else {
+            Terms::None
+        };
+
         Ok(Escrow {
             is_initialized,
             initializer_pubkey: Pubkey::new_from_array(*initializer_pubkey),
             temp_token_account_pubkey: Pubkey::new_from_array(*temp_token_account_pubkey),
             initializer_token_to_receive_account_pubkey: Pubkey::new_from_array(*initializer_token_to_receive_account_pubkey),
             expected_amount: u64::from_le_bytes(*expected_amount),
+            mode,
+            offered_amount: u64::from_le_bytes(*offered_amount),
+            rent_payer: Pubkey::new_from_array(*rent_payer),
+            recipient_token_account_pubkey: Pubkey::new_from_array(*recipient_token_account_pubkey),
+            request,
+            request_key: Pubkey::new_from_array(*request_key),
+            royalties,
+            authority_pubkey: Pubkey::new_from_array(*authority_pubkey),
+            terms,
         })
     }
 
-    // other way around
-    fn pack_into_slice(&self, dst: &mut [u8]) {
-        // we're saying we want to work with the entire array from 0 to escro length
-        let dst = array_mut_ref![dst, 0, Escrow::LEN];
+    /// `dst` has to be exactly Escrow::len(src.mode), and the terms have to be the mode's
+    pub fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        if dst.len() != Self::len(src.mode) || !src.terms.fits(src.mode) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // we're saying we want to work with the header, from 0 to its length
+        let (header, terms_dst) = dst.split_at_mut(Self::HEADER_LEN);
+        let header = array_mut_ref![header, 0, Escrow::HEADER_LEN];
 
         // slice the array up
         let (
-            is_initialized_dst,
+            account_type_dst,
             initializer_pubkey_dst,
             temp_token_account_pubkey_dst,
             initializer_token_to_receive_account_pubkey_dst,
             expected_amount_dst,
-        ) = mut_array_refs![dst, 1, 32, 32, 32, 8];
+            mode_dst,
+            offered_amount_dst,
+            rent_payer_dst,
+            recipient_token_account_pubkey_dst,
+            request_dst,
+            request_key_dst,
+            royalties_dst,
+            authority_pubkey_dst,
+        ) = mut_array_refs![header, 1, 32, 32, 32, 8, 1, 8, 32, 32, 1, 32, 1, 32];
 
-        // destructure self into an instance of escrow
+        // destructure the escrow
         let Escrow {
             is_initialized,
             initializer_pubkey,
             temp_token_account_pubkey,
             initializer_token_to_receive_account_pubkey,
             expected_amount,
-        } = self;
+            mode,
+            offered_amount,
+            rent_payer,
+            recipient_token_account_pubkey,
+            request,
+            request_key,
+            royalties,
+            authority_pubkey,
+            terms,
+        } = src;
 
         // finally populate the slices we pre-prepared with the data
-        is_initialized_dst[0] = *is_initialized as u8;
+        account_type_dst[0] = AccountType::Escrow.pack_initialized(is_initialized);
         initializer_pubkey_dst.copy_from_slice(initializer_pubkey.as_ref());
         temp_token_account_pubkey_dst.copy_from_slice(temp_token_account_pubkey.as_ref());
         initializer_token_to_receive_account_pubkey_dst.copy_from_slice(initializer_token_to_receive_account_pubkey.as_ref());
         *expected_amount_dst = expected_amount.to_le_bytes();
+        mode_dst[0] = mode as u8;
+        *offered_amount_dst = offered_amount.to_le_bytes();
+        rent_payer_dst.copy_from_slice(rent_payer.as_ref());
+        recipient_token_account_pubkey_dst.copy_from_slice(recipient_token_account_pubkey.as_ref());
+        request_dst[0] = request as u8;
+        request_key_dst.copy_from_slice(request_key.as_ref());
+        royalties_dst[0] = royalties as u8;
+        authority_pubkey_dst.copy_from_slice(authority_pubkey.as_ref());
+        terms.pack(terms_dst);
+        Ok(())
+    }
+}
+
+// what only an escrow of one mode has, packed right after its header. Vault, Approve and FlashSettling don't have any,
+// so a flash exchange can switch between them
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Terms {
+    #[default]
+    None,
+    Htlc(HtlcTerms),
+    CoveredCall(OptionTerms),
+    DutchAuction(DutchTerms),
+    EnglishAuction(EnglishTerms),
+    SealedAuction(SealedTerms),
+}
+
+impl Terms {
+    /// What an escrow in `mode` starts out with, before its init fills them in
+    pub fn new(mode: EscrowMode) -> Self {
+        match mode {
+            EscrowMode::Vault | EscrowMode::Approve | EscrowMode::FlashSettling => Terms::None,
+            EscrowMode::Htlc => Terms::Htlc(HtlcTerms::default()),
+            EscrowMode::CoveredCall => Terms::CoveredCall(OptionTerms::default()),
+            EscrowMode::DutchAuction => Terms::DutchAuction(DutchTerms::default()),
+            EscrowMode::EnglishAuction => Terms::EnglishAuction(EnglishTerms::default()),
+            EscrowMode::SealedAuction => Terms::SealedAuction(SealedTerms::default()),
+        }
+    }
+
+    pub fn len(mode: EscrowMode) -> usize {
+        match mode {
+            EscrowMode::Vault | EscrowMode::Approve | EscrowMode::FlashSettling => 0,
+            EscrowMode::Htlc => HtlcTerms::LEN,
+            EscrowMode::CoveredCall => OptionTerms::LEN,
+            EscrowMode::DutchAuction => DutchTerms::LEN,
+            EscrowMode::EnglishAuction => EnglishTerms::LEN,
+            EscrowMode::SealedAuction => SealedTerms::LEN,
+        }
+    }
+
+    /// Whether these are the terms of an escrow in `mode`
+    pub fn fits(&self, mode: EscrowMode) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(&Terms::new(mode))
+    }
+
+    /// What both kinds of auction have
+    pub fn auction(&self) -> Option<&AuctionTerms> {
+        match self {
+            Terms::EnglishAuction(terms) => Some(&terms.auction),
+            Terms::SealedAuction(terms) => Some(&terms.auction),
+            _ => None,
+        }
+    }
+
+    fn unpack(mode: EscrowMode, src: &[u8]) -> Result<Self, ProgramError> {
+        Ok(match mode {
+            EscrowMode::Vault | EscrowMode::Approve | EscrowMode::FlashSettling => Terms::None,
+            EscrowMode::Htlc => Terms::Htlc(HtlcTerms::unpack_from_slice(src)?),
+            EscrowMode::CoveredCall => Terms::CoveredCall(OptionTerms::unpack_from_slice(src)?),
+            EscrowMode::DutchAuction => Terms::DutchAuction(DutchTerms::unpack_from_slice(src)?),
+            EscrowMode::EnglishAuction => Terms::EnglishAuction(EnglishTerms::unpack_from_slice(src)?),
+            EscrowMode::SealedAuction => Terms::SealedAuction(SealedTerms::unpack_from_slice(src)?),
+        })
+    }
+
+    fn pack(&self, dst: &mut [u8]) {
+        match self {
+            Terms::None => {}
+            Terms::Htlc(terms) => terms.pack_into_slice(dst),
+            Terms::CoveredCall(terms) => terms.pack_into_slice(dst),
+            Terms::DutchAuction(terms) => terms.pack_into_slice(dst),
+            Terms::EnglishAuction(terms) => terms.pack_into_slice(dst),
+            Terms::SealedAuction(terms) => terms.pack_into_slice(dst),
+        }
+    }
+}
+
+// a vault released by a hash preimage, or refunded after a timelock
+// 40 bytes of data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HtlcTerms {
+    pub hashlock: [u8; 32], //sha256 of the secret that releases the vault
+    pub timelock: i64, //unix timestamp after which alice can take the vault back
+}
+
+impl Sealed for HtlcTerms {}
+
+impl Pack for HtlcTerms {
+    // 32 (hashlock) + 8 (timelock i64)
+    const LEN: usize = 40;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, HtlcTerms::LEN];
+        let (hashlock, timelock) = array_refs![src, 32, 8];
+
+        Ok(HtlcTerms {
+            hashlock: *hashlock,
+            timelock: i64::from_le_bytes(*timelock),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, HtlcTerms::LEN];
+        let (hashlock_dst, timelock_dst) = mut_array_refs![dst, 32, 8];
+
+        hashlock_dst.copy_from_slice(&self.hashlock);
+        *timelock_dst = self.timelock.to_le_bytes();
+    }
+}
+
+// a call option on the vault: whoever bought it may pay the strike (expected_amount) for X until the expiry
+// 48 bytes of data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OptionTerms {
+    pub expiry: i64, //unix timestamp, alice can take the vault back from then on
+    pub premium: u64, //Y the buyer pays alice for the option
+    pub holder_pubkey: Pubkey, //who bought it and may exercise it, all zeroes while it's for sale
+}
+
+impl Sealed for OptionTerms {}
+
+impl Pack for OptionTerms {
+    // 8 (expiry i64) + 8 (premium) + 32 (holder)
+    const LEN: usize = 48;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, OptionTerms::LEN];
+        let (expiry, premium, holder_pubkey) = array_refs![src, 8, 8, 32];
+
+        Ok(OptionTerms {
+            expiry: i64::from_le_bytes(*expiry),
+            premium: u64::from_le_bytes(*premium),
+            holder_pubkey: Pubkey::new_from_array(*holder_pubkey),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, OptionTerms::LEN];
+        let (expiry_dst, premium_dst, holder_pubkey_dst) = mut_array_refs![dst, 8, 8, 32];
+
+        *expiry_dst = self.expiry.to_le_bytes();
+        *premium_dst = self.premium.to_le_bytes();
+        holder_pubkey_dst.copy_from_slice(self.holder_pubkey.as_ref());
+    }
+}
+
+// a price falling from start_price at auction_start to the floor (expected_amount) at auction_end, see auction.rs
+// 25 bytes of data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DutchTerms {
+    pub start_price: u64,
+    pub auction_start: i64, //unix timestamps
+    pub auction_end: i64,
+    pub decay: u8, //0 = linear, n = the price's excess over the floor halves n times on the way down
+}
+
+impl Sealed for DutchTerms {}
+
+impl Pack for DutchTerms {
+    // 8 (start price) + 2x8 (start, end i64) + 1 (decay)
+    const LEN: usize = 25;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, DutchTerms::LEN];
+        let (start_price, auction_start, auction_end, decay) = array_refs![src, 8, 8, 8, 1];
+
+        Ok(DutchTerms {
+            start_price: u64::from_le_bytes(*start_price),
+            auction_start: i64::from_le_bytes(*auction_start),
+            auction_end: i64::from_le_bytes(*auction_end),
+            decay: decay[0],
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, DutchTerms::LEN];
+        let (start_price_dst, auction_start_dst, auction_end_dst, decay_dst) =
+            mut_array_refs![dst, 8, 8, 8, 1];
+
+        *start_price_dst = self.start_price.to_le_bytes();
+        *auction_start_dst = self.auction_start.to_le_bytes();
+        *auction_end_dst = self.auction_end.to_le_bytes();
+        decay_dst[0] = self.decay;
+    }
+}
+
+// what English and sealed-bid auctions both have: bids sit in a second vault, in Y, and expected_amount is the reserve.
+// Packed at the start of either one's terms
+// 80 bytes of data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AuctionTerms {
+    pub end: i64, //unix timestamp. For a sealed-bid auction that's the end of the reveals
+    pub bid_vault_pubkey: Pubkey, //Y account owned by the PDA, holds the highest bid
+    pub highest_bid: u64,
+    pub bidder_pubkey: Pubkey, //the highest bidder, all zeroes until the first bid
+}
+
+impl Sealed for AuctionTerms {}
+
+impl Pack for AuctionTerms {
+    // 8 (end i64) + 32 (bid vault) + 8 (highest bid) + 32 (bidder)
+    const LEN: usize = 80;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, AuctionTerms::LEN];
+        let (end, bid_vault_pubkey, highest_bid, bidder_pubkey) = array_refs![src, 8, 32, 8, 32];
+
+        Ok(AuctionTerms {
+            end: i64::from_le_bytes(*end),
+            bid_vault_pubkey: Pubkey::new_from_array(*bid_vault_pubkey),
+            highest_bid: u64::from_le_bytes(*highest_bid),
+            bidder_pubkey: Pubkey::new_from_array(*bidder_pubkey),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, AuctionTerms::LEN];
+        let (end_dst, bid_vault_pubkey_dst, highest_bid_dst, bidder_pubkey_dst) =
+            mut_array_refs![dst, 8, 32, 8, 32];
+
+        *end_dst = self.end.to_le_bytes();
+        bid_vault_pubkey_dst.copy_from_slice(self.bid_vault_pubkey.as_ref());
+        *highest_bid_dst = self.highest_bid.to_le_bytes();
+        bidder_pubkey_dst.copy_from_slice(self.bidder_pubkey.as_ref());
+    }
+}
+
+// an auction that goes to the highest bidder at the end
+// 96 bytes of data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EnglishTerms {
+    pub auction: AuctionTerms,
+    pub min_increment: u64, //how much each bid has to beat the last one by
+    pub extension: i64, //a bid this close to the end pushes the end back to this many seconds from the bid
+}
+
+impl Sealed for EnglishTerms {}
+
+impl Pack for EnglishTerms {
+    // 80 (auction) + 8 (min increment) + 8 (extension i64)
+    const LEN: usize = 96;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, EnglishTerms::LEN];
+        let (auction, min_increment, extension) = array_refs![src, AuctionTerms::LEN, 8, 8];
+
+        Ok(EnglishTerms {
+            auction: AuctionTerms::unpack_from_slice(auction)?,
+            min_increment: u64::from_le_bytes(*min_increment),
+            extension: i64::from_le_bytes(*extension),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, EnglishTerms::LEN];
+        let (auction_dst, min_increment_dst, extension_dst) =
+            mut_array_refs![dst, AuctionTerms::LEN, 8, 8];
+
+        self.auction.pack_into_slice(auction_dst);
+        *min_increment_dst = self.min_increment.to_le_bytes();
+        *extension_dst = self.extension.to_le_bytes();
+    }
+}
+
+// like an English auction, but bids are committed as hashes until commit_end and only revealed after that, until the
+// auction's end
+// 105 bytes of data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SealedTerms {
+    pub auction: AuctionTerms,
+    pub commit_end: i64,
+    pub bond: u64, //what a commitment costs, lost if it's never revealed
+    pub second_bid: u64, //the best revealed bid that isn't the highest
+    pub second_price: bool, //the winner pays max(second_bid, reserve) instead of their own bid
+}
+
+impl Sealed for SealedTerms {}
+
+impl Pack for SealedTerms {
+    // 80 (auction) + 8 (commit end i64) + 8 (bond) + 8 (second bid) + 1 (second price bool)
+    const LEN: usize = 105;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, SealedTerms::LEN];
+        let (auction, commit_end, bond, second_bid, second_price) =
+            array_refs![src, AuctionTerms::LEN, 8, 8, 8, 1];
+
+        let second_price = match second_price {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(SealedTerms {
+            auction: AuctionTerms::unpack_from_slice(auction)?,
+            commit_end: i64::from_le_bytes(*commit_end),
+            bond: u64::from_le_bytes(*bond),
+            second_bid: u64::from_le_bytes(*second_bid),
+            second_price,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, SealedTerms::LEN];
+        let (auction_dst, commit_end_dst, bond_dst, second_bid_dst, second_price_dst) =
+            mut_array_refs![dst, AuctionTerms::LEN, 8, 8, 8, 1];
+
+        self.auction.pack_into_slice(auction_dst);
+        *commit_end_dst = self.commit_end.to_le_bytes();
+        *bond_dst = self.bond.to_le_bytes();
+        *second_bid_dst = self.second_bid.to_le_bytes();
+        second_price_dst[0] = self.second_price as u8;
+    }
+}
+
+// the escrow as it was first deployed: no account type, no modes, a plain is_initialized flag up front.
+// Those accounts are still out there - Cancel takes them as the Vault mode escrows they were
+// 105 bytes of data, less than any Escrow
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LegacyEscrow {
+    pub is_initialized: bool,
+    pub initializer_pubkey: Pubkey,
+    pub temp_token_account_pubkey: Pubkey,
+    pub initializer_token_to_receive_account_pubkey: Pubkey,
+    pub expected_amount: u64,
+}
+
+impl From<LegacyEscrow> for Escrow {
+    fn from(legacy: LegacyEscrow) -> Self {
+        // nobody else could pay the rent back then, or sign for the initializer
+        Escrow {
+            is_initialized: legacy.is_initialized,
+            initializer_pubkey: legacy.initializer_pubkey,
+            temp_token_account_pubkey: legacy.temp_token_account_pubkey,
+            initializer_token_to_receive_account_pubkey: legacy.initializer_token_to_receive_account_pubkey,
+            expected_amount: legacy.expected_amount,
+            mode: EscrowMode::Vault,
+            rent_payer: legacy.initializer_pubkey,
+            ..Escrow::default()
+        }
+    }
+}
+
+impl Sealed for LegacyEscrow {}
+
+impl IsInitialized for LegacyEscrow {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for LegacyEscrow {
+    // 1 (bool) + 3x32 (pubkeys) + 8 (expected amount)
+    const LEN: usize = 105;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, LegacyEscrow::LEN];
+        let (
+            is_initialized,
+            initializer_pubkey,
+            temp_token_account_pubkey,
+            initializer_token_to_receive_account_pubkey,
+            expected_amount,
+        ) = array_refs![src, 1, 32, 32, 32, 8];
+
+        // the 1 is AccountType::Legacy now
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(LegacyEscrow {
+            is_initialized,
+            initializer_pubkey: Pubkey::new_from_array(*initializer_pubkey),
+            temp_token_account_pubkey: Pubkey::new_from_array(*temp_token_account_pubkey),
+            initializer_token_to_receive_account_pubkey: Pubkey::new_from_array(*initializer_token_to_receive_account_pubkey),
+            expected_amount: u64::from_le_bytes(*expected_amount),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, LegacyEscrow::LEN];
+        let (
+            is_initialized_dst,
+            initializer_pubkey_dst,
+            temp_token_account_pubkey_dst,
+            initializer_token_to_receive_account_pubkey_dst,
+            expected_amount_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 8];
+
+        is_initialized_dst[0] = self.is_initialized as u8;
+        initializer_pubkey_dst.copy_from_slice(self.initializer_pubkey.as_ref());
+        temp_token_account_pubkey_dst.copy_from_slice(self.temp_token_account_pubkey.as_ref());
+        initializer_token_to_receive_account_pubkey_dst.copy_from_slice(self.initializer_token_to_receive_account_pubkey.as_ref());
+        *expected_amount_dst = self.expected_amount.to_le_bytes();
+    }
+}
+
+// a peer-to-peer loan against collateral. The collateral sits in a vault owned by the PDA, same as an escrow's X tokens
+// 225 bytes of data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Loan {
+    pub is_initialized: bool,
+    pub borrower_pubkey: Pubkey,
+    pub collateral_account_pubkey: Pubkey, //the vault, handed to the PDA at Offer
+    pub borrower_principal_account_pubkey: Pubkey, //where the lender sends the principal
+    pub principal: u64,
+    pub interest: u64, //on top of the principal, flat - the borrower repays principal + interest
+    pub duration: i64, //seconds from Fund until the loan is due
+    pub rent_payer: Pubkey, //gets the rent of the loan and vault accounts back on close, usually the borrower
+    // set at Fund, all zeroes until then
+    pub lender_pubkey: Pubkey,
+    pub lender_principal_account_pubkey: Pubkey, //the lender paid from here, repayment goes back to it
+    pub deadline: i64, //unix timestamp, repay before it or the lender can foreclose from it on
+}
+
+impl Loan {
+    pub fn is_funded(&self) -> bool {
+        self.lender_pubkey != Pubkey::default()
+    }
+}
+
+impl Sealed for Loan {}
+
+impl IsInitialized for Loan {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Loan {
+    // 1 (account type) + 3x32 (pubkeys) + 3x8 (principal, interest, duration) + 3x32 (rent payer, lender, lender's account) + 8 (deadline)
+    const LEN: usize = 225;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Loan::LEN];
+        let (
+            is_initialized,
+            borrower_pubkey,
+            collateral_account_pubkey,
+            borrower_principal_account_pubkey,
+            principal,
+            interest,
+            duration,
+            rent_payer,
+            lender_pubkey,
+            lender_principal_account_pubkey,
+            deadline,
+        ) = array_refs![src, 1, 32, 32, 32, 8, 8, 8, 32, 32, 32, 8];
+
+        let is_initialized = AccountType::Loan.unpack_initialized(is_initialized)?;
+
+        Ok(Loan {
+            is_initialized,
+            borrower_pubkey: Pubkey::new_from_array(*borrower_pubkey),
+            collateral_account_pubkey: Pubkey::new_from_array(*collateral_account_pubkey),
+            borrower_principal_account_pubkey: Pubkey::new_from_array(*borrower_principal_account_pubkey),
+            principal: u64::from_le_bytes(*principal),
+            interest: u64::from_le_bytes(*interest),
+            duration: i64::from_le_bytes(*duration),
+            rent_payer: Pubkey::new_from_array(*rent_payer),
+            lender_pubkey: Pubkey::new_from_array(*lender_pubkey),
+            lender_principal_account_pubkey: Pubkey::new_from_array(*lender_principal_account_pubkey),
+            deadline: i64::from_le_bytes(*deadline),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Loan::LEN];
+        let (
+            is_initialized_dst,
+            borrower_pubkey_dst,
+            collateral_account_pubkey_dst,
+            borrower_principal_account_pubkey_dst,
+            principal_dst,
+            interest_dst,
+            duration_dst,
+            rent_payer_dst,
+            lender_pubkey_dst,
+            lender_principal_account_pubkey_dst,
+            deadline_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 8, 8, 8, 32, 32, 32, 8];
+
+        let Loan {
+            is_initialized,
+            borrower_pubkey,
+            collateral_account_pubkey,
+            borrower_principal_account_pubkey,
+            principal,
+            interest,
+            duration,
+            rent_payer,
+            lender_pubkey,
+            lender_principal_account_pubkey,
+            deadline,
+        } = self;
+
+        is_initialized_dst[0] = AccountType::Loan.pack_initialized(*is_initialized);
+        borrower_pubkey_dst.copy_from_slice(borrower_pubkey.as_ref());
+        collateral_account_pubkey_dst.copy_from_slice(collateral_account_pubkey.as_ref());
+        borrower_principal_account_pubkey_dst.copy_from_slice(borrower_principal_account_pubkey.as_ref());
+        *principal_dst = principal.to_le_bytes();
+        *interest_dst = interest.to_le_bytes();
+        *duration_dst = duration.to_le_bytes();
+        rent_payer_dst.copy_from_slice(rent_payer.as_ref());
+        lender_pubkey_dst.copy_from_slice(lender_pubkey.as_ref());
+        lender_principal_account_pubkey_dst.copy_from_slice(lender_principal_account_pubkey.as_ref());
+        *deadline_dst = deadline.to_le_bytes();
+    }
+}
+
+// one committed bid in a sealed-bid auction, closed when it's revealed
+// 113 bytes of data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SealedBid {
+    pub is_initialized: bool,
+    pub escrow_pubkey: Pubkey, //the auction it's for
+    pub bidder_pubkey: Pubkey, //gets the rent back
+    pub commitment: [u8; 32], //sha256 of the amount (8 bytes LE), a 32 byte salt and the bidder's pubkey
+    pub bond: u64, //what was paid into the bid vault with it
+    pub reveal_end: i64, //copied from the auction, so the account can be closed even after the auction is gone
+}
+
+impl Sealed for SealedBid {}
+
+impl IsInitialized for SealedBid {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for SealedBid {
+    // 1 (account type) + 2x32 (pubkeys) + 32 (commitment) + 8 (bond) + 8 (reveal end i64)
+    const LEN: usize = 113;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, SealedBid::LEN];
+        let (is_initialized, escrow_pubkey, bidder_pubkey, commitment, bond, reveal_end) =
+            array_refs![src, 1, 32, 32, 32, 8, 8];
+
+        let is_initialized = AccountType::SealedBid.unpack_initialized(is_initialized)?;
+
+        Ok(SealedBid {
+            is_initialized,
+            escrow_pubkey: Pubkey::new_from_array(*escrow_pubkey),
+            bidder_pubkey: Pubkey::new_from_array(*bidder_pubkey),
+            commitment: *commitment,
+            bond: u64::from_le_bytes(*bond),
+            reveal_end: i64::from_le_bytes(*reveal_end),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, SealedBid::LEN];
+        let (is_initialized_dst, escrow_pubkey_dst, bidder_pubkey_dst, commitment_dst, bond_dst, reveal_end_dst) =
+            mut_array_refs![dst, 1, 32, 32, 32, 8, 8];
+
+        let SealedBid {
+            is_initialized,
+            escrow_pubkey,
+            bidder_pubkey,
+            commitment,
+            bond,
+            reveal_end,
+        } = self;
+
+        is_initialized_dst[0] = AccountType::SealedBid.pack_initialized(*is_initialized);
+        escrow_pubkey_dst.copy_from_slice(escrow_pubkey.as_ref());
+        bidder_pubkey_dst.copy_from_slice(bidder_pubkey.as_ref());
+        commitment_dst.copy_from_slice(commitment);
+        *bond_dst = bond.to_le_bytes();
+        *reveal_end_dst = reveal_end.to_le_bytes();
+    }
+}
+
+// which of a maker's signed order nonces are used up, filled or cancelled. One account per NonceBitmap::NONCES of them,
+// at the PDA order::nonce_bitmap_address(maker, window), created the first time one of its nonces is used
+// 297 bytes of data
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NonceBitmap {
+    pub is_initialized: bool,
+    pub maker_pubkey: Pubkey,
+    pub window: u64, //holds nonces window * NONCES up to (window + 1) * NONCES
+    pub bits: [u8; 256], //bit n % 8 of byte n / 8 is set once nonce n of the window is used
+}
+
+impl NonceBitmap {
+    pub const NONCES: u64 = 256 * 8;
+
+    pub fn new(maker_pubkey: Pubkey, window: u64) -> Self {
+        NonceBitmap {
+            is_initialized: true,
+            maker_pubkey,
+            window,
+            bits: [0; 256],
+        }
+    }
+
+    /// The window a nonce falls in
+    pub fn window(nonce: u64) -> u64 {
+        nonce / Self::NONCES
     }
-}
\ No newline at end of file
+
+    fn position(nonce: u64) -> (usize, u8) {
+        let bit = nonce % Self::NONCES;
+        ((bit / 8) as usize, 1 << (bit % 8))
+    }
+
+    pub fn is_used(&self, nonce: u64) -> bool {
+        let (byte, mask) = Self::position(nonce);
+        self.bits[byte] & mask != 0
+    }
+
+    pub fn set_used(&mut self, nonce: u64) {
+        let (byte, mask) = Self::position(nonce);
+        self.bits[byte] |= mask;
+    }
+}
+
+impl Sealed for NonceBitmap {}
+
+impl IsInitialized for NonceBitmap {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for NonceBitmap {
+    // 1 (account type) + 32 (maker) + 8 (window) + 256 (bits)
+    const LEN: usize = 297;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, NonceBitmap::LEN];
+        let (is_initialized, maker_pubkey, window, bits) = array_refs![src, 1, 32, 8, 256];
+
+        let is_initialized = AccountType::NonceBitmap.unpack_initialized(is_initialized)?;
+
+        Ok(NonceBitmap {
+            is_initialized,
+            maker_pubkey: Pubkey::new_from_array(*maker_pubkey),
+            window: u64::from_le_bytes(*window),
+            bits: *bits,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, NonceBitmap::LEN];
+        let (is_initialized_dst, maker_pubkey_dst, window_dst, bits_dst) =
+            mut_array_refs![dst, 1, 32, 8, 256];
+
+        is_initialized_dst[0] = AccountType::NonceBitmap.pack_initialized(self.is_initialized);
+        maker_pubkey_dst.copy_from_slice(self.maker_pubkey.as_ref());
+        *window_dst = self.window.to_le_bytes();
+        bits_dst.copy_from_slice(&self.bits);
+    }
+}
+
+// a one-to-many payout: the vault is handed to the PDA like an escrow's, and each recipient in the merkle tree
+// (see merkle.rs) can claim their amount out of it once, until the deadline. Then the initializer gets the rest
+// 145 bytes of data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Distribution {
+    pub is_initialized: bool,
+    pub initializer_pubkey: Pubkey, //gets what's left and the rent of all three accounts at the end
+    pub vault_pubkey: Pubkey,
+    pub claimed_bitmap_pubkey: Pubkey, //see ClaimedBitmap
+    pub merkle_root: [u8; 32],
+    pub num_recipients: u64, //leaf indexes go from 0 up to this
+    pub deadline: i64, //unix timestamp, claims stop and the initializer can reclaim from then on
+}
+
+impl Sealed for Distribution {}
+
+impl IsInitialized for Distribution {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Distribution {
+    // 1 (account type) + 3x32 (pubkeys) + 32 (root) + 8 (recipients) + 8 (deadline i64)
+    const LEN: usize = 145;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Distribution::LEN];
+        let (
+            is_initialized,
+            initializer_pubkey,
+            vault_pubkey,
+            claimed_bitmap_pubkey,
+            merkle_root,
+            num_recipients,
+            deadline,
+        ) = array_refs![src, 1, 32, 32, 32, 32, 8, 8];
+
+        let is_initialized = AccountType::Distribution.unpack_initialized(is_initialized)?;
+
+        Ok(Distribution {
+            is_initialized,
+            initializer_pubkey: Pubkey::new_from_array(*initializer_pubkey),
+            vault_pubkey: Pubkey::new_from_array(*vault_pubkey),
+            claimed_bitmap_pubkey: Pubkey::new_from_array(*claimed_bitmap_pubkey),
+            merkle_root: *merkle_root,
+            num_recipients: u64::from_le_bytes(*num_recipients),
+            deadline: i64::from_le_bytes(*deadline),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Distribution::LEN];
+        let (
+            is_initialized_dst,
+            initializer_pubkey_dst,
+            vault_pubkey_dst,
+            claimed_bitmap_pubkey_dst,
+            merkle_root_dst,
+            num_recipients_dst,
+            deadline_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 32, 8, 8];
+
+        is_initialized_dst[0] = AccountType::Distribution.pack_initialized(self.is_initialized);
+        initializer_pubkey_dst.copy_from_slice(self.initializer_pubkey.as_ref());
+        vault_pubkey_dst.copy_from_slice(self.vault_pubkey.as_ref());
+        claimed_bitmap_pubkey_dst.copy_from_slice(self.claimed_bitmap_pubkey.as_ref());
+        merkle_root_dst.copy_from_slice(&self.merkle_root);
+        *num_recipients_dst = self.num_recipients.to_le_bytes();
+        *deadline_dst = self.deadline.to_le_bytes();
+    }
+}
+
+// which leaves of a distribution were claimed. Its length depends on the number of recipients, so it isn't a Pack:
+// the account type, the distribution it belongs to, then a bit per leaf. Bit i % 8 of byte i / 8 is leaf i
+pub struct ClaimedBitmap;
+
+impl ClaimedBitmap {
+    pub const HEADER_LEN: usize = 33;
+
+    /// The account size for `num_recipients` leaves
+    pub fn len(num_recipients: u64) -> usize {
+        Self::HEADER_LEN + num_recipients.div_ceil(8) as usize
+    }
+
+    pub fn init(data: &mut [u8], distribution: &Pubkey) {
+        data[0] = AccountType::ClaimedBitmap as u8;
+        data[1..Self::HEADER_LEN].copy_from_slice(distribution.as_ref());
+    }
+
+    /// Whether anything was written to `data` yet, a bitmap or not
+    pub fn is_initialized(data: &[u8]) -> bool {
+        data.first().is_some_and(|b| *b != AccountType::Uninitialized as u8)
+    }
+
+    /// Whether `data` is the bitmap `init` wrote for `distribution`, sized for `num_recipients` leaves
+    pub fn belongs_to(data: &[u8], distribution: &Pubkey, num_recipients: u64) -> bool {
+        data.len() == Self::len(num_recipients)
+            && AccountType::of(data) == Some(AccountType::ClaimedBitmap)
+            && data[1..Self::HEADER_LEN] == *distribution.as_ref()
+    }
+
+    pub fn is_claimed(data: &[u8], index: u64) -> bool {
+        data[Self::HEADER_LEN + (index / 8) as usize] & (1 << (index % 8)) != 0
+    }
+
+    pub fn set_claimed(data: &mut [u8], index: u64) {
+        data[Self::HEADER_LEN + (index / 8) as usize] |= 1 << (index % 8);
+    }
+}
+
+// team tokens unlocking over time out of a vault owned by the PDA: nothing until the cliff, then linearly from start
+// to end as if the cliff wasn't there, see vesting.rs
+// 169 bytes of data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vesting {
+    pub is_initialized: bool,
+    pub initializer_pubkey: Pubkey, //funded the vault, gets the rent of both accounts back at the end
+    pub beneficiary_pubkey: Pubkey, //withdraws into any token account of theirs
+    pub vault_pubkey: Pubkey,
+    pub revoker_pubkey: Pubkey, //may claw back whatever hasn't vested yet, all zeroes if nobody can
+    pub start: i64, //unix timestamps
+    pub cliff: i64,
+    pub end: i64,
+    pub total_amount: u64, //what the vault held at init. A revocation cuts it down to what had vested by then
+    pub withdrawn: u64,
+}
+
+impl Sealed for Vesting {}
+
+impl IsInitialized for Vesting {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Vesting {
+    // 1 (account type) + 4x32 (pubkeys) + 3x8 (start, cliff, end i64) + 2x8 (total, withdrawn)
+    const LEN: usize = 169;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Vesting::LEN];
+        let (
+            is_initialized,
+            initializer_pubkey,
+            beneficiary_pubkey,
+            vault_pubkey,
+            revoker_pubkey,
+            start,
+            cliff,
+            end,
+            total_amount,
+            withdrawn,
+        ) = array_refs![src, 1, 32, 32, 32, 32, 8, 8, 8, 8, 8];
+
+        let is_initialized = AccountType::Vesting.unpack_initialized(is_initialized)?;
+
+        Ok(Vesting {
+            is_initialized,
+            initializer_pubkey: Pubkey::new_from_array(*initializer_pubkey),
+            beneficiary_pubkey: Pubkey::new_from_array(*beneficiary_pubkey),
+            vault_pubkey: Pubkey::new_from_array(*vault_pubkey),
+            revoker_pubkey: Pubkey::new_from_array(*revoker_pubkey),
+            start: i64::from_le_bytes(*start),
+            cliff: i64::from_le_bytes(*cliff),
+            end: i64::from_le_bytes(*end),
+            total_amount: u64::from_le_bytes(*total_amount),
+            withdrawn: u64::from_le_bytes(*withdrawn),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Vesting::LEN];
+        let (
+            is_initialized_dst,
+            initializer_pubkey_dst,
+            beneficiary_pubkey_dst,
+            vault_pubkey_dst,
+            revoker_pubkey_dst,
+            start_dst,
+            cliff_dst,
+            end_dst,
+            total_amount_dst,
+            withdrawn_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 32, 8, 8, 8, 8, 8];
+
+        is_initialized_dst[0] = AccountType::Vesting.pack_initialized(self.is_initialized);
+        initializer_pubkey_dst.copy_from_slice(self.initializer_pubkey.as_ref());
+        beneficiary_pubkey_dst.copy_from_slice(self.beneficiary_pubkey.as_ref());
+        vault_pubkey_dst.copy_from_slice(self.vault_pubkey.as_ref());
+        revoker_pubkey_dst.copy_from_slice(self.revoker_pubkey.as_ref());
+        *start_dst = self.start.to_le_bytes();
+        *cliff_dst = self.cliff.to_le_bytes();
+        *end_dst = self.end.to_le_bytes();
+        *total_amount_dst = self.total_amount.to_le_bytes();
+        *withdrawn_dst = self.withdrawn.to_le_bytes();
+    }
+}
+
+// a payment streamed by the second: `rate` tokens per second accrue to the recipient from start to end, out of a vault
+// owned by the PDA that holds exactly rate * (end - start) at the start
+// 129 bytes of data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stream {
+    pub is_initialized: bool,
+    pub payer_pubkey: Pubkey, //funded the vault, may cancel and gets the rent of both accounts back at the end
+    pub recipient_pubkey: Pubkey, //withdraws into any token account of theirs
+    pub vault_pubkey: Pubkey,
+    pub rate: u64, //per second
+    pub start: i64, //unix timestamps
+    pub end: i64,
+    pub withdrawn: u64,
+}
+
+impl Stream {
+    /// Everything the stream pays out, start to end
+    pub fn total(&self) -> u64 {
+        self.accrued(self.end)
+    }
+
+    /// How much has accrued to the recipient by `now`, withdrawn or not
+    pub fn accrued(&self, now: i64) -> u64 {
+        let elapsed = now.min(self.end).saturating_sub(self.start).max(0) as u64;
+        // can't overflow for a stream the program set up, it checked the total fits
+        self.rate.saturating_mul(elapsed)
+    }
+}
+
+impl Sealed for Stream {}
+
+impl IsInitialized for Stream {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Stream {
+    // 1 (account type) + 3x32 (pubkeys) + 8 (rate) + 2x8 (start, end i64) + 8 (withdrawn)
+    const LEN: usize = 129;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Stream::LEN];
+        let (
+            is_initialized,
+            payer_pubkey,
+            recipient_pubkey,
+            vault_pubkey,
+            rate,
+            start,
+            end,
+            withdrawn,
+        ) = array_refs![src, 1, 32, 32, 32, 8, 8, 8, 8];
+
+        let is_initialized = AccountType::Stream.unpack_initialized(is_initialized)?;
+
+        Ok(Stream {
+            is_initialized,
+            payer_pubkey: Pubkey::new_from_array(*payer_pubkey),
+            recipient_pubkey: Pubkey::new_from_array(*recipient_pubkey),
+            vault_pubkey: Pubkey::new_from_array(*vault_pubkey),
+            rate: u64::from_le_bytes(*rate),
+            start: i64::from_le_bytes(*start),
+            end: i64::from_le_bytes(*end),
+            withdrawn: u64::from_le_bytes(*withdrawn),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Stream::LEN];
+        let (
+            is_initialized_dst,
+            payer_pubkey_dst,
+            recipient_pubkey_dst,
+            vault_pubkey_dst,
+            rate_dst,
+            start_dst,
+            end_dst,
+            withdrawn_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 8, 8, 8, 8];
+
+        is_initialized_dst[0] = AccountType::Stream.pack_initialized(self.is_initialized);
+        payer_pubkey_dst.copy_from_slice(self.payer_pubkey.as_ref());
+        recipient_pubkey_dst.copy_from_slice(self.recipient_pubkey.as_ref());
+        vault_pubkey_dst.copy_from_slice(self.vault_pubkey.as_ref());
+        *rate_dst = self.rate.to_le_bytes();
+        *start_dst = self.start.to_le_bytes();
+        *end_dst = self.end.to_le_bytes();
+        *withdrawn_dst = self.withdrawn.to_le_bytes();
+    }
+}
+
+// freelance work paid out milestone by milestone, in order, out of a vault owned by the PDA. The payer releases the
+// current milestone to the payee, or either of them disputes it and the arbiter splits it between them
+// 196 bytes of data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MilestoneEscrow {
+    pub is_initialized: bool,
+    pub payer_pubkey: Pubkey, //funded the vault, gets the rent of both accounts back once the last milestone is paid
+    pub payee_pubkey: Pubkey,
+    pub arbiter_pubkey: Pubkey,
+    pub vault_pubkey: Pubkey,
+    pub num_milestones: u8, //at most MAX_MILESTONES, amounts past it are zero
+    pub current: u8, //the next milestone to be paid, num_milestones once they all are
+    pub disputed: bool, //the current one is, only the arbiter can pay it out then
+    pub amounts: [u64; MilestoneEscrow::MAX_MILESTONES],
+}
+
+impl MilestoneEscrow {
+    pub const MAX_MILESTONES: usize = 8;
+
+    /// What the current milestone pays, none once they're all paid
+    pub fn current_amount(&self) -> Option<u64> {
+        self.amounts[..self.num_milestones as usize]
+            .get(self.current as usize)
+            .copied()
+    }
+}
+
+impl Sealed for MilestoneEscrow {}
+
+impl IsInitialized for MilestoneEscrow {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for MilestoneEscrow {
+    // 1 (account type) + 4x32 (pubkeys) + 1 (count) + 1 (current) + 1 (bool) + 8x8 (amounts)
+    const LEN: usize = 196;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, MilestoneEscrow::LEN];
+        let (
+            is_initialized,
+            payer_pubkey,
+            payee_pubkey,
+            arbiter_pubkey,
+            vault_pubkey,
+            num_milestones,
+            current,
+            disputed,
+            amounts_src,
+        ) = array_refs![src, 1, 32, 32, 32, 32, 1, 1, 1, 64];
+
+        let is_initialized = AccountType::MilestoneEscrow.unpack_initialized(is_initialized)?;
+        let disputed = match disputed {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        if num_milestones[0] as usize > MilestoneEscrow::MAX_MILESTONES
+            || current[0] > num_milestones[0]
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut amounts = [0; MilestoneEscrow::MAX_MILESTONES];
+        for (amount, src) in amounts.iter_mut().zip(amounts_src.chunks_exact(8)) {
+            *amount = u64::from_le_bytes(*array_ref![src, 0, 8]);
+        }
+
+        Ok(MilestoneEscrow {
+            is_initialized,
+            payer_pubkey: Pubkey::new_from_array(*payer_pubkey),
+            payee_pubkey: Pubkey::new_from_array(*payee_pubkey),
+            arbiter_pubkey: Pubkey::new_from_array(*arbiter_pubkey),
+            vault_pubkey: Pubkey::new_from_array(*vault_pubkey),
+            num_milestones: num_milestones[0],
+            current: current[0],
+            disputed,
+            amounts,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, MilestoneEscrow::LEN];
+        let (
+            is_initialized_dst,
+            payer_pubkey_dst,
+            payee_pubkey_dst,
+            arbiter_pubkey_dst,
+            vault_pubkey_dst,
+            num_milestones_dst,
+            current_dst,
+            disputed_dst,
+            amounts_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 32, 1, 1, 1, 64];
+
+        is_initialized_dst[0] = AccountType::MilestoneEscrow.pack_initialized(self.is_initialized);
+        payer_pubkey_dst.copy_from_slice(self.payer_pubkey.as_ref());
+        payee_pubkey_dst.copy_from_slice(self.payee_pubkey.as_ref());
+        arbiter_pubkey_dst.copy_from_slice(self.arbiter_pubkey.as_ref());
+        vault_pubkey_dst.copy_from_slice(self.vault_pubkey.as_ref());
+        num_milestones_dst[0] = self.num_milestones;
+        current_dst[0] = self.current;
+        disputed_dst[0] = self.disputed as u8;
+        for (dst, amount) in amounts_dst.chunks_exact_mut(8).zip(self.amounts.iter()) {
+            dst.copy_from_slice(&amount.to_le_bytes());
+        }
+    }
+}
+
+// a set of keys that act together as one: M of the N signers have to sign an instruction for it to count, like
+// spl-token's multisig. Set as an escrow's authority with SetEscrowAuthority
+// 355 bytes of data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Multisig {
+    pub is_initialized: bool,
+    pub m: u8, //how many of the signers have to sign
+    pub n: u8, //how many signers there are, at most MAX_SIGNERS. Slots past it are zero
+    pub signers: [Pubkey; Multisig::MAX_SIGNERS],
+}
+
+impl Multisig {
+    pub const MAX_SIGNERS: usize = 11;
+}
+
+impl Sealed for Multisig {}
+
+impl IsInitialized for Multisig {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Multisig {
+    // 1 (account type) + 1 (m) + 1 (n) + 11x32 (signers)
+    const LEN: usize = 355;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Multisig::LEN];
+        let (is_initialized, m, n, signers_src) = array_refs![src, 1, 1, 1, 352];
+
+        let is_initialized = AccountType::Multisig.unpack_initialized(is_initialized)?;
+        if n[0] as usize > Multisig::MAX_SIGNERS || m[0] > n[0] {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut signers = [Pubkey::default(); Multisig::MAX_SIGNERS];
+        for (signer, src) in signers.iter_mut().zip(signers_src.chunks_exact(32)) {
+            *signer = Pubkey::new_from_array(*array_ref![src, 0, 32]);
+        }
+
+        Ok(Multisig {
+            is_initialized,
+            m: m[0],
+            n: n[0],
+            signers,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Multisig::LEN];
+        let (is_initialized_dst, m_dst, n_dst, signers_dst) = mut_array_refs![dst, 1, 1, 1, 352];
+
+        is_initialized_dst[0] = AccountType::Multisig.pack_initialized(self.is_initialized);
+        m_dst[0] = self.m;
+        n_dst[0] = self.n;
+        for (dst, signer) in signers_dst.chunks_exact_mut(32).zip(self.signers.iter()) {
+            dst.copy_from_slice(signer.as_ref());
+        }
+    }
+}
+
+// a pooled buy: contributors put Y into a vault owned by the PDA until there's enough to take the escrow, then it's
+// taken in one go and each of them claims their share of the X. If that never happens they get their Y back
+// 170 bytes of data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Pool {
+    pub is_initialized: bool,
+    pub escrow_pubkey: Pubkey, //the Vault mode escrow it's out to take
+    pub creator_pubkey: Pubkey, //set the pool up, gets the rent of it and both vaults back at the end
+    pub y_vault_pubkey: Pubkey,
+    pub x_vault_pubkey: Pubkey,
+    pub target: u64, //the escrow's expected_amount
+    pub raised: u64, //never more than the target
+    pub deadline: i64, //unix timestamp, no contributions from then on and refunds if it isn't filled
+    pub contributors: u64, //how many contribution accounts are still open, the last one to leave closes the pool
+    pub executed: bool, //took the escrow, contributors claim X from here on
+    pub received_x: u64, //what the escrow paid out, split pro rata
+}
+
+impl Sealed for Pool {}
+
+impl IsInitialized for Pool {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Pool {
+    // 1 (account type) + 4x32 (pubkeys) + 8 (target) + 8 (raised) + 8 (deadline i64) + 8 (contributors) + 1 (bool)
+    // + 8 (received x)
+    const LEN: usize = 170;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Pool::LEN];
+        let (
+            is_initialized,
+            escrow_pubkey,
+            creator_pubkey,
+            y_vault_pubkey,
+            x_vault_pubkey,
+            target,
+            raised,
+            deadline,
+            contributors,
+            executed,
+            received_x,
+        ) = array_refs![src, 1, 32, 32, 32, 32, 8, 8, 8, 8, 1, 8];
+
+        let is_initialized = AccountType::Pool.unpack_initialized(is_initialized)?;
+        let executed = match executed {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+
+        Ok(Pool {
+            is_initialized,
+            escrow_pubkey: Pubkey::new_from_array(*escrow_pubkey),
+            creator_pubkey: Pubkey::new_from_array(*creator_pubkey),
+            y_vault_pubkey: Pubkey::new_from_array(*y_vault_pubkey),
+            x_vault_pubkey: Pubkey::new_from_array(*x_vault_pubkey),
+            target: u64::from_le_bytes(*target),
+            raised: u64::from_le_bytes(*raised),
+            deadline: i64::from_le_bytes(*deadline),
+            contributors: u64::from_le_bytes(*contributors),
+            executed,
+            received_x: u64::from_le_bytes(*received_x),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Pool::LEN];
+        let (
+            is_initialized_dst,
+            escrow_pubkey_dst,
+            creator_pubkey_dst,
+            y_vault_pubkey_dst,
+            x_vault_pubkey_dst,
+            target_dst,
+            raised_dst,
+            deadline_dst,
+            contributors_dst,
+            executed_dst,
+            received_x_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 32, 8, 8, 8, 8, 1, 8];
+
+        is_initialized_dst[0] = AccountType::Pool.pack_initialized(self.is_initialized);
+        escrow_pubkey_dst.copy_from_slice(self.escrow_pubkey.as_ref());
+        creator_pubkey_dst.copy_from_slice(self.creator_pubkey.as_ref());
+        y_vault_pubkey_dst.copy_from_slice(self.y_vault_pubkey.as_ref());
+        x_vault_pubkey_dst.copy_from_slice(self.x_vault_pubkey.as_ref());
+        *target_dst = self.target.to_le_bytes();
+        *raised_dst = self.raised.to_le_bytes();
+        *deadline_dst = self.deadline.to_le_bytes();
+        *contributors_dst = self.contributors.to_le_bytes();
+        executed_dst[0] = self.executed as u8;
+        *received_x_dst = self.received_x.to_le_bytes();
+    }
+}
+
+// what one contributor put into a pool, closed when they take their share or their refund
+// 73 bytes of data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Contribution {
+    pub is_initialized: bool,
+    pub pool_pubkey: Pubkey,
+    pub contributor_pubkey: Pubkey, //gets the rent back
+    pub amount: u64,
+}
+
+impl Sealed for Contribution {}
+
+impl IsInitialized for Contribution {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Contribution {
+    // 1 (account type) + 2x32 (pubkeys) + 8 (amount)
+    const LEN: usize = 73;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, Contribution::LEN];
+        let (is_initialized, pool_pubkey, contributor_pubkey, amount) = array_refs![src, 1, 32, 32, 8];
+
+        let is_initialized = AccountType::Contribution.unpack_initialized(is_initialized)?;
+
+        Ok(Contribution {
+            is_initialized,
+            pool_pubkey: Pubkey::new_from_array(*pool_pubkey),
+            contributor_pubkey: Pubkey::new_from_array(*contributor_pubkey),
+            amount: u64::from_le_bytes(*amount),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, Contribution::LEN];
+        let (is_initialized_dst, pool_pubkey_dst, contributor_pubkey_dst, amount_dst) =
+            mut_array_refs![dst, 1, 32, 32, 8];
+
+        is_initialized_dst[0] = AccountType::Contribution.pack_initialized(self.is_initialized);
+        pool_pubkey_dst.copy_from_slice(self.pool_pubkey.as_ref());
+        contributor_pubkey_dst.copy_from_slice(self.contributor_pubkey.as_ref());
+        *amount_dst = self.amount.to_le_bytes();
+    }
+}
+
+// a DCA standing order: a budget of Y spent on X tranche by tranche, each one triggered by a keeper
+// 153 bytes of data
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StandingOrder {
+    pub is_initialized: bool,
+    pub owner_pubkey: Pubkey, //put the budget up, can cancel and gets the rent back
+    pub vault_pubkey: Pubkey, //the budget, in Y
+    pub x_account_pubkey: Pubkey, //every tranche's X goes here
+    pub amount_per_interval: u64, //the most Y one tranche spends, not counting the keeper's reward
+    pub min_amount_x: u64, //the price limit: the least X a full tranche buys, pro rata for a smaller one
+    pub interval: i64, //seconds between tranches
+    pub keeper_reward: u64, //Y out of the budget for whoever triggers a tranche
+    pub last_execution: i64, //unix timestamp, 0 until the first tranche
+    pub remaining: u64, //what's left of the budget
+    pub tranches: u64, //how many have been executed
+}
+
+impl StandingOrder {
+    /// Whether the next tranche can go at `now`. The first one can go right away
+    pub fn due(&self, now: i64) -> bool {
+        self.last_execution == 0 || now >= self.last_execution.saturating_add(self.interval)
+    }
+
+    /// Whether getting `amount_x` for `amount_y` is within the price limit
+    pub fn within_limit(&self, amount_x: u64, amount_y: u64) -> bool {
+        amount_x as u128 * self.amount_per_interval as u128
+            >= self.min_amount_x as u128 * amount_y as u128
+    }
+}
+
+impl Sealed for StandingOrder {}
+
+impl IsInitialized for StandingOrder {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for StandingOrder {
+    // 1 (account type) + 3x32 (pubkeys) + 8 (amount per interval) + 8 (min amount x) + 8 (interval i64) + 8 (keeper reward)
+    // + 8 (last execution i64) + 8 (remaining) + 8 (tranches)
+    const LEN: usize = 153;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, StandingOrder::LEN];
+        let (
+            is_initialized,
+            owner_pubkey,
+            vault_pubkey,
+            x_account_pubkey,
+            amount_per_interval,
+            min_amount_x,
+            interval,
+            keeper_reward,
+            last_execution,
+            remaining,
+            tranches,
+        ) = array_refs![src, 1, 32, 32, 32, 8, 8, 8, 8, 8, 8, 8];
+
+        let is_initialized = AccountType::StandingOrder.unpack_initialized(is_initialized)?;
+
+        Ok(StandingOrder {
+            is_initialized,
+            owner_pubkey: Pubkey::new_from_array(*owner_pubkey),
+            vault_pubkey: Pubkey::new_from_array(*vault_pubkey),
+            x_account_pubkey: Pubkey::new_from_array(*x_account_pubkey),
+            amount_per_interval: u64::from_le_bytes(*amount_per_interval),
+            min_amount_x: u64::from_le_bytes(*min_amount_x),
+            interval: i64::from_le_bytes(*interval),
+            keeper_reward: u64::from_le_bytes(*keeper_reward),
+            last_execution: i64::from_le_bytes(*last_execution),
+            remaining: u64::from_le_bytes(*remaining),
+            tranches: u64::from_le_bytes(*tranches),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, StandingOrder::LEN];
+        let (
+            is_initialized_dst,
+            owner_pubkey_dst,
+            vault_pubkey_dst,
+            x_account_pubkey_dst,
+            amount_per_interval_dst,
+            min_amount_x_dst,
+            interval_dst,
+            keeper_reward_dst,
+            last_execution_dst,
+            remaining_dst,
+            tranches_dst,
+        ) = mut_array_refs![dst, 1, 32, 32, 32, 8, 8, 8, 8, 8, 8, 8];
+
+        is_initialized_dst[0] = AccountType::StandingOrder.pack_initialized(self.is_initialized);
+        owner_pubkey_dst.copy_from_slice(self.owner_pubkey.as_ref());
+        vault_pubkey_dst.copy_from_slice(self.vault_pubkey.as_ref());
+        x_account_pubkey_dst.copy_from_slice(self.x_account_pubkey.as_ref());
+        *amount_per_interval_dst = self.amount_per_interval.to_le_bytes();
+        *min_amount_x_dst = self.min_amount_x.to_le_bytes();
+        *interval_dst = self.interval.to_le_bytes();
+        *keeper_reward_dst = self.keeper_reward.to_le_bytes();
+        *last_execution_dst = self.last_execution.to_le_bytes();
+        *remaining_dst = self.remaining.to_le_bytes();
+        *tranches_dst = self.tranches.to_le_bytes();
+    }
+}
@@ -0,0 +1,414 @@
+// lets the escrow work with both the original token program and Token-2022
+// Token-2022 keeps the original account layouts and instruction encodings and appends extensions after them,
+// so we read the base layout with spl_token and only parse the extension TLVs we care about.
+// The spl_token instruction builders refuse any program id but their own, hence the hand-rolled ones below.
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+};
+
+use arrayref::{array_ref, array_refs};
+use spl_token::state::{Account as TokenAccount, Mint};
+
+use crate::error::EscrowError;
+
+pub mod token_2022 {
+    solana_program::declare_id!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+}
+
+// extensions start after the base account (mints are padded up to its size) and a one byte account type
+const ACCOUNT_TYPE_OFFSET: usize = TokenAccount::LEN;
+const ACCOUNT_TYPE_MINT: u8 = 1;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+
+// Token-2022's ExtensionType discriminators
+const EXTENSION_TRANSFER_FEE_CONFIG: u16 = 1;
+const EXTENSION_TRANSFER_FEE_AMOUNT: u16 = 2;
+const EXTENSION_IMMUTABLE_OWNER: u16 = 7;
+const EXTENSION_MEMO_TRANSFER: u16 = 8;
+const EXTENSION_CPI_GUARD: u16 = 11;
+// mint extensions that don't change how a plain transfer between two accounts behaves.
+// Anything else - non-transferable, permanent delegate, transfer hooks, pausable, ... - either lets a third party
+// move the escrowed tokens or can make the Exchange fail, so we refuse the mint up front.
+const SAFE_MINT_EXTENSIONS: &[u16] = &[
+    EXTENSION_TRANSFER_FEE_CONFIG,
+    3,  // MintCloseAuthority - can only close a mint with zero supply
+    6,  // DefaultAccountState
+    10, // InterestBearingConfig - only changes the UI amount
+    18, // MetadataPointer
+    19, // TokenMetadata
+    20, // GroupPointer
+    21, // TokenGroup
+    22, // GroupMemberPointer
+    23, // TokenGroupMember
+];
+
+const ONE_IN_BASIS_POINTS: u128 = 10_000;
+
+pub fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == spl_token::id() || *program_id == token_2022::id()
+}
+
+pub fn check_token_program(token_program: &AccountInfo) -> ProgramResult {
+    if !is_token_program(token_program.key) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+// walks the type-length-value entries after the base layout
+fn extensions(data: &[u8], account_type: u8) -> Result<Vec<(u16, &[u8])>, ProgramError> {
+    let mut found = Vec::new();
+    if data.len() <= ACCOUNT_TYPE_OFFSET {
+        return Ok(found);
+    }
+    if data[ACCOUNT_TYPE_OFFSET] != account_type {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let mut offset = ACCOUNT_TYPE_OFFSET + 1;
+    while offset + 4 <= data.len() {
+        let extension_type = u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let len = u16::from_le_bytes([data[offset + 2], data[offset + 3]]) as usize;
+        // zeroed space at the end is unused
+        if extension_type == 0 {
+            break;
+        }
+        let value = data
+            .get(offset + 4..offset + 4 + len)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        found.push((extension_type, value));
+        offset += 4 + len;
+    }
+    Ok(found)
+}
+
+/// The token account extensions that get in the way of what the escrow does with an account
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AccountExtensions {
+    // the owner can't be changed, so the account can't become a vault
+    pub immutable_owner: bool,
+    // incoming transfers need a memo instruction right before them, which a CPI can't provide
+    pub memo_transfer: bool,
+    // the owner's approve, set authority and transfers can't come from a CPI
+    pub cpi_guard: bool,
+}
+
+/// Unpacks a token account owned by `token_program_id`, ignoring any Token-2022 extensions
+pub fn unpack_token_account(
+    account: &AccountInfo,
+    token_program_id: &Pubkey,
+) -> Result<TokenAccount, ProgramError> {
+    Ok(unpack_token_account_with_extensions(account, token_program_id)?.0)
+}
+
+/// Same as `unpack_token_account`, plus the extensions the escrow cares about
+pub fn unpack_token_account_with_extensions(
+    account: &AccountInfo,
+    token_program_id: &Pubkey,
+) -> Result<(TokenAccount, AccountExtensions), ProgramError> {
+    if account.owner != token_program_id || !is_token_program(token_program_id) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let data = account.data.borrow();
+    if data.len() < TokenAccount::LEN
+        || (data.len() > TokenAccount::LEN && *token_program_id == spl_token::id())
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let token_account = TokenAccount::unpack_from_slice(&data[..TokenAccount::LEN])?;
+    if !token_account.is_initialized() {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    let mut found = AccountExtensions::default();
+    for (extension_type, value) in extensions(&data, ACCOUNT_TYPE_ACCOUNT)? {
+        // memo transfer and cpi guard are a single bool, and only count while switched on
+        let enabled = value.first().is_some_and(|enabled| *enabled != 0);
+        match extension_type {
+            EXTENSION_IMMUTABLE_OWNER => found.immutable_owner = true,
+            EXTENSION_MEMO_TRANSFER => found.memo_transfer = enabled,
+            EXTENSION_CPI_GUARD => found.cpi_guard = enabled,
+            _ => {}
+        }
+    }
+    Ok((token_account, found))
+}
+
+/// Transfer fees still withheld in a Token-2022 account - they have to be harvested before it can be closed
+pub fn withheld_amount(account: &AccountInfo) -> Result<u64, ProgramError> {
+    let data = account.data.borrow();
+    for (extension_type, value) in extensions(&data, ACCOUNT_TYPE_ACCOUNT)? {
+        if extension_type == EXTENSION_TRANSFER_FEE_AMOUNT {
+            if value.len() < 8 {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            return Ok(u64::from_le_bytes(*array_ref![value, 0, 8]));
+        }
+    }
+    Ok(0)
+}
+
+// mirrors Token-2022's TransferFee
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TransferFee {
+    pub epoch: u64,
+    pub maximum_fee: u64,
+    pub transfer_fee_basis_points: u16,
+}
+
+impl TransferFee {
+    fn unpack(src: &[u8; 18]) -> Self {
+        let (epoch, maximum_fee, transfer_fee_basis_points) = array_refs![src, 8, 8, 2];
+        Self {
+            epoch: u64::from_le_bytes(*epoch),
+            maximum_fee: u64::from_le_bytes(*maximum_fee),
+            transfer_fee_basis_points: u16::from_le_bytes(*transfer_fee_basis_points),
+        }
+    }
+
+    /// The fee withheld when sending `amount`, rounded up like Token-2022 does
+    pub fn calculate_fee(&self, amount: u64) -> Option<u64> {
+        let basis_points = self.transfer_fee_basis_points as u128;
+        if basis_points == 0 || amount == 0 {
+            return Some(0);
+        }
+        let numerator = (amount as u128).checked_mul(basis_points)?;
+        let fee = numerator
+            .checked_add(ONE_IN_BASIS_POINTS)?
+            .checked_sub(1)?
+            .checked_div(ONE_IN_BASIS_POINTS)?;
+        Some((fee as u64).min(self.maximum_fee))
+    }
+
+    /// The smallest amount to send so that at least `received` arrives
+    pub fn calculate_pre_fee_amount(&self, received: u64) -> Option<u64> {
+        let basis_points = self.transfer_fee_basis_points as u128;
+        if basis_points == 0 || received == 0 {
+            return Some(received);
+        }
+        if basis_points >= ONE_IN_BASIS_POINTS {
+            return received.checked_add(self.maximum_fee);
+        }
+        let numerator = (received as u128).checked_mul(ONE_IN_BASIS_POINTS)?;
+        let denominator = ONE_IN_BASIS_POINTS - basis_points;
+        let raw = numerator
+            .checked_add(denominator)?
+            .checked_sub(1)?
+            .checked_div(denominator)?;
+        if raw - received as u128 >= self.maximum_fee as u128 {
+            received.checked_add(self.maximum_fee)
+        } else if raw > u64::MAX as u128 {
+            None
+        } else {
+            Some(raw as u64)
+        }
+    }
+}
+
+/// The parts of a mint the escrow needs to move its tokens
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MintInfo {
+    pub decimals: u8,
+    pub supply: u64, //1 with 0 decimals makes it an NFT
+    // (older, newer) - which one applies depends on the current epoch
+    pub transfer_fee: Option<(TransferFee, TransferFee)>,
+}
+
+impl MintInfo {
+    pub fn epoch_fee(&self, epoch: u64) -> TransferFee {
+        match self.transfer_fee {
+            Some((_, newer)) if epoch >= newer.epoch => newer,
+            Some((older, _)) => older,
+            None => TransferFee::default(),
+        }
+    }
+
+    /// What arrives when `amount` is sent during `epoch`
+    pub fn received(&self, amount: u64, epoch: u64) -> Result<u64, ProgramError> {
+        let fee = self
+            .epoch_fee(epoch)
+            .calculate_fee(amount)
+            .ok_or(EscrowError::AmountOverflow)?;
+        Ok(amount - fee)
+    }
+
+    /// What has to be sent during `epoch` so that `received` arrives
+    pub fn pre_fee_amount(&self, received: u64, epoch: u64) -> Result<u64, ProgramError> {
+        Ok(self
+            .epoch_fee(epoch)
+            .calculate_pre_fee_amount(received)
+            .ok_or(EscrowError::AmountOverflow)?)
+    }
+}
+
+/// Unpacks a mint owned by `token_program_id` and rejects Token-2022 extensions the escrow can't handle
+pub fn unpack_mint(
+    mint: &AccountInfo,
+    token_program_id: &Pubkey,
+) -> Result<MintInfo, ProgramError> {
+    if mint.owner != token_program_id || !is_token_program(token_program_id) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let data = mint.data.borrow();
+    if data.len() < Mint::LEN
+        || (data.len() > Mint::LEN && *token_program_id == spl_token::id())
+        || (data.len() > Mint::LEN && data.len() <= ACCOUNT_TYPE_OFFSET)
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let base = Mint::unpack_from_slice(&data[..Mint::LEN])?;
+    if !base.is_initialized() {
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    let mut info = MintInfo {
+        decimals: base.decimals,
+        supply: base.supply,
+        transfer_fee: None,
+    };
+    for (extension_type, value) in extensions(&data, ACCOUNT_TYPE_MINT)? {
+        if !SAFE_MINT_EXTENSIONS.contains(&extension_type) {
+            return Err(EscrowError::UnsupportedMintExtension.into());
+        }
+        if extension_type == EXTENSION_TRANSFER_FEE_CONFIG {
+            // 2 authorities and the withheld amount come before the two fee schedules
+            if value.len() < 108 {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let (older, newer) = array_refs![array_ref![value, 72, 36], 18, 18];
+            info.transfer_fee = Some((TransferFee::unpack(older), TransferFee::unpack(newer)));
+        }
+    }
+    Ok(info)
+}
+
+// ----------------------------------------------------------------------------- instructions
+// same encodings in both programs
+
+pub fn transfer_checked(
+    token_program_id: &Pubkey,
+    source: &Pubkey,
+    mint: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    check_program_id(token_program_id)?;
+    let mut data = vec![12];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(decimals);
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*source, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data,
+    })
+}
+
+/// Hands the `AccountOwner` authority of `account` over to `new_owner`
+pub fn set_owner(
+    token_program_id: &Pubkey,
+    account: &Pubkey,
+    new_owner: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    check_program_id(token_program_id)?;
+    let mut data = vec![
+        6,
+        spl_token::instruction::AuthorityType::AccountOwner as u8,
+        1,
+    ];
+    data.extend_from_slice(new_owner.as_ref());
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*account, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+        data,
+    })
+}
+
+/// Lets `delegate` move up to `amount` out of `source`
+pub fn approve(
+    token_program_id: &Pubkey,
+    source: &Pubkey,
+    delegate: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    check_program_id(token_program_id)?;
+    let mut data = vec![4];
+    data.extend_from_slice(&amount.to_le_bytes());
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*source, false),
+            AccountMeta::new_readonly(*delegate, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+        data,
+    })
+}
+
+/// Takes back whatever `approve` handed out
+pub fn revoke(
+    token_program_id: &Pubkey,
+    source: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    check_program_id(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*source, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+        data: vec![5],
+    })
+}
+
+pub fn close_account(
+    token_program_id: &Pubkey,
+    account: &Pubkey,
+    destination: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    check_program_id(token_program_id)?;
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts: vec![
+            AccountMeta::new(*account, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+        data: vec![9],
+    })
+}
+
+/// Token-2022 only: moves withheld transfer fees from `accounts` into the mint. Permissionless.
+pub fn harvest_withheld_tokens_to_mint(mint: &Pubkey, accounts: &[&Pubkey]) -> Instruction {
+    let mut metas = vec![AccountMeta::new(*mint, false)];
+    metas.extend(accounts.iter().map(|a| AccountMeta::new(**a, false)));
+    Instruction {
+        program_id: token_2022::id(),
+        accounts: metas,
+        // TransferFeeExtension, HarvestWithheldTokensToMint
+        data: vec![26, 4],
+    }
+}
+
+fn check_program_id(token_program_id: &Pubkey) -> ProgramResult {
+    if !is_token_program(token_program_id) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
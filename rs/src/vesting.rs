@@ -0,0 +1,28 @@
+// the unlock schedule of a vesting vault. Like the auction curves, the math is done in u128 so the products can't overflow
+use solana_program::entrypoint::ProgramResult;
+
+use crate::{error::EscrowError, state::Vesting};
+
+/// Rejects a schedule that ends before it starts, or whose cliff is outside of it
+pub fn check_schedule(start: i64, cliff: i64, end: i64) -> ProgramResult {
+    if end <= start || cliff < start || cliff > end {
+        return Err(EscrowError::InvalidSchedule.into());
+    }
+    Ok(())
+}
+
+/// How much of `total_amount` has vested at `now`: nothing before the cliff, all of it from the end on, and in between
+/// the straight line from start to end. Rounds down, the last bit unlocks at the end
+pub fn vested(vesting: &Vesting, now: i64) -> u64 {
+    if now < vesting.cliff {
+        return 0;
+    }
+    if now >= vesting.end {
+        return vesting.total_amount;
+    }
+    // start <= cliff <= now < end, so both are positive and fit in a u64
+    let elapsed = (now as i128 - vesting.start as i128) as u128;
+    let duration = (vesting.end as i128 - vesting.start as i128) as u128;
+    // elapsed < duration, so this is below total_amount
+    (vesting.total_amount as u128 * elapsed / duration) as u64
+}
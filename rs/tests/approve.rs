@@ -0,0 +1,135 @@
+mod common;
+
+use solana_program::{program_error::ProgramError, program_option::COption};
+
+use bpf_program_template::state::{Escrow, EscrowMode};
+
+use common::{token_2022, EscrowFixture, ProgramTest};
+
+#[test]
+fn exchange_pulls_through_the_delegation() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 5_000);
+    test.process(&f.init_approve_escrow(&test, 500, 400))
+        .unwrap();
+
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(escrow.mode, EscrowMode::Approve);
+    assert_eq!(escrow.offered_amount, 400);
+    let account = test.token_account(&f.temp_x).unwrap();
+    assert_eq!(account.owner, f.initializer);
+    assert_eq!(account.delegate, COption::Some(test.pda().0));
+    assert_eq!(account.delegated_amount, 400);
+
+    let rent = test.lamports(&f.escrow);
+    let initializer_lamports = test.lamports(&f.initializer);
+    test.process(&f.exchange(&test, 400)).unwrap();
+
+    assert_eq!(test.token_balance(&f.taker_x), 400);
+    assert_eq!(test.token_balance(&f.temp_x), 600);
+    assert_eq!(test.token_balance(&f.initializer_y), 500);
+    assert_eq!(test.token_balance(&f.taker_y), 4_500);
+    // alice's account stays open, only the escrow's rent comes back
+    assert_eq!(
+        test.token_account(&f.temp_x).unwrap().delegate,
+        COption::None
+    );
+    assert_eq!(test.lamports(&f.escrow), 0);
+    assert_eq!(test.lamports(&f.initializer), initializer_lamports + rent);
+}
+
+#[test]
+fn exchange_expects_the_offered_amount_not_the_balance() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 5_000);
+    test.process(&f.init_approve_escrow(&test, 500, 400))
+        .unwrap();
+
+    assert!(test.process(&f.exchange(&test, 1_000)).is_err());
+}
+
+#[test]
+fn cancel_revokes_the_delegation() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 5_000);
+    test.process(&f.init_approve_escrow(&test, 500, 400))
+        .unwrap();
+    test.process(&f.cancel(&test)).unwrap();
+
+    let account = test.token_account(&f.temp_x).unwrap();
+    assert_eq!(account.amount, 1_000);
+    assert_eq!(account.delegate, COption::None);
+    assert_eq!(account.delegated_amount, 0);
+    assert_eq!(test.lamports(&f.escrow), 0);
+}
+
+#[test]
+fn cannot_list_someone_elses_account() {
+    let mut test = ProgramTest::new();
+    let alice = EscrowFixture::new(&mut test, 1_000, 5_000);
+    test.process(&alice.init_approve_escrow(&test, 500, 400))
+        .unwrap();
+
+    // alice's account is already delegated to the PDA, mallory tries to sell it for next to nothing
+    let mallory = test.add_wallet(1_000_000_000);
+    let mallory_listing = EscrowFixture {
+        initializer: mallory,
+        initializer_y: test.add_token_account(&alice.mint_y, &mallory, 0),
+        escrow: test.add_program_account(Escrow::len(EscrowMode::Approve)),
+        ..alice
+    };
+    assert_eq!(
+        test.process(&mallory_listing.init_approve_escrow(&test, 1, 400)),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn cannot_offer_more_than_the_balance() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 5_000);
+
+    assert_eq!(
+        test.process(&f.init_approve_escrow(&test, 500, 1_001)),
+        Err(ProgramError::InsufficientFunds)
+    );
+    assert_eq!(
+        test.process(&f.init_approve_escrow(&test, 500, 0)),
+        Err(ProgramError::InsufficientFunds)
+    );
+}
+
+#[test]
+fn spent_delegation_fails_the_exchange() {
+    let mut test = ProgramTest::new();
+    let first = EscrowFixture::new(&mut test, 1_000, 5_000);
+    let second = EscrowFixture {
+        escrow: test.add_program_account(Escrow::len(EscrowMode::Approve)),
+        ..first
+    };
+    // a token account has a single delegate, so the second listing replaces the first one's allowance
+    test.process(&first.init_approve_escrow(&test, 500, 400))
+        .unwrap();
+    test.process(&second.init_approve_escrow(&test, 200, 300))
+        .unwrap();
+    test.process(&second.exchange(&test, 300)).unwrap();
+
+    assert!(test.process(&first.exchange(&test, 400)).is_err());
+    assert_eq!(test.token_balance(&first.temp_x), 700);
+    assert_eq!(test.token_balance(&first.taker_y), 4_800);
+}
+
+#[test]
+fn token_2022_fees_come_out_of_the_offered_amount() {
+    let mut test = ProgramTest::new();
+    let mint_x = test.add_mint_2022(0, &[token_2022::transfer_fee_config(100, u64::MAX)]);
+    let mint_y = test.add_mint(0);
+    let f = EscrowFixture::for_pair(&mut test, mint_x, mint_y, 1_000, 5_000);
+    test.process(&f.init_approve_escrow(&test, 500, 400))
+        .unwrap();
+
+    // 1% of 400
+    test.process(&f.exchange(&test, 396)).unwrap();
+    assert_eq!(test.token_balance(&f.taker_x), 396);
+    assert_eq!(test.token_balance(&f.temp_x), 600);
+}
@@ -0,0 +1,556 @@
+// fuzz scenarios, shared by the cargo-fuzz targets in fuzz/ and the proptest runs in tests/fuzz.rs
+// each one takes raw bytes and panics if the program misbehaves
+
+use solana_program::{
+    clock::Clock,
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar,
+};
+
+use bpf_program_template::{
+    instruction::EscrowInstruction,
+    merkle,
+    state::{
+        ClaimedBitmap, Contribution, Distribution, Escrow, EscrowMode, Pool, StandingOrder, Stream,
+        Vesting,
+    },
+};
+
+use super::{Account, EscrowFixture, PoolAccounts, ProgramTest, StandingOrderAccounts};
+
+/// Decoding arbitrary bytes must never panic
+pub fn unpack_instruction(data: &[u8]) {
+    let _ = EscrowInstruction::unpack(data);
+}
+
+// fixed, so the seeds can carry the right bump seed
+fn program_id() -> Pubkey {
+    Pubkey::new_from_array([7; 32])
+}
+
+/// How many accounts the fuzzer picks from, see `World::new`
+pub const POOL_LEN: usize = 35;
+
+// everything the fuzzer can put into the account list
+struct World {
+    test: ProgramTest,
+    alice: EscrowFixture,
+    mallory: EscrowFixture,
+    pool: Vec<Pubkey>,
+    wallets: Vec<Pubkey>,
+    // alice's payouts to the taker, each out of its own X account in `temp_x`
+    vesting: EscrowFixture,
+    stream: EscrowFixture,
+    distribution: EscrowFixture,
+    bitmap: Pubkey,
+    // the taker's ways of taking alice's listing
+    pool_accounts: PoolAccounts,
+    contribution: Pubkey,
+    standing_order: StandingOrderAccounts,
+    // vaults that pay out without anything coming back, and the wallets they may pay
+    payouts: Vec<(Pubkey, [Pubkey; 2])>,
+}
+
+impl World {
+    fn new(flags: u8) -> Self {
+        let mut test = ProgramTest::with_program_id(program_id());
+        let alice = EscrowFixture::new(&mut test, 1_000, 5_000);
+        // mallory trades the same pair, so every account of hers is a plausible substitute for one of alice's
+        let mut mallory = EscrowFixture::for_pair(&mut test, alice.mint_x, alice.mint_y, 700, 0);
+        mallory.taker = alice.taker;
+        mallory.taker_x = alice.taker_x;
+        mallory.taker_y = alice.taker_y;
+
+        // alice also pays the taker out of three more X accounts of hers: a vesting, a stream and a distribution
+        let vesting = EscrowFixture {
+            temp_x: test.add_token_account(&alice.mint_x, &alice.initializer, 300),
+            escrow: test.add_program_account(Vesting::LEN),
+            ..alice
+        };
+        let stream = EscrowFixture {
+            temp_x: test.add_token_account(&alice.mint_x, &alice.initializer, 200),
+            escrow: test.add_program_account(Stream::LEN),
+            ..alice
+        };
+        let distribution = EscrowFixture {
+            temp_x: test.add_token_account(&alice.mint_x, &alice.initializer, 100),
+            escrow: test.add_program_account(Distribution::LEN),
+            ..alice
+        };
+        let bitmap = test.add_program_account(ClaimedBitmap::len(1));
+        // the taker pools Y to take alice's listing, and has a standing order out to take it a tranche at a time
+        let pool_accounts = PoolAccounts {
+            pool: test.add_program_account(Pool::LEN),
+            creator: alice.taker,
+            x_vault: test.add_token_account(&alice.mint_x, &alice.taker, 0),
+            y_vault: test.add_token_account(&alice.mint_y, &alice.taker, 0),
+        };
+        let contribution = test.add_program_account(Contribution::LEN);
+        let standing_order = StandingOrderAccounts {
+            standing_order: test.add_program_account(StandingOrder::LEN),
+            owner: alice.taker,
+            vault: test.add_token_account(&alice.mint_y, &alice.taker, 510),
+            owner_x: alice.taker_x,
+            owner_y: alice.taker_y,
+            keeper: mallory.initializer,
+            keeper_y: mallory.initializer_y,
+        };
+
+        if flags & 1 != 0 {
+            test.process(&alice.init_escrow(&test, 500)).unwrap();
+        }
+        if flags & 2 != 0 {
+            test.process(&mallory.init_escrow(&test, 1)).unwrap();
+        }
+        if flags & 4 != 0 {
+            let ix = vesting.init_vesting(
+                &test,
+                vesting.escrow,
+                alice.taker,
+                Some(alice.initializer),
+                0,
+                0,
+                100,
+            );
+            test.process(&ix).unwrap();
+        }
+        if flags & 8 != 0 {
+            test.process(&stream.init_stream(&test, stream.escrow, alice.taker, 2, 100))
+                .unwrap();
+        }
+        if flags & 0x10 != 0 {
+            let root = merkle::leaf(0, &alice.taker, 100);
+            let ix =
+                distribution.init_distribution(&test, distribution.escrow, bitmap, root, 1, 1_000);
+            test.process(&ix).unwrap();
+        }
+        // the pool needs alice's listing
+        if flags & 0x21 == 0x21 {
+            test.process(&alice.init_pool(&test, &pool_accounts, 1_000))
+                .unwrap();
+            if flags & 0x80 != 0 {
+                let ix = alice.contribute(
+                    &test,
+                    &pool_accounts,
+                    alice.taker,
+                    alice.taker_y,
+                    contribution,
+                    500,
+                );
+                test.process(&ix).unwrap();
+            }
+        }
+        if flags & 0x40 != 0 {
+            let ix = alice.init_standing_order(&test, &standing_order, 500, 1_000, 100, 10);
+            test.process(&ix).unwrap();
+        }
+        // halfway through the vesting and the stream
+        test.set_clock(Clock {
+            unix_timestamp: 50,
+            ..Clock::default()
+        });
+
+        // copies of accounts the program trusts, under another owner
+        let other_program = Pubkey::new_unique();
+        let foreign = |test: &mut ProgramTest, key: Pubkey| {
+            let account = Account {
+                owner: other_program,
+                ..test.account(&key).clone()
+            };
+            test.add_account(Pubkey::new_unique(), account)
+        };
+        let foreign_escrow = foreign(&mut test, alice.escrow);
+        let foreign_vesting = foreign(&mut test, vesting.escrow);
+        let foreign_temp = foreign(&mut test, alice.temp_x);
+
+        let fresh_escrow = test.add_program_account(Escrow::len(EscrowMode::Vault));
+        let pool = vec![
+            alice.initializer,
+            alice.temp_x,
+            alice.initializer_x,
+            alice.initializer_y,
+            alice.taker,
+            alice.taker_x,
+            alice.taker_y,
+            alice.escrow,
+            test.pda().0,
+            spl_token::id(),
+            sysvar::rent::id(),
+            mallory.initializer,
+            mallory.temp_x,
+            mallory.initializer_y,
+            mallory.escrow,
+            fresh_escrow,
+            alice.mint_x,
+            alice.mint_y,
+            bpf_program_template::token::token_2022::id(),
+            vesting.escrow,
+            vesting.temp_x,
+            stream.escrow,
+            stream.temp_x,
+            distribution.escrow,
+            bitmap,
+            distribution.temp_x,
+            pool_accounts.pool,
+            pool_accounts.x_vault,
+            pool_accounts.y_vault,
+            contribution,
+            standing_order.standing_order,
+            standing_order.vault,
+            foreign_escrow,
+            foreign_vesting,
+            foreign_temp,
+        ];
+        assert_eq!(pool.len(), POOL_LEN);
+        let wallets = vec![alice.initializer, alice.taker, mallory.initializer];
+        let parties = [alice.initializer, alice.taker];
+        let payouts = vec![
+            (vesting.temp_x, parties),
+            (stream.temp_x, parties),
+            (distribution.temp_x, parties),
+        ];
+        Self {
+            test,
+            alice,
+            mallory,
+            pool,
+            wallets,
+            vesting,
+            stream,
+            distribution,
+            bitmap,
+            pool_accounts,
+            contribution,
+            standing_order,
+            payouts,
+        }
+    }
+
+    // what `wallets` hold of `mint`, outside the payout vaults
+    fn held_by(&self, wallets: &[Pubkey], mint: &Pubkey) -> u128 {
+        self.test
+            .accounts
+            .keys()
+            .filter(|k| self.payouts.iter().all(|(vault, _)| vault != *k))
+            .filter_map(|k| self.test.token_account(k))
+            .filter(|a| a.mint == *mint && wallets.contains(&a.owner))
+            .map(|a| a.amount as u128)
+            .sum()
+    }
+
+    fn total(&self, mint: &Pubkey) -> u128 {
+        self.test
+            .accounts
+            .keys()
+            .filter_map(|k| self.test.token_account(k))
+            .filter(|a| a.mint == *mint)
+            .map(|a| a.amount as u128)
+            .sum()
+    }
+}
+
+const S: u8 = 0x40;
+const W: u8 = 0x80;
+
+/// Well-formed inputs for `process`, one per instruction, used to seed the fuzzers
+pub fn seeds() -> Vec<Vec<u8>> {
+    let with_amount = |tag: u8, amount: u64| {
+        let mut data = vec![tag];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data
+    };
+    let seed = |flags: u8, accounts: &[u8], data: Vec<u8>| {
+        let mut input = vec![flags, accounts.len() as u8];
+        input.extend_from_slice(accounts);
+        input.extend(data);
+        input
+    };
+    let bump_seed = Pubkey::find_program_address(&[b"escrow"], &program_id()).1;
+    vec![
+        // alice lists 1000 X for 500 Y
+        seed(0, &[S, 1 | W, 3, 7 | W, 10, 9, 16, 17], with_amount(0, 500)),
+        // alice lists 400 X for 500 Y out of her own account, approving the PDA
+        seed(0, &[S, 1 | W, 3, 7 | W, 10, 9, 16, 17, 8], {
+            let mut data = with_amount(3, 500);
+            data.extend_from_slice(&400u64.to_le_bytes());
+            data
+        }),
+        // bob takes it
+        seed(
+            1,
+            &[
+                4 | S,
+                6 | W,
+                5 | W,
+                1 | W,
+                W,
+                3 | W,
+                7 | W,
+                9,
+                8,
+                16 | W,
+                17,
+                9,
+            ],
+            with_amount(1, 1_000),
+        ),
+        // alice cancels
+        seed(
+            1,
+            &[S | W, 9, 1 | W, 2 | W, 7 | W, 8, 16 | W],
+            vec![2, bump_seed],
+        ),
+        // bob takes mallory's listing
+        seed(
+            2,
+            &[
+                4 | S,
+                6 | W,
+                5 | W,
+                12 | W,
+                11 | W,
+                13 | W,
+                14 | W,
+                9,
+                8,
+                16 | W,
+                17,
+                9,
+            ],
+            with_amount(1, 700),
+        ),
+        // alice vests 300 X to the taker, who withdraws what has vested, then alice revokes the rest
+        from_instruction(0, |w| {
+            w.vesting.init_vesting(
+                &w.test,
+                w.vesting.escrow,
+                w.alice.taker,
+                Some(w.alice.initializer),
+                0,
+                0,
+                100,
+            )
+        }),
+        from_instruction(4, |w| {
+            w.vesting
+                .withdraw(&w.test, w.vesting.escrow, w.alice.taker, w.alice.taker_x)
+        }),
+        from_instruction(4, |w| {
+            w.vesting.revoke_vesting(
+                &w.test,
+                w.vesting.escrow,
+                w.alice.initializer,
+                w.alice.initializer_x,
+            )
+        }),
+        // alice streams 2 X a second to the taker for 100s, who withdraws, then alice cancels
+        from_instruction(0, |w| {
+            w.stream
+                .init_stream(&w.test, w.stream.escrow, w.alice.taker, 2, 100)
+        }),
+        from_instruction(8, |w| {
+            w.stream
+                .withdraw(&w.test, w.stream.escrow, w.alice.taker, w.alice.taker_x)
+        }),
+        from_instruction(8, |w| {
+            w.stream
+                .cancel_stream(&w.test, w.stream.escrow, w.alice.taker_x)
+        }),
+        // alice airdrops 100 X to the taker, who claims it
+        from_instruction(0, |w| {
+            let root = merkle::leaf(0, &w.alice.taker, 100);
+            w.distribution.init_distribution(
+                &w.test,
+                w.distribution.escrow,
+                w.bitmap,
+                root,
+                1,
+                1_000,
+            )
+        }),
+        from_instruction(0x10, |w| {
+            w.distribution.claim_distribution(
+                &w.test,
+                w.distribution.escrow,
+                w.bitmap,
+                w.alice.taker_x,
+                0,
+                100,
+                &[],
+            )
+        }),
+        // the taker pools Y for alice's listing, puts 500 in, and takes it
+        from_instruction(1, |w| w.alice.init_pool(&w.test, &w.pool_accounts, 1_000)),
+        from_instruction(0x21, |w| {
+            w.alice.contribute(
+                &w.test,
+                &w.pool_accounts,
+                w.alice.taker,
+                w.alice.taker_y,
+                w.contribution,
+                500,
+            )
+        }),
+        from_instruction(0xa1, |w| w.alice.execute_pool(&w.test, &w.pool_accounts)),
+        // the taker sets up a standing order on alice's listing, mallory keeps it, then the taker cancels
+        from_instruction(1, |w| {
+            w.alice
+                .init_standing_order(&w.test, &w.standing_order, 500, 1_000, 100, 10)
+        }),
+        from_instruction(0x41, |w| {
+            w.alice.execute_tranche(&w.test, &w.standing_order)
+        }),
+        from_instruction(0x40, |w| {
+            w.alice.cancel_standing_order(&w.test, &w.standing_order)
+        }),
+    ]
+}
+
+// the `process` input for an instruction built against the world `flags` sets up
+fn from_instruction(flags: u8, build: impl Fn(&World) -> Instruction) -> Vec<u8> {
+    let world = World::new(flags);
+    let instruction = build(&world);
+    let mut input = vec![flags, instruction.accounts.len() as u8];
+    input.extend(instruction.accounts.iter().map(|m| {
+        let index = world
+            .pool
+            .iter()
+            .position(|k| *k == m.pubkey)
+            .expect("account missing from the pool") as u8;
+        index | if m.is_signer { S } else { 0 } | if m.is_writable { W } else { 0 }
+    }));
+    input.extend(instruction.data);
+    input
+}
+
+/// Runs arbitrary instruction data against an arbitrary account list, returns whether the program accepted it.
+///
+/// Layout: `[flags, n_accounts, account bytes.., instruction data..]`. Bit 0/1 of flags pre-initialize
+/// alice's/mallory's escrow, bits 2-4 alice's vesting/stream/distribution, bit 5 the taker's pool on alice's
+/// listing (which bit 7 puts 500 Y into) and bit 6 the taker's standing order. The low 6 bits of each account
+/// byte pick from the pool, bit 6 asks for a signature (only honoured for wallets, nobody can sign for a PDA or a
+/// token account) and bit 7 makes it writable.
+pub fn process(data: &[u8]) -> bool {
+    let (flags, rest) = match data.split_first() {
+        Some(split) => split,
+        None => return false,
+    };
+    let (n_accounts, rest) = match rest.split_first() {
+        Some((n, rest)) => ((*n as usize % 17).min(rest.len()), rest),
+        None => return false,
+    };
+    let (account_bytes, instruction_data) = rest.split_at(n_accounts);
+
+    let mut world = World::new(*flags);
+    let accounts = account_bytes
+        .iter()
+        .map(|b| {
+            let pubkey = world.pool[(*b & 0x3f) as usize % world.pool.len()];
+            AccountMeta {
+                pubkey,
+                is_signer: *b & 0x40 != 0 && world.wallets.contains(&pubkey),
+                is_writable: *b & 0x80 != 0,
+            }
+        })
+        .collect::<Vec<_>>();
+    let instruction = world.test.instruction(instruction_data.to_vec(), accounts);
+
+    let escrows_before = [&world.alice, &world.mallory]
+        .iter()
+        .map(|f| {
+            let escrow = Escrow::unpack(&world.test.account(&f.escrow).data).ok();
+            (
+                escrow,
+                world.test.token_balance(&f.temp_x),
+                world.test.token_balance(&f.initializer_y),
+            )
+        })
+        .collect::<Vec<_>>();
+    let payouts_before = world
+        .payouts
+        .iter()
+        .map(|(vault, parties)| {
+            (
+                world.test.token_balance(vault),
+                world.held_by(parties, &world.alice.mint_x),
+            )
+        })
+        .collect::<Vec<_>>();
+    let x_before = world.total(&world.alice.mint_x);
+    let y_before = world.total(&world.alice.mint_y);
+
+    if world.test.process(&instruction).is_err() {
+        return false;
+    }
+
+    assert_eq!(
+        x_before,
+        world.total(&world.alice.mint_x),
+        "X minted or burned"
+    );
+    assert_eq!(
+        y_before,
+        world.total(&world.alice.mint_y),
+        "Y minted or burned"
+    );
+
+    for (fixture, (escrow, vault_before, paid_before)) in
+        [&world.alice, &world.mallory].iter().zip(escrows_before)
+    {
+        let vault_after = world.test.token_balance(&fixture.temp_x);
+        if vault_after >= vault_before {
+            continue;
+        }
+        let initializer_signed = instruction
+            .accounts
+            .iter()
+            .any(|m| m.pubkey == fixture.initializer && m.is_signer);
+        if initializer_signed {
+            continue;
+        }
+        // tokens left a vault without the initializer's say-so - only ok if they got paid in full
+        let expected = escrow
+            .filter(|e| e.is_initialized && e.temp_token_account_pubkey == fixture.temp_x)
+            .map(|e| e.expected_amount)
+            .expect("vault drained without an escrow");
+        let paid = world
+            .test
+            .token_balance(&fixture.initializer_y)
+            .saturating_sub(paid_before);
+        assert!(
+            paid >= expected,
+            "vault drained for {} of {}",
+            paid,
+            expected
+        );
+    }
+    // the pool's and the standing order's vaults only ever pay out against Y going the other way, which the
+    // conservation checks above and the escrow checks cover
+    for ((vault, parties), (vault_before, held_before)) in world.payouts.iter().zip(payouts_before)
+    {
+        let drop = vault_before.saturating_sub(world.test.token_balance(vault));
+        if drop == 0 {
+            continue;
+        }
+        let party_signed = instruction
+            .accounts
+            .iter()
+            .any(|m| parties.contains(&m.pubkey) && m.is_signer);
+        if party_signed {
+            continue;
+        }
+        // nobody asked for it, so it has to have gone to alice or the taker
+        let gained = world
+            .held_by(parties, &world.alice.mint_x)
+            .saturating_sub(held_before);
+        assert!(
+            gained >= drop as u128,
+            "payout vault lost {} but its parties gained {}",
+            drop,
+            gained
+        );
+    }
+    true
+}
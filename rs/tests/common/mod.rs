@@ -0,0 +1,2090 @@
+// in-process harness shared by the integration tests and the fuzz targets in fuzz/
+// runs the escrow processor against in-memory accounts and routes its CPIs into the real spl-token processor,
+// so we get the same account semantics as the runtime without needing a validator
+#![allow(dead_code)]
+
+pub mod fuzz;
+pub mod token_2022;
+
+use std::{cell::RefCell, collections::HashMap, convert::TryInto, sync::Once};
+
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    message::Message,
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+    sysvar::{self, Sysvar},
+};
+
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+
+use bpf_program_template::{
+    metadata::{metadata_address, metadata_program, Metadata},
+    order::{delegate_address, ed25519_program, nonce_bitmap_address, Order},
+    processor::Processor,
+    state::NonceBitmap,
+    state::Request,
+    state::{Escrow, EscrowMode},
+};
+
+/// Returned when the escrow program breaks a rule the runtime would enforce (eg writing to an account it doesn't own)
+pub const RUNTIME_VIOLATION: u32 = u32::MAX;
+
+// owner, lamports and data
+type Seen = (Pubkey, u64, Vec<u8>);
+
+thread_local! {
+    // the program currently executing at the top level - PDAs in invoke_signed are derived from it
+    static CALLER: RefCell<Pubkey> = RefCell::new(Pubkey::default());
+    static CLOCK: RefCell<Clock> = RefCell::new(Clock::default());
+    // every account as the runtime last saw it, used to catch illegal writes across CPIs
+    static SNAPSHOT: RefCell<HashMap<Pubkey, Seen>> = RefCell::new(HashMap::new());
+    // owners handed out by the system program stub during the current instruction. AccountInfo has no way to change
+    // its owner, so until the instruction is done, this is where the new one lives
+    static ASSIGNED: RefCell<HashMap<Pubkey, Pubkey>> = RefCell::new(HashMap::new());
+    // what the last instruction logged
+    static LOGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+struct HarnessStubs;
+
+impl SyscallStubs for HarnessStubs {
+    fn sol_log(&self, message: &str) {
+        // the processor logs a lot, keep test output readable - tests look at them through ProgramTest::logs
+        LOGS.with(|l| l.borrow_mut().push(message.to_string()));
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let caller = CALLER.with(|c| *c.borrow());
+        let signers = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &caller))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| ProgramError::InvalidSeeds)?;
+
+        // It's a rule that the program being called through a CPI must be passed in as an account
+        if !account_infos
+            .iter()
+            .any(|a| *a.key == instruction.program_id)
+        {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+
+        let mut infos = Vec::with_capacity(instruction.accounts.len());
+        for meta in instruction.accounts.iter() {
+            let mut info = account_infos
+                .iter()
+                .find(|a| *a.key == meta.pubkey)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?
+                .clone();
+            // the runtime merges the privileges of repeated keys
+            let (is_signer, is_writable) = instruction
+                .accounts
+                .iter()
+                .filter(|m| m.pubkey == meta.pubkey)
+                .fold((false, false), |(s, w), m| {
+                    (s || m.is_signer, w || m.is_writable)
+                });
+            // no privilege escalation: signatures only come from the caller or its PDAs
+            if is_signer && !info.is_signer && !signers.contains(&meta.pubkey) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if is_writable && !info.is_writable {
+                return Err(ProgramError::Custom(RUNTIME_VIOLATION));
+            }
+            info.is_signer = is_signer;
+            info.is_writable = is_writable;
+            infos.push(info);
+        }
+
+        verify_writes(&caller, account_infos)?;
+        take_snapshot(account_infos);
+        if instruction.program_id == spl_token::id() {
+            spl_token::processor::Processor::process(
+                &instruction.program_id,
+                &infos,
+                &instruction.data,
+            )?;
+        } else if instruction.program_id == token_2022::id() {
+            let epoch = CLOCK.with(|c| c.borrow().epoch);
+            token_2022::process(&infos, &instruction.data, epoch)?;
+        } else if instruction.program_id == system_program::id() {
+            system_program_stub(&infos, &instruction.data)?;
+        } else {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        // the callee is held to the same rules
+        verify_writes(&instruction.program_id, account_infos)?;
+        take_snapshot(account_infos);
+        Ok(())
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = CLOCK.with(|c| c.borrow().clone());
+        unsafe { *(var_addr as *mut Clock) = clock };
+        solana_program::entrypoint::SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        solana_program::entrypoint::SUCCESS
+    }
+}
+
+fn owner(info: &AccountInfo) -> Pubkey {
+    ASSIGNED.with(|a| a.borrow().get(info.key).copied().unwrap_or(*info.owner))
+}
+
+fn take_snapshot(account_infos: &[AccountInfo]) {
+    SNAPSHOT.with(|s| {
+        let mut s = s.borrow_mut();
+        for info in account_infos {
+            s.insert(
+                *info.key,
+                (owner(info), info.lamports(), info.data.borrow().to_vec()),
+            );
+        }
+    });
+}
+
+// a program may only change the data of, and debit, accounts it owned going in. Read-only accounts may not change at all.
+fn verify_writes(program_id: &Pubkey, account_infos: &[AccountInfo]) -> ProgramResult {
+    SNAPSHOT.with(|s| {
+        let s = s.borrow();
+        for info in account_infos {
+            let (owner, lamports, data) = match s.get(info.key) {
+                Some(seen) => seen,
+                None => continue,
+            };
+            let changed = info.lamports() != *lamports || **info.data.borrow() != data[..];
+            let owned = owner == program_id;
+            if (changed && !info.is_writable)
+                || (!owned && (info.lamports() < *lamports || **info.data.borrow() != data[..]))
+            {
+                return Err(ProgramError::Custom(RUNTIME_VIOLATION));
+            }
+        }
+        Ok(())
+    })
+}
+
+// just the system instructions the escrow program sends: CreateAccount, Assign, Transfer and Allocate, on accounts
+// that are new - no lamports or data yet, or only lamports
+fn system_program_stub(infos: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    // bincode: a u32 variant, then the fields as they are
+    let u64_at = |at: usize| {
+        data.get(at..at + 8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+            .ok_or(ProgramError::InvalidInstructionData)
+    };
+    let pubkey_at = |at: usize| {
+        data.get(at..at + 32)
+            .map(Pubkey::new)
+            .ok_or(ProgramError::InvalidInstructionData)
+    };
+    let variant = u32::from_le_bytes(
+        data.get(..4)
+            .ok_or(ProgramError::InvalidInstructionData)?
+            .try_into()
+            .unwrap(),
+    );
+    let account = |i: usize| -> Result<&AccountInfo, ProgramError> {
+        let info = infos.get(i).ok_or(ProgramError::NotEnoughAccountKeys)?;
+        if !info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if owner(info) != system_program::id() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        Ok(info)
+    };
+    let transfer = |from: &AccountInfo, to: &AccountInfo, lamports: u64| -> ProgramResult {
+        let left = from
+            .lamports()
+            .checked_sub(lamports)
+            .ok_or(ProgramError::InsufficientFunds)?;
+        **from.lamports.borrow_mut() = left;
+        **to.lamports.borrow_mut() += lamports;
+        Ok(())
+    };
+    let allocate = |info: &AccountInfo, space: u64| -> ProgramResult {
+        if !info.data_is_empty() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        // every clone of the AccountInfo shares this RefCell, so they all see the new data
+        *info.data.borrow_mut() = Box::leak(vec![0; space as usize].into_boxed_slice());
+        Ok(())
+    };
+    let assign = |info: &AccountInfo, owner: Pubkey| {
+        ASSIGNED.with(|a| a.borrow_mut().insert(*info.key, owner));
+    };
+
+    match variant {
+        0 => {
+            let (from, to) = (account(0)?, account(1)?);
+            if to.lamports() != 0 {
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
+            transfer(from, to, u64_at(4)?)?;
+            allocate(to, u64_at(12)?)?;
+            assign(to, pubkey_at(20)?);
+        }
+        1 => assign(account(0)?, pubkey_at(4)?),
+        2 => transfer(
+            account(0)?,
+            infos.get(1).ok_or(ProgramError::NotEnoughAccountKeys)?,
+            u64_at(4)?,
+        )?,
+        8 => allocate(account(0)?, u64_at(4)?)?,
+        _ => return Err(ProgramError::InvalidInstructionData),
+    }
+    Ok(())
+}
+
+fn install_stubs() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        set_syscall_stubs(Box::new(HarnessStubs));
+    });
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Account {
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub executable: bool,
+}
+
+/// A tiny bank: a set of accounts plus the escrow program, processing one instruction at a time.
+/// Like the runtime, a failed instruction leaves every account untouched.
+pub struct ProgramTest {
+    pub program_id: Pubkey,
+    pub accounts: HashMap<Pubkey, Account>,
+}
+
+impl Default for ProgramTest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgramTest {
+    pub fn new() -> Self {
+        Self::with_program_id(Pubkey::new_unique())
+    }
+
+    pub fn with_program_id(program_id: Pubkey) -> Self {
+        install_stubs();
+        CLOCK.with(|c| *c.borrow_mut() = Clock::default());
+        let mut test = Self {
+            program_id,
+            accounts: HashMap::new(),
+        };
+        for token_program in [spl_token::id(), token_2022::id()].iter() {
+            test.accounts.insert(
+                *token_program,
+                Account {
+                    owner: solana_program::bpf_loader::id(),
+                    lamports: 1,
+                    executable: true,
+                    ..Account::default()
+                },
+            );
+        }
+        let mut rent = Account {
+            owner: sysvar::id(),
+            lamports: 1,
+            data: vec![0; Rent::size_of()],
+            executable: false,
+        };
+        {
+            let key = sysvar::rent::id();
+            let mut info = AccountInfo::new(
+                &key,
+                false,
+                false,
+                &mut rent.lamports,
+                &mut rent.data,
+                &rent.owner,
+                false,
+                0,
+            );
+            Rent::default().to_account_info(&mut info).unwrap();
+        }
+        test.accounts.insert(sysvar::rent::id(), rent);
+        test
+    }
+
+    pub fn set_clock(&self, clock: Clock) {
+        CLOCK.with(|c| *c.borrow_mut() = clock);
+    }
+
+    pub fn add_account(&mut self, key: Pubkey, account: Account) -> Pubkey {
+        self.accounts.insert(key, account);
+        key
+    }
+
+    /// A plain wallet, owned by the system program
+    pub fn add_wallet(&mut self, lamports: u64) -> Pubkey {
+        self.add_account(
+            Pubkey::new_unique(),
+            Account {
+                owner: system_program::id(),
+                lamports,
+                ..Account::default()
+            },
+        )
+    }
+
+    /// An empty, rent-exempt account owned by the escrow program, the way a client would create one before InitEscrow
+    pub fn add_program_account(&mut self, len: usize) -> Pubkey {
+        let owner = self.program_id;
+        self.add_account(
+            Pubkey::new_unique(),
+            Account {
+                owner,
+                lamports: Rent::default().minimum_balance(len),
+                data: vec![0; len],
+                executable: false,
+            },
+        )
+    }
+
+    /// A copy of `key` under another owner, to pass off as one of the program's own accounts
+    pub fn add_foreign_copy(&mut self, key: &Pubkey) -> Pubkey {
+        let account = Account {
+            owner: Pubkey::new_unique(),
+            ..self.account(key).clone()
+        };
+        self.add_account(Pubkey::new_unique(), account)
+    }
+
+    fn mint(decimals: u8) -> Mint {
+        Mint {
+            mint_authority: COption::Some(Pubkey::new_unique()),
+            supply: u64::MAX,
+            decimals,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        }
+    }
+
+    pub fn add_mint(&mut self, decimals: u8) -> Pubkey {
+        let mut data = vec![0; Mint::LEN];
+        Mint::pack(Self::mint(decimals), &mut data).unwrap();
+        self.add_account(
+            Pubkey::new_unique(),
+            Account {
+                owner: spl_token::id(),
+                lamports: Rent::default().minimum_balance(Mint::LEN),
+                data,
+                executable: false,
+            },
+        )
+    }
+
+    /// A Token-2022 mint, see token_2022.rs for building extensions
+    pub fn add_mint_2022(&mut self, decimals: u8, extensions: &[(u16, Vec<u8>)]) -> Pubkey {
+        let data = token_2022::mint_data(Self::mint(decimals), extensions);
+        self.add_account(
+            Pubkey::new_unique(),
+            Account {
+                owner: token_2022::id(),
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                executable: false,
+            },
+        )
+    }
+
+    /// A one-of-one mint: 0 decimals and a supply of 1
+    pub fn add_nft(&mut self) -> Pubkey {
+        let mut data = vec![0; Mint::LEN];
+        let mint = Mint {
+            supply: 1,
+            ..Self::mint(0)
+        };
+        Mint::pack(mint, &mut data).unwrap();
+        self.add_account(
+            Pubkey::new_unique(),
+            Account {
+                owner: spl_token::id(),
+                lamports: Rent::default().minimum_balance(Mint::LEN),
+                data,
+                executable: false,
+            },
+        )
+    }
+
+    /// `metadata` as the metadata program would store it for `metadata.mint`, see `metadata_data`
+    pub fn add_metadata(&mut self, metadata: &Metadata) -> Pubkey {
+        let data = metadata_data(metadata);
+        self.add_account(
+            metadata_address(&metadata.mint).0,
+            Account {
+                owner: metadata_program::id(),
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                executable: false,
+            },
+        )
+    }
+
+    /// A token account owned by whichever token program owns the mint
+    pub fn add_token_account(&mut self, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+        self.add_token_account_with(TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        })
+    }
+
+    pub fn add_token_account_with(&mut self, account: TokenAccount) -> Pubkey {
+        let mint = &self.accounts[&account.mint];
+        let token_program = mint.owner;
+        let data = if token_program == token_2022::id() {
+            token_2022::account_data(account, &mint.data)
+        } else {
+            let mut data = vec![0; TokenAccount::LEN];
+            TokenAccount::pack(account, &mut data).unwrap();
+            data
+        };
+        self.add_account(
+            Pubkey::new_unique(),
+            Account {
+                owner: token_program,
+                lamports: Rent::default().minimum_balance(TokenAccount::LEN),
+                data,
+                executable: false,
+            },
+        )
+    }
+
+    pub fn account(&self, key: &Pubkey) -> &Account {
+        &self.accounts[key]
+    }
+
+    pub fn lamports(&self, key: &Pubkey) -> u64 {
+        self.accounts.get(key).map(|a| a.lamports).unwrap_or(0)
+    }
+
+    /// None once the account has been closed
+    pub fn token_account(&self, key: &Pubkey) -> Option<TokenAccount> {
+        self.accounts
+            .get(key)
+            .filter(|a| a.data.len() >= TokenAccount::LEN)
+            .and_then(|a| TokenAccount::unpack(&a.data[..TokenAccount::LEN]).ok())
+    }
+
+    pub fn token_balance(&self, key: &Pubkey) -> u64 {
+        self.token_account(key).map(|a| a.amount).unwrap_or(0)
+    }
+
+    pub fn pda(&self) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[b"escrow"], &self.program_id)
+    }
+
+    /// Everything the last processed instruction logged
+    pub fn logs(&self) -> Vec<String> {
+        LOGS.with(|l| l.borrow().clone())
+    }
+
+    pub fn instruction(&self, data: Vec<u8>, accounts: Vec<AccountMeta>) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts,
+            data,
+        }
+    }
+
+    /// Processes `transaction[index]` with the instructions sysvar filled in for the whole transaction. The other
+    /// instructions aren't run - an Ed25519 one in there is taken as verified, the way it would be if the runtime got to us
+    pub fn process_at(&mut self, transaction: &[Instruction], index: usize) -> ProgramResult {
+        let mut data = Message::new(transaction, None).serialize_instructions(false);
+        data.extend_from_slice(&(index as u16).to_le_bytes());
+        self.add_account(
+            sysvar::instructions::id(),
+            Account {
+                owner: sysvar::id(),
+                lamports: 1,
+                data,
+                executable: false,
+            },
+        );
+        self.process(&transaction[index])
+    }
+
+    /// Processes every escrow instruction in `transaction` in order, with the instructions sysvar filled in. Like the
+    /// runtime, one failing undoes all the others. Instructions for other programs are skipped, see `process_at`
+    pub fn process_transaction(&mut self, transaction: &[Instruction]) -> ProgramResult {
+        let before = self.accounts.clone();
+        for index in 0..transaction.len() {
+            if transaction[index].program_id != self.program_id {
+                continue;
+            }
+            if let Err(e) = self.process_at(transaction, index) {
+                self.accounts = before;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn process(&mut self, instruction: &Instruction) -> ProgramResult {
+        install_stubs();
+        CALLER.with(|c| *c.borrow_mut() = instruction.program_id);
+        LOGS.with(|l| l.borrow_mut().clear());
+        ASSIGNED.with(|a| a.borrow_mut().clear());
+
+        // one working copy per unique key - duplicates in the account list share it, just like in the runtime
+        let mut keys: Vec<Pubkey> = Vec::new();
+        for meta in instruction.accounts.iter() {
+            if !keys.contains(&meta.pubkey) {
+                keys.push(meta.pubkey);
+            }
+        }
+        let mut working: Vec<(Pubkey, Account)> = keys
+            .iter()
+            .map(|key| (*key, self.accounts.get(key).cloned().unwrap_or_default()))
+            .collect();
+        let lamports_before: u128 = working.iter().map(|(_, a)| a.lamports as u128).sum();
+
+        let unique: Vec<AccountInfo> = working
+            .iter_mut()
+            .map(|(key, account)| {
+                let is_signer = instruction
+                    .accounts
+                    .iter()
+                    .any(|m| m.pubkey == *key && m.is_signer);
+                let is_writable = instruction
+                    .accounts
+                    .iter()
+                    .any(|m| m.pubkey == *key && m.is_writable);
+                AccountInfo::new(
+                    key,
+                    is_signer,
+                    is_writable,
+                    &mut account.lamports,
+                    &mut account.data,
+                    &account.owner,
+                    account.executable,
+                    0,
+                )
+            })
+            .collect();
+        let infos: Vec<AccountInfo> = instruction
+            .accounts
+            .iter()
+            .map(|m| unique[keys.iter().position(|k| *k == m.pubkey).unwrap()].clone())
+            .collect();
+
+        SNAPSHOT.with(|s| s.borrow_mut().clear());
+        take_snapshot(&unique);
+        Processor::process(&instruction.program_id, &infos, &instruction.data)?;
+        verify_writes(&instruction.program_id, &unique)?;
+
+        let after: Vec<(u64, Vec<u8>)> = unique
+            .iter()
+            .map(|info| (info.lamports(), info.data.borrow().to_vec()))
+            .collect();
+        drop(infos);
+        drop(unique);
+
+        let lamports_after: u128 = after.iter().map(|(l, _)| *l as u128).sum();
+        if lamports_before != lamports_after {
+            return Err(ProgramError::Custom(RUNTIME_VIOLATION));
+        }
+        for ((key, mut account), (lamports, data)) in working.into_iter().zip(after) {
+            account.lamports = lamports;
+            account.data = data;
+            if let Some(owner) = ASSIGNED.with(|a| a.borrow().get(&key).copied()) {
+                account.owner = owner;
+            }
+            // like the runtime, accounts left with no lamports are garbage collected
+            if account.lamports == 0 {
+                self.accounts.remove(&key);
+            } else {
+                self.accounts.insert(key, account);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An Ed25519 program instruction for `signer`'s signature of `message`, laid out the way the web3 and SDK helpers do
+/// it. The signature is left zeroed, nothing in the harness checks it
+pub fn ed25519_instruction(signer: &Pubkey, message: &[u8]) -> Instruction {
+    let (pubkey_offset, signature_offset, message_offset) = (16u16, 48u16, 112u16);
+    let mut data = vec![1, 0];
+    for offset in [
+        signature_offset,
+        u16::MAX,
+        pubkey_offset,
+        u16::MAX,
+        message_offset,
+        message.len() as u16,
+        u16::MAX,
+    ]
+    .iter()
+    {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(signer.as_ref());
+    data.extend_from_slice(&[0; 64]);
+    data.extend_from_slice(message);
+    Instruction {
+        program_id: ed25519_program::id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// InitMultisig for `m` of `signers` into `multisig`, a program account of Multisig::LEN
+pub fn init_multisig(
+    test: &ProgramTest,
+    multisig: Pubkey,
+    m: u8,
+    signers: &[Pubkey],
+) -> Instruction {
+    let mut accounts = vec![AccountMeta::new(multisig, false)];
+    accounts.extend(
+        signers
+            .iter()
+            .map(|signer| AccountMeta::new_readonly(*signer, false)),
+    );
+    test.instruction(vec![47, m], accounts)
+}
+
+/// `ix` approved by `signers` of `multisig` instead of the initializer, who no longer signs it
+pub fn with_multisig(mut ix: Instruction, multisig: Pubkey, signers: &[Pubkey]) -> Instruction {
+    ix.accounts[0].is_signer = false;
+    ix.accounts.push(AccountMeta::new_readonly(multisig, false));
+    ix.accounts.extend(
+        signers
+            .iter()
+            .map(|signer| AccountMeta::new_readonly(*signer, true)),
+    );
+    ix
+}
+
+/// A MetadataV1 account's bytes, with some made up name, symbol and uri and the fields we don't read filled in
+pub fn metadata_data(metadata: &Metadata) -> Vec<u8> {
+    let mut data = vec![4];
+    data.extend_from_slice(Pubkey::new_unique().as_ref());
+    data.extend_from_slice(metadata.mint.as_ref());
+    for string in [&b"Escrow Ape"[..], b"EAPE", b"https://example.com/1.json"].iter() {
+        data.extend_from_slice(&(string.len() as u32).to_le_bytes());
+        data.extend_from_slice(string);
+    }
+    data.extend_from_slice(&metadata.seller_fee_basis_points.to_le_bytes());
+    if metadata.creators.is_empty() {
+        data.push(0);
+    } else {
+        data.push(1);
+        data.extend_from_slice(&(metadata.creators.len() as u32).to_le_bytes());
+        for creator in &metadata.creators {
+            data.extend_from_slice(creator.address.as_ref());
+            data.push(creator.verified as u8);
+            data.push(creator.share);
+        }
+    }
+    // primary sale happened, is mutable, edition nonce, token standard (NonFungible)
+    data.extend_from_slice(&[1, 1, 1, 255, 1, 0]);
+    match metadata.collection {
+        Some(collection) => {
+            data.push(1);
+            data.push(collection.verified as u8);
+            data.extend_from_slice(collection.key.as_ref());
+        }
+        None => data.push(0),
+    }
+    // uses, collection details, programmable config
+    data.extend_from_slice(&[0, 0, 0]);
+    data
+}
+
+/// A pool's account and the empty vaults its creator hands over at InitPool
+#[derive(Clone, Copy)]
+pub struct PoolAccounts {
+    pub pool: Pubkey,
+    pub creator: Pubkey,
+    pub x_vault: Pubkey,
+    pub y_vault: Pubkey,
+}
+
+/// A standing order's account, its owner's and the keeper's, with the owner's Y budget in `vault` before
+/// InitStandingOrder hands it over
+#[derive(Clone, Copy)]
+pub struct StandingOrderAccounts {
+    pub standing_order: Pubkey,
+    pub owner: Pubkey,
+    pub vault: Pubkey,
+    pub owner_x: Pubkey,
+    pub owner_y: Pubkey,
+    pub keeper: Pubkey,
+    pub keeper_y: Pubkey,
+}
+
+/// The accounts of a single X-for-Y escrow, set up the way a client would before calling InitEscrow
+pub struct EscrowFixture {
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub initializer: Pubkey,
+    pub initializer_x: Pubkey,
+    pub temp_x: Pubkey,
+    pub initializer_y: Pubkey,
+    pub taker: Pubkey,
+    pub taker_x: Pubkey,
+    pub taker_y: Pubkey,
+    pub escrow: Pubkey,
+    pub token_program_x: Pubkey,
+    pub token_program_y: Pubkey,
+    /// Sponsors the escrow's rent, `None` leaves it to the initializer
+    pub rent_payer: Option<Pubkey>,
+    /// For the loan instructions, which keep their state in a Loan instead of `escrow`
+    pub loan: Pubkey,
+    /// An empty Y account of the initializer's, for auctions to hold the bids in
+    pub bid_vault: Pubkey,
+}
+
+impl EscrowFixture {
+    /// `offered` X goes into the temp account, the taker holds `taker_y` of Y
+    pub fn new(test: &mut ProgramTest, offered: u64, taker_y: u64) -> Self {
+        let mint_x = test.add_mint(0);
+        let mint_y = test.add_mint(0);
+        Self::for_pair(test, mint_x, mint_y, offered, taker_y)
+    }
+
+    /// Same as `new`, but trading existing mints
+    pub fn for_pair(
+        test: &mut ProgramTest,
+        mint_x: Pubkey,
+        mint_y: Pubkey,
+        offered: u64,
+        taker_y: u64,
+    ) -> Self {
+        let initializer = test.add_wallet(1_000_000_000);
+        let taker = test.add_wallet(1_000_000_000);
+        Self {
+            mint_x,
+            mint_y,
+            initializer,
+            initializer_x: test.add_token_account(&mint_x, &initializer, 0),
+            temp_x: test.add_token_account(&mint_x, &initializer, offered),
+            initializer_y: test.add_token_account(&mint_y, &initializer, 0),
+            taker,
+            taker_x: test.add_token_account(&mint_x, &taker, 0),
+            taker_y: test.add_token_account(&mint_y, &taker, taker_y),
+            escrow: test.add_program_account(Escrow::len(EscrowMode::Vault)),
+            token_program_x: test.account(&mint_x).owner,
+            token_program_y: test.account(&mint_y).owner,
+            rent_payer: None,
+            loan: test.add_program_account(bpf_program_template::state::Loan::LEN),
+            bid_vault: test.add_token_account(&mint_y, &initializer, 0),
+        }
+    }
+
+    /// Swaps `escrow` for one the size an escrow in `mode` needs, `new` sizes it for Vault and Approve mode
+    pub fn sized_for(mut self, test: &mut ProgramTest, mode: EscrowMode) -> Self {
+        self.escrow = test.add_program_account(Escrow::len(mode));
+        self
+    }
+
+    pub fn init_escrow(&self, test: &ProgramTest, amount: u64) -> Instruction {
+        let mut data = vec![0];
+        data.extend_from_slice(&amount.to_le_bytes());
+        self.init_with(test, data, None)
+    }
+
+    /// Approve mode: `temp_x` stays the initializer's and the PDA becomes its delegate for `offered_amount`
+    pub fn init_approve_escrow(
+        &self,
+        test: &ProgramTest,
+        amount: u64,
+        offered_amount: u64,
+    ) -> Instruction {
+        let mut data = vec![3];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&offered_amount.to_le_bytes());
+        self.init_with(test, data, Some(test.pda().0))
+    }
+
+    fn init_with(&self, test: &ProgramTest, mut data: Vec<u8>, pda: Option<Pubkey>) -> Instruction {
+        data.extend(self.rent_payer_flag());
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.initializer, true),
+            AccountMeta::new(self.temp_x, false),
+            AccountMeta::new_readonly(self.initializer_y, false),
+            AccountMeta::new(self.escrow, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(self.token_program_x, false),
+            AccountMeta::new_readonly(self.mint_x, false),
+            AccountMeta::new_readonly(self.mint_y, false),
+        ];
+        accounts.extend(pda.map(|pda| AccountMeta::new_readonly(pda, false)));
+        accounts.extend(self.rent_payer_signer());
+        test.instruction(data, accounts)
+    }
+
+    pub fn exchange(&self, test: &ProgramTest, amount: u64) -> Instruction {
+        let mut data = vec![1];
+        data.extend_from_slice(&amount.to_le_bytes());
+        let mut ix = test.instruction(
+            data,
+            vec![
+                AccountMeta::new_readonly(self.taker, true),
+                AccountMeta::new(self.taker_y, false),
+                AccountMeta::new(self.taker_x, false),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.initializer, false),
+                AccountMeta::new(self.initializer_y, false),
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+                AccountMeta::new(self.mint_x, false),
+                AccountMeta::new_readonly(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+            ],
+        );
+        ix.accounts.extend(self.rent_payer_meta());
+        ix
+    }
+
+    pub fn cancel(&self, test: &ProgramTest) -> Instruction {
+        let (pda, bump_seed) = test.pda();
+        let mut ix = test.instruction(
+            vec![2, bump_seed],
+            vec![
+                AccountMeta::new(self.initializer, true),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.initializer_x, false),
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new_readonly(pda, false),
+                AccountMeta::new(self.mint_x, false),
+            ],
+        );
+        ix.accounts.extend(self.rent_payer_meta());
+        ix
+    }
+
+    // init instructions end their data with a flag when a rent payer signs along
+    fn rent_payer_flag(&self) -> Option<u8> {
+        self.rent_payer.map(|_| 1)
+    }
+
+    fn rent_payer_signer(&self) -> Option<AccountMeta> {
+        self.rent_payer
+            .map(|rent_payer| AccountMeta::new_readonly(rent_payer, true))
+    }
+
+    fn rent_payer_meta(&self) -> Option<AccountMeta> {
+        self.rent_payer
+            .map(|rent_payer| AccountMeta::new(rent_payer, false))
+    }
+
+    /// HTLC mode: `temp_x` is locked for `taker_x`, the taker plays the recipient
+    pub fn init_htlc(&self, test: &ProgramTest, hashlock: [u8; 32], timelock: i64) -> Instruction {
+        let mut data = vec![4];
+        data.extend_from_slice(&hashlock);
+        data.extend_from_slice(&timelock.to_le_bytes());
+        data.extend(self.rent_payer_flag());
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.initializer, true),
+            AccountMeta::new(self.temp_x, false),
+            AccountMeta::new_readonly(self.taker_x, false),
+            AccountMeta::new(self.escrow, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(self.token_program_x, false),
+            AccountMeta::new_readonly(self.mint_x, false),
+        ];
+        accounts.extend(self.rent_payer_signer());
+        test.instruction(data, accounts)
+    }
+
+    pub fn claim(&self, test: &ProgramTest, preimage: [u8; 32]) -> Instruction {
+        let mut data = vec![5];
+        data.extend_from_slice(&preimage);
+        let mut ix = test.instruction(
+            data,
+            vec![
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.taker_x, false),
+                AccountMeta::new(self.initializer, false),
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+                AccountMeta::new(self.mint_x, false),
+            ],
+        );
+        ix.accounts.extend(self.rent_payer_meta());
+        ix
+    }
+
+    pub fn refund(&self, test: &ProgramTest) -> Instruction {
+        let mut ix = test.instruction(
+            vec![6],
+            vec![
+                AccountMeta::new(self.initializer, true),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.initializer_x, false),
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+                AccountMeta::new(self.mint_x, false),
+            ],
+        );
+        ix.accounts.extend(self.rent_payer_meta());
+        ix
+    }
+
+    /// Covered call: the initializer writes the option on `temp_x`, premium and strike go to `initializer_y`
+    pub fn write_option(
+        &self,
+        test: &ProgramTest,
+        strike: u64,
+        premium: u64,
+        expiry: i64,
+    ) -> Instruction {
+        let mut data = vec![7];
+        data.extend_from_slice(&strike.to_le_bytes());
+        data.extend_from_slice(&premium.to_le_bytes());
+        data.extend_from_slice(&expiry.to_le_bytes());
+        self.init_with(test, data, None)
+    }
+
+    /// The taker buys the option
+    pub fn buy_option(&self, test: &ProgramTest, premium: u64) -> Instruction {
+        let mut data = vec![8];
+        data.extend_from_slice(&premium.to_le_bytes());
+        test.instruction(
+            data,
+            vec![
+                AccountMeta::new_readonly(self.taker, true),
+                AccountMeta::new(self.taker_y, false),
+                AccountMeta::new(self.initializer_y, false),
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new_readonly(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+            ],
+        )
+    }
+
+    /// Same accounts as `exchange`
+    pub fn exercise_option(&self, test: &ProgramTest, amount: u64) -> Instruction {
+        let mut ix = self.exchange(test, amount);
+        ix.data[0] = 9;
+        ix
+    }
+
+    /// Same accounts as `refund`
+    pub fn reclaim_option(&self, test: &ProgramTest) -> Instruction {
+        let mut ix = self.refund(test);
+        ix.data[0] = 10;
+        ix
+    }
+
+    /// Dutch auction over `temp_x`, the Y goes to `initializer_y`
+    pub fn init_dutch_auction(
+        &self,
+        test: &ProgramTest,
+        start_price: u64,
+        floor_price: u64,
+        start_time: i64,
+        end_time: i64,
+        decay: u8,
+    ) -> Instruction {
+        let mut data = vec![16];
+        data.extend_from_slice(&start_price.to_le_bytes());
+        data.extend_from_slice(&floor_price.to_le_bytes());
+        data.extend_from_slice(&start_time.to_le_bytes());
+        data.extend_from_slice(&end_time.to_le_bytes());
+        data.push(decay);
+        self.init_with(test, data, None)
+    }
+
+    /// Same accounts as `exchange`
+    pub fn take_dutch_auction(
+        &self,
+        test: &ProgramTest,
+        amount: u64,
+        max_price: u64,
+    ) -> Instruction {
+        let mut ix = self.exchange(test, amount);
+        ix.data[0] = 17;
+        ix.data.extend_from_slice(&max_price.to_le_bytes());
+        ix
+    }
+
+    /// English auction over `temp_x`, bids go into `bid_vault`
+    pub fn init_english_auction(
+        &self,
+        test: &ProgramTest,
+        reserve_price: u64,
+        min_increment: u64,
+        end_time: i64,
+        extension: i64,
+    ) -> Instruction {
+        let mut data = vec![18];
+        data.extend_from_slice(&reserve_price.to_le_bytes());
+        data.extend_from_slice(&min_increment.to_le_bytes());
+        data.extend_from_slice(&end_time.to_le_bytes());
+        data.extend_from_slice(&extension.to_le_bytes());
+        let mut ix = self.init_with(test, data, None);
+        ix.accounts
+            .insert(8, AccountMeta::new(self.bid_vault, false));
+        ix.accounts
+            .insert(9, AccountMeta::new_readonly(self.token_program_y, false));
+        ix
+    }
+
+    /// `previous_y` refunds the last bid, if there is one
+    pub fn bid(
+        &self,
+        test: &ProgramTest,
+        bidder: Pubkey,
+        bidder_y: Pubkey,
+        amount: u64,
+        previous_y: Option<Pubkey>,
+    ) -> Instruction {
+        let mut data = vec![19];
+        data.extend_from_slice(&amount.to_le_bytes());
+        let mut ix = test.instruction(
+            data,
+            vec![
+                AccountMeta::new_readonly(bidder, true),
+                AccountMeta::new(bidder_y, false),
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(self.bid_vault, false),
+                AccountMeta::new_readonly(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+            ],
+        );
+        ix.accounts
+            .extend(previous_y.map(|previous_y| AccountMeta::new(previous_y, false)));
+        ix
+    }
+
+    /// X goes to `winner_x`, the bids to `initializer_y`
+    pub fn settle_auction(&self, test: &ProgramTest, winner_x: Pubkey) -> Instruction {
+        let mut ix = test.instruction(
+            vec![20],
+            vec![
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(winner_x, false),
+                AccountMeta::new(self.bid_vault, false),
+                AccountMeta::new(self.initializer_y, false),
+                AccountMeta::new(self.initializer, false),
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+                AccountMeta::new(self.mint_x, false),
+                AccountMeta::new(self.mint_y, false),
+            ],
+        );
+        ix.accounts.extend(self.rent_payer_meta());
+        ix
+    }
+
+    /// Same accounts as InitEnglishAuction
+    pub fn init_sealed_auction(
+        &self,
+        test: &ProgramTest,
+        reserve_price: u64,
+        bond: u64,
+        commit_end: i64,
+        reveal_end: i64,
+        second_price: bool,
+    ) -> Instruction {
+        let mut data = vec![21];
+        data.extend_from_slice(&reserve_price.to_le_bytes());
+        data.extend_from_slice(&bond.to_le_bytes());
+        data.extend_from_slice(&commit_end.to_le_bytes());
+        data.extend_from_slice(&reveal_end.to_le_bytes());
+        data.push(second_price as u8);
+        let mut ix = self.init_with(test, data, None);
+        ix.accounts
+            .insert(8, AccountMeta::new(self.bid_vault, false));
+        ix.accounts
+            .insert(9, AccountMeta::new_readonly(self.token_program_y, false));
+        ix
+    }
+
+    /// `bid` is an empty program account of SealedBid::LEN
+    pub fn commit_bid(
+        &self,
+        test: &ProgramTest,
+        bidder: Pubkey,
+        bidder_y: Pubkey,
+        bid: Pubkey,
+        commitment: [u8; 32],
+    ) -> Instruction {
+        let mut data = vec![22];
+        data.extend_from_slice(&commitment);
+        test.instruction(
+            data,
+            vec![
+                AccountMeta::new_readonly(bidder, true),
+                AccountMeta::new(bidder_y, false),
+                AccountMeta::new_readonly(self.escrow, false),
+                AccountMeta::new(bid, false),
+                AccountMeta::new(self.bid_vault, false),
+                AccountMeta::new_readonly(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+            ],
+        )
+    }
+
+    /// `previous_y` refunds the highest bid so far, if this one beats it
+    #[allow(clippy::too_many_arguments)]
+    pub fn reveal_bid(
+        &self,
+        test: &ProgramTest,
+        bidder: Pubkey,
+        bidder_y: Pubkey,
+        bid: Pubkey,
+        amount: u64,
+        salt: [u8; 32],
+        previous_y: Option<Pubkey>,
+    ) -> Instruction {
+        let mut data = vec![23];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&salt);
+        let mut ix = test.instruction(
+            data,
+            vec![
+                AccountMeta::new(bidder, true),
+                AccountMeta::new(bidder_y, false),
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(bid, false),
+                AccountMeta::new(self.bid_vault, false),
+                AccountMeta::new_readonly(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+            ],
+        );
+        ix.accounts
+            .extend(previous_y.map(|previous_y| AccountMeta::new(previous_y, false)));
+        ix
+    }
+
+    pub fn close_bid(&self, test: &ProgramTest, bidder: Pubkey, bid: Pubkey) -> Instruction {
+        test.instruction(
+            vec![24],
+            vec![
+                AccountMeta::new(bidder, false),
+                AccountMeta::new(bid, false),
+            ],
+        )
+    }
+
+    /// Same as settle_auction, plus `winner_y` for what a second-price winner overpaid
+    pub fn settle_sealed_auction(
+        &self,
+        test: &ProgramTest,
+        winner_x: Pubkey,
+        winner_y: Pubkey,
+    ) -> Instruction {
+        let mut ix = self.settle_auction(test, winner_x);
+        ix.accounts.insert(11, AccountMeta::new(winner_y, false));
+        ix
+    }
+
+    /// Matches this escrow against `other`, which sells this one's Y for its X. The recipients get each side's price
+    /// improvement, the cranker's accounts their cut of it
+    pub fn match_with(
+        &self,
+        test: &ProgramTest,
+        other: &EscrowFixture,
+        recipient: Pubkey,
+        other_recipient: Pubkey,
+        cranker: (Pubkey, Pubkey),
+    ) -> Instruction {
+        let mut ix = test.instruction(
+            vec![25],
+            vec![
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.initializer, false),
+                AccountMeta::new(self.initializer_y, false),
+                AccountMeta::new(recipient, false),
+                AccountMeta::new(other.escrow, false),
+                AccountMeta::new(other.temp_x, false),
+                AccountMeta::new(other.initializer, false),
+                AccountMeta::new(other.initializer_y, false),
+                AccountMeta::new(other_recipient, false),
+                AccountMeta::new(cranker.0, false),
+                AccountMeta::new(cranker.1, false),
+                AccountMeta::new(self.mint_x, false),
+                AccountMeta::new(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+            ],
+        );
+        ix.accounts.extend(self.rent_payer_meta());
+        ix.accounts.extend(other.rent_payer_meta());
+        ix
+    }
+
+    /// Hands the escrow to the multisig `authority`, or back to the initializer if that's who it is
+    pub fn set_escrow_authority(&self, test: &ProgramTest, authority: Pubkey) -> Instruction {
+        test.instruction(
+            vec![48],
+            vec![
+                AccountMeta::new_readonly(self.initializer, true),
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new_readonly(authority, false),
+            ],
+        )
+    }
+
+    pub fn set_match_recipient(&self, test: &ProgramTest, recipient: Pubkey) -> Instruction {
+        test.instruction(
+            vec![26],
+            vec![
+                AccountMeta::new_readonly(self.initializer, true),
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new_readonly(self.temp_x, false),
+                AccountMeta::new_readonly(recipient, false),
+            ],
+        )
+    }
+
+    /// This fixture's taker sweeps `listings`, which have to trade the same mints
+    pub fn exchange_many(
+        &self,
+        test: &ProgramTest,
+        target_x: u64,
+        max_y: u64,
+        listings: &[&EscrowFixture],
+    ) -> Instruction {
+        let mut data = vec![27];
+        data.extend_from_slice(&target_x.to_le_bytes());
+        data.extend_from_slice(&max_y.to_le_bytes());
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.taker, true),
+            AccountMeta::new(self.taker_y, false),
+            AccountMeta::new(self.taker_x, false),
+            AccountMeta::new_readonly(self.token_program_x, false),
+            AccountMeta::new_readonly(self.token_program_y, false),
+            AccountMeta::new_readonly(test.pda().0, false),
+            AccountMeta::new(self.mint_x, false),
+            AccountMeta::new_readonly(self.mint_y, false),
+        ];
+        for listing in listings {
+            accounts.extend(vec![
+                AccountMeta::new(listing.escrow, false),
+                AccountMeta::new(listing.temp_x, false),
+                AccountMeta::new(listing.initializer, false),
+                AccountMeta::new(listing.initializer_y, false),
+                AccountMeta::new(listing.rent_payer.unwrap_or(listing.initializer), false),
+            ]);
+        }
+        test.instruction(data, accounts)
+    }
+
+    /// Loan: the initializer borrows Y against `temp_x` and gets the principal into `initializer_y`
+    /// Takes the escrow now and pays for it with a `settle_flash` later in the same transaction
+    pub fn flash_exchange(&self, test: &ProgramTest, amount: u64) -> Instruction {
+        let mut data = vec![30];
+        data.extend_from_slice(&amount.to_le_bytes());
+        let mut ix = test.instruction(
+            data,
+            vec![
+                AccountMeta::new_readonly(self.taker, true),
+                AccountMeta::new(self.taker_x, false),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.initializer, false),
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+                AccountMeta::new(self.mint_x, false),
+                AccountMeta::new_readonly(sysvar::instructions::id(), false),
+            ],
+        );
+        ix.accounts.extend(self.rent_payer_meta());
+        ix
+    }
+
+    /// `payer` pays the escrow's price out of `payer_y`
+    pub fn settle_flash(&self, test: &ProgramTest, payer: Pubkey, payer_y: Pubkey) -> Instruction {
+        let mut ix = test.instruction(
+            vec![31],
+            vec![
+                AccountMeta::new_readonly(payer, true),
+                AccountMeta::new(payer_y, false),
+                AccountMeta::new(self.initializer_y, false),
+                AccountMeta::new(self.initializer, false),
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new_readonly(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+            ],
+        );
+        ix.accounts.extend(self.rent_payer_meta());
+        ix
+    }
+
+    /// An order from this fixture's initializer selling `amount_x` for `amount_y`
+    pub fn order(&self, amount_x: u64, amount_y: u64, expiry: i64, nonce: u64) -> Order {
+        Order {
+            maker: self.initializer,
+            mint_x: self.mint_x,
+            mint_y: self.mint_y,
+            amount_x,
+            amount_y,
+            expiry,
+            nonce,
+        }
+    }
+
+    /// This fixture's taker fills `order`, paying into `initializer_y` and taking out of `temp_x` through the
+    /// maker's order delegate.
+    /// The Ed25519 instruction has to go in front of it, see `ProgramTest::process_at`
+    pub fn fill_signed_order(&self, test: &ProgramTest, order: &Order) -> Instruction {
+        let mut data = vec![28; 1 + Order::LEN];
+        order.pack_into_slice(&mut data[1..]);
+        let window = NonceBitmap::window(order.nonce);
+        test.instruction(
+            data,
+            vec![
+                AccountMeta::new(self.taker, true),
+                AccountMeta::new(self.taker_y, false),
+                AccountMeta::new(self.taker_x, false),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.initializer_y, false),
+                AccountMeta::new(
+                    nonce_bitmap_address(&order.maker, window, &test.program_id).0,
+                    false,
+                ),
+                AccountMeta::new(self.mint_x, false),
+                AccountMeta::new_readonly(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+                AccountMeta::new_readonly(
+                    delegate_address(&order.maker, &test.program_id).0,
+                    false,
+                ),
+                AccountMeta::new_readonly(sysvar::instructions::id(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )
+    }
+
+    /// The initializer burns `nonce` without a fill
+    pub fn cancel_order_nonce(&self, test: &ProgramTest, nonce: u64) -> Instruction {
+        let mut data = vec![29];
+        data.extend_from_slice(&nonce.to_le_bytes());
+        let window = NonceBitmap::window(nonce);
+        test.instruction(
+            data,
+            vec![
+                AccountMeta::new(self.initializer, true),
+                AccountMeta::new(
+                    nonce_bitmap_address(&self.initializer, window, &test.program_id).0,
+                    false,
+                ),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )
+    }
+
+    pub fn offer_loan(
+        &self,
+        test: &ProgramTest,
+        principal: u64,
+        interest: u64,
+        duration: i64,
+    ) -> Instruction {
+        let mut data = vec![11];
+        data.extend_from_slice(&principal.to_le_bytes());
+        data.extend_from_slice(&interest.to_le_bytes());
+        data.extend_from_slice(&duration.to_le_bytes());
+        let mut ix = self.init_with(test, data, None);
+        ix.accounts[3].pubkey = self.loan;
+        ix
+    }
+
+    /// The taker lends out of `taker_y`
+    pub fn fund_loan(&self, test: &ProgramTest, principal: u64) -> Instruction {
+        let mut data = vec![12];
+        data.extend_from_slice(&principal.to_le_bytes());
+        test.instruction(
+            data,
+            vec![
+                AccountMeta::new_readonly(self.taker, true),
+                AccountMeta::new(self.taker_y, false),
+                AccountMeta::new(self.initializer_y, false),
+                AccountMeta::new(self.loan, false),
+                AccountMeta::new_readonly(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+            ],
+        )
+    }
+
+    /// Repays out of `initializer_y`, the collateral goes back to `initializer_x`
+    pub fn repay_loan(&self, test: &ProgramTest) -> Instruction {
+        let mut ix = test.instruction(
+            vec![13],
+            vec![
+                AccountMeta::new(self.initializer, true),
+                AccountMeta::new(self.initializer_y, false),
+                AccountMeta::new(self.taker_y, false),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.initializer_x, false),
+                AccountMeta::new(self.loan, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+                AccountMeta::new(self.mint_x, false),
+                AccountMeta::new_readonly(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+            ],
+        );
+        ix.accounts.extend(self.rent_payer_meta());
+        ix
+    }
+
+    /// The taker takes the collateral into `taker_x`
+    pub fn foreclose_loan(&self, test: &ProgramTest) -> Instruction {
+        let mut ix = test.instruction(
+            vec![14],
+            vec![
+                AccountMeta::new_readonly(self.taker, true),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.taker_x, false),
+                AccountMeta::new(self.initializer, false),
+                AccountMeta::new(self.loan, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+                AccountMeta::new(self.mint_x, false),
+            ],
+        );
+        ix.accounts.extend(self.rent_payer_meta());
+        ix
+    }
+
+    /// Same accounts as `refund`, with the loan account
+    pub fn cancel_loan(&self, test: &ProgramTest) -> Instruction {
+        let mut ix = self.refund(test);
+        ix.data[0] = 15;
+        ix.accounts[3].pubkey = self.loan;
+        ix
+    }
+
+    /// Pays out `temp_x` by `merkle_root`. `distribution` and `bitmap` are empty program accounts of Distribution::LEN
+    /// and ClaimedBitmap::len(num_recipients)
+    pub fn init_distribution(
+        &self,
+        test: &ProgramTest,
+        distribution: Pubkey,
+        bitmap: Pubkey,
+        merkle_root: [u8; 32],
+        num_recipients: u64,
+        deadline: i64,
+    ) -> Instruction {
+        let mut data = vec![32];
+        data.extend_from_slice(&merkle_root);
+        data.extend_from_slice(&num_recipients.to_le_bytes());
+        data.extend_from_slice(&deadline.to_le_bytes());
+        test.instruction(
+            data,
+            vec![
+                AccountMeta::new_readonly(self.initializer, true),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(distribution, false),
+                AccountMeta::new(bitmap, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(self.mint_x, false),
+            ],
+        )
+    }
+
+    /// Claims leaf `index` into `recipient_x`, sent by nobody in particular
+    #[allow(clippy::too_many_arguments)]
+    pub fn claim_distribution(
+        &self,
+        test: &ProgramTest,
+        distribution: Pubkey,
+        bitmap: Pubkey,
+        recipient_x: Pubkey,
+        index: u64,
+        amount: u64,
+        proof: &[[u8; 32]],
+    ) -> Instruction {
+        let mut data = vec![33];
+        data.extend_from_slice(&index.to_le_bytes());
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.push(proof.len() as u8);
+        for hash in proof {
+            data.extend_from_slice(hash);
+        }
+        test.instruction(
+            data,
+            vec![
+                AccountMeta::new(recipient_x, false),
+                AccountMeta::new_readonly(distribution, false),
+                AccountMeta::new(bitmap, false),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.mint_x, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+            ],
+        )
+    }
+
+    /// The rest goes back to `initializer_x`
+    pub fn reclaim_distribution(
+        &self,
+        test: &ProgramTest,
+        distribution: Pubkey,
+        bitmap: Pubkey,
+    ) -> Instruction {
+        test.instruction(
+            vec![34],
+            vec![
+                AccountMeta::new(self.initializer, true),
+                AccountMeta::new(self.initializer_x, false),
+                AccountMeta::new(distribution, false),
+                AccountMeta::new(bitmap, false),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.mint_x, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+            ],
+        )
+    }
+
+    /// InitNftEscrow, selling `temp_x` for the NFT `request_key` or any from that collection
+    pub fn init_nft_escrow(
+        &self,
+        test: &ProgramTest,
+        request: Request,
+        request_key: Pubkey,
+    ) -> Instruction {
+        let mut data = vec![35, request as u8];
+        data.extend_from_slice(request_key.as_ref());
+        data.extend(self.rent_payer_flag());
+        let mut ix = test.instruction(
+            data,
+            vec![
+                AccountMeta::new_readonly(self.initializer, true),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(self.mint_x, false),
+            ],
+        );
+        ix.accounts.extend(self.rent_payer_signer());
+        ix
+    }
+
+    /// The taker sends the NFT `nft_mint` out of `taker_nft` into the initializer's `initializer_nft`
+    pub fn exchange_nft(
+        &self,
+        test: &ProgramTest,
+        nft_mint: Pubkey,
+        taker_nft: Pubkey,
+        initializer_nft: Pubkey,
+        amount: u64,
+    ) -> Instruction {
+        let mut data = vec![36];
+        data.extend_from_slice(&amount.to_le_bytes());
+        let mut ix = test.instruction(
+            data,
+            vec![
+                AccountMeta::new_readonly(self.taker, true),
+                AccountMeta::new(taker_nft, false),
+                AccountMeta::new(initializer_nft, false),
+                AccountMeta::new(self.taker_x, false),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.initializer, false),
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+                AccountMeta::new(self.mint_x, false),
+                AccountMeta::new_readonly(nft_mint, false),
+                AccountMeta::new_readonly(metadata_address(&nft_mint).0, false),
+                AccountMeta::new_readonly(test.account(&nft_mint).owner, false),
+            ],
+        );
+        ix.accounts.extend(self.rent_payer_meta());
+        ix
+    }
+
+    /// InitEscrow for an NFT whose creators get royalties, `mint_x` has to be one
+    pub fn init_royalty_escrow(&self, test: &ProgramTest, amount: u64) -> Instruction {
+        let mut data = vec![37];
+        data.extend_from_slice(&amount.to_le_bytes());
+        self.init_with(test, data, None)
+    }
+
+    /// Exchange for a royalty escrow, paying the creators into `creators_y`
+    pub fn exchange_with_royalties(
+        &self,
+        test: &ProgramTest,
+        amount: u64,
+        creators_y: &[Pubkey],
+    ) -> Instruction {
+        let mut ix = self.exchange(test, amount);
+        let royalty_accounts = std::iter::once(AccountMeta::new_readonly(
+            metadata_address(&self.mint_x).0,
+            false,
+        ))
+        .chain(
+            creators_y
+                .iter()
+                .map(|creator_y| AccountMeta::new(*creator_y, false)),
+        );
+        ix.accounts.splice(12..12, royalty_accounts);
+        ix
+    }
+
+    /// Locks `temp_x` for `beneficiary`. `vesting` is an empty program account of Vesting::LEN
+    #[allow(clippy::too_many_arguments)]
+    pub fn init_vesting(
+        &self,
+        test: &ProgramTest,
+        vesting: Pubkey,
+        beneficiary: Pubkey,
+        revoker: Option<Pubkey>,
+        start: i64,
+        cliff: i64,
+        end: i64,
+    ) -> Instruction {
+        let mut data = vec![38];
+        data.extend_from_slice(&start.to_le_bytes());
+        data.extend_from_slice(&cliff.to_le_bytes());
+        data.extend_from_slice(&end.to_le_bytes());
+        let mut ix = test.instruction(
+            data,
+            vec![
+                AccountMeta::new_readonly(self.initializer, true),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(vesting, false),
+                AccountMeta::new_readonly(beneficiary, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(self.mint_x, false),
+            ],
+        );
+        ix.accounts
+            .extend(revoker.map(|revoker| AccountMeta::new_readonly(revoker, false)));
+        ix
+    }
+
+    /// What has vested goes into `destination_x`
+    pub fn withdraw(
+        &self,
+        test: &ProgramTest,
+        vesting: Pubkey,
+        beneficiary: Pubkey,
+        destination_x: Pubkey,
+    ) -> Instruction {
+        self.vesting_payout(test, 39, vesting, beneficiary, destination_x)
+    }
+
+    /// What hasn't vested goes into `destination_x`
+    pub fn revoke_vesting(
+        &self,
+        test: &ProgramTest,
+        vesting: Pubkey,
+        revoker: Pubkey,
+        destination_x: Pubkey,
+    ) -> Instruction {
+        self.vesting_payout(test, 40, vesting, revoker, destination_x)
+    }
+
+    fn vesting_payout(
+        &self,
+        test: &ProgramTest,
+        tag: u8,
+        vesting: Pubkey,
+        signer: Pubkey,
+        destination_x: Pubkey,
+    ) -> Instruction {
+        test.instruction(
+            vec![tag],
+            vec![
+                AccountMeta::new_readonly(signer, true),
+                AccountMeta::new(destination_x, false),
+                AccountMeta::new(vesting, false),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.mint_x, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+                AccountMeta::new(self.initializer, false),
+            ],
+        )
+    }
+
+    /// Streams `temp_x` to `recipient`. `stream` is an empty program account of Stream::LEN. Withdrawing goes through
+    /// `withdraw` same as for a vesting
+    pub fn init_stream(
+        &self,
+        test: &ProgramTest,
+        stream: Pubkey,
+        recipient: Pubkey,
+        rate: u64,
+        duration: i64,
+    ) -> Instruction {
+        let mut data = vec![41];
+        data.extend_from_slice(&rate.to_le_bytes());
+        data.extend_from_slice(&duration.to_le_bytes());
+        test.instruction(
+            data,
+            vec![
+                AccountMeta::new_readonly(self.initializer, true),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(stream, false),
+                AccountMeta::new_readonly(recipient, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(self.mint_x, false),
+            ],
+        )
+    }
+
+    /// The accrued part goes into `recipient_x`, the rest back to `initializer_x`
+    pub fn cancel_stream(
+        &self,
+        test: &ProgramTest,
+        stream: Pubkey,
+        recipient_x: Pubkey,
+    ) -> Instruction {
+        test.instruction(
+            vec![42],
+            vec![
+                AccountMeta::new(self.initializer, true),
+                AccountMeta::new(self.initializer_x, false),
+                AccountMeta::new(recipient_x, false),
+                AccountMeta::new(stream, false),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.mint_x, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+            ],
+        )
+    }
+
+    /// Pays `temp_x` to the taker milestone by milestone. `milestones` is an empty program account of
+    /// MilestoneEscrow::LEN
+    pub fn init_milestone_escrow(
+        &self,
+        test: &ProgramTest,
+        milestones: Pubkey,
+        arbiter: Pubkey,
+        amounts: &[u64],
+    ) -> Instruction {
+        let mut data = vec![43, amounts.len() as u8];
+        for amount in amounts {
+            data.extend_from_slice(&amount.to_le_bytes());
+        }
+        test.instruction(
+            data,
+            vec![
+                AccountMeta::new_readonly(self.initializer, true),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(milestones, false),
+                AccountMeta::new_readonly(self.taker, false),
+                AccountMeta::new_readonly(arbiter, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(self.mint_x, false),
+            ],
+        )
+    }
+
+    /// The initializer pays `milestone` into `payee_x`
+    pub fn release_milestone(
+        &self,
+        test: &ProgramTest,
+        milestones: Pubkey,
+        payee_x: Pubkey,
+        milestone: u8,
+    ) -> Instruction {
+        test.instruction(
+            vec![44, milestone],
+            vec![
+                AccountMeta::new(self.initializer, true),
+                AccountMeta::new(payee_x, false),
+                AccountMeta::new(milestones, false),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.mint_x, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+            ],
+        )
+    }
+
+    pub fn dispute_milestone(
+        &self,
+        test: &ProgramTest,
+        milestones: Pubkey,
+        party: Pubkey,
+        milestone: u8,
+    ) -> Instruction {
+        test.instruction(
+            vec![45, milestone],
+            vec![
+                AccountMeta::new_readonly(party, true),
+                AccountMeta::new(milestones, false),
+            ],
+        )
+    }
+
+    /// `payee_amount` of `milestone` into `payee_x`, the rest into `payer_x`
+    #[allow(clippy::too_many_arguments)]
+    pub fn resolve_dispute(
+        &self,
+        test: &ProgramTest,
+        milestones: Pubkey,
+        arbiter: Pubkey,
+        payee_x: Pubkey,
+        payer_x: Pubkey,
+        milestone: u8,
+        payee_amount: u64,
+    ) -> Instruction {
+        let mut data = vec![46, milestone];
+        data.extend_from_slice(&payee_amount.to_le_bytes());
+        test.instruction(
+            data,
+            vec![
+                AccountMeta::new_readonly(arbiter, true),
+                AccountMeta::new(payee_x, false),
+                AccountMeta::new(payer_x, false),
+                AccountMeta::new(milestones, false),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.mint_x, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+                AccountMeta::new(self.initializer, false),
+            ],
+        )
+    }
+
+    /// `pool` out to take this escrow with contributions until `deadline`
+    pub fn init_pool(&self, test: &ProgramTest, pool: &PoolAccounts, deadline: i64) -> Instruction {
+        test.instruction(
+            [vec![49], deadline.to_le_bytes().to_vec()].concat(),
+            vec![
+                AccountMeta::new_readonly(pool.creator, true),
+                AccountMeta::new(pool.pool, false),
+                AccountMeta::new_readonly(self.escrow, false),
+                AccountMeta::new(pool.y_vault, false),
+                AccountMeta::new(pool.x_vault, false),
+                AccountMeta::new_readonly(self.temp_x, false),
+                AccountMeta::new_readonly(self.initializer_y, false),
+                AccountMeta::new_readonly(self.mint_x, false),
+                AccountMeta::new_readonly(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+            ],
+        )
+    }
+
+    pub fn contribute(
+        &self,
+        test: &ProgramTest,
+        pool: &PoolAccounts,
+        contributor: Pubkey,
+        contributor_y: Pubkey,
+        contribution: Pubkey,
+        amount: u64,
+    ) -> Instruction {
+        test.instruction(
+            [vec![50], amount.to_le_bytes().to_vec()].concat(),
+            vec![
+                AccountMeta::new_readonly(contributor, true),
+                AccountMeta::new(contributor_y, false),
+                AccountMeta::new(pool.pool, false),
+                AccountMeta::new(contribution, false),
+                AccountMeta::new(pool.y_vault, false),
+                AccountMeta::new_readonly(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+            ],
+        )
+    }
+
+    /// Anyone can send it, nobody signs
+    pub fn execute_pool(&self, test: &ProgramTest, pool: &PoolAccounts) -> Instruction {
+        let mut ix = test.instruction(
+            vec![51],
+            vec![
+                AccountMeta::new(pool.pool, false),
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(pool.x_vault, false),
+                AccountMeta::new(self.mint_x, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new(pool.y_vault, false),
+                AccountMeta::new(self.initializer_y, false),
+                AccountMeta::new(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+                AccountMeta::new(pool.creator, false),
+                AccountMeta::new(self.initializer, false),
+            ],
+        );
+        ix.accounts.extend(self.rent_payer_meta());
+        ix
+    }
+
+    /// `contributor` takes their share of X, or their Y back, into `destination`
+    pub fn leave_pool(
+        &self,
+        test: &ProgramTest,
+        pool: &PoolAccounts,
+        contributor: Pubkey,
+        destination: Pubkey,
+        contribution: Pubkey,
+    ) -> Instruction {
+        test.instruction(
+            vec![52],
+            vec![
+                AccountMeta::new(contributor, true),
+                AccountMeta::new(destination, false),
+                AccountMeta::new(pool.pool, false),
+                AccountMeta::new(contribution, false),
+                AccountMeta::new(pool.x_vault, false),
+                AccountMeta::new(self.mint_x, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new(pool.y_vault, false),
+                AccountMeta::new(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+                AccountMeta::new(pool.creator, false),
+                AccountMeta::new_readonly(self.escrow, false),
+            ],
+        )
+    }
+
+    /// Same mints as this fixture
+    pub fn init_standing_order(
+        &self,
+        test: &ProgramTest,
+        so: &StandingOrderAccounts,
+        amount_per_interval: u64,
+        min_amount_x: u64,
+        interval: i64,
+        keeper_reward: u64,
+    ) -> Instruction {
+        test.instruction(
+            [
+                vec![53],
+                amount_per_interval.to_le_bytes().to_vec(),
+                min_amount_x.to_le_bytes().to_vec(),
+                interval.to_le_bytes().to_vec(),
+                keeper_reward.to_le_bytes().to_vec(),
+            ]
+            .concat(),
+            vec![
+                AccountMeta::new_readonly(so.owner, true),
+                AccountMeta::new(so.vault, false),
+                AccountMeta::new(so.standing_order, false),
+                AccountMeta::new_readonly(so.owner_x, false),
+                AccountMeta::new_readonly(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+            ],
+        )
+    }
+
+    /// The keeper spends a tranche on this fixture's escrow
+    pub fn execute_tranche(&self, test: &ProgramTest, so: &StandingOrderAccounts) -> Instruction {
+        let mut ix = test.instruction(
+            vec![54],
+            vec![
+                AccountMeta::new_readonly(so.keeper, true),
+                AccountMeta::new(so.keeper_y, false),
+                AccountMeta::new(so.standing_order, false),
+                AccountMeta::new(so.vault, false),
+                AccountMeta::new(so.owner_x, false),
+                AccountMeta::new(self.escrow, false),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.initializer, false),
+                AccountMeta::new(self.initializer_y, false),
+                AccountMeta::new(self.mint_x, false),
+                AccountMeta::new_readonly(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+            ],
+        );
+        ix.accounts.extend(self.rent_payer_meta());
+        ix
+    }
+
+    /// The keeper spends a tranche on `order`, from this fixture's initializer out of `temp_x` like
+    /// `fill_signed_order`. The Ed25519 instruction has to go in front of it
+    pub fn execute_tranche_with_order(
+        &self,
+        test: &ProgramTest,
+        so: &StandingOrderAccounts,
+        order: &Order,
+    ) -> Instruction {
+        let mut data = vec![55; 1 + Order::LEN];
+        order.pack_into_slice(&mut data[1..]);
+        let window = NonceBitmap::window(order.nonce);
+        test.instruction(
+            data,
+            vec![
+                AccountMeta::new(so.keeper, true),
+                AccountMeta::new(so.keeper_y, false),
+                AccountMeta::new(so.standing_order, false),
+                AccountMeta::new(so.vault, false),
+                AccountMeta::new(so.owner_x, false),
+                AccountMeta::new(self.temp_x, false),
+                AccountMeta::new(self.initializer_y, false),
+                AccountMeta::new(
+                    nonce_bitmap_address(&order.maker, window, &test.program_id).0,
+                    false,
+                ),
+                AccountMeta::new(self.mint_x, false),
+                AccountMeta::new_readonly(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_x, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+                AccountMeta::new_readonly(
+                    delegate_address(&order.maker, &test.program_id).0,
+                    false,
+                ),
+                AccountMeta::new_readonly(sysvar::instructions::id(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+        )
+    }
+
+    pub fn cancel_standing_order(
+        &self,
+        test: &ProgramTest,
+        so: &StandingOrderAccounts,
+    ) -> Instruction {
+        test.instruction(
+            vec![56],
+            vec![
+                AccountMeta::new(so.owner, true),
+                AccountMeta::new(so.owner_y, false),
+                AccountMeta::new(so.standing_order, false),
+                AccountMeta::new(so.vault, false),
+                AccountMeta::new_readonly(self.mint_y, false),
+                AccountMeta::new_readonly(self.token_program_y, false),
+                AccountMeta::new_readonly(test.pda().0, false),
+            ],
+        )
+    }
+}
@@ -0,0 +1,261 @@
+// just enough of Token-2022 for the escrow's CPIs: SetAuthority (owner), Approve/Revoke, CloseAccount, TransferChecked with transfer fees
+// and harvesting withheld fees. spl-token-2022 itself doesn't build against the solana-program we're on.
+// Accounts use the real layout: base account, padding (mints), account type byte, then extension TLVs.
+
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError,
+    program_option::COption, program_pack::Pack, pubkey::Pubkey,
+};
+
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+
+pub use bpf_program_template::token::token_2022::id;
+
+pub const TRANSFER_FEE_CONFIG: u16 = 1;
+pub const TRANSFER_FEE_AMOUNT: u16 = 2;
+pub const IMMUTABLE_OWNER: u16 = 7;
+pub const MEMO_TRANSFER: u16 = 8;
+pub const NON_TRANSFERABLE: u16 = 9;
+pub const CPI_GUARD: u16 = 11;
+pub const PERMANENT_DELEGATE: u16 = 12;
+
+const ACCOUNT_TYPE_MINT: u8 = 1;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+
+/// Mint data with the given extensions
+pub fn mint_data(mint: Mint, extensions: &[(u16, Vec<u8>)]) -> Vec<u8> {
+    let mut data = vec![0; Mint::LEN];
+    Mint::pack(mint, &mut data).unwrap();
+    if !extensions.is_empty() {
+        data.resize(TokenAccount::LEN, 0);
+        data.push(ACCOUNT_TYPE_MINT);
+        append(&mut data, extensions);
+    }
+    data
+}
+
+/// Token account data, with a TransferFeeAmount extension when the mint charges fees - like Token-2022 sizes them
+pub fn account_data(account: TokenAccount, mint_data: &[u8]) -> Vec<u8> {
+    let mut data = vec![0; TokenAccount::LEN];
+    TokenAccount::pack(account, &mut data).unwrap();
+    data.push(ACCOUNT_TYPE_ACCOUNT);
+    if find(mint_data, TRANSFER_FEE_CONFIG).is_some() {
+        append(&mut data, &[(TRANSFER_FEE_AMOUNT, vec![0; 8])]);
+    }
+    data
+}
+
+/// TransferFeeConfig value with the same fee for both epochs
+pub fn transfer_fee_config(basis_points: u16, maximum_fee: u64) -> (u16, Vec<u8>) {
+    let mut value = vec![0; 72];
+    for _ in 0..2 {
+        value.extend_from_slice(&0u64.to_le_bytes());
+        value.extend_from_slice(&maximum_fee.to_le_bytes());
+        value.extend_from_slice(&basis_points.to_le_bytes());
+    }
+    (TRANSFER_FEE_CONFIG, value)
+}
+
+/// Adds an extension to existing account data
+pub fn push_extension(data: &mut Vec<u8>, extension: (u16, Vec<u8>)) {
+    append(data, &[extension]);
+}
+
+fn append(data: &mut Vec<u8>, extensions: &[(u16, Vec<u8>)]) {
+    for (extension_type, value) in extensions {
+        data.extend_from_slice(&extension_type.to_le_bytes());
+        data.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        data.extend_from_slice(value);
+    }
+}
+
+/// Byte range of an extension's value
+pub fn find(data: &[u8], wanted: u16) -> Option<std::ops::Range<usize>> {
+    let mut offset = TokenAccount::LEN + 1;
+    while offset + 4 <= data.len() {
+        let extension_type = u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let len = u16::from_le_bytes([data[offset + 2], data[offset + 3]]) as usize;
+        if extension_type == wanted {
+            return Some(offset + 4..offset + 4 + len);
+        }
+        offset += 4 + len;
+    }
+    None
+}
+
+fn read_u64(data: &[u8], at: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[at..at + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+fn add_u64(data: &mut [u8], at: usize, amount: u64) -> ProgramResult {
+    let sum = read_u64(data, at)
+        .checked_add(amount)
+        .ok_or(ProgramError::InvalidArgument)?;
+    data[at..at + 8].copy_from_slice(&sum.to_le_bytes());
+    Ok(())
+}
+
+fn account(info: &AccountInfo) -> Result<TokenAccount, ProgramError> {
+    if *info.owner != id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    TokenAccount::unpack(&info.data.borrow()[..TokenAccount::LEN])
+}
+
+fn store(info: &AccountInfo, account: TokenAccount) {
+    TokenAccount::pack(account, &mut info.data.borrow_mut()[..TokenAccount::LEN]).unwrap();
+}
+
+fn fee(mint_data: &[u8], amount: u64, epoch: u64) -> u64 {
+    let config = match find(mint_data, TRANSFER_FEE_CONFIG) {
+        Some(range) => range.start,
+        None => return 0,
+    };
+    let newer = config + 72 + 18;
+    let schedule = if epoch >= read_u64(mint_data, newer) {
+        newer
+    } else {
+        config + 72
+    };
+    let maximum_fee = read_u64(mint_data, schedule + 8);
+    let basis_points =
+        u16::from_le_bytes([mint_data[schedule + 16], mint_data[schedule + 17]]) as u128;
+    let fee = (amount as u128 * basis_points).div_ceil(10_000) as u64;
+    fee.min(maximum_fee)
+}
+
+pub fn process(accounts: &[AccountInfo], data: &[u8], epoch: u64) -> ProgramResult {
+    match data {
+        // SetAuthority, AccountOwner only
+        [6, 2, 1, new_owner @ ..] if new_owner.len() == 32 => {
+            let mut token_account = account(&accounts[0])?;
+            if token_account.owner != *accounts[1].key || !accounts[1].is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            token_account.owner = Pubkey::new(new_owner);
+            store(&accounts[0], token_account);
+            Ok(())
+        }
+        // Approve
+        [4, rest @ ..] if rest.len() == 8 => {
+            let mut token_account = account(&accounts[0])?;
+            if token_account.owner != *accounts[2].key || !accounts[2].is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            token_account.delegate = COption::Some(*accounts[1].key);
+            token_account.delegated_amount = read_u64(rest, 0);
+            store(&accounts[0], token_account);
+            Ok(())
+        }
+        // Revoke
+        [5] => {
+            let mut token_account = account(&accounts[0])?;
+            if token_account.owner != *accounts[1].key || !accounts[1].is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            token_account.delegate = COption::None;
+            token_account.delegated_amount = 0;
+            store(&accounts[0], token_account);
+            Ok(())
+        }
+        // CloseAccount
+        [9] => {
+            let token_account = account(&accounts[0])?;
+            if token_account.owner != *accounts[2].key || !accounts[2].is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            let withheld = find(&accounts[0].data.borrow(), TRANSFER_FEE_AMOUNT)
+                .map(|range| read_u64(&accounts[0].data.borrow(), range.start))
+                .unwrap_or(0);
+            if token_account.amount != 0 || withheld != 0 {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let lamports = accounts[0].lamports();
+            **accounts[1].lamports.borrow_mut() += lamports;
+            **accounts[0].lamports.borrow_mut() = 0;
+            for byte in accounts[0].data.borrow_mut().iter_mut() {
+                *byte = 0;
+            }
+            Ok(())
+        }
+        // TransferChecked
+        [12, rest @ ..] if rest.len() == 9 => {
+            let amount = read_u64(rest, 0);
+            let (source, mint, destination, authority) =
+                (&accounts[0], &accounts[1], &accounts[2], &accounts[3]);
+            let mut from = account(source)?;
+            let mut to = account(destination)?;
+            if *mint.owner != id() {
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            let mint_data = mint.data.borrow().to_vec();
+            let decimals = Mint::unpack(&mint_data[..Mint::LEN])?.decimals;
+            if from.mint != *mint.key || to.mint != *mint.key || decimals != rest[8] {
+                return Err(ProgramError::InvalidArgument);
+            }
+            if from.state != AccountState::Initialized || to.state != AccountState::Initialized {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if find(&mint_data, NON_TRANSFERABLE).is_some() {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if !authority.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if from.owner != *authority.key {
+                match from.delegate {
+                    COption::Some(delegate)
+                        if delegate == *authority.key && from.delegated_amount >= amount =>
+                    {
+                        from.delegated_amount -= amount;
+                        if from.delegated_amount == 0 {
+                            from.delegate = COption::None;
+                        }
+                    }
+                    _ => return Err(ProgramError::MissingRequiredSignature),
+                }
+            }
+            from.amount = from
+                .amount
+                .checked_sub(amount)
+                .ok_or(ProgramError::InsufficientFunds)?;
+            let fee = fee(&mint_data, amount, epoch);
+            if source.key == destination.key {
+                from.amount += amount;
+                store(source, from);
+                return Ok(());
+            }
+            to.amount += amount - fee;
+            store(source, from);
+            store(destination, to);
+            if fee > 0 {
+                let range = find(&destination.data.borrow(), TRANSFER_FEE_AMOUNT)
+                    .ok_or(ProgramError::InvalidAccountData)?;
+                add_u64(&mut destination.data.borrow_mut(), range.start, fee)?;
+            }
+            Ok(())
+        }
+        // TransferFeeExtension::HarvestWithheldTokensToMint
+        [26, 4] => {
+            let mint = &accounts[0];
+            let config = find(&mint.data.borrow(), TRANSFER_FEE_CONFIG)
+                .ok_or(ProgramError::InvalidAccountData)?;
+            for source in &accounts[1..] {
+                if account(source)?.mint != *mint.key {
+                    continue;
+                }
+                let range = match find(&source.data.borrow(), TRANSFER_FEE_AMOUNT) {
+                    Some(range) => range,
+                    None => continue,
+                };
+                let withheld = read_u64(&source.data.borrow(), range.start);
+                source.data.borrow_mut()[range.clone()].copy_from_slice(&0u64.to_le_bytes());
+                add_u64(&mut mint.data.borrow_mut(), config.start + 64, withheld)?;
+            }
+            Ok(())
+        }
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
@@ -0,0 +1,343 @@
+mod common;
+
+use solana_program::{
+    clock::Clock, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+};
+
+use bpf_program_template::{
+    error::EscrowError,
+    merkle,
+    state::{ClaimedBitmap, Distribution},
+};
+
+use common::{Account, EscrowFixture, ProgramTest};
+
+const DEADLINE: i64 = 1_000;
+
+// the root of (recipient, amount) leaves and a proof for each of them, odd nodes are carried up as they are
+fn tree(shares: &[(Pubkey, u64)]) -> ([u8; 32], Vec<Vec<[u8; 32]>>) {
+    let mut level: Vec<[u8; 32]> = shares
+        .iter()
+        .enumerate()
+        .map(|(i, (recipient, amount))| merkle::leaf(i as u64, recipient, *amount))
+        .collect();
+    let mut proofs = vec![vec![]; shares.len()];
+    // which node of the current level each leaf is under
+    let mut positions: Vec<usize> = (0..shares.len()).collect();
+    while level.len() > 1 {
+        for (proof, position) in proofs.iter_mut().zip(positions.iter_mut()) {
+            if let Some(sibling) = level.get(*position ^ 1) {
+                proof.push(*sibling);
+            }
+            *position /= 2;
+        }
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => merkle::node(a, b),
+                [a] => *a,
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    (level[0], proofs)
+}
+
+struct Airdrop {
+    f: EscrowFixture,
+    distribution: Pubkey,
+    bitmap: Pubkey,
+    recipients: Vec<(Pubkey, Pubkey)>, //wallet, X account
+    shares: Vec<(Pubkey, u64)>,
+    proofs: Vec<Vec<[u8; 32]>>,
+}
+
+impl Airdrop {
+    fn claim(&self, test: &ProgramTest, index: usize) -> solana_program::instruction::Instruction {
+        self.f.claim_distribution(
+            test,
+            self.distribution,
+            self.bitmap,
+            self.recipients[index].1,
+            index as u64,
+            self.shares[index].1,
+            &self.proofs[index],
+        )
+    }
+}
+
+// alice pays 100, 200, .. out of 1000 X to `amounts.len()` people
+fn airdrop(test: &mut ProgramTest, amounts: &[u64]) -> Airdrop {
+    let f = EscrowFixture::new(test, 1_000, 0);
+    let recipients: Vec<_> = amounts
+        .iter()
+        .map(|_| {
+            let wallet = test.add_wallet(1_000_000_000);
+            (wallet, test.add_token_account(&f.mint_x, &wallet, 0))
+        })
+        .collect();
+    let shares: Vec<_> = recipients
+        .iter()
+        .zip(amounts)
+        .map(|((wallet, _), amount)| (*wallet, *amount))
+        .collect();
+    let (root, proofs) = tree(&shares);
+    let distribution = test.add_program_account(Distribution::LEN);
+    let bitmap = test.add_program_account(ClaimedBitmap::len(amounts.len() as u64));
+    test.process(&f.init_distribution(
+        test,
+        distribution,
+        bitmap,
+        root,
+        amounts.len() as u64,
+        DEADLINE,
+    ))
+    .unwrap();
+    Airdrop {
+        f,
+        distribution,
+        bitmap,
+        recipients,
+        shares,
+        proofs,
+    }
+}
+
+#[test]
+fn everyone_claims_their_share() {
+    let mut test = ProgramTest::new();
+    let d = airdrop(&mut test, &[100, 200, 300, 150, 250]);
+
+    assert_eq!(test.token_account(&d.f.temp_x).unwrap().owner, test.pda().0);
+    let distribution = Distribution::unpack(&test.account(&d.distribution).data).unwrap();
+    assert_eq!(distribution.initializer_pubkey, d.f.initializer);
+    assert_eq!(distribution.num_recipients, 5);
+
+    for i in 0..5 {
+        test.process(&d.claim(&test, i)).unwrap();
+        assert_eq!(test.token_balance(&d.recipients[i].1), d.shares[i].1);
+        assert!(ClaimedBitmap::is_claimed(
+            &test.account(&d.bitmap).data,
+            i as u64
+        ));
+    }
+    assert_eq!(test.token_balance(&d.f.temp_x), 0);
+}
+
+#[test]
+fn shares_can_only_be_claimed_once() {
+    let mut test = ProgramTest::new();
+    let d = airdrop(&mut test, &[100, 200, 300]);
+
+    test.process(&d.claim(&test, 1)).unwrap();
+    assert_eq!(
+        test.process(&d.claim(&test, 1)),
+        Err(EscrowError::AlreadyClaimed.into())
+    );
+    // not even into another account of theirs
+    let other_x = test.add_token_account(&d.f.mint_x, &d.recipients[1].0, 0);
+    let mut ix = d.claim(&test, 1);
+    ix.accounts[0].pubkey = other_x;
+    assert_eq!(test.process(&ix), Err(EscrowError::AlreadyClaimed.into()));
+    assert_eq!(test.token_balance(&d.f.temp_x), 800);
+    assert!(!ClaimedBitmap::is_claimed(&test.account(&d.bitmap).data, 0));
+}
+
+#[test]
+fn proofs_have_to_match_the_leaf() {
+    let mut test = ProgramTest::new();
+    let d = airdrop(&mut test, &[100, 200, 300]);
+    let invalid = Err(EscrowError::InvalidProof.into());
+
+    // paying someone else
+    let mut ix = d.claim(&test, 0);
+    ix.accounts[0].pubkey = d.recipients[1].1;
+    assert_eq!(test.process(&ix), invalid);
+    // more than the share
+    let proof = d.proofs[0].clone();
+    let ix = d.f.claim_distribution(
+        &test,
+        d.distribution,
+        d.bitmap,
+        d.recipients[0].1,
+        0,
+        101,
+        &proof,
+    );
+    assert_eq!(test.process(&ix), invalid);
+    // someone else's index
+    let ix = d.f.claim_distribution(
+        &test,
+        d.distribution,
+        d.bitmap,
+        d.recipients[0].1,
+        1,
+        100,
+        &proof,
+    );
+    assert_eq!(test.process(&ix), invalid);
+    // a proof that stops short of the root
+    let ix = d.f.claim_distribution(
+        &test,
+        d.distribution,
+        d.bitmap,
+        d.recipients[0].1,
+        0,
+        100,
+        &proof[1..],
+    );
+    assert_eq!(test.process(&ix), invalid);
+    // past the last leaf
+    let ix = d.f.claim_distribution(
+        &test,
+        d.distribution,
+        d.bitmap,
+        d.recipients[0].1,
+        3,
+        100,
+        &proof,
+    );
+    assert_eq!(test.process(&ix), invalid);
+    // someone else's bitmap
+    let mut ix = d.claim(&test, 0);
+    ix.accounts[2].pubkey = test.add_program_account(ClaimedBitmap::len(3));
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+
+    assert_eq!(test.token_balance(&d.f.temp_x), 1_000);
+    test.process(&d.claim(&test, 0)).unwrap();
+}
+
+#[test]
+fn initializer_reclaims_after_the_deadline() {
+    let mut test = ProgramTest::new();
+    let d = airdrop(&mut test, &[100, 200, 300]);
+    test.process(&d.claim(&test, 0)).unwrap();
+    let reclaim = d.f.reclaim_distribution(&test, d.distribution, d.bitmap);
+
+    assert_eq!(
+        test.process(&reclaim),
+        Err(EscrowError::TimelockNotExpired.into())
+    );
+    test.set_clock(Clock {
+        unix_timestamp: DEADLINE,
+        ..Clock::default()
+    });
+    assert_eq!(
+        test.process(&d.claim(&test, 1)),
+        Err(EscrowError::DistributionEnded.into())
+    );
+    // only the initializer
+    let mut ix = reclaim.clone();
+    ix.accounts[0].pubkey = d.f.taker;
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+
+    let rent =
+        test.lamports(&d.distribution) + test.lamports(&d.bitmap) + test.lamports(&d.f.temp_x);
+    let initializer_lamports = test.lamports(&d.f.initializer);
+    test.process(&reclaim).unwrap();
+    assert_eq!(test.token_balance(&d.f.initializer_x), 900);
+    assert!(test.token_account(&d.f.temp_x).is_none());
+    assert_eq!(test.lamports(&d.distribution), 0);
+    assert_eq!(test.lamports(&d.bitmap), 0);
+    assert_eq!(test.lamports(&d.f.initializer), initializer_lamports + rent);
+}
+
+#[test]
+fn init_checks() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 0);
+    let distribution = test.add_program_account(Distribution::LEN);
+    let bitmap = test.add_program_account(ClaimedBitmap::len(10));
+    let init = |test: &ProgramTest, bitmap, num_recipients, deadline| {
+        f.init_distribution(
+            test,
+            distribution,
+            bitmap,
+            [7; 32],
+            num_recipients,
+            deadline,
+        )
+    };
+
+    assert_eq!(
+        test.process(&init(&test, bitmap, 0, DEADLINE)),
+        Err(EscrowError::InvalidInstruction.into())
+    );
+    test.set_clock(Clock {
+        unix_timestamp: DEADLINE,
+        ..Clock::default()
+    });
+    assert_eq!(
+        test.process(&init(&test, bitmap, 10, DEADLINE)),
+        Err(EscrowError::TimelockInThePast.into())
+    );
+    // the bitmap has to fit the leaves exactly
+    assert_eq!(
+        test.process(&init(&test, bitmap, 100, DEADLINE + 1)),
+        Err(ProgramError::InvalidAccountData)
+    );
+    // and can't be the distribution itself, even when the leaves fit in one that size
+    assert_eq!(ClaimedBitmap::len(896), Distribution::LEN);
+    assert_eq!(
+        test.process(&init(&test, distribution, 896, DEADLINE + 1)),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    test.process(&init(&test, bitmap, 10, DEADLINE + 1))
+        .unwrap();
+    // neither account can be reused
+    let other = EscrowFixture::new(&mut test, 1_000, 0);
+    let again = other.init_distribution(&test, distribution, bitmap, [7; 32], 10, DEADLINE + 1);
+    assert_eq!(
+        test.process(&again),
+        Err(ProgramError::AccountAlreadyInitialized)
+    );
+    let fresh = test.add_program_account(Distribution::LEN);
+    let again = other.init_distribution(&test, fresh, bitmap, [7; 32], 10, DEADLINE + 1);
+    assert_eq!(
+        test.process(&again),
+        Err(ProgramError::AccountAlreadyInitialized)
+    );
+}
+
+#[test]
+fn forged_distributions_cant_drain_other_vaults() {
+    let mut test = ProgramTest::new();
+    let d = airdrop(&mut test, &[100, 200, 300]);
+    // someone else's listing, its X in a vault of the PDA's
+    let victim = EscrowFixture::new(&mut test, 1_000, 0);
+    test.process(&victim.init_escrow(&test, 500)).unwrap();
+
+    // a distribution of the attacker's own, paying them all of that vault and pointing at the real airdrop's bitmap
+    let attacker = test.add_wallet(1_000_000_000);
+    let attacker_x = test.add_token_account(&victim.mint_x, &attacker, 0);
+    let mut data = vec![0; Distribution::LEN];
+    Distribution {
+        is_initialized: true,
+        initializer_pubkey: attacker,
+        vault_pubkey: victim.temp_x,
+        claimed_bitmap_pubkey: d.bitmap,
+        merkle_root: merkle::leaf(0, &attacker, 1_000),
+        num_recipients: 3,
+        deadline: i64::MAX,
+    }
+    .pack_into_slice(&mut data);
+    let forged = test.add_account(
+        Pubkey::new_unique(),
+        Account {
+            owner: attacker,
+            lamports: 1_000_000_000,
+            data,
+            executable: false,
+        },
+    );
+    let claim = victim.claim_distribution(&test, forged, d.bitmap, attacker_x, 0, 1_000, &[]);
+    assert_eq!(test.process(&claim), Err(ProgramError::IncorrectProgramId));
+
+    // and even one of ours only goes with the bitmap that was set up for it
+    test.accounts.get_mut(&forged).unwrap().owner = test.program_id;
+    assert_eq!(test.process(&claim), Err(ProgramError::InvalidAccountData));
+    assert_eq!(test.token_balance(&victim.temp_x), 1_000);
+    assert_eq!(test.token_balance(&attacker_x), 0);
+    test.process(&d.claim(&test, 0)).unwrap();
+}
@@ -0,0 +1,182 @@
+mod common;
+
+use proptest::prelude::*;
+use solana_program::clock::Clock;
+
+use bpf_program_template::{
+    auction::dutch_price,
+    error::EscrowError,
+    state::{DutchTerms, Escrow, EscrowMode, Terms},
+};
+
+use common::{EscrowFixture, ProgramTest};
+
+const START_PRICE: u64 = 1_600;
+const FLOOR: u64 = 100;
+const START: i64 = 1_000;
+const END: i64 = 1_400;
+
+fn at(test: &ProgramTest, unix_timestamp: i64) {
+    test.set_clock(Clock {
+        unix_timestamp,
+        ..Clock::default()
+    });
+}
+
+// alice auctions 1000 X, the taker has 5000 Y
+fn listed(test: &mut ProgramTest, decay: u8) -> EscrowFixture {
+    let f = EscrowFixture::new(test, 1_000, 5_000).sized_for(test, EscrowMode::DutchAuction);
+    test.process(&f.init_dutch_auction(test, START_PRICE, FLOOR, START, END, decay))
+        .unwrap();
+    f
+}
+
+fn auction(start_price: u64, start: i64, end: i64, decay: u8) -> DutchTerms {
+    DutchTerms {
+        start_price,
+        auction_start: start,
+        auction_end: end,
+        decay,
+    }
+}
+
+#[test]
+fn take_at_the_current_price() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test, 0);
+
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(escrow.mode, EscrowMode::DutchAuction);
+    assert_eq!(escrow.expected_amount, FLOOR);
+    assert_eq!(
+        escrow.terms,
+        Terms::DutchAuction(DutchTerms {
+            start_price: START_PRICE,
+            auction_start: START,
+            auction_end: END,
+            decay: 0,
+        })
+    );
+
+    // halfway down the line
+    at(&test, START + 200);
+    test.process(&f.take_dutch_auction(&test, 1_000, START_PRICE))
+        .unwrap();
+    assert_eq!(test.token_balance(&f.initializer_y), 850);
+    assert_eq!(test.token_balance(&f.taker_y), 5_000 - 850);
+    assert_eq!(test.token_balance(&f.taker_x), 1_000);
+    assert!(test.token_account(&f.temp_x).is_none());
+    assert_eq!(test.lamports(&f.escrow), 0);
+}
+
+#[test]
+fn max_price_is_respected() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test, 0);
+
+    at(&test, START + 200);
+    assert_eq!(
+        test.process(&f.take_dutch_auction(&test, 1_000, 849)),
+        Err(EscrowError::PriceAboveMaximum.into())
+    );
+    test.process(&f.take_dutch_auction(&test, 1_000, 850))
+        .unwrap();
+}
+
+#[test]
+fn auction_needs_the_auction_instruction() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test, 0);
+    // a plain Exchange would get it for the floor
+    assert_eq!(
+        test.process(&f.exchange(&test, 1_000)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 5_000);
+    test.process(&f.init_escrow(&test, 500)).unwrap();
+    assert_eq!(
+        test.process(&f.take_dutch_auction(&test, 1_000, 500)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+}
+
+#[test]
+fn invalid_curves_are_rejected() {
+    let mut test = ProgramTest::new();
+    let f =
+        EscrowFixture::new(&mut test, 1_000, 5_000).sized_for(&mut test, EscrowMode::DutchAuction);
+
+    for (start_price, floor, start, end, decay) in [
+        (FLOOR, START_PRICE, START, END, 0),
+        (START_PRICE, FLOOR, END, START, 0),
+        (START_PRICE, FLOOR, START, START, 0),
+        (START_PRICE, FLOOR, START, END, 65),
+    ]
+    .iter()
+    {
+        assert_eq!(
+            test.process(&f.init_dutch_auction(&test, *start_price, *floor, *start, *end, *decay)),
+            Err(EscrowError::InvalidPriceCurve.into())
+        );
+    }
+}
+
+#[test]
+fn seller_can_cancel() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test, 4);
+
+    test.process(&f.cancel(&test)).unwrap();
+    assert_eq!(test.token_balance(&f.initializer_x), 1_000);
+    assert_eq!(test.lamports(&f.escrow), 0);
+}
+
+#[test]
+fn exponential_decay_halves_the_excess() {
+    let terms = auction(START_PRICE, START, END, 4);
+    assert_eq!(dutch_price(&terms, FLOOR, START).unwrap(), START_PRICE);
+    // 4 halvings over 400s, one every 100s
+    assert_eq!(
+        dutch_price(&terms, FLOOR, START + 100).unwrap(),
+        FLOOR + 750
+    );
+    assert_eq!(
+        dutch_price(&terms, FLOOR, START + 150).unwrap(),
+        FLOOR + 563
+    );
+    assert_eq!(
+        dutch_price(&terms, FLOOR, START + 200).unwrap(),
+        FLOOR + 375
+    );
+    // the last interval goes down to the floor rather than to another half
+    assert_eq!(dutch_price(&terms, FLOOR, START + 350).unwrap(), FLOOR + 94);
+    assert_eq!(dutch_price(&terms, FLOOR, END).unwrap(), FLOOR);
+}
+
+proptest! {
+    #[test]
+    fn price_falls_from_start_to_floor(
+        floor in any::<u64>(),
+        excess in any::<u64>(),
+        start in any::<i64>(),
+        duration in 1u64..=u64::MAX,
+        decay in 0u8..=64,
+        a in any::<i64>(),
+        b in any::<i64>(),
+    ) {
+        let start_price = floor.saturating_add(excess);
+        let end = start.saturating_add(duration as i64);
+        prop_assume!(end > start);
+        let terms = auction(start_price, start, end, decay);
+
+        let (earlier, later) = if a <= b { (a, b) } else { (b, a) };
+        let p1 = dutch_price(&terms, floor, earlier).unwrap();
+        let p2 = dutch_price(&terms, floor, later).unwrap();
+        prop_assert!(p1 >= p2);
+        prop_assert!(p1 <= start_price && p2 >= floor);
+        prop_assert_eq!(dutch_price(&terms, floor, start).unwrap(), start_price);
+        prop_assert_eq!(dutch_price(&terms, floor, end).unwrap(), floor);
+    }
+}
@@ -0,0 +1,214 @@
+mod common;
+
+use solana_program::{
+    clock::Clock, program_error::ProgramError, pubkey::Pubkey,
+};
+
+use bpf_program_template::{
+    error::EscrowError,
+    state::{Escrow, EscrowMode},
+};
+
+use common::{EscrowFixture, ProgramTest};
+
+const RESERVE: u64 = 500;
+const INCREMENT: u64 = 50;
+const END: i64 = 2_000;
+const EXTENSION: i64 = 100;
+
+fn at(test: &ProgramTest, unix_timestamp: i64) {
+    test.set_clock(Clock {
+        unix_timestamp,
+        ..Clock::default()
+    });
+}
+
+// alice auctions 1000 X, the taker has 5000 Y to bid with
+fn listed(test: &mut ProgramTest) -> EscrowFixture {
+    let f = EscrowFixture::new(test, 1_000, 5_000).sized_for(test, EscrowMode::EnglishAuction);
+    at(test, 1_000);
+    test.process(&f.init_english_auction(test, RESERVE, INCREMENT, END, EXTENSION))
+        .unwrap();
+    f
+}
+
+// a second bidder with 5000 Y, and an X account to win into
+fn bidder(test: &mut ProgramTest, f: &EscrowFixture) -> (Pubkey, Pubkey, Pubkey) {
+    let wallet = test.add_wallet(1_000_000_000);
+    let y = test.add_token_account(&f.mint_y, &wallet, 5_000);
+    let x = test.add_token_account(&f.mint_x, &wallet, 0);
+    (wallet, y, x)
+}
+
+#[test]
+fn outbid_then_settle() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test);
+    let (carol, carol_y, carol_x) = bidder(&mut test, &f);
+
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(escrow.mode, EscrowMode::EnglishAuction);
+    assert_eq!(
+        escrow.terms.auction().unwrap().bid_vault_pubkey,
+        f.bid_vault
+    );
+    assert_eq!(
+        test.token_account(&f.bid_vault).unwrap().owner,
+        test.pda().0
+    );
+
+    test.process(&f.bid(&test, f.taker, f.taker_y, RESERVE, None))
+        .unwrap();
+    assert_eq!(test.token_balance(&f.bid_vault), RESERVE);
+    assert_eq!(test.token_balance(&f.taker_y), 5_000 - RESERVE);
+
+    // carol's bid sends the taker's back
+    test.process(&f.bid(&test, carol, carol_y, 700, Some(f.taker_y)))
+        .unwrap();
+    assert_eq!(test.token_balance(&f.bid_vault), 700);
+    assert_eq!(test.token_balance(&f.taker_y), 5_000);
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    let auction = escrow.terms.auction().unwrap();
+    assert_eq!((auction.bidder_pubkey, auction.highest_bid), (carol, 700));
+
+    at(&test, END);
+    let rent = test.lamports(&f.escrow) + test.lamports(&f.temp_x) + test.lamports(&f.bid_vault);
+    let initializer_lamports = test.lamports(&f.initializer);
+    test.process(&f.settle_auction(&test, carol_x)).unwrap();
+    assert_eq!(test.token_balance(&carol_x), 1_000);
+    assert_eq!(test.token_balance(&carol_y), 5_000 - 700);
+    assert_eq!(test.token_balance(&f.initializer_y), 700);
+    assert!(test.token_account(&f.temp_x).is_none());
+    assert!(test.token_account(&f.bid_vault).is_none());
+    assert_eq!(test.lamports(&f.escrow), 0);
+    assert_eq!(test.lamports(&f.initializer), initializer_lamports + rent);
+}
+
+#[test]
+fn bids_have_to_meet_reserve_and_increment() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test);
+    let (carol, carol_y, _) = bidder(&mut test, &f);
+
+    assert_eq!(
+        test.process(&f.bid(&test, f.taker, f.taker_y, RESERVE - 1, None)),
+        Err(EscrowError::BidTooLow.into())
+    );
+    test.process(&f.bid(&test, f.taker, f.taker_y, RESERVE, None))
+        .unwrap();
+    assert_eq!(
+        test.process(&f.bid(
+            &test,
+            carol,
+            carol_y,
+            RESERVE + INCREMENT - 1,
+            Some(f.taker_y)
+        )),
+        Err(EscrowError::BidTooLow.into())
+    );
+    test.process(&f.bid(&test, carol, carol_y, RESERVE + INCREMENT, Some(f.taker_y)))
+        .unwrap();
+}
+
+#[test]
+fn refund_goes_to_the_previous_bidder() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test);
+    let (carol, carol_y, _) = bidder(&mut test, &f);
+    test.process(&f.bid(&test, f.taker, f.taker_y, RESERVE, None))
+        .unwrap();
+
+    // nor can the refund be skipped
+    assert_eq!(
+        test.process(&f.bid(&test, carol, carol_y, 700, None)),
+        Err(ProgramError::NotEnoughAccountKeys)
+    );
+    assert_eq!(
+        test.process(&f.bid(&test, carol, carol_y, 700, Some(carol_y))),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    // but any of the taker's Y accounts will do
+    let taker_other_y = test.add_token_account(&f.mint_y, &f.taker, 0);
+    test.process(&f.bid(&test, carol, carol_y, 700, Some(taker_other_y)))
+        .unwrap();
+    assert_eq!(test.token_balance(&taker_other_y), RESERVE);
+}
+
+#[test]
+fn late_bids_extend_the_auction() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test);
+    let (carol, carol_y, _) = bidder(&mut test, &f);
+
+    at(&test, END - EXTENSION - 1);
+    test.process(&f.bid(&test, f.taker, f.taker_y, RESERVE, None))
+        .unwrap();
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(escrow.terms.auction().unwrap().end, END);
+
+    at(&test, END - 50);
+    test.process(&f.bid(&test, carol, carol_y, 600, Some(f.taker_y)))
+        .unwrap();
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(escrow.terms.auction().unwrap().end, END - 50 + EXTENSION);
+
+    at(&test, END);
+    assert_eq!(
+        test.process(&f.settle_auction(&test, f.taker_x)),
+        Err(EscrowError::TimelockNotExpired.into())
+    );
+    at(&test, END - 50 + EXTENSION);
+    assert_eq!(
+        test.process(&f.bid(&test, f.taker, f.taker_y, 700, Some(carol_y))),
+        Err(EscrowError::AuctionEnded.into())
+    );
+}
+
+#[test]
+fn unsold_goes_back_to_the_seller() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test);
+
+    at(&test, END);
+    assert_eq!(
+        test.process(&f.settle_auction(&test, f.taker_x)),
+        Err(ProgramError::InvalidAccountData)
+    );
+    test.process(&f.settle_auction(&test, f.initializer_x))
+        .unwrap();
+    assert_eq!(test.token_balance(&f.initializer_x), 1_000);
+    assert!(test.token_account(&f.bid_vault).is_none());
+    assert_eq!(test.lamports(&f.escrow), 0);
+}
+
+#[test]
+fn only_the_winner_gets_x() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test);
+    test.process(&f.bid(&test, f.taker, f.taker_y, RESERVE, None))
+        .unwrap();
+
+    at(&test, END);
+    assert_eq!(
+        test.process(&f.settle_auction(&test, f.initializer_x)),
+        Err(ProgramError::InvalidAccountData)
+    );
+    test.process(&f.settle_auction(&test, f.taker_x)).unwrap();
+    assert_eq!(test.token_balance(&f.taker_x), 1_000);
+}
+
+#[test]
+fn no_exchange_or_cancel() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test);
+
+    assert_eq!(
+        test.process(&f.exchange(&test, 1_000)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+    assert_eq!(
+        test.process(&f.cancel(&test)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use solana_program::program_error::ProgramError;
+
+use bpf_program_template::{error::EscrowError, state::EscrowMode};
+
+use common::{EscrowFixture, ProgramTest};
+
+// three sellers of 1000 X each, asking 500, 300 and 800 Y. The first one's taker has 5000 Y to sweep them with
+fn book(test: &mut ProgramTest) -> Vec<EscrowFixture> {
+    let first = EscrowFixture::new(test, 1_000, 5_000);
+    let (mint_x, mint_y) = (first.mint_x, first.mint_y);
+    let mut book = vec![first];
+    for _ in 0..2 {
+        book.push(EscrowFixture::for_pair(test, mint_x, mint_y, 1_000, 0));
+    }
+    for (f, price) in book.iter().zip([500, 300, 800].iter()) {
+        test.process(&f.init_escrow(test, *price)).unwrap();
+    }
+    book
+}
+
+#[test]
+fn cheapest_first() {
+    let mut test = ProgramTest::new();
+    let book = book(&mut test);
+    let taker = &book[0];
+
+    test.process(&taker.exchange_many(&test, 1_500, 800, &[&book[0], &book[1], &book[2]]))
+        .unwrap();
+    // the 300 and the 500 are enough, the 800 stays listed
+    assert_eq!(test.token_balance(&taker.taker_x), 2_000);
+    assert_eq!(test.token_balance(&taker.taker_y), 5_000 - 800);
+    assert_eq!(test.token_balance(&book[1].initializer_y), 300);
+    assert_eq!(test.token_balance(&book[0].initializer_y), 500);
+    assert_eq!(test.lamports(&book[0].escrow), 0);
+    assert_eq!(test.lamports(&book[1].escrow), 0);
+    assert_eq!(test.token_balance(&book[2].temp_x), 1_000);
+    assert!(test.lamports(&book[2].escrow) > 0);
+}
+
+#[test]
+fn max_y_covers_the_total() {
+    let mut test = ProgramTest::new();
+    let book = book(&mut test);
+    let taker = &book[0];
+
+    assert_eq!(
+        test.process(&taker.exchange_many(&test, 1_500, 799, &[&book[0], &book[1], &book[2]])),
+        Err(EscrowError::PriceAboveMaximum.into())
+    );
+    // nothing was taken
+    assert_eq!(test.token_balance(&taker.taker_y), 5_000);
+    assert_eq!(test.token_balance(&book[1].temp_x), 1_000);
+}
+
+#[test]
+fn target_has_to_be_reached() {
+    let mut test = ProgramTest::new();
+    let book = book(&mut test);
+    let taker = &book[0];
+
+    assert_eq!(
+        test.process(&taker.exchange_many(&test, 3_001, 5_000, &[&book[0], &book[1], &book[2]])),
+        Err(EscrowError::TargetNotFilled.into())
+    );
+    test.process(&taker.exchange_many(&test, 3_000, 1_600, &[&book[0], &book[1], &book[2]]))
+        .unwrap();
+    assert_eq!(test.token_balance(&taker.taker_x), 3_000);
+}
+
+#[test]
+fn approve_mode_and_sponsored_listings() {
+    let mut test = ProgramTest::new();
+    let first = EscrowFixture::new(&mut test, 1_000, 5_000);
+    test.process(&first.init_approve_escrow(&test, 500, 600))
+        .unwrap();
+    let second = EscrowFixture::for_pair(&mut test, first.mint_x, first.mint_y, 1_000, 0);
+    let second = EscrowFixture {
+        rent_payer: Some(test.add_wallet(1_000_000_000)),
+        ..second
+    };
+    test.process(&second.init_escrow(&test, 400)).unwrap();
+
+    let rent = test.lamports(&second.escrow) + test.lamports(&second.temp_x);
+    let rent_payer_lamports = test.lamports(&second.rent_payer.unwrap());
+    test.process(&first.exchange_many(&test, 1_600, 900, &[&first, &second]))
+        .unwrap();
+    // only the offered 600 come out of the first initializer's account
+    assert_eq!(test.token_balance(&first.taker_x), 1_600);
+    assert_eq!(test.token_balance(&first.temp_x), 400);
+    assert_eq!(
+        test.lamports(&second.rent_payer.unwrap()),
+        rent_payer_lamports + rent
+    );
+}
+
+#[test]
+fn bad_listings_fail_the_whole_sweep() {
+    let mut test = ProgramTest::new();
+    let book = book(&mut test);
+    let taker = &book[0];
+
+    assert_eq!(
+        test.process(&taker.exchange_many(&test, 1_000, 5_000, &[&book[1], &book[1]])),
+        Err(ProgramError::InvalidAccountData)
+    );
+    let mut ix = taker.exchange_many(&test, 1_000, 5_000, &[&book[1]]);
+    ix.accounts.pop();
+    assert_eq!(test.process(&ix), Err(ProgramError::NotEnoughAccountKeys));
+
+    // the cheapest one is fine, the next one pays someone else
+    let mut ix = taker.exchange_many(&test, 1_500, 5_000, &[&book[1], &book[0]]);
+    ix.accounts[8 + 5 + 3].pubkey = taker.taker_y;
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+    assert_eq!(test.token_balance(&book[1].temp_x), 1_000);
+    assert_eq!(test.token_balance(&taker.taker_y), 5_000);
+
+    let htlc = EscrowFixture::for_pair(&mut test, taker.mint_x, taker.mint_y, 1_000, 0)
+        .sized_for(&mut test, EscrowMode::Htlc);
+    test.process(&htlc.init_htlc(&test, [7; 32], i64::MAX))
+        .unwrap();
+    assert_eq!(
+        test.process(&taker.exchange_many(&test, 1_000, 5_000, &[&book[1], &htlc])),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+}
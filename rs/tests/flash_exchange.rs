@@ -0,0 +1,157 @@
+mod common;
+
+use solana_program::{program_error::ProgramError};
+
+use bpf_program_template::{
+    error::EscrowError,
+    state::{Escrow, EscrowMode},
+};
+
+use common::{EscrowFixture, ProgramTest};
+
+// alice sells 1000 X for 500 Y, the taker has `taker_y` to pay with
+fn listed(test: &mut ProgramTest, taker_y: u64) -> EscrowFixture {
+    let f = EscrowFixture::new(test, 1_000, taker_y);
+    test.process(&f.init_escrow(test, 500)).unwrap();
+    f
+}
+
+#[test]
+fn flash_then_settle() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test, 500);
+
+    let rent = test.lamports(&f.escrow) + test.lamports(&f.temp_x);
+    let initializer_lamports = test.lamports(&f.initializer);
+    test.process_transaction(&[
+        f.flash_exchange(&test, 1_000),
+        f.settle_flash(&test, f.taker, f.taker_y),
+    ])
+    .unwrap();
+    assert_eq!(test.token_balance(&f.taker_x), 1_000);
+    assert_eq!(test.token_balance(&f.taker_y), 0);
+    assert_eq!(test.token_balance(&f.initializer_y), 500);
+    assert!(test.token_account(&f.temp_x).is_none());
+    assert_eq!(test.lamports(&f.escrow), 0);
+    assert_eq!(test.lamports(&f.initializer), initializer_lamports + rent);
+}
+
+#[test]
+fn anyone_can_settle() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test, 0);
+    // eg the pool the taker sold the X into pays alice directly
+    let pool = test.add_wallet(1_000_000_000);
+    let pool_y = test.add_token_account(&f.mint_y, &pool, 500);
+
+    test.process_transaction(&[
+        f.flash_exchange(&test, 1_000),
+        f.settle_flash(&test, pool, pool_y),
+    ])
+    .unwrap();
+    assert_eq!(test.token_balance(&f.taker_x), 1_000);
+    assert_eq!(test.token_balance(&f.initializer_y), 500);
+}
+
+#[test]
+fn flash_needs_a_settle_after_it() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test, 500);
+    let other = listed(&mut test, 500);
+    let flash = f.flash_exchange(&test, 1_000);
+    let settle = f.settle_flash(&test, f.taker, f.taker_y);
+
+    assert_eq!(
+        test.process_transaction(std::slice::from_ref(&flash)),
+        Err(EscrowError::FlashNotSettled.into())
+    );
+    // settling some other escrow doesn't count
+    assert_eq!(
+        test.process_transaction(&[flash.clone(), other.settle_flash(&test, f.taker, f.taker_y)]),
+        Err(EscrowError::FlashNotSettled.into())
+    );
+    // and neither does one that came before
+    assert_eq!(
+        test.process_at(&[settle.clone(), flash.clone()], 1),
+        Err(EscrowError::FlashNotSettled.into())
+    );
+    // the sysvar has to be the real one
+    let mut fake_sysvar = flash.clone();
+    fake_sysvar.accounts[8].pubkey = f.taker_y;
+    assert_eq!(
+        test.process_at(&[fake_sysvar, settle.clone()], 0),
+        Err(ProgramError::InvalidAccountData)
+    );
+    assert_eq!(test.token_balance(&f.temp_x), 1_000);
+}
+
+#[test]
+fn failed_settle_reverts_the_flash() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test, 499);
+
+    assert!(test
+        .process_transaction(&[
+            f.flash_exchange(&test, 1_000),
+            f.settle_flash(&test, f.taker, f.taker_y),
+        ])
+        .is_err());
+    assert_eq!(test.token_balance(&f.taker_x), 0);
+    assert_eq!(test.token_balance(&f.temp_x), 1_000);
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(escrow.mode, EscrowMode::Vault);
+}
+
+#[test]
+fn settling_escrow_is_off_limits() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test, 500);
+    let flash = f.flash_exchange(&test, 1_000);
+    let settle = f.settle_flash(&test, f.taker, f.taker_y);
+
+    // nothing to settle yet
+    assert_eq!(
+        test.process(&settle),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+    // it can't be flashed twice for one payment, nor taken or cancelled before it's paid
+    for between in [flash.clone(), f.exchange(&test, 1_000), f.cancel(&test)].iter() {
+        assert_eq!(
+            test.process_transaction(&[flash.clone(), between.clone(), settle.clone()]),
+            Err(EscrowError::WrongEscrowMode.into())
+        );
+    }
+    // and a flash only takes what the taker expects
+    assert_eq!(
+        test.process_transaction(&[f.flash_exchange(&test, 999), settle.clone()]),
+        Err(EscrowError::ExpectedAmountMismatch.into())
+    );
+    test.process_transaction(&[flash, settle]).unwrap();
+}
+
+#[test]
+fn approve_mode_and_sponsored_rent() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 500);
+    let f = EscrowFixture {
+        rent_payer: Some(test.add_wallet(1_000_000_000)),
+        ..f
+    };
+    test.process(&f.init_approve_escrow(&test, 500, 600))
+        .unwrap();
+
+    let rent = test.lamports(&f.escrow);
+    let rent_payer_lamports = test.lamports(&f.rent_payer.unwrap());
+    test.process_transaction(&[
+        f.flash_exchange(&test, 600),
+        f.settle_flash(&test, f.taker, f.taker_y),
+    ])
+    .unwrap();
+    assert_eq!(test.token_balance(&f.taker_x), 600);
+    assert_eq!(test.token_balance(&f.temp_x), 400);
+    assert_eq!(test.token_balance(&f.initializer_y), 500);
+    assert_eq!(
+        test.lamports(&f.rent_payer.unwrap()),
+        rent_payer_lamports + rent
+    );
+}
@@ -0,0 +1,60 @@
+// runs the fuzz scenarios from tests/common/fuzz.rs for a bounded number of cases on every `cargo test`
+// for open-ended fuzzing use the cargo-fuzz targets in fuzz/
+mod common;
+
+use proptest::prelude::*;
+
+use common::fuzz;
+
+// a well-formed input with a few accounts swapped for others from the pool and a few bytes overwritten,
+// so most cases get past unpack and land on the account checks
+fn mutated_seed() -> impl Strategy<Value = Vec<u8>> {
+    let seeds = fuzz::seeds();
+    (
+        0..seeds.len(),
+        proptest::collection::vec(
+            (any::<prop::sample::Index>(), 0..fuzz::POOL_LEN as u8),
+            0..3,
+        ),
+        proptest::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 0..2),
+    )
+        .prop_map(move |(i, swaps, edits)| {
+            let mut input = seeds[i].clone();
+            let n_accounts = input[1] as usize;
+            for (at, account) in swaps {
+                let at = 2 + at.index(n_accounts);
+                input[at] = (input[at] & 0xc0) | account;
+            }
+            for (at, byte) in edits {
+                let at = at.index(input.len());
+                input[at] = byte;
+            }
+            input
+        })
+}
+
+#[test]
+fn seeds_are_accepted() {
+    for seed in fuzz::seeds() {
+        assert!(fuzz::process(&seed), "seed rejected: {:?}", seed);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2048))]
+
+    #[test]
+    fn unpack_never_panics(data in proptest::collection::vec(any::<u8>(), 0..64)) {
+        fuzz::unpack_instruction(&data);
+    }
+
+    #[test]
+    fn process_never_drains_vaults(input in proptest::collection::vec(any::<u8>(), 0..32)) {
+        fuzz::process(&input);
+    }
+
+    #[test]
+    fn process_never_drains_vaults_near_valid_inputs(input in mutated_seed()) {
+        fuzz::process(&input);
+    }
+}
@@ -0,0 +1,163 @@
+mod common;
+
+use solana_program::{clock::Clock, hash::hash, program_error::ProgramError};
+
+use bpf_program_template::{error::EscrowError, state::EscrowMode};
+
+use common::{EscrowFixture, ProgramTest};
+
+const PREIMAGE: [u8; 32] = [42; 32];
+const TIMELOCK: i64 = 2_000;
+
+fn at(test: &ProgramTest, unix_timestamp: i64) {
+    test.set_clock(Clock {
+        unix_timestamp,
+        ..Clock::default()
+    });
+}
+
+// alice locks 1000 X for the taker, unlocked by PREIMAGE or refundable after TIMELOCK
+fn locked(test: &mut ProgramTest) -> EscrowFixture {
+    let f = EscrowFixture::new(test, 1_000, 0).sized_for(test, EscrowMode::Htlc);
+    at(test, 1_000);
+    test.process(&f.init_htlc(test, hash(&PREIMAGE).to_bytes(), TIMELOCK))
+        .unwrap();
+    assert_eq!(test.token_account(&f.temp_x).unwrap().owner, test.pda().0);
+    f
+}
+
+#[test]
+fn claim_releases_the_vault_and_logs_the_preimage() {
+    let mut test = ProgramTest::new();
+    let f = locked(&mut test);
+
+    let rent = test.lamports(&f.escrow) + test.lamports(&f.temp_x);
+    let initializer_lamports = test.lamports(&f.initializer);
+    test.process(&f.claim(&test, PREIMAGE)).unwrap();
+
+    assert_eq!(test.token_balance(&f.taker_x), 1_000);
+    assert!(test.token_account(&f.temp_x).is_none());
+    assert_eq!(test.lamports(&f.escrow), 0);
+    assert_eq!(test.lamports(&f.initializer), initializer_lamports + rent);
+
+    let event = format!(
+        "EscrowEvent HtlcClaimed escrow={} hashlock={} preimage={}",
+        f.escrow,
+        hex(&hash(&PREIMAGE).to_bytes()),
+        "2a".repeat(32)
+    );
+    assert!(test.logs().contains(&event), "{:?}", test.logs());
+}
+
+#[test]
+fn claim_works_after_the_timelock_too() {
+    let mut test = ProgramTest::new();
+    let f = locked(&mut test);
+    at(&test, TIMELOCK + 1);
+
+    test.process(&f.claim(&test, PREIMAGE)).unwrap();
+    assert_eq!(test.token_balance(&f.taker_x), 1_000);
+}
+
+#[test]
+fn claim_rejects_wrong_preimage() {
+    let mut test = ProgramTest::new();
+    let f = locked(&mut test);
+
+    assert_eq!(
+        test.process(&f.claim(&test, [43; 32])),
+        Err(EscrowError::HashlockMismatch.into())
+    );
+}
+
+#[test]
+fn claim_only_pays_the_recipient() {
+    let mut test = ProgramTest::new();
+    let f = locked(&mut test);
+
+    let mallory = test.add_wallet(1_000_000_000);
+    let mallory_x = test.add_token_account(&f.mint_x, &mallory, 0);
+    let mut ix = f.claim(&test, PREIMAGE);
+    ix.accounts[1].pubkey = mallory_x;
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+}
+
+#[test]
+fn refund_waits_for_the_timelock() {
+    let mut test = ProgramTest::new();
+    let f = locked(&mut test);
+
+    at(&test, TIMELOCK - 1);
+    assert_eq!(
+        test.process(&f.refund(&test)),
+        Err(EscrowError::TimelockNotExpired.into())
+    );
+
+    at(&test, TIMELOCK);
+    test.process(&f.refund(&test)).unwrap();
+    assert_eq!(test.token_balance(&f.initializer_x), 1_000);
+    assert!(test.token_account(&f.temp_x).is_none());
+    assert_eq!(test.lamports(&f.escrow), 0);
+}
+
+#[test]
+fn refund_needs_the_initializer() {
+    let mut test = ProgramTest::new();
+    let f = locked(&mut test);
+    at(&test, TIMELOCK);
+
+    let mut ix = f.refund(&test);
+    ix.accounts[0].is_signer = false;
+    assert_eq!(
+        test.process(&ix),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+}
+
+#[test]
+fn htlc_cannot_be_exchanged_or_cancelled() {
+    let mut test = ProgramTest::new();
+    let f = locked(&mut test);
+
+    assert_eq!(
+        test.process(&f.exchange(&test, 1_000)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+    assert_eq!(
+        test.process(&f.cancel(&test)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+}
+
+#[test]
+fn plain_escrow_cannot_be_claimed_or_refunded() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 5_000);
+    test.process(&f.init_escrow(&test, 500)).unwrap();
+
+    // a zeroed hashlock has no known preimage, but it shouldn't even get that far
+    assert_eq!(
+        test.process(&f.claim(&test, PREIMAGE)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+    assert_eq!(
+        test.process(&f.refund(&test)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+}
+
+#[test]
+fn timelock_has_to_be_in_the_future() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 0).sized_for(&mut test, EscrowMode::Htlc);
+    at(&test, TIMELOCK);
+
+    assert_eq!(
+        test.process(&f.init_htlc(&test, hash(&PREIMAGE).to_bytes(), TIMELOCK)),
+        Err(EscrowError::TimelockInThePast.into())
+    );
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
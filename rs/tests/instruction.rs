@@ -0,0 +1,278 @@
+use proptest::prelude::*;
+
+use solana_program::pubkey::Pubkey;
+
+use bpf_program_template::{instruction::EscrowInstruction, order::Order, state::Request};
+
+fn order() -> impl Strategy<Value = Order> {
+    (
+        any::<[u8; 32]>(),
+        any::<[u8; 32]>(),
+        any::<[u8; 32]>(),
+        (any::<u64>(), any::<u64>(), any::<i64>(), any::<u64>()),
+    )
+        .prop_map(
+            |(maker, mint_x, mint_y, (amount_x, amount_y, expiry, nonce))| Order {
+                maker: Pubkey::new_from_array(maker),
+                mint_x: Pubkey::new_from_array(mint_x),
+                mint_y: Pubkey::new_from_array(mint_y),
+                amount_x,
+                amount_y,
+                expiry,
+                nonce,
+            },
+        )
+}
+
+fn instruction() -> impl Strategy<Value = EscrowInstruction> {
+    prop_oneof![
+        (any::<u64>(), any::<bool>())
+            .prop_map(|(amount, rent_payer)| EscrowInstruction::InitEscrow { amount, rent_payer }),
+        any::<u64>().prop_map(|amount| EscrowInstruction::Exchange { amount }),
+        any::<u8>().prop_map(|bump_seed| EscrowInstruction::Cancel { bump_seed }),
+        (any::<u64>(), any::<u64>(), any::<bool>()).prop_map(
+            |(amount, offered_amount, rent_payer)| EscrowInstruction::InitApproveEscrow {
+                amount,
+                offered_amount,
+                rent_payer,
+            }
+        ),
+        (any::<[u8; 32]>(), any::<i64>(), any::<bool>()).prop_map(
+            |(hashlock, timelock, rent_payer)| EscrowInstruction::InitHtlcEscrow {
+                hashlock,
+                timelock,
+                rent_payer,
+            }
+        ),
+        any::<[u8; 32]>().prop_map(|preimage| EscrowInstruction::Claim { preimage }),
+        Just(EscrowInstruction::Refund),
+        (any::<u64>(), any::<u64>(), any::<i64>(), any::<bool>()).prop_map(
+            |(strike, premium, expiry, rent_payer)| EscrowInstruction::WriteOption {
+                strike,
+                premium,
+                expiry,
+                rent_payer,
+            }
+        ),
+        any::<u64>().prop_map(|premium| EscrowInstruction::BuyOption { premium }),
+        any::<u64>().prop_map(|amount| EscrowInstruction::ExerciseOption { amount }),
+        Just(EscrowInstruction::ReclaimOption),
+        (any::<u64>(), any::<u64>(), any::<i64>(), any::<bool>()).prop_map(
+            |(principal, interest, duration, rent_payer)| EscrowInstruction::OfferLoan {
+                principal,
+                interest,
+                duration,
+                rent_payer,
+            }
+        ),
+        any::<u64>().prop_map(|principal| EscrowInstruction::FundLoan { principal }),
+        Just(EscrowInstruction::RepayLoan),
+        Just(EscrowInstruction::ForecloseLoan),
+        Just(EscrowInstruction::CancelLoan),
+        (
+            any::<u64>(),
+            any::<u64>(),
+            any::<i64>(),
+            any::<i64>(),
+            any::<u8>(),
+            any::<bool>()
+        )
+            .prop_map(
+                |(start_price, floor_price, start_time, end_time, decay, rent_payer)| {
+                    EscrowInstruction::InitDutchAuction {
+                        start_price,
+                        floor_price,
+                        start_time,
+                        end_time,
+                        decay,
+                        rent_payer,
+                    }
+                }
+            ),
+        (any::<u64>(), any::<u64>()).prop_map(|(amount, max_price)| {
+            EscrowInstruction::TakeDutchAuction { amount, max_price }
+        }),
+        (
+            any::<u64>(),
+            any::<u64>(),
+            any::<i64>(),
+            any::<i64>(),
+            any::<bool>()
+        )
+            .prop_map(
+                |(reserve_price, min_increment, end_time, extension, rent_payer)| {
+                    EscrowInstruction::InitEnglishAuction {
+                        reserve_price,
+                        min_increment,
+                        end_time,
+                        extension,
+                        rent_payer,
+                    }
+                }
+            ),
+        any::<u64>().prop_map(|amount| EscrowInstruction::Bid { amount }),
+        Just(EscrowInstruction::SettleAuction),
+        (
+            any::<u64>(),
+            any::<u64>(),
+            any::<i64>(),
+            any::<i64>(),
+            any::<bool>(),
+            any::<bool>()
+        )
+            .prop_map(
+                |(reserve_price, bond, commit_end, reveal_end, second_price, rent_payer)| {
+                    EscrowInstruction::InitSealedAuction {
+                        reserve_price,
+                        bond,
+                        commit_end,
+                        reveal_end,
+                        second_price,
+                        rent_payer,
+                    }
+                }
+            ),
+        any::<[u8; 32]>().prop_map(|commitment| EscrowInstruction::CommitBid { commitment }),
+        (any::<u64>(), any::<[u8; 32]>())
+            .prop_map(|(amount, salt)| EscrowInstruction::RevealBid { amount, salt }),
+        Just(EscrowInstruction::CloseBid),
+        Just(EscrowInstruction::Match),
+        Just(EscrowInstruction::SetMatchRecipient),
+        (any::<u64>(), any::<u64>())
+            .prop_map(|(target_x, max_y)| EscrowInstruction::ExchangeMany { target_x, max_y }),
+        order().prop_map(|order| EscrowInstruction::FillSignedOrder { order }),
+        any::<u64>().prop_map(|nonce| EscrowInstruction::CancelOrderNonce { nonce }),
+        any::<u64>().prop_map(|amount| EscrowInstruction::FlashExchange { amount }),
+        Just(EscrowInstruction::SettleFlash),
+        (any::<[u8; 32]>(), any::<u64>(), any::<i64>()).prop_map(
+            |(merkle_root, num_recipients, deadline)| EscrowInstruction::InitDistribution {
+                merkle_root,
+                num_recipients,
+                deadline,
+            }
+        ),
+        (
+            any::<u64>(),
+            any::<u64>(),
+            prop::collection::vec(any::<[u8; 32]>(), 0..8)
+        )
+            .prop_map(
+                |(index, amount, proof)| EscrowInstruction::ClaimDistribution {
+                    index,
+                    amount,
+                    proof,
+                }
+            ),
+        Just(EscrowInstruction::ReclaimDistribution),
+        (
+            prop_oneof![
+                Just(Request::Tokens),
+                Just(Request::Mint),
+                Just(Request::Collection)
+            ],
+            any::<[u8; 32]>(),
+            any::<bool>()
+        )
+            .prop_map(|(request, request_key, rent_payer)| {
+                EscrowInstruction::InitNftEscrow {
+                    request,
+                    request_key: Pubkey::new_from_array(request_key),
+                    rent_payer,
+                }
+            }),
+        any::<u64>().prop_map(|amount| EscrowInstruction::ExchangeNft { amount }),
+        (any::<u64>(), any::<bool>()).prop_map(|(amount, rent_payer)| {
+            EscrowInstruction::InitRoyaltyEscrow { amount, rent_payer }
+        }),
+        (any::<i64>(), any::<i64>(), any::<i64>())
+            .prop_map(|(start, cliff, end)| EscrowInstruction::InitVesting { start, cliff, end }),
+        Just(EscrowInstruction::Withdraw),
+        Just(EscrowInstruction::RevokeVesting),
+        (any::<u64>(), any::<i64>())
+            .prop_map(|(rate, duration)| EscrowInstruction::InitStream { rate, duration }),
+        Just(EscrowInstruction::CancelStream),
+        prop::collection::vec(any::<u64>(), 0..=8)
+            .prop_map(|amounts| EscrowInstruction::InitMilestoneEscrow { amounts }),
+        any::<u8>().prop_map(|milestone| EscrowInstruction::ReleaseMilestone { milestone }),
+        any::<u8>().prop_map(|milestone| EscrowInstruction::DisputeMilestone { milestone }),
+        (any::<u8>(), any::<u64>()).prop_map(|(milestone, payee_amount)| {
+            EscrowInstruction::ResolveDispute {
+                milestone,
+                payee_amount,
+            }
+        }),
+        any::<u8>().prop_map(|m| EscrowInstruction::InitMultisig { m }),
+        Just(EscrowInstruction::SetEscrowAuthority),
+        any::<i64>().prop_map(|deadline| EscrowInstruction::InitPool { deadline }),
+        any::<u64>().prop_map(|amount| EscrowInstruction::Contribute { amount }),
+        Just(EscrowInstruction::ExecutePool),
+        Just(EscrowInstruction::LeavePool),
+        (any::<u64>(), any::<u64>(), any::<i64>(), any::<u64>()).prop_map(
+            |(amount_per_interval, min_amount_x, interval, keeper_reward)| {
+                EscrowInstruction::InitStandingOrder {
+                    amount_per_interval,
+                    min_amount_x,
+                    interval,
+                    keeper_reward,
+                }
+            }
+        ),
+        Just(EscrowInstruction::ExecuteTranche),
+        order().prop_map(|order| EscrowInstruction::ExecuteTrancheWithOrder { order }),
+        Just(EscrowInstruction::CancelStandingOrder),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn instruction_round_trips(ix in instruction()) {
+        prop_assert_eq!(EscrowInstruction::unpack(&ix.pack()).unwrap(), ix);
+    }
+
+    #[test]
+    fn truncated_instruction_is_rejected(ix in instruction()) {
+        let packed = ix.pack();
+        for len in 0..packed.len() {
+            // except for dropping the rent payer flag, that's the same instruction without one
+            if let Ok(shorter) = EscrowInstruction::unpack(&packed[..len]) {
+                prop_assert_eq!(len, packed.len() - 1);
+                prop_assert_eq!(&shorter.pack()[..], &packed[..len]);
+            }
+        }
+    }
+}
+
+#[test]
+fn second_price_flag_has_to_be_a_bool() {
+    let mut packed = EscrowInstruction::InitSealedAuction {
+        reserve_price: 500,
+        bond: 100,
+        commit_end: 1,
+        reveal_end: 2,
+        second_price: true,
+        rent_payer: false,
+    }
+    .pack();
+    *packed.last_mut().unwrap() = 2;
+    assert!(EscrowInstruction::unpack(&packed).is_err());
+}
+
+#[test]
+fn rent_payer_flag_has_to_be_a_bool() {
+    let mut packed = EscrowInstruction::InitEscrow {
+        amount: 500,
+        rent_payer: true,
+    }
+    .pack();
+    assert_eq!(packed.len(), 10);
+    *packed.last_mut().unwrap() = 2;
+    assert!(EscrowInstruction::unpack(&packed).is_err());
+    // and without it there's no rent payer, like before there was a flag
+    assert_eq!(
+        EscrowInstruction::unpack(&packed[..9]).unwrap(),
+        EscrowInstruction::InitEscrow {
+            amount: 500,
+            rent_payer: false
+        }
+    );
+}
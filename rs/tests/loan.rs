@@ -0,0 +1,212 @@
+mod common;
+
+use solana_program::{clock::Clock, program_error::ProgramError, program_pack::Pack};
+use spl_token::state::Account as TokenAccount;
+
+use bpf_program_template::{error::EscrowError, state::Loan};
+
+use common::{EscrowFixture, ProgramTest};
+
+const PRINCIPAL: u64 = 500;
+const INTEREST: u64 = 25;
+const DURATION: i64 = 1_000;
+const FUNDED_AT: i64 = 5_000;
+
+fn at(test: &ProgramTest, unix_timestamp: i64) {
+    test.set_clock(Clock {
+        unix_timestamp,
+        ..Clock::default()
+    });
+}
+
+// alice puts up 1000 X to borrow 500 Y, the taker has 5000 Y to lend.
+// she'll need the interest on top of the principal to repay, so she starts with that
+fn offered(test: &mut ProgramTest) -> EscrowFixture {
+    let f = EscrowFixture::new(test, 1_000, 5_000);
+    let mut initializer_y = test.token_account(&f.initializer_y).unwrap();
+    initializer_y.amount = INTEREST;
+    TokenAccount::pack(
+        initializer_y,
+        &mut test.accounts.get_mut(&f.initializer_y).unwrap().data,
+    )
+    .unwrap();
+    test.process(&f.offer_loan(test, PRINCIPAL, INTEREST, DURATION))
+        .unwrap();
+    f
+}
+
+fn funded(test: &mut ProgramTest) -> EscrowFixture {
+    let f = offered(test);
+    at(test, FUNDED_AT);
+    test.process(&f.fund_loan(test, PRINCIPAL)).unwrap();
+    f
+}
+
+#[test]
+fn fund_then_repay() {
+    let mut test = ProgramTest::new();
+    let f = offered(&mut test);
+
+    let loan = Loan::unpack(&test.account(&f.loan).data).unwrap();
+    assert!(!loan.is_funded());
+    assert_eq!(test.token_account(&f.temp_x).unwrap().owner, test.pda().0);
+
+    at(&test, FUNDED_AT);
+    test.process(&f.fund_loan(&test, PRINCIPAL)).unwrap();
+    let loan = Loan::unpack(&test.account(&f.loan).data).unwrap();
+    assert_eq!(loan.lender_pubkey, f.taker);
+    assert_eq!(loan.lender_principal_account_pubkey, f.taker_y);
+    assert_eq!(loan.deadline, FUNDED_AT + DURATION);
+    assert_eq!(test.token_balance(&f.initializer_y), INTEREST + PRINCIPAL);
+
+    at(&test, FUNDED_AT + DURATION - 1);
+    let rent = test.lamports(&f.loan) + test.lamports(&f.temp_x);
+    let initializer_lamports = test.lamports(&f.initializer);
+    test.process(&f.repay_loan(&test)).unwrap();
+    assert_eq!(test.token_balance(&f.initializer_x), 1_000);
+    assert_eq!(test.token_balance(&f.initializer_y), 0);
+    assert_eq!(test.token_balance(&f.taker_y), 5_000 + INTEREST);
+    assert!(test.token_account(&f.temp_x).is_none());
+    assert_eq!(test.lamports(&f.loan), 0);
+    assert_eq!(test.lamports(&f.initializer), initializer_lamports + rent);
+}
+
+#[test]
+fn principal_has_to_match() {
+    let mut test = ProgramTest::new();
+    let f = offered(&mut test);
+
+    assert_eq!(
+        test.process(&f.fund_loan(&test, PRINCIPAL + 1)),
+        Err(EscrowError::ExpectedAmountMismatch.into())
+    );
+}
+
+#[test]
+fn loan_is_funded_once() {
+    let mut test = ProgramTest::new();
+    let f = funded(&mut test);
+
+    assert_eq!(
+        test.process(&f.fund_loan(&test, PRINCIPAL)),
+        Err(EscrowError::LoanAlreadyFunded.into())
+    );
+}
+
+#[test]
+fn repay_needs_a_lender() {
+    let mut test = ProgramTest::new();
+    let f = offered(&mut test);
+
+    assert_eq!(
+        test.process(&f.repay_loan(&test)),
+        Err(EscrowError::LoanNotFunded.into())
+    );
+    assert_eq!(
+        test.process(&f.foreclose_loan(&test)),
+        Err(EscrowError::LoanNotFunded.into())
+    );
+}
+
+#[test]
+fn repay_after_the_deadline_fails() {
+    let mut test = ProgramTest::new();
+    let f = funded(&mut test);
+
+    at(&test, FUNDED_AT + DURATION);
+    assert_eq!(
+        test.process(&f.repay_loan(&test)),
+        Err(EscrowError::LoanOverdue.into())
+    );
+}
+
+#[test]
+fn foreclose_after_the_deadline() {
+    let mut test = ProgramTest::new();
+    let f = funded(&mut test);
+
+    at(&test, FUNDED_AT + DURATION - 1);
+    assert_eq!(
+        test.process(&f.foreclose_loan(&test)),
+        Err(EscrowError::TimelockNotExpired.into())
+    );
+
+    at(&test, FUNDED_AT + DURATION);
+    let rent = test.lamports(&f.loan) + test.lamports(&f.temp_x);
+    let initializer_lamports = test.lamports(&f.initializer);
+    test.process(&f.foreclose_loan(&test)).unwrap();
+    assert_eq!(test.token_balance(&f.taker_x), 1_000);
+    assert_eq!(test.token_balance(&f.taker_y), 5_000 - PRINCIPAL);
+    assert!(test.token_account(&f.temp_x).is_none());
+    assert_eq!(test.lamports(&f.loan), 0);
+    // the rent goes back to alice, not the lender
+    assert_eq!(test.lamports(&f.initializer), initializer_lamports + rent);
+}
+
+#[test]
+fn only_the_lender_forecloses() {
+    let mut test = ProgramTest::new();
+    let f = funded(&mut test);
+    at(&test, FUNDED_AT + DURATION);
+
+    let mallory = test.add_wallet(1_000_000_000);
+    let mut ix = f.foreclose_loan(&test);
+    ix.accounts[0].pubkey = mallory;
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+}
+
+#[test]
+fn loans_have_to_be_ours() {
+    let mut test = ProgramTest::new();
+    let f = funded(&mut test);
+    at(&test, FUNDED_AT + DURATION);
+
+    // a copy of the loan under another owner, swapped in for the real one
+    let copy = test.add_foreign_copy(&f.loan);
+    let instructions = [
+        f.repay_loan(&test),
+        f.foreclose_loan(&test),
+        f.cancel_loan(&test),
+    ];
+    for ix in instructions.iter() {
+        let mut ix = ix.clone();
+        for meta in ix.accounts.iter_mut().filter(|m| m.pubkey == f.loan) {
+            meta.pubkey = copy;
+        }
+        assert_eq!(test.process(&ix), Err(ProgramError::IncorrectProgramId));
+    }
+}
+
+#[test]
+fn cancel_only_before_funding() {
+    let mut test = ProgramTest::new();
+    let f = funded(&mut test);
+    assert_eq!(
+        test.process(&f.cancel_loan(&test)),
+        Err(EscrowError::LoanAlreadyFunded.into())
+    );
+
+    let mut test = ProgramTest::new();
+    let f = offered(&mut test);
+    test.process(&f.cancel_loan(&test)).unwrap();
+    assert_eq!(test.token_balance(&f.initializer_x), 1_000);
+    assert!(test.token_account(&f.temp_x).is_none());
+    assert_eq!(test.lamports(&f.loan), 0);
+}
+
+#[test]
+fn offer_needs_a_duration() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 5_000);
+
+    assert_eq!(
+        test.process(&f.offer_loan(&test, PRINCIPAL, INTEREST, 0)),
+        Err(EscrowError::TimelockInThePast.into())
+    );
+    assert_eq!(
+        test.account(&f.loan).data,
+        vec![0; Loan::LEN],
+        "nothing written"
+    );
+    assert_eq!(test.token_account(&f.temp_x).unwrap().owner, f.initializer);
+}
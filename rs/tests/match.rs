@@ -0,0 +1,201 @@
+mod common;
+
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+use bpf_program_template::{error::EscrowError, state::Escrow};
+
+use common::{EscrowFixture, ProgramTest};
+
+// alice sells 1000 X for 500 Y, bob sells `bob_offers` Y for `bob_asks` X. Both are listed
+fn listed(
+    test: &mut ProgramTest,
+    bob_offers: u64,
+    bob_asks: u64,
+) -> (EscrowFixture, EscrowFixture) {
+    let alice = EscrowFixture::new(test, 1_000, 0);
+    test.process(&alice.init_escrow(test, 500)).unwrap();
+    // bob's fixture has the mints the other way round, his "X" is alice's Y
+    let bob = EscrowFixture::for_pair(test, alice.mint_y, alice.mint_x, bob_offers, 0);
+    test.process(&bob.init_escrow(test, bob_asks)).unwrap();
+    (alice, bob)
+}
+
+fn cranker(test: &mut ProgramTest, alice: &EscrowFixture) -> (Pubkey, Pubkey) {
+    let wallet = test.add_wallet(1_000_000_000);
+    (
+        test.add_token_account(&alice.mint_x, &wallet, 0),
+        test.add_token_account(&alice.mint_y, &wallet, 0),
+    )
+}
+
+#[test]
+fn crossing_escrows_settle() {
+    let mut test = ProgramTest::new();
+    let (alice, bob) = listed(&mut test, 600, 800);
+    let cranker = cranker(&mut test, &alice);
+
+    let alice_rent = test.lamports(&alice.escrow) + test.lamports(&alice.temp_x);
+    let alice_lamports = test.lamports(&alice.initializer);
+    test.process(&alice.match_with(&test, &bob, alice.initializer_x, bob.initializer_x, cranker))
+        .unwrap();
+
+    // both get what they asked for
+    assert_eq!(test.token_balance(&alice.initializer_y), 500);
+    assert_eq!(test.token_balance(&bob.initializer_y), 800);
+    // 200 X and 100 Y left over, a tenth of each to the cranker
+    assert_eq!(test.token_balance(&cranker.0), 20);
+    assert_eq!(test.token_balance(&cranker.1), 10);
+    assert_eq!(test.token_balance(&alice.initializer_x), 180);
+    assert_eq!(test.token_balance(&bob.initializer_x), 90);
+
+    assert!(test.token_account(&alice.temp_x).is_none());
+    assert!(test.token_account(&bob.temp_x).is_none());
+    assert_eq!(test.lamports(&alice.escrow), 0);
+    assert_eq!(test.lamports(&bob.escrow), 0);
+    assert_eq!(
+        test.lamports(&alice.initializer),
+        alice_lamports + alice_rent
+    );
+}
+
+#[test]
+fn exact_prices_leave_nothing_over() {
+    let mut test = ProgramTest::new();
+    let (alice, bob) = listed(&mut test, 500, 1_000);
+    let cranker = cranker(&mut test, &alice);
+
+    test.process(&alice.match_with(&test, &bob, alice.initializer_x, bob.initializer_x, cranker))
+        .unwrap();
+    assert_eq!(test.token_balance(&alice.initializer_y), 500);
+    assert_eq!(test.token_balance(&bob.initializer_y), 1_000);
+    assert_eq!(test.token_balance(&cranker.0), 0);
+    assert_eq!(test.token_balance(&cranker.1), 0);
+    assert_eq!(test.token_balance(&alice.initializer_x), 0);
+    assert_eq!(test.token_balance(&bob.initializer_x), 0);
+}
+
+#[test]
+fn escrows_have_to_cross() {
+    for (bob_offers, bob_asks) in [(500, 1_001), (499, 1_000)].iter() {
+        let mut test = ProgramTest::new();
+        let (alice, bob) = listed(&mut test, *bob_offers, *bob_asks);
+        let cranker = cranker(&mut test, &alice);
+
+        assert_eq!(
+            test.process(&alice.match_with(
+                &test,
+                &bob,
+                alice.initializer_x,
+                bob.initializer_x,
+                cranker
+            )),
+            Err(EscrowError::EscrowsDontCross.into())
+        );
+    }
+}
+
+#[test]
+fn improvement_goes_to_the_configured_recipient() {
+    let mut test = ProgramTest::new();
+    let (alice, bob) = listed(&mut test, 600, 800);
+    let cranker = cranker(&mut test, &alice);
+    let treasury = test.add_wallet(1_000_000_000);
+    let treasury_x = test.add_token_account(&alice.mint_x, &treasury, 0);
+
+    // only alice can set it, and it has to be in X
+    let mut ix = alice.set_match_recipient(&test, treasury_x);
+    ix.accounts[0].pubkey = bob.initializer;
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+    let treasury_y = test.add_token_account(&alice.mint_y, &treasury, 0);
+    assert_eq!(
+        test.process(&alice.set_match_recipient(&test, treasury_y)),
+        Err(ProgramError::InvalidAccountData)
+    );
+    test.process(&alice.set_match_recipient(&test, treasury_x))
+        .unwrap();
+    let escrow = Escrow::unpack(&test.account(&alice.escrow).data).unwrap();
+    assert_eq!(escrow.recipient_token_account_pubkey, treasury_x);
+
+    // once it's set, alice's own accounts won't do
+    assert_eq!(
+        test.process(&alice.match_with(
+            &test,
+            &bob,
+            alice.initializer_x,
+            bob.initializer_x,
+            cranker
+        )),
+        Err(ProgramError::InvalidAccountData)
+    );
+    test.process(&alice.match_with(&test, &bob, treasury_x, bob.initializer_x, cranker))
+        .unwrap();
+    assert_eq!(test.token_balance(&treasury_x), 180);
+    assert_eq!(test.token_balance(&alice.initializer_x), 0);
+}
+
+#[test]
+fn cranker_cannot_take_the_improvement() {
+    let mut test = ProgramTest::new();
+    let (alice, bob) = listed(&mut test, 600, 800);
+    let cranker = cranker(&mut test, &alice);
+
+    assert_eq!(
+        test.process(&alice.match_with(&test, &bob, cranker.0, bob.initializer_x, cranker)),
+        Err(ProgramError::InvalidAccountData)
+    );
+    assert_eq!(
+        test.process(&alice.match_with(&test, &bob, alice.initializer_x, cranker.1, cranker)),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn only_mirrored_vaults_match() {
+    let mut test = ProgramTest::new();
+    let (alice, bob) = listed(&mut test, 600, 800);
+    let cranker = cranker(&mut test, &alice);
+
+    // the same escrow twice
+    assert_eq!(
+        test.process(&alice.match_with(
+            &test,
+            &alice,
+            alice.initializer_x,
+            alice.initializer_x,
+            cranker
+        )),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    // carol sells X too
+    let carol = EscrowFixture::for_pair(&mut test, alice.mint_x, alice.mint_y, 1_000, 0);
+    test.process(&carol.init_escrow(&test, 500)).unwrap();
+    assert_eq!(
+        test.process(&alice.match_with(
+            &test,
+            &carol,
+            alice.initializer_x,
+            carol.initializer_x,
+            cranker
+        )),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    // and a delegation has no vault to match
+    let dave = EscrowFixture::for_pair(&mut test, alice.mint_y, alice.mint_x, 600, 0);
+    test.process(&dave.init_approve_escrow(&test, 800, 600))
+        .unwrap();
+    assert_eq!(
+        test.process(&alice.match_with(
+            &test,
+            &dave,
+            alice.initializer_x,
+            dave.initializer_x,
+            cranker
+        )),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+
+    test.process(&alice.match_with(&test, &bob, alice.initializer_x, bob.initializer_x, cranker))
+        .unwrap();
+}
@@ -0,0 +1,280 @@
+mod common;
+
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+
+use bpf_program_template::{error::EscrowError, state::MilestoneEscrow};
+
+use common::{EscrowFixture, ProgramTest};
+
+// alice pays the taker 1000 X over `amounts`, with an arbiter for disputes
+struct Job {
+    f: EscrowFixture,
+    milestones: Pubkey,
+    arbiter: Pubkey,
+}
+
+impl Job {
+    fn new(test: &mut ProgramTest, amounts: &[u64]) -> Self {
+        let f = EscrowFixture::new(test, 1_000, 0);
+        let milestones = test.add_program_account(MilestoneEscrow::LEN);
+        let arbiter = test.add_wallet(1_000_000_000);
+        test.process(&f.init_milestone_escrow(test, milestones, arbiter, amounts))
+            .unwrap();
+        Self {
+            f,
+            milestones,
+            arbiter,
+        }
+    }
+
+    fn release(&self, test: &mut ProgramTest, milestone: u8) -> Result<(), ProgramError> {
+        test.process(
+            &self
+                .f
+                .release_milestone(test, self.milestones, self.f.taker_x, milestone),
+        )
+    }
+
+    fn dispute(
+        &self,
+        test: &mut ProgramTest,
+        party: Pubkey,
+        milestone: u8,
+    ) -> Result<(), ProgramError> {
+        test.process(
+            &self
+                .f
+                .dispute_milestone(test, self.milestones, party, milestone),
+        )
+    }
+
+    fn resolve(
+        &self,
+        test: &mut ProgramTest,
+        milestone: u8,
+        payee_amount: u64,
+    ) -> Result<(), ProgramError> {
+        test.process(&self.f.resolve_dispute(
+            test,
+            self.milestones,
+            self.arbiter,
+            self.f.taker_x,
+            self.f.initializer_x,
+            milestone,
+            payee_amount,
+        ))
+    }
+
+    fn event(&self, name: &str, rest: &str) -> String {
+        format!("EscrowEvent {} escrow={}{}", name, self.milestones, rest)
+    }
+}
+
+#[test]
+fn payer_releases_milestones_in_order() {
+    let mut test = ProgramTest::new();
+    let job = Job::new(&mut test, &[100, 300, 600]);
+    let opened = job.event(
+        "MilestoneEscrowOpened",
+        &format!(
+            " payer={} payee={} arbiter={} total=1000",
+            job.f.initializer, job.f.taker, job.arbiter
+        ),
+    );
+    assert!(test.logs().contains(&opened), "{:?}", test.logs());
+    let escrow = MilestoneEscrow::unpack(&test.account(&job.milestones).data).unwrap();
+    assert_eq!(
+        (
+            escrow.num_milestones,
+            escrow.current,
+            escrow.current_amount()
+        ),
+        (3, 0, Some(100))
+    );
+    assert_eq!(
+        test.token_account(&job.f.temp_x).unwrap().owner,
+        test.pda().0
+    );
+
+    assert_eq!(
+        job.release(&mut test, 1),
+        Err(EscrowError::WrongMilestone.into())
+    );
+    job.release(&mut test, 0).unwrap();
+    assert_eq!(test.token_balance(&job.f.taker_x), 100);
+    let released = job.event("MilestoneReleased", " milestone=0 amount=100");
+    assert!(test.logs().contains(&released), "{:?}", test.logs());
+    job.release(&mut test, 1).unwrap();
+    assert_eq!(test.token_balance(&job.f.taker_x), 400);
+
+    let rent = test.lamports(&job.milestones) + test.lamports(&job.f.temp_x);
+    let payer_lamports = test.lamports(&job.f.initializer);
+    job.release(&mut test, 2).unwrap();
+    assert_eq!(test.token_balance(&job.f.taker_x), 1_000);
+    assert!(test.token_account(&job.f.temp_x).is_none());
+    assert_eq!(test.lamports(&job.milestones), 0);
+    assert_eq!(test.lamports(&job.f.initializer), payer_lamports + rent);
+    assert!(test
+        .logs()
+        .contains(&job.event("MilestoneEscrowClosed", "")));
+}
+
+#[test]
+fn only_the_payer_releases_to_the_payee() {
+    let mut test = ProgramTest::new();
+    let job = Job::new(&mut test, &[400, 600]);
+
+    let mut ix = job
+        .f
+        .release_milestone(&test, job.milestones, job.f.taker_x, 0);
+    ix.accounts[0].pubkey = job.f.taker;
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+    let ix = job
+        .f
+        .release_milestone(&test, job.milestones, job.f.initializer_x, 0);
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+    // nor out of a copy of the escrow that isn't ours
+    let copy = test.add_foreign_copy(&job.milestones);
+    let ix = job.f.release_milestone(&test, copy, job.f.taker_x, 0);
+    assert_eq!(test.process(&ix), Err(ProgramError::IncorrectProgramId));
+
+    job.release(&mut test, 0).unwrap();
+    assert_eq!(test.token_balance(&job.f.taker_x), 400);
+}
+
+#[test]
+fn arbiter_splits_a_disputed_milestone() {
+    let mut test = ProgramTest::new();
+    let job = Job::new(&mut test, &[100, 300, 600]);
+
+    assert_eq!(
+        job.resolve(&mut test, 0, 50),
+        Err(EscrowError::NotDisputed.into())
+    );
+    // only the two parties, and only the current milestone
+    assert_eq!(
+        job.dispute(&mut test, job.arbiter, 0),
+        Err(ProgramError::InvalidAccountData)
+    );
+    assert_eq!(
+        job.dispute(&mut test, job.f.taker, 1),
+        Err(EscrowError::WrongMilestone.into())
+    );
+    let copy = test.add_foreign_copy(&job.milestones);
+    let ix = job.f.dispute_milestone(&test, copy, job.f.taker, 0);
+    assert_eq!(test.process(&ix), Err(ProgramError::IncorrectProgramId));
+    job.dispute(&mut test, job.f.taker, 0).unwrap();
+    let disputed = job.event(
+        "MilestoneDisputed",
+        &format!(" milestone=0 by={}", job.f.taker),
+    );
+    assert!(test.logs().contains(&disputed), "{:?}", test.logs());
+    assert_eq!(
+        job.dispute(&mut test, job.f.initializer, 0),
+        Err(EscrowError::AlreadyDisputed.into())
+    );
+
+    assert_eq!(
+        job.resolve(&mut test, 0, 101),
+        Err(EscrowError::AmountOverflow.into())
+    );
+    let ix = job.f.resolve_dispute(
+        &test,
+        job.milestones,
+        job.f.initializer,
+        job.f.taker_x,
+        job.f.initializer_x,
+        0,
+        100,
+    );
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+    // both of the parties' accounts have to be theirs
+    let ix = job.f.resolve_dispute(
+        &test,
+        job.milestones,
+        job.arbiter,
+        job.f.taker_x,
+        job.f.taker_x,
+        0,
+        30,
+    );
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+
+    let ix = job.f.resolve_dispute(
+        &test,
+        copy,
+        job.arbiter,
+        job.f.taker_x,
+        job.f.initializer_x,
+        0,
+        30,
+    );
+    assert_eq!(test.process(&ix), Err(ProgramError::IncorrectProgramId));
+
+    job.resolve(&mut test, 0, 30).unwrap();
+    assert_eq!(test.token_balance(&job.f.taker_x), 30);
+    assert_eq!(test.token_balance(&job.f.initializer_x), 70);
+    let resolved = job.event(
+        "DisputeResolved",
+        " milestone=0 payee_amount=30 payer_amount=70",
+    );
+    assert!(test.logs().contains(&resolved), "{:?}", test.logs());
+
+    // the payer can still give in and release a disputed one in full
+    job.dispute(&mut test, job.f.initializer, 1).unwrap();
+    job.release(&mut test, 1).unwrap();
+    assert_eq!(test.token_balance(&job.f.taker_x), 330);
+    assert_eq!(
+        job.resolve(&mut test, 2, 0),
+        Err(EscrowError::NotDisputed.into())
+    );
+}
+
+#[test]
+fn resolving_the_last_milestone_closes_the_escrow() {
+    let mut test = ProgramTest::new();
+    let job = Job::new(&mut test, &[1_000]);
+    job.dispute(&mut test, job.f.initializer, 0).unwrap();
+
+    let rent = test.lamports(&job.milestones) + test.lamports(&job.f.temp_x);
+    let payer_lamports = test.lamports(&job.f.initializer);
+    job.resolve(&mut test, 0, 0).unwrap();
+    assert_eq!(test.token_balance(&job.f.initializer_x), 1_000);
+    assert_eq!(test.token_balance(&job.f.taker_x), 0);
+    assert!(test.token_account(&job.f.temp_x).is_none());
+    assert_eq!(test.lamports(&job.milestones), 0);
+    assert_eq!(test.lamports(&job.f.initializer), payer_lamports + rent);
+    assert!(test
+        .logs()
+        .contains(&job.event("MilestoneEscrowClosed", "")));
+}
+
+#[test]
+fn milestones_have_to_add_up_to_the_deposit() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 0);
+    let milestones = test.add_program_account(MilestoneEscrow::LEN);
+    let arbiter = Pubkey::new_unique();
+
+    for (amounts, error) in [
+        (vec![], EscrowError::InvalidInstruction),
+        (vec![125; 9], EscrowError::InvalidInstruction),
+        (vec![500, 499], EscrowError::ExpectedAmountMismatch),
+        (vec![u64::MAX, 1_001], EscrowError::AmountOverflow),
+    ]
+    .iter()
+    {
+        assert_eq!(
+            test.process(&f.init_milestone_escrow(&test, milestones, arbiter, amounts)),
+            Err((*error).into())
+        );
+    }
+
+    test.process(&f.init_milestone_escrow(&test, milestones, arbiter, &[125; 8]))
+        .unwrap();
+    let other = EscrowFixture::new(&mut test, 1_000, 0);
+    assert_eq!(
+        test.process(&other.init_milestone_escrow(&test, milestones, arbiter, &[1_000])),
+        Err(ProgramError::AccountAlreadyInitialized)
+    );
+}
@@ -0,0 +1,249 @@
+mod common;
+
+use solana_program::{
+    clock::Clock, hash::hash, program_error::ProgramError, program_option::COption,
+    program_pack::Pack, pubkey::Pubkey,
+};
+
+use bpf_program_template::{
+    error::EscrowError,
+    state::{Escrow, EscrowMode, Multisig},
+};
+
+use common::{init_multisig, with_multisig, EscrowFixture, ProgramTest};
+
+// a treasury: 2 of 3 keys have to sign for it
+struct Treasury {
+    multisig: Pubkey,
+    signers: Vec<Pubkey>,
+}
+
+impl Treasury {
+    fn new(test: &mut ProgramTest) -> Self {
+        let multisig = test.add_program_account(Multisig::LEN);
+        let signers: Vec<Pubkey> = (0..3).map(|_| test.add_wallet(1_000_000_000)).collect();
+        test.process(&init_multisig(test, multisig, 2, &signers))
+            .unwrap();
+        Self { multisig, signers }
+    }
+}
+
+// alice lists 1000 X for 500 Y and hands the escrow to the treasury
+fn listed(test: &mut ProgramTest) -> (EscrowFixture, Treasury) {
+    let f = EscrowFixture::new(test, 1_000, 1_000);
+    test.process(&f.init_escrow(test, 500)).unwrap();
+    let treasury = Treasury::new(test);
+    test.process(&f.set_escrow_authority(test, treasury.multisig))
+        .unwrap();
+    (f, treasury)
+}
+
+#[test]
+fn two_of_three_cancel() {
+    let mut test = ProgramTest::new();
+    let (f, t) = listed(&mut test);
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(escrow.authority_pubkey, t.multisig);
+
+    // alice alone doesn't cut it any more, the multisig has to be there
+    assert_eq!(
+        test.process(&f.cancel(&test)),
+        Err(ProgramError::NotEnoughAccountKeys)
+    );
+    let mut ix = with_multisig(f.cancel(&test), t.multisig, &t.signers[..1]);
+    ix.accounts[0].is_signer = true;
+    assert_eq!(
+        test.process(&ix),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    for signers in [&t.signers[..1], &[t.signers[0], t.signers[0]][..]].iter() {
+        assert_eq!(
+            test.process(&with_multisig(f.cancel(&test), t.multisig, signers)),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+    // someone who isn't on the list
+    let outsider = test.add_wallet(1_000_000_000);
+    assert_eq!(
+        test.process(&with_multisig(
+            f.cancel(&test),
+            t.multisig,
+            &[t.signers[0], outsider]
+        )),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+
+    test.process(&with_multisig(
+        f.cancel(&test),
+        t.multisig,
+        &[t.signers[2], t.signers[0]],
+    ))
+    .unwrap();
+    assert_eq!(test.token_balance(&f.initializer_x), 1_000);
+    assert_eq!(test.lamports(&f.escrow), 0);
+}
+
+#[test]
+fn only_the_escrows_own_multisig_counts() {
+    let mut test = ProgramTest::new();
+    let (f, t) = listed(&mut test);
+
+    // another multisig with the same signers
+    let other = test.add_program_account(Multisig::LEN);
+    test.process(&init_multisig(&test, other, 1, &t.signers))
+        .unwrap();
+    assert_eq!(
+        test.process(&with_multisig(f.cancel(&test), other, &t.signers)),
+        Err(ProgramError::InvalidAccountData)
+    );
+    assert_eq!(
+        test.process(&with_multisig(f.cancel(&test), t.multisig, &[])),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    assert_eq!(
+        test.process(&f.set_escrow_authority(&test, other)),
+        Err(ProgramError::NotEnoughAccountKeys)
+    );
+}
+
+#[test]
+fn the_multisig_amends_and_can_hand_back() {
+    let mut test = ProgramTest::new();
+    let (f, t) = listed(&mut test);
+    let treasury_x = test.add_token_account(&f.mint_x, &t.multisig, 0);
+
+    assert_eq!(
+        test.process(&f.set_match_recipient(&test, treasury_x)),
+        Err(ProgramError::NotEnoughAccountKeys)
+    );
+    test.process(&with_multisig(
+        f.set_match_recipient(&test, treasury_x),
+        t.multisig,
+        &t.signers[1..],
+    ))
+    .unwrap();
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(escrow.recipient_token_account_pubkey, treasury_x);
+
+    // back to alice signing alone
+    test.process(&with_multisig(
+        f.set_escrow_authority(&test, f.initializer),
+        t.multisig,
+        &t.signers[..2],
+    ))
+    .unwrap();
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(escrow.authority_pubkey, Pubkey::default());
+    test.process(&f.cancel(&test)).unwrap();
+}
+
+#[test]
+fn the_multisig_refunds_an_htlc() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 0).sized_for(&mut test, EscrowMode::Htlc);
+    test.process(&f.init_htlc(&test, hash(&[42; 32]).to_bytes(), 2_000))
+        .unwrap();
+    let t = Treasury::new(&mut test);
+    test.process(&f.set_escrow_authority(&test, t.multisig))
+        .unwrap();
+    test.set_clock(Clock {
+        unix_timestamp: 2_000,
+        ..Clock::default()
+    });
+
+    assert_eq!(
+        test.process(&f.refund(&test)),
+        Err(ProgramError::NotEnoughAccountKeys)
+    );
+    test.process(&with_multisig(f.refund(&test), t.multisig, &t.signers))
+        .unwrap();
+    assert_eq!(test.token_balance(&f.initializer_x), 1_000);
+}
+
+#[test]
+fn the_multisig_cancels_an_approve_escrow_with_alice() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 1_000);
+    test.process(&f.init_approve_escrow(&test, 500, 400))
+        .unwrap();
+    let t = Treasury::new(&mut test);
+    test.process(&f.set_escrow_authority(&test, t.multisig))
+        .unwrap();
+
+    // only alice can revoke the delegation, so the multisig can't cancel without her
+    assert_eq!(
+        test.process(&with_multisig(f.cancel(&test), t.multisig, &t.signers[..2])),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    assert_eq!(
+        test.token_account(&f.temp_x).unwrap().delegate,
+        COption::Some(test.pda().0)
+    );
+
+    // and she can't without the multisig either
+    let mut ix = with_multisig(f.cancel(&test), t.multisig, &t.signers[..1]);
+    ix.accounts[0].is_signer = true;
+    assert_eq!(
+        test.process(&ix),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+
+    let mut ix = with_multisig(f.cancel(&test), t.multisig, &t.signers[1..]);
+    ix.accounts[0].is_signer = true;
+    test.process(&ix).unwrap();
+    assert_eq!(test.lamports(&f.escrow), 0);
+    let account = test.token_account(&f.temp_x).unwrap();
+    assert_eq!(account.amount, 1_000);
+    assert_eq!(account.owner, f.initializer);
+    assert_eq!(account.delegate, COption::None);
+    assert!(test.process(&f.exchange(&test, 400)).is_err());
+}
+
+#[test]
+fn multisigs_have_to_make_sense() {
+    let mut test = ProgramTest::new();
+    let multisig = test.add_program_account(Multisig::LEN);
+    let signers: Vec<Pubkey> = (0..12).map(|_| test.add_wallet(1_000_000_000)).collect();
+    for (m, signers) in [
+        (0, &signers[..3]),
+        (4, &signers[..3]),
+        (1, &signers[..0]),
+        (1, &signers[..]),
+    ]
+    .iter()
+    {
+        assert_eq!(
+            test.process(&init_multisig(&test, multisig, *m, signers)),
+            Err(EscrowError::InvalidInstruction.into())
+        );
+    }
+    assert_eq!(
+        test.process(&init_multisig(
+            &test,
+            multisig,
+            2,
+            &[signers[0], signers[1], signers[0]]
+        )),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    test.process(&init_multisig(&test, multisig, 11, &signers[..11]))
+        .unwrap();
+    assert_eq!(
+        test.process(&init_multisig(&test, multisig, 1, &signers[..1])),
+        Err(ProgramError::AccountAlreadyInitialized)
+    );
+
+    // an escrow can only be handed to a multisig that's set up
+    let f = EscrowFixture::new(&mut test, 1_000, 1_000);
+    test.process(&f.init_escrow(&test, 500)).unwrap();
+    let empty = test.add_program_account(Multisig::LEN);
+    assert_eq!(
+        test.process(&f.set_escrow_authority(&test, empty)),
+        Err(ProgramError::UninitializedAccount)
+    );
+    assert_eq!(
+        test.process(&f.set_escrow_authority(&test, f.taker)),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
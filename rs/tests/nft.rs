@@ -0,0 +1,234 @@
+mod common;
+
+use solana_program::{
+    instruction::Instruction, program_error::ProgramError, pubkey::Pubkey,
+};
+
+use bpf_program_template::{
+    error::EscrowError,
+    metadata::{metadata_address, Collection, Metadata},
+    state::{Escrow, Request},
+};
+
+use common::{metadata_data, EscrowFixture, ProgramTest};
+
+// an NFT the taker holds, and the empty account of alice's it would go into
+struct Nft {
+    mint: Pubkey,
+    taker: Pubkey,
+    initializer: Pubkey,
+}
+
+fn nft(test: &mut ProgramTest, f: &EscrowFixture, collection: Option<Collection>) -> Nft {
+    let mint = test.add_nft();
+    test.add_metadata(&Metadata {
+        mint,
+        collection,
+        ..Metadata::default()
+    });
+    Nft {
+        mint,
+        taker: test.add_token_account(&mint, &f.taker, 1),
+        initializer: test.add_token_account(&mint, &f.initializer, 0),
+    }
+}
+
+fn verified(key: Pubkey) -> Option<Collection> {
+    Some(Collection {
+        verified: true,
+        key,
+    })
+}
+
+fn exchange(test: &ProgramTest, f: &EscrowFixture, nft: &Nft) -> Instruction {
+    f.exchange_nft(test, nft.mint, nft.taker, nft.initializer, 1_000)
+}
+
+#[test]
+fn swap_for_a_specific_nft() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 0);
+    let wanted = nft(&mut test, &f, None);
+    let other = nft(&mut test, &f, None);
+    test.process(&f.init_nft_escrow(&test, Request::Mint, wanted.mint))
+        .unwrap();
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(
+        (escrow.request, escrow.request_key),
+        (Request::Mint, wanted.mint)
+    );
+    assert_eq!(test.token_account(&f.temp_x).unwrap().owner, test.pda().0);
+
+    assert_eq!(
+        test.process(&exchange(&test, &f, &other)),
+        Err(EscrowError::NftNotRequested.into())
+    );
+
+    let rent = test.lamports(&f.escrow) + test.lamports(&f.temp_x);
+    let initializer_lamports = test.lamports(&f.initializer);
+    test.process(&exchange(&test, &f, &wanted)).unwrap();
+    assert_eq!(test.token_balance(&wanted.initializer), 1);
+    assert_eq!(test.token_balance(&wanted.taker), 0);
+    assert_eq!(test.token_balance(&f.taker_x), 1_000);
+    assert!(test.token_account(&f.temp_x).is_none());
+    assert_eq!(test.lamports(&f.escrow), 0);
+    assert_eq!(test.lamports(&f.initializer), initializer_lamports + rent);
+}
+
+#[test]
+fn collection_offer() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 0);
+    let collection = Pubkey::new_unique();
+    test.process(&f.init_nft_escrow(&test, Request::Collection, collection))
+        .unwrap();
+
+    // only claims to be in it
+    let unverified = nft(
+        &mut test,
+        &f,
+        Some(Collection {
+            verified: false,
+            key: collection,
+        }),
+    );
+    let elsewhere = nft(&mut test, &f, verified(Pubkey::new_unique()));
+    let none = nft(&mut test, &f, None);
+    for nft in [unverified, elsewhere, none].iter() {
+        assert_eq!(
+            test.process(&exchange(&test, &f, nft)),
+            Err(EscrowError::NftNotRequested.into())
+        );
+    }
+    // metadata from before collections existed
+    let old = nft(&mut test, &f, verified(collection));
+    let metadata = metadata_address(&old.mint).0;
+    let data = metadata_data(&Metadata {
+        mint: old.mint,
+        ..Metadata::default()
+    });
+    // it ended right after is_mutable
+    test.accounts.get_mut(&metadata).unwrap().data = data[..data.len() - 8].to_vec();
+    assert_eq!(
+        test.process(&exchange(&test, &f, &old)),
+        Err(EscrowError::NftNotRequested.into())
+    );
+
+    let member = nft(&mut test, &f, verified(collection));
+    test.process(&exchange(&test, &f, &member)).unwrap();
+    assert_eq!(test.token_balance(&member.initializer), 1);
+    assert_eq!(test.token_balance(&f.taker_x), 1_000);
+}
+
+#[test]
+fn metadata_has_to_be_the_real_one() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 0);
+    let collection = Pubkey::new_unique();
+    test.process(&f.init_nft_escrow(&test, Request::Collection, collection))
+        .unwrap();
+    let member = nft(&mut test, &f, verified(collection));
+    let genuine = metadata_address(&member.mint).0;
+
+    // the same bytes, owned by someone else
+    let mut forged = test.account(&genuine).clone();
+    forged.owner = Pubkey::new_unique();
+    test.add_account(genuine, forged);
+    assert_eq!(
+        test.process(&exchange(&test, &f, &member)),
+        Err(ProgramError::InvalidAccountData)
+    );
+    // or another NFT's metadata
+    let other = nft(&mut test, &f, verified(collection));
+    let mut ix = exchange(&test, &f, &member);
+    ix.accounts[11].pubkey = metadata_address(&other.mint).0;
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+}
+
+#[test]
+fn only_one_of_ones() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 0);
+    let collection = Pubkey::new_unique();
+    test.process(&f.init_nft_escrow(&test, Request::Collection, collection))
+        .unwrap();
+
+    // a fungible mint with metadata claiming the collection
+    for decimals in [0, 6].iter() {
+        let mint = test.add_mint(*decimals);
+        test.add_metadata(&Metadata {
+            mint,
+            collection: verified(collection),
+            ..Metadata::default()
+        });
+        let fake = Nft {
+            mint,
+            taker: test.add_token_account(&mint, &f.taker, 1),
+            initializer: test.add_token_account(&mint, &f.initializer, 0),
+        };
+        assert_eq!(
+            test.process(&exchange(&test, &f, &fake)),
+            Err(EscrowError::NotAnNft.into())
+        );
+    }
+}
+
+#[test]
+fn nft_goes_to_the_initializer() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 0);
+    let wanted = nft(&mut test, &f, None);
+    test.process(&f.init_nft_escrow(&test, Request::Mint, wanted.mint))
+        .unwrap();
+
+    // an account of the taker's own
+    let taker_second = test.add_token_account(&wanted.mint, &f.taker, 0);
+    let ix = f.exchange_nft(&test, wanted.mint, wanted.taker, taker_second, 1_000);
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+    // and the taker has to expect the right amount
+    let ix = f.exchange_nft(&test, wanted.mint, wanted.taker, wanted.initializer, 999);
+    assert_eq!(
+        test.process(&ix),
+        Err(EscrowError::ExpectedAmountMismatch.into())
+    );
+    test.process(&exchange(&test, &f, &wanted)).unwrap();
+}
+
+#[test]
+fn nft_escrows_only_go_by_exchange_nft() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 500);
+    let wanted = nft(&mut test, &f, None);
+
+    assert_eq!(
+        test.process(&f.init_nft_escrow(&test, Request::Tokens, wanted.mint)),
+        Err(EscrowError::InvalidInstruction.into())
+    );
+    test.process(&f.init_nft_escrow(&test, Request::Mint, wanted.mint))
+        .unwrap();
+    // Y isn't what alice asked for
+    assert_eq!(
+        test.process(&f.exchange(&test, 1_000)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+
+    test.process(&f.cancel(&test)).unwrap();
+    assert_eq!(test.token_balance(&f.initializer_x), 1_000);
+    assert_eq!(test.lamports(&f.escrow), 0);
+}
+
+#[test]
+fn nft_for_nft() {
+    let mut test = ProgramTest::new();
+    let offered = test.add_nft();
+    let mint_y = test.add_mint(0);
+    let f = EscrowFixture::for_pair(&mut test, offered, mint_y, 1, 0);
+    let wanted = nft(&mut test, &f, None);
+    test.process(&f.init_nft_escrow(&test, Request::Mint, wanted.mint))
+        .unwrap();
+
+    test.process(&f.exchange_nft(&test, wanted.mint, wanted.taker, wanted.initializer, 1))
+        .unwrap();
+    assert_eq!(test.token_balance(&f.taker_x), 1);
+    assert_eq!(test.token_balance(&wanted.initializer), 1);
+}
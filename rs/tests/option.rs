@@ -0,0 +1,190 @@
+mod common;
+
+use solana_program::{clock::Clock, pubkey::Pubkey};
+
+use bpf_program_template::{
+    error::EscrowError,
+    state::{Escrow, EscrowMode, OptionTerms, Terms},
+};
+
+use common::{EscrowFixture, ProgramTest};
+
+const STRIKE: u64 = 500;
+const PREMIUM: u64 = 50;
+const EXPIRY: i64 = 2_000;
+
+fn at(test: &ProgramTest, unix_timestamp: i64) {
+    test.set_clock(Clock {
+        unix_timestamp,
+        ..Clock::default()
+    });
+}
+
+// alice writes a call on 1000 X, struck at 500 Y. The taker has 5000 Y to buy and exercise it with
+fn written(test: &mut ProgramTest) -> EscrowFixture {
+    let f = EscrowFixture::new(test, 1_000, 5_000).sized_for(test, EscrowMode::CoveredCall);
+    at(test, 1_000);
+    test.process(&f.write_option(test, STRIKE, PREMIUM, EXPIRY))
+        .unwrap();
+    f
+}
+
+fn bought(test: &mut ProgramTest) -> EscrowFixture {
+    let f = written(test);
+    test.process(&f.buy_option(test, PREMIUM)).unwrap();
+    f
+}
+
+#[test]
+fn buy_then_exercise() {
+    let mut test = ProgramTest::new();
+    let f = written(&mut test);
+
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(escrow.mode, EscrowMode::CoveredCall);
+    assert_eq!(
+        escrow.terms,
+        Terms::CoveredCall(OptionTerms {
+            expiry: EXPIRY,
+            premium: PREMIUM,
+            holder_pubkey: Pubkey::default(),
+        })
+    );
+    assert_eq!(test.token_account(&f.temp_x).unwrap().owner, test.pda().0);
+
+    test.process(&f.buy_option(&test, PREMIUM)).unwrap();
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(
+        escrow.terms,
+        Terms::CoveredCall(OptionTerms {
+            expiry: EXPIRY,
+            premium: PREMIUM,
+            holder_pubkey: f.taker,
+        })
+    );
+    assert_eq!(test.token_balance(&f.initializer_y), PREMIUM);
+
+    at(&test, EXPIRY - 1);
+    test.process(&f.exercise_option(&test, 1_000)).unwrap();
+    assert_eq!(test.token_balance(&f.taker_x), 1_000);
+    assert_eq!(test.token_balance(&f.initializer_y), PREMIUM + STRIKE);
+    assert_eq!(test.token_balance(&f.taker_y), 5_000 - PREMIUM - STRIKE);
+    assert!(test.token_account(&f.temp_x).is_none());
+    assert_eq!(test.lamports(&f.escrow), 0);
+}
+
+#[test]
+fn premium_has_to_match() {
+    let mut test = ProgramTest::new();
+    let f = written(&mut test);
+
+    assert_eq!(
+        test.process(&f.buy_option(&test, PREMIUM - 1)),
+        Err(EscrowError::ExpectedAmountMismatch.into())
+    );
+}
+
+#[test]
+fn option_is_sold_once() {
+    let mut test = ProgramTest::new();
+    let f = bought(&mut test);
+
+    assert_eq!(
+        test.process(&f.buy_option(&test, PREMIUM)),
+        Err(EscrowError::OptionAlreadySold.into())
+    );
+}
+
+#[test]
+fn only_the_holder_exercises() {
+    let mut test = ProgramTest::new();
+    let f = bought(&mut test);
+
+    let mallory = test.add_wallet(1_000_000_000);
+    let mallory_taker = EscrowFixture {
+        taker: mallory,
+        taker_x: test.add_token_account(&f.mint_x, &mallory, 0),
+        taker_y: test.add_token_account(&f.mint_y, &mallory, 5_000),
+        ..f
+    };
+    assert_eq!(
+        test.process(&mallory_taker.exercise_option(&test, 1_000)),
+        Err(EscrowError::NotOptionHolder.into())
+    );
+}
+
+#[test]
+fn cannot_exercise_or_buy_after_expiry() {
+    let mut test = ProgramTest::new();
+    let f = bought(&mut test);
+    at(&test, EXPIRY);
+
+    assert_eq!(
+        test.process(&f.exercise_option(&test, 1_000)),
+        Err(EscrowError::OptionExpired.into())
+    );
+
+    let mut test = ProgramTest::new();
+    let f = written(&mut test);
+    at(&test, EXPIRY);
+    assert_eq!(
+        test.process(&f.buy_option(&test, PREMIUM)),
+        Err(EscrowError::OptionExpired.into())
+    );
+}
+
+#[test]
+fn writer_reclaims_after_expiry() {
+    let mut test = ProgramTest::new();
+    let f = bought(&mut test);
+
+    at(&test, EXPIRY - 1);
+    assert_eq!(
+        test.process(&f.reclaim_option(&test)),
+        Err(EscrowError::TimelockNotExpired.into())
+    );
+
+    at(&test, EXPIRY);
+    test.process(&f.reclaim_option(&test)).unwrap();
+    assert_eq!(test.token_balance(&f.initializer_x), 1_000);
+    // the writer keeps the premium
+    assert_eq!(test.token_balance(&f.initializer_y), PREMIUM);
+    assert_eq!(test.lamports(&f.escrow), 0);
+}
+
+#[test]
+fn unsold_option_can_be_reclaimed_any_time() {
+    let mut test = ProgramTest::new();
+    let f = written(&mut test);
+
+    test.process(&f.reclaim_option(&test)).unwrap();
+    assert_eq!(test.token_balance(&f.initializer_x), 1_000);
+}
+
+#[test]
+fn option_is_not_an_ordinary_escrow() {
+    let mut test = ProgramTest::new();
+    let f = bought(&mut test);
+
+    assert_eq!(
+        test.process(&f.exchange(&test, 1_000)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+    assert_eq!(
+        test.process(&f.cancel(&test)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+
+    // and the other way round
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 5_000);
+    test.process(&f.init_escrow(&test, 500)).unwrap();
+    assert_eq!(
+        test.process(&f.buy_option(&test, 0)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+    assert_eq!(
+        test.process(&f.exercise_option(&test, 1_000)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+}
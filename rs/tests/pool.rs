@@ -0,0 +1,266 @@
+mod common;
+
+use solana_program::{
+    clock::Clock, hash::hash, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+};
+
+use bpf_program_template::{
+    error::EscrowError,
+    state::{Contribution, EscrowMode, Pool},
+};
+
+use common::{EscrowFixture, PoolAccounts, ProgramTest};
+
+const DEADLINE: i64 = 2_000;
+
+struct Contributor {
+    wallet: Pubkey,
+    x: Pubkey,
+    y: Pubkey,
+    contribution: Pubkey,
+}
+
+impl Contributor {
+    fn new(test: &mut ProgramTest, f: &EscrowFixture) -> Self {
+        let wallet = test.add_wallet(1_000_000_000);
+        Self {
+            wallet,
+            x: test.add_token_account(&f.mint_x, &wallet, 0),
+            y: test.add_token_account(&f.mint_y, &wallet, 1_000),
+            contribution: test.add_program_account(Contribution::LEN),
+        }
+    }
+
+    fn contribute(
+        &self,
+        test: &mut ProgramTest,
+        f: &EscrowFixture,
+        p: &PoolAccounts,
+        amount: u64,
+    ) -> Result<(), ProgramError> {
+        test.process(&f.contribute(test, p, self.wallet, self.y, self.contribution, amount))
+    }
+
+    fn leave(
+        &self,
+        test: &mut ProgramTest,
+        f: &EscrowFixture,
+        p: &PoolAccounts,
+        destination: Pubkey,
+    ) -> Result<(), ProgramError> {
+        test.process(&f.leave_pool(test, p, self.wallet, destination, self.contribution))
+    }
+}
+
+fn at(test: &ProgramTest, unix_timestamp: i64) {
+    test.set_clock(Clock {
+        unix_timestamp,
+        ..Clock::default()
+    });
+}
+
+fn pool_accounts(test: &mut ProgramTest, f: &EscrowFixture) -> PoolAccounts {
+    let creator = test.add_wallet(1_000_000_000);
+    PoolAccounts {
+        pool: test.add_program_account(Pool::LEN),
+        creator,
+        x_vault: test.add_token_account(&f.mint_x, &creator, 0),
+        y_vault: test.add_token_account(&f.mint_y, &creator, 0),
+    }
+}
+
+// alice lists 1000 X for 600 Y, and a pool sets out to take it
+fn pooled(test: &mut ProgramTest) -> (EscrowFixture, PoolAccounts) {
+    at(test, 1_000);
+    let f = EscrowFixture::new(test, 1_000, 0);
+    test.process(&f.init_escrow(test, 600)).unwrap();
+    let p = pool_accounts(test, &f);
+    test.process(&f.init_pool(test, &p, DEADLINE)).unwrap();
+    assert_eq!(test.token_account(&p.y_vault).unwrap().owner, test.pda().0);
+    assert_eq!(test.token_account(&p.x_vault).unwrap().owner, test.pda().0);
+    (f, p)
+}
+
+fn pool(test: &ProgramTest, p: &PoolAccounts) -> Pool {
+    Pool::unpack(&test.account(&p.pool).data).unwrap()
+}
+
+#[test]
+fn filled_pool_takes_the_escrow_and_splits_the_x() {
+    let mut test = ProgramTest::new();
+    let (f, p) = pooled(&mut test);
+    let contributors: Vec<Contributor> = (0..3).map(|_| Contributor::new(&mut test, &f)).collect();
+
+    // a copy of the pool that isn't ours
+    let copy = PoolAccounts {
+        pool: test.add_foreign_copy(&p.pool),
+        ..p
+    };
+    assert_eq!(
+        contributors[0].contribute(&mut test, &f, &copy, 50),
+        Err(ProgramError::IncorrectProgramId)
+    );
+    contributors[0].contribute(&mut test, &f, &p, 50).unwrap();
+    contributors[0].contribute(&mut test, &f, &p, 50).unwrap();
+    contributors[1].contribute(&mut test, &f, &p, 200).unwrap();
+    assert_eq!(
+        test.process(&f.execute_pool(&test, &p)),
+        Err(EscrowError::TargetNotFilled.into())
+    );
+    // only takes what's still missing
+    contributors[2]
+        .contribute(&mut test, &f, &p, 1_000)
+        .unwrap();
+    assert_eq!(test.token_balance(&contributors[2].y), 700);
+    assert_eq!(
+        (pool(&test, &p).raised, pool(&test, &p).contributors),
+        (600, 3)
+    );
+    assert_eq!(
+        contributors[0].contribute(&mut test, &f, &p, 1),
+        Err(EscrowError::PoolClosed.into())
+    );
+
+    assert_eq!(
+        test.process(&f.execute_pool(&test, &copy)),
+        Err(ProgramError::IncorrectProgramId)
+    );
+    test.process(&f.execute_pool(&test, &p)).unwrap();
+    assert_eq!(test.token_balance(&f.initializer_y), 600);
+    assert_eq!(test.token_balance(&p.x_vault), 1_000);
+    assert_eq!(test.lamports(&f.escrow), 0);
+    assert!(test.token_account(&f.temp_x).is_none());
+    assert!(test.token_account(&p.y_vault).is_none());
+    assert_eq!(
+        test.process(&f.execute_pool(&test, &p)),
+        Err(EscrowError::PoolClosed.into())
+    );
+
+    for (c, share) in contributors.iter().zip([166, 333].iter()) {
+        c.leave(&mut test, &f, &p, c.x).unwrap();
+        assert_eq!(test.token_balance(&c.x), *share);
+        assert_eq!(test.lamports(&c.contribution), 0);
+    }
+    // the last one out gets the rounding and the creator the rent
+    let rent = test.lamports(&p.pool) + test.lamports(&p.x_vault);
+    let creator_lamports = test.lamports(&p.creator);
+    contributors[2]
+        .leave(&mut test, &f, &p, contributors[2].x)
+        .unwrap();
+    assert_eq!(test.token_balance(&contributors[2].x), 501);
+    assert!(test.token_account(&p.x_vault).is_none());
+    assert_eq!(test.lamports(&p.pool), 0);
+    assert_eq!(test.lamports(&p.creator), creator_lamports + rent);
+}
+
+#[test]
+fn contributions_close_at_the_deadline() {
+    let mut test = ProgramTest::new();
+    let (f, p) = pooled(&mut test);
+    let carol = Contributor::new(&mut test, &f);
+    let dave = Contributor::new(&mut test, &f);
+
+    assert_eq!(
+        carol.contribute(&mut test, &f, &p, 0),
+        Err(EscrowError::InvalidInstruction.into())
+    );
+    carol.contribute(&mut test, &f, &p, 100).unwrap();
+    // one contribution account per contributor
+    let ix = f.contribute(&test, &p, dave.wallet, dave.y, carol.contribution, 100);
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+
+    at(&test, DEADLINE);
+    assert_eq!(
+        dave.contribute(&mut test, &f, &p, 100),
+        Err(EscrowError::PoolClosed.into())
+    );
+    // it didn't fill up, so it's refunds all round
+    assert_eq!(
+        test.process(&f.execute_pool(&test, &p)),
+        Err(EscrowError::TargetNotFilled.into())
+    );
+    let rent = test.lamports(&p.pool) + test.lamports(&p.x_vault) + test.lamports(&p.y_vault);
+    let creator_lamports = test.lamports(&p.creator);
+    carol.leave(&mut test, &f, &p, carol.y).unwrap();
+    assert_eq!(test.token_balance(&carol.y), 1_000);
+    assert!(test.token_account(&p.x_vault).is_none());
+    assert!(test.token_account(&p.y_vault).is_none());
+    assert_eq!(test.lamports(&p.creator), creator_lamports + rent);
+
+    // the escrow is still alice's
+    test.process(&f.cancel(&test)).unwrap();
+}
+
+#[test]
+fn refunds_once_the_escrow_is_gone() {
+    let mut test = ProgramTest::new();
+    let (f, p) = pooled(&mut test);
+    let carol = Contributor::new(&mut test, &f);
+    let dave = Contributor::new(&mut test, &f);
+    carol.contribute(&mut test, &f, &p, 100).unwrap();
+    dave.contribute(&mut test, &f, &p, 200).unwrap();
+
+    assert_eq!(
+        carol.leave(&mut test, &f, &p, carol.y),
+        Err(EscrowError::PoolStillOpen.into())
+    );
+    test.process(&f.cancel(&test)).unwrap();
+
+    // someone else's contribution
+    let ix = f.leave_pool(&test, &p, carol.wallet, carol.y, dave.contribution);
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+    // or a copy of the pool that isn't ours
+    let copy = PoolAccounts {
+        pool: test.add_foreign_copy(&p.pool),
+        ..p
+    };
+    assert_eq!(
+        carol.leave(&mut test, &f, &copy, carol.y),
+        Err(ProgramError::IncorrectProgramId)
+    );
+    carol.leave(&mut test, &f, &p, carol.y).unwrap();
+    assert_eq!(test.token_balance(&carol.y), 1_000);
+    assert_eq!(pool(&test, &p).contributors, 1);
+    assert_eq!(
+        carol.leave(&mut test, &f, &p, carol.y),
+        Err(ProgramError::IncorrectProgramId)
+    );
+    dave.leave(&mut test, &f, &p, dave.y).unwrap();
+    assert_eq!(test.token_balance(&dave.y), 1_000);
+    assert_eq!(test.lamports(&p.pool), 0);
+}
+
+#[test]
+fn only_plain_listings_can_be_pooled() {
+    let mut test = ProgramTest::new();
+    at(&test, 1_000);
+    let f = EscrowFixture::new(&mut test, 1_000, 0).sized_for(&mut test, EscrowMode::Htlc);
+    test.process(&f.init_htlc(&test, hash(&[42; 32]).to_bytes(), DEADLINE))
+        .unwrap();
+    let p = pool_accounts(&mut test, &f);
+    assert_eq!(
+        test.process(&f.init_pool(&test, &p, DEADLINE)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+
+    let f = EscrowFixture::new(&mut test, 1_000, 0);
+    test.process(&f.init_escrow(&test, 600)).unwrap();
+    let p = pool_accounts(&mut test, &f);
+    assert_eq!(
+        test.process(&f.init_pool(&test, &p, 1_000)),
+        Err(EscrowError::TimelockInThePast.into())
+    );
+    let full = PoolAccounts {
+        y_vault: test.add_token_account(&f.mint_y, &p.creator, 1),
+        ..p
+    };
+    assert_eq!(
+        test.process(&f.init_pool(&test, &full, DEADLINE)),
+        Err(ProgramError::InvalidAccountData)
+    );
+    test.process(&f.init_pool(&test, &p, DEADLINE)).unwrap();
+    assert_eq!(
+        test.process(&f.init_pool(&test, &p, DEADLINE)),
+        Err(ProgramError::AccountAlreadyInitialized)
+    );
+}
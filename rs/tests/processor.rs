@@ -0,0 +1,185 @@
+mod common;
+
+use solana_program::{
+    entrypoint::ProgramResult, program_error::ProgramError, program_option::COption,
+    program_pack::Pack,
+};
+use spl_token::state::{Account as TokenAccount, AccountState};
+
+use bpf_program_template::{
+    error::EscrowError,
+    state::{Escrow, LegacyEscrow},
+};
+
+use common::{EscrowFixture, ProgramTest};
+
+#[test]
+fn exchange_swaps_both_legs_and_closes_everything() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 5_000);
+    test.process(&f.init_escrow(&test, 500)).unwrap();
+
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(escrow.expected_amount, 500);
+    assert_eq!(test.token_account(&f.temp_x).unwrap().owner, test.pda().0);
+
+    let rent = test.lamports(&f.escrow) + test.lamports(&f.temp_x);
+    let initializer_lamports = test.lamports(&f.initializer);
+    test.process(&f.exchange(&test, 1_000)).unwrap();
+
+    assert_eq!(test.token_balance(&f.taker_x), 1_000);
+    assert_eq!(test.token_balance(&f.initializer_y), 500);
+    assert_eq!(test.token_balance(&f.taker_y), 4_500);
+    assert!(test.token_account(&f.temp_x).is_none());
+    assert_eq!(test.lamports(&f.escrow), 0);
+    assert_eq!(test.lamports(&f.initializer), initializer_lamports + rent);
+}
+
+#[test]
+fn exchange_rejects_amount_mismatch() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 5_000);
+    test.process(&f.init_escrow(&test, 500)).unwrap();
+
+    assert_eq!(
+        test.process(&f.exchange(&test, 999)),
+        Err(EscrowError::ExpectedAmountMismatch.into())
+    );
+}
+
+#[test]
+fn cancel_returns_tokens_to_initializer() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 5_000);
+    test.process(&f.init_escrow(&test, 500)).unwrap();
+    test.process(&f.cancel(&test)).unwrap();
+
+    assert_eq!(test.token_balance(&f.initializer_x), 1_000);
+    assert!(test.token_account(&f.temp_x).is_none());
+    assert_eq!(test.lamports(&f.escrow), 0);
+}
+
+#[test]
+fn cancel_without_bump_seed_is_an_error() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 5_000);
+    let mut ix = f.cancel(&test);
+    ix.data.truncate(1);
+
+    assert_eq!(
+        test.process(&ix),
+        Err(ProgramError::Custom(EscrowError::InvalidInstruction as u32))
+    );
+}
+
+// lists `temp` instead of the fixture's own temp account
+fn init_with_temp(temp: impl FnOnce(&EscrowFixture) -> TokenAccount) -> ProgramResult {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 5_000);
+    let account = temp(&f);
+    let f = EscrowFixture {
+        temp_x: test.add_token_account_with(account),
+        ..f
+    };
+    test.process(&f.init_escrow(&test, 500))
+}
+
+fn temp_account(f: &EscrowFixture) -> TokenAccount {
+    TokenAccount {
+        mint: f.mint_x,
+        owner: f.initializer,
+        amount: 1_000,
+        state: AccountState::Initialized,
+        ..TokenAccount::default()
+    }
+}
+
+#[test]
+fn init_rejects_temp_account_with_delegate() {
+    assert_eq!(
+        init_with_temp(|f| TokenAccount {
+            delegate: COption::Some(f.taker),
+            delegated_amount: 1_000,
+            ..temp_account(f)
+        }),
+        Err(EscrowError::TempAccountHasDelegate.into())
+    );
+}
+
+#[test]
+fn init_rejects_temp_account_with_close_authority() {
+    assert_eq!(
+        init_with_temp(|f| TokenAccount {
+            close_authority: COption::Some(f.taker),
+            ..temp_account(f)
+        }),
+        Err(EscrowError::TempAccountHasCloseAuthority.into())
+    );
+}
+
+#[test]
+fn init_rejects_frozen_temp_account() {
+    assert_eq!(
+        init_with_temp(|f| TokenAccount {
+            state: AccountState::Frozen,
+            ..temp_account(f)
+        }),
+        Err(EscrowError::TempAccountFrozen.into())
+    );
+}
+
+#[test]
+fn init_rejects_native_temp_account() {
+    assert_eq!(
+        init_with_temp(|f| TokenAccount {
+            is_native: COption::Some(2_039_280),
+            ..temp_account(f)
+        }),
+        Err(EscrowError::TempAccountIsNative.into())
+    );
+}
+
+#[test]
+fn init_rejects_empty_temp_account() {
+    assert_eq!(
+        init_with_temp(|f| TokenAccount {
+            amount: 0,
+            ..temp_account(f)
+        }),
+        Err(EscrowError::TempAccountEmpty.into())
+    );
+}
+
+// escrows from before the account type are still 105 bytes with a 1 up front, and can still be cancelled
+#[test]
+fn cancel_takes_a_legacy_escrow() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 0, 5_000);
+    let pda = test.pda().0;
+    let f = EscrowFixture {
+        temp_x: test.add_token_account(&f.mint_x, &pda, 1_000),
+        escrow: test.add_program_account(LegacyEscrow::LEN),
+        ..f
+    };
+    LegacyEscrow {
+        is_initialized: true,
+        initializer_pubkey: f.initializer,
+        temp_token_account_pubkey: f.temp_x,
+        initializer_token_to_receive_account_pubkey: f.initializer_y,
+        expected_amount: 500,
+    }
+    .pack_into_slice(&mut test.accounts.get_mut(&f.escrow).unwrap().data);
+    assert_eq!(
+        Escrow::unpack(&test.account(&f.escrow).data),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    let rent = test.lamports(&f.escrow) + test.lamports(&f.temp_x);
+    let initializer_lamports = test.lamports(&f.initializer);
+    test.process(&f.cancel(&test)).unwrap();
+
+    assert_eq!(test.token_balance(&f.initializer_x), 1_000);
+    assert!(test.token_account(&f.temp_x).is_none());
+    assert_eq!(test.lamports(&f.escrow), 0);
+    assert_eq!(test.lamports(&f.initializer), initializer_lamports + rent);
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use solana_program::{program_error::ProgramError};
+
+use bpf_program_template::state::{Escrow, EscrowMode};
+
+use common::{EscrowFixture, ProgramTest};
+
+// a marketplace pays the rent for alice's listing
+fn sponsored(test: &mut ProgramTest) -> EscrowFixture {
+    let f = EscrowFixture::new(test, 1_000, 5_000);
+    EscrowFixture {
+        rent_payer: Some(test.add_wallet(1_000_000_000)),
+        ..f
+    }
+}
+
+#[test]
+fn rent_payer_defaults_to_the_initializer() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 5_000);
+    test.process(&f.init_escrow(&test, 500)).unwrap();
+
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(escrow.rent_payer, f.initializer);
+}
+
+#[test]
+fn exchange_refunds_the_rent_payer() {
+    let mut test = ProgramTest::new();
+    let f = sponsored(&mut test);
+    let rent_payer = f.rent_payer.unwrap();
+    test.process(&f.init_escrow(&test, 500)).unwrap();
+
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(escrow.rent_payer, rent_payer);
+
+    let rent = test.lamports(&f.escrow) + test.lamports(&f.temp_x);
+    let rent_payer_lamports = test.lamports(&rent_payer);
+    let initializer_lamports = test.lamports(&f.initializer);
+    test.process(&f.exchange(&test, 1_000)).unwrap();
+
+    assert_eq!(test.token_balance(&f.initializer_y), 500);
+    assert_eq!(test.lamports(&rent_payer), rent_payer_lamports + rent);
+    assert_eq!(test.lamports(&f.initializer), initializer_lamports);
+}
+
+#[test]
+fn cancel_refunds_the_rent_payer() {
+    let mut test = ProgramTest::new();
+    let f = sponsored(&mut test);
+    let rent_payer = f.rent_payer.unwrap();
+    test.process(&f.init_escrow(&test, 500)).unwrap();
+
+    let rent = test.lamports(&f.escrow) + test.lamports(&f.temp_x);
+    let rent_payer_lamports = test.lamports(&rent_payer);
+    test.process(&f.cancel(&test)).unwrap();
+
+    assert_eq!(test.token_balance(&f.initializer_x), 1_000);
+    assert_eq!(test.lamports(&rent_payer), rent_payer_lamports + rent);
+}
+
+#[test]
+fn approve_mode_refunds_the_rent_payer() {
+    let mut test = ProgramTest::new();
+    let f = sponsored(&mut test);
+    let rent_payer = f.rent_payer.unwrap();
+    test.process(&f.init_approve_escrow(&test, 500, 400))
+        .unwrap();
+
+    let rent = test.lamports(&f.escrow);
+    let rent_payer_lamports = test.lamports(&rent_payer);
+    test.process(&f.exchange(&test, 400)).unwrap();
+
+    assert_eq!(test.lamports(&rent_payer), rent_payer_lamports + rent);
+}
+
+#[test]
+fn exchange_needs_the_recorded_rent_payer() {
+    let mut test = ProgramTest::new();
+    let f = sponsored(&mut test);
+    test.process(&f.init_escrow(&test, 500)).unwrap();
+
+    let mut ix = f.exchange(&test, 1_000);
+    ix.accounts.pop();
+    assert_eq!(test.process(&ix), Err(ProgramError::NotEnoughAccountKeys));
+
+    // the taker can't redirect the rent to themselves
+    let f = EscrowFixture {
+        rent_payer: Some(f.taker),
+        ..f
+    };
+    assert_eq!(
+        test.process(&f.exchange(&test, 1_000)),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn the_rent_payer_signs_up_for_it() {
+    let mut test = ProgramTest::new();
+    let f = sponsored(&mut test);
+
+    // named but not signing - nobody gets made the rent payer behind their back
+    let mut ix = f.init_escrow(&test, 500);
+    ix.accounts.last_mut().unwrap().is_signer = false;
+    assert_eq!(
+        test.process(&ix),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    // the flag says it's there, so it has to be
+    ix.accounts.pop();
+    assert_eq!(test.process(&ix), Err(ProgramError::NotEnoughAccountKeys));
+
+    // without the flag an extra account is just an extra account
+    ix.data.pop();
+    ix.accounts.extend(f.init_escrow(&test, 500).accounts.pop());
+    test.process(&ix).unwrap();
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(escrow.rent_payer, f.initializer);
+}
+
+#[test]
+fn every_init_takes_the_flag() {
+    let mut test = ProgramTest::new();
+    let f = sponsored(&mut test);
+    let rent_payer = f.rent_payer.unwrap();
+
+    // the Approve PDA and the auction's bid vault come before it
+    test.process(&f.init_approve_escrow(&test, 500, 400))
+        .unwrap();
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(escrow.rent_payer, rent_payer);
+
+    let f = EscrowFixture {
+        rent_payer: Some(rent_payer),
+        ..EscrowFixture::new(&mut test, 1_000, 0).sized_for(&mut test, EscrowMode::EnglishAuction)
+    };
+    test.process(&f.init_english_auction(&test, 500, 10, 1_000, 60))
+        .unwrap();
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(escrow.rent_payer, rent_payer);
+    assert_eq!(
+        escrow.terms.auction().unwrap().bid_vault_pubkey,
+        f.bid_vault
+    );
+
+    let f = EscrowFixture {
+        rent_payer: Some(rent_payer),
+        ..EscrowFixture::new(&mut test, 1_000, 0).sized_for(&mut test, EscrowMode::Htlc)
+    };
+    let mut ix = f.init_htlc(&test, [7; 32], 1_000);
+    ix.accounts.last_mut().unwrap().is_signer = false;
+    assert_eq!(
+        test.process(&ix),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+}
@@ -0,0 +1,182 @@
+mod common;
+
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+use bpf_program_template::{
+    error::EscrowError,
+    metadata::{Creator, Metadata},
+    state::Escrow,
+};
+
+use common::{EscrowFixture, ProgramTest};
+
+// alice sells an NFT for Y, its creators have `shares` of `bps`. Returns their Y accounts, for the creators with a share
+fn listed(
+    test: &mut ProgramTest,
+    price: u64,
+    bps: u16,
+    shares: &[u8],
+) -> (EscrowFixture, Vec<Pubkey>) {
+    let nft = test.add_nft();
+    let mint_y = test.add_mint(0);
+    let f = EscrowFixture::for_pair(test, nft, mint_y, 1, 10_000);
+    let creators: Vec<Creator> = shares
+        .iter()
+        .map(|share| Creator {
+            address: test.add_wallet(1_000_000_000),
+            verified: true,
+            share: *share,
+        })
+        .collect();
+    test.add_metadata(&Metadata {
+        mint: nft,
+        seller_fee_basis_points: bps,
+        creators: creators.clone(),
+        ..Metadata::default()
+    });
+    let creators_y = creators
+        .iter()
+        .filter(|creator| creator.share > 0)
+        .map(|creator| test.add_token_account(&mint_y, &creator.address, 0))
+        .collect();
+    test.process(&f.init_royalty_escrow(test, price)).unwrap();
+    (f, creators_y)
+}
+
+#[test]
+fn creators_get_their_cut_of_the_price() {
+    let mut test = ProgramTest::new();
+    let (f, creators_y) = listed(&mut test, 1_000, 500, &[70, 0, 30]);
+    assert!(
+        Escrow::unpack(&test.account(&f.escrow).data)
+            .unwrap()
+            .royalties
+    );
+
+    test.process(&f.exchange_with_royalties(&test, 1, &creators_y))
+        .unwrap();
+    assert_eq!(test.token_balance(&f.taker_x), 1);
+    assert_eq!(test.token_balance(&creators_y[0]), 35);
+    assert_eq!(test.token_balance(&creators_y[1]), 15);
+    assert_eq!(test.token_balance(&f.initializer_y), 950);
+    assert_eq!(test.token_balance(&f.taker_y), 9_000);
+    assert_eq!(test.lamports(&f.escrow), 0);
+}
+
+#[test]
+fn rounding_leftovers_stay_with_the_seller() {
+    let mut test = ProgramTest::new();
+    let (f, creators_y) = listed(&mut test, 999, 333, &[70, 30]);
+
+    test.process(&f.exchange_with_royalties(&test, 1, &creators_y))
+        .unwrap();
+    assert_eq!(test.token_balance(&creators_y[0]), 23);
+    assert_eq!(test.token_balance(&creators_y[1]), 9);
+    assert_eq!(test.token_balance(&f.initializer_y), 967);
+    assert_eq!(test.token_balance(&f.taker_y), 10_000 - 999);
+}
+
+#[test]
+fn creator_accounts_have_to_match_the_metadata() {
+    let mut test = ProgramTest::new();
+    let (f, creators_y) = listed(&mut test, 1_000, 500, &[70, 30]);
+
+    // missing altogether, or one of them
+    assert_eq!(
+        test.process(&f.exchange(&test, 1)),
+        Err(ProgramError::NotEnoughAccountKeys)
+    );
+    assert_eq!(
+        test.process(&f.exchange_with_royalties(&test, 1, &creators_y[..1])),
+        Err(ProgramError::NotEnoughAccountKeys)
+    );
+    // out of order
+    let swapped = [creators_y[1], creators_y[0]];
+    assert_eq!(
+        test.process(&f.exchange_with_royalties(&test, 1, &swapped)),
+        Err(EscrowError::CreatorAccountMismatch.into())
+    );
+    // someone else's
+    let taker_instead = [f.taker_y, creators_y[1]];
+    assert_eq!(
+        test.process(&f.exchange_with_royalties(&test, 1, &taker_instead)),
+        Err(EscrowError::CreatorAccountMismatch.into())
+    );
+    // the metadata of another NFT
+    let (other, _) = listed(&mut test, 1_000, 0, &[]);
+    let mut ix = f.exchange_with_royalties(&test, 1, &creators_y);
+    ix.accounts[12] = other.exchange_with_royalties(&test, 1, &[]).accounts[12].clone();
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+
+    test.process(&f.exchange_with_royalties(&test, 1, &creators_y))
+        .unwrap();
+}
+
+#[test]
+fn sponsored_rent_comes_after_the_creators() {
+    let mut test = ProgramTest::new();
+    let nft = test.add_nft();
+    let mint_y = test.add_mint(0);
+    let f = EscrowFixture::for_pair(&mut test, nft, mint_y, 1, 1_000);
+    let f = EscrowFixture {
+        rent_payer: Some(test.add_wallet(1_000_000_000)),
+        ..f
+    };
+    let creator = test.add_wallet(1_000_000_000);
+    test.add_metadata(&Metadata {
+        mint: nft,
+        seller_fee_basis_points: 1_000,
+        creators: vec![Creator {
+            address: creator,
+            verified: false,
+            share: 100,
+        }],
+        ..Metadata::default()
+    });
+    let creator_y = test.add_token_account(&mint_y, &creator, 0);
+    test.process(&f.init_royalty_escrow(&test, 1_000)).unwrap();
+
+    let rent = test.lamports(&f.escrow) + test.lamports(&f.temp_x);
+    let rent_payer_lamports = test.lamports(&f.rent_payer.unwrap());
+    test.process(&f.exchange_with_royalties(&test, 1, &[creator_y]))
+        .unwrap();
+    assert_eq!(test.token_balance(&creator_y), 100);
+    assert_eq!(test.token_balance(&f.initializer_y), 900);
+    assert_eq!(
+        test.lamports(&f.rent_payer.unwrap()),
+        rent_payer_lamports + rent
+    );
+}
+
+#[test]
+fn shares_adding_up_to_more_than_the_price() {
+    let mut test = ProgramTest::new();
+    let (f, creators_y) = listed(&mut test, 1_000, 10_000, &[100, 100]);
+
+    assert_eq!(
+        test.process(&f.exchange_with_royalties(&test, 1, &creators_y)),
+        Err(EscrowError::AmountOverflow.into())
+    );
+}
+
+#[test]
+fn royalty_escrows_are_nfts_taken_by_exchange() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 1_000);
+    assert_eq!(
+        test.process(&f.init_royalty_escrow(&test, 500)),
+        Err(EscrowError::NotAnNft.into())
+    );
+
+    let (f, _) = listed(&mut test, 1_000, 500, &[100]);
+    // a flash exchange would skip the creators
+    assert_eq!(
+        test.process_transaction(&[
+            f.flash_exchange(&test, 1),
+            f.settle_flash(&test, f.taker, f.taker_y),
+        ]),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+    test.process(&f.cancel(&test)).unwrap();
+    assert_eq!(test.token_balance(&f.initializer_x), 1);
+}
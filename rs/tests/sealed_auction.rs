@@ -0,0 +1,328 @@
+mod common;
+
+use solana_program::{clock::Clock, program_pack::Pack, pubkey::Pubkey};
+
+use bpf_program_template::{
+    auction::bid_commitment,
+    error::EscrowError,
+    state::{AuctionTerms, Escrow, EscrowMode, SealedBid, SealedTerms, Terms},
+};
+
+use common::{EscrowFixture, ProgramTest};
+
+const RESERVE: u64 = 500;
+const BOND: u64 = 100;
+const COMMIT_END: i64 = 2_000;
+const REVEAL_END: i64 = 3_000;
+
+fn at(test: &ProgramTest, unix_timestamp: i64) {
+    test.set_clock(Clock {
+        unix_timestamp,
+        ..Clock::default()
+    });
+}
+
+// alice auctions 1000 X, the taker has 5000 Y to bid with
+fn listed(test: &mut ProgramTest, second_price: bool) -> EscrowFixture {
+    let f = EscrowFixture::new(test, 1_000, 5_000).sized_for(test, EscrowMode::SealedAuction);
+    at(test, 1_000);
+    test.process(&f.init_sealed_auction(test, RESERVE, BOND, COMMIT_END, REVEAL_END, second_price))
+        .unwrap();
+    f
+}
+
+// a second bidder with 5000 Y, and an X account to win into
+fn bidder(test: &mut ProgramTest, f: &EscrowFixture) -> (Pubkey, Pubkey, Pubkey) {
+    let wallet = test.add_wallet(1_000_000_000);
+    let y = test.add_token_account(&f.mint_y, &wallet, 5_000);
+    let x = test.add_token_account(&f.mint_x, &wallet, 0);
+    (wallet, y, x)
+}
+
+fn salt(bidder: &Pubkey) -> [u8; 32] {
+    bidder.to_bytes()
+}
+
+// commits to `amount` with a fresh bid account
+fn commit(
+    test: &mut ProgramTest,
+    f: &EscrowFixture,
+    bidder: Pubkey,
+    bidder_y: Pubkey,
+    amount: u64,
+) -> Pubkey {
+    let bid = test.add_program_account(SealedBid::LEN);
+    let commitment = bid_commitment(amount, &salt(&bidder), &bidder);
+    test.process(&f.commit_bid(test, bidder, bidder_y, bid, commitment))
+        .unwrap();
+    bid
+}
+
+#[test]
+fn first_price_goes_to_the_highest_bid() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test, false);
+    let (carol, carol_y, _) = bidder(&mut test, &f);
+
+    let taker_bid = commit(&mut test, &f, f.taker, f.taker_y, 700);
+    let carol_bid = commit(&mut test, &f, carol, carol_y, 600);
+    assert_eq!(test.token_balance(&f.bid_vault), 2 * BOND);
+    let bid = SealedBid::unpack(&test.account(&taker_bid).data).unwrap();
+    assert_eq!((bid.escrow_pubkey, bid.bidder_pubkey), (f.escrow, f.taker));
+    assert_eq!((bid.bond, bid.reveal_end), (BOND, REVEAL_END));
+
+    at(&test, COMMIT_END);
+    let rent = test.lamports(&taker_bid);
+    let taker_lamports = test.lamports(&f.taker);
+    test.process(&f.reveal_bid(
+        &test,
+        f.taker,
+        f.taker_y,
+        taker_bid,
+        700,
+        salt(&f.taker),
+        None,
+    ))
+    .unwrap();
+    assert_eq!(test.token_balance(&f.taker_y), 5_000 - 700);
+    assert_eq!(test.lamports(&taker_bid), 0);
+    assert_eq!(test.lamports(&f.taker), taker_lamports + rent);
+
+    // carol's lower bid only gets her bond back
+    test.process(&f.reveal_bid(&test, carol, carol_y, carol_bid, 600, salt(&carol), None))
+        .unwrap();
+    assert_eq!(test.token_balance(&carol_y), 5_000);
+    assert_eq!(test.token_balance(&f.bid_vault), 700);
+    let escrow = Escrow::unpack(&test.account(&f.escrow).data).unwrap();
+    assert_eq!(escrow.mode, EscrowMode::SealedAuction);
+    assert_eq!(
+        escrow.terms,
+        Terms::SealedAuction(SealedTerms {
+            auction: AuctionTerms {
+                end: REVEAL_END,
+                bid_vault_pubkey: f.bid_vault,
+                highest_bid: 700,
+                bidder_pubkey: f.taker,
+            },
+            commit_end: COMMIT_END,
+            bond: BOND,
+            second_bid: 600,
+            second_price: false,
+        })
+    );
+
+    at(&test, REVEAL_END);
+    test.process(&f.settle_sealed_auction(&test, f.taker_x, f.taker_y))
+        .unwrap();
+    assert_eq!(test.token_balance(&f.taker_x), 1_000);
+    assert_eq!(test.token_balance(&f.taker_y), 5_000 - 700);
+    assert_eq!(test.token_balance(&f.initializer_y), 700);
+    assert!(test.token_account(&f.bid_vault).is_none());
+    assert_eq!(test.lamports(&f.escrow), 0);
+}
+
+#[test]
+fn second_price_refunds_the_difference() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test, true);
+    let (carol, carol_y, _) = bidder(&mut test, &f);
+
+    let taker_bid = commit(&mut test, &f, f.taker, f.taker_y, 700);
+    let carol_bid = commit(&mut test, &f, carol, carol_y, 600);
+
+    // carol is ahead until the taker's bid is opened
+    at(&test, COMMIT_END);
+    test.process(&f.reveal_bid(&test, carol, carol_y, carol_bid, 600, salt(&carol), None))
+        .unwrap();
+    assert_eq!(test.token_balance(&carol_y), 5_000 - 600);
+    test.process(&f.reveal_bid(
+        &test,
+        f.taker,
+        f.taker_y,
+        taker_bid,
+        700,
+        salt(&f.taker),
+        Some(carol_y),
+    ))
+    .unwrap();
+    assert_eq!(test.token_balance(&carol_y), 5_000);
+
+    at(&test, REVEAL_END);
+    test.process(&f.settle_sealed_auction(&test, f.taker_x, f.taker_y))
+        .unwrap();
+    assert_eq!(test.token_balance(&f.taker_x), 1_000);
+    assert_eq!(test.token_balance(&f.taker_y), 5_000 - 600);
+    assert_eq!(test.token_balance(&f.initializer_y), 600);
+}
+
+#[test]
+fn unrevealed_bonds_go_to_the_seller() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test, true);
+    let (carol, carol_y, _) = bidder(&mut test, &f);
+
+    let taker_bid = commit(&mut test, &f, f.taker, f.taker_y, 700);
+    let carol_bid = commit(&mut test, &f, carol, carol_y, 900);
+
+    at(&test, COMMIT_END);
+    test.process(&f.reveal_bid(
+        &test,
+        f.taker,
+        f.taker_y,
+        taker_bid,
+        700,
+        salt(&f.taker),
+        None,
+    ))
+    .unwrap();
+
+    // nobody else revealed, so the taker pays the reserve, and carol's bond is the seller's
+    at(&test, REVEAL_END);
+    test.process(&f.settle_sealed_auction(&test, f.taker_x, f.taker_y))
+        .unwrap();
+    assert_eq!(test.token_balance(&f.taker_y), 5_000 - RESERVE);
+    assert_eq!(test.token_balance(&f.initializer_y), RESERVE + BOND);
+    assert_eq!(test.token_balance(&carol_y), 5_000 - BOND);
+
+    // all carol gets back is the bid account's rent
+    let rent = test.lamports(&carol_bid);
+    let carol_lamports = test.lamports(&carol);
+    test.process(&f.close_bid(&test, carol, carol_bid)).unwrap();
+    assert_eq!(test.lamports(&carol_bid), 0);
+    assert_eq!(test.lamports(&carol), carol_lamports + rent);
+}
+
+#[test]
+fn each_step_has_its_phase() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test, false);
+    let bid = commit(&mut test, &f, f.taker, f.taker_y, 700);
+    let reveal =
+        |test: &ProgramTest| f.reveal_bid(test, f.taker, f.taker_y, bid, 700, salt(&f.taker), None);
+
+    assert_eq!(
+        test.process(&reveal(&test)),
+        Err(EscrowError::WrongAuctionPhase.into())
+    );
+    assert_eq!(
+        test.process(&f.close_bid(&test, f.taker, bid)),
+        Err(EscrowError::TimelockNotExpired.into())
+    );
+
+    at(&test, COMMIT_END);
+    let late = test.add_program_account(SealedBid::LEN);
+    assert_eq!(
+        test.process(&f.commit_bid(&test, f.taker, f.taker_y, late, [0; 32])),
+        Err(EscrowError::WrongAuctionPhase.into())
+    );
+    assert_eq!(
+        test.process(&f.settle_sealed_auction(&test, f.taker_x, f.taker_y)),
+        Err(EscrowError::TimelockNotExpired.into())
+    );
+
+    at(&test, REVEAL_END);
+    assert_eq!(
+        test.process(&reveal(&test)),
+        Err(EscrowError::WrongAuctionPhase.into())
+    );
+}
+
+#[test]
+fn reveal_has_to_match_the_commitment() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test, false);
+    let (carol, carol_y, _) = bidder(&mut test, &f);
+    let bid = commit(&mut test, &f, f.taker, f.taker_y, 700);
+
+    // carol copies the taker's commitment, but it's tied to the taker
+    let copy = test.add_program_account(SealedBid::LEN);
+    let commitment = SealedBid::unpack(&test.account(&bid).data)
+        .unwrap()
+        .commitment;
+    test.process(&f.commit_bid(&test, carol, carol_y, copy, commitment))
+        .unwrap();
+
+    at(&test, COMMIT_END);
+    assert_eq!(
+        test.process(&f.reveal_bid(&test, f.taker, f.taker_y, bid, 600, salt(&f.taker), None)),
+        Err(EscrowError::CommitmentMismatch.into())
+    );
+    assert_eq!(
+        test.process(&f.reveal_bid(&test, carol, carol_y, copy, 700, salt(&f.taker), None)),
+        Err(EscrowError::CommitmentMismatch.into())
+    );
+    test.process(&f.reveal_bid(&test, f.taker, f.taker_y, bid, 700, salt(&f.taker), None))
+        .unwrap();
+}
+
+#[test]
+fn below_reserve_leaves_it_unsold() {
+    let mut test = ProgramTest::new();
+    let f = listed(&mut test, true);
+    let bid = commit(&mut test, &f, f.taker, f.taker_y, RESERVE - 1);
+
+    at(&test, COMMIT_END);
+    test.process(&f.reveal_bid(
+        &test,
+        f.taker,
+        f.taker_y,
+        bid,
+        RESERVE - 1,
+        salt(&f.taker),
+        None,
+    ))
+    .unwrap();
+    assert_eq!(test.token_balance(&f.taker_y), 5_000);
+
+    at(&test, REVEAL_END);
+    test.process(&f.settle_sealed_auction(&test, f.initializer_x, f.initializer_y))
+        .unwrap();
+    assert_eq!(test.token_balance(&f.initializer_x), 1_000);
+    assert_eq!(test.token_balance(&f.initializer_y), 0);
+    assert_eq!(test.lamports(&f.escrow), 0);
+}
+
+#[test]
+fn bad_terms_and_no_exchange_or_cancel() {
+    let mut test = ProgramTest::new();
+    let f =
+        EscrowFixture::new(&mut test, 1_000, 5_000).sized_for(&mut test, EscrowMode::SealedAuction);
+    at(&test, 1_000);
+    for (bond, commit_end, reveal_end) in [
+        (RESERVE + 1, COMMIT_END, REVEAL_END),
+        (BOND, COMMIT_END, COMMIT_END),
+    ]
+    .iter()
+    {
+        assert_eq!(
+            test.process(&f.init_sealed_auction(
+                &test,
+                RESERVE,
+                *bond,
+                *commit_end,
+                *reveal_end,
+                false
+            )),
+            Err(EscrowError::InvalidInstruction.into())
+        );
+    }
+    assert_eq!(
+        test.process(&f.init_sealed_auction(&test, RESERVE, BOND, 1_000, REVEAL_END, false)),
+        Err(EscrowError::TimelockInThePast.into())
+    );
+
+    let f = listed(&mut test, false);
+    assert_eq!(
+        test.process(&f.exchange(&test, 1_000)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+    assert_eq!(
+        test.process(&f.cancel(&test)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+    // nor is it an English auction
+    assert_eq!(
+        test.process(&f.bid(&test, f.taker, f.taker_y, 700, None)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+}
@@ -0,0 +1,259 @@
+mod common;
+
+use solana_program::{
+    clock::Clock, instruction::Instruction, program_error::ProgramError, program_option::COption,
+    program_pack::Pack, pubkey::Pubkey, rent::Rent, system_program,
+};
+use spl_token::state::Account as TokenAccount;
+
+use bpf_program_template::{
+    error::EscrowError,
+    order::{delegate_address, nonce_bitmap_address, Order},
+    state::NonceBitmap,
+};
+
+use common::{ed25519_instruction, Account, EscrowFixture, ProgramTest};
+
+const EXPIRY: i64 = 1_000;
+
+// the maker has 1000 X in temp_x and lets their order delegate move `approved` of it, the taker has 5000 Y
+fn maker(test: &mut ProgramTest, approved: u64) -> EscrowFixture {
+    let f = EscrowFixture::new(test, 1_000, 5_000);
+    let mut token = test.token_account(&f.temp_x).unwrap();
+    token.delegate = COption::Some(delegate_address(&f.initializer, &test.program_id).0);
+    token.delegated_amount = approved;
+    TokenAccount::pack(token, &mut test.accounts.get_mut(&f.temp_x).unwrap().data).unwrap();
+    f
+}
+
+// the transaction the taker would send: the maker's signature checked, then the fill
+fn signed(test: &ProgramTest, f: &EscrowFixture, order: &Order) -> Vec<Instruction> {
+    vec![
+        ed25519_instruction(&order.maker, &order.message(&test.program_id)),
+        f.fill_signed_order(test, order),
+    ]
+}
+
+fn bitmap(test: &ProgramTest, maker: &Pubkey, window: u64) -> Pubkey {
+    nonce_bitmap_address(maker, window, &test.program_id).0
+}
+
+#[test]
+fn fill_swaps_and_uses_up_the_nonce() {
+    let mut test = ProgramTest::new();
+    let f = maker(&mut test, 1_000);
+    let order = f.order(600, 300, EXPIRY, 7);
+
+    let taker_lamports = test.lamports(&f.taker);
+    test.process_at(&signed(&test, &f, &order), 1).unwrap();
+    assert_eq!(test.token_balance(&f.taker_x), 600);
+    assert_eq!(test.token_balance(&f.temp_x), 400);
+    assert_eq!(test.token_balance(&f.initializer_y), 300);
+    assert_eq!(test.token_balance(&f.taker_y), 4_700);
+    assert_eq!(test.token_account(&f.temp_x).unwrap().delegated_amount, 400);
+
+    // the taker paid for the bitmap
+    let bitmap = bitmap(&test, &f.initializer, 0);
+    let rent = Rent::default().minimum_balance(NonceBitmap::LEN);
+    assert_eq!(test.lamports(&f.taker), taker_lamports - rent);
+    assert_eq!(test.account(&bitmap).owner, test.program_id);
+    let nonces = NonceBitmap::unpack(&test.account(&bitmap).data).unwrap();
+    assert_eq!((nonces.maker_pubkey, nonces.window), (f.initializer, 0));
+    assert!(nonces.is_used(7));
+    assert!(!nonces.is_used(6));
+
+    assert_eq!(
+        test.process_at(&signed(&test, &f, &order), 1),
+        Err(EscrowError::NonceAlreadyUsed.into())
+    );
+    // a fresh nonce is fine, and the bitmap is already there
+    test.process_at(&signed(&test, &f, &f.order(400, 200, EXPIRY, 8)), 1)
+        .unwrap();
+    assert_eq!(test.lamports(&f.taker), taker_lamports - rent);
+    assert_eq!(test.token_balance(&f.temp_x), 0);
+}
+
+#[test]
+fn signature_has_to_cover_the_order() {
+    let mut test = ProgramTest::new();
+    let f = maker(&mut test, 1_000);
+    let order = f.order(600, 300, EXPIRY, 7);
+    let fill = f.fill_signed_order(&test, &order);
+    let message = order.message(&test.program_id);
+
+    // no signature at all
+    assert_eq!(
+        test.process_at(std::slice::from_ref(&fill), 0),
+        Err(EscrowError::InvalidSignature.into())
+    );
+    // signed by the taker
+    let by_taker = ed25519_instruction(&f.taker, &message);
+    // signed, but for a better price
+    let cheaper = f.order(600, 200, EXPIRY, 7).message(&test.program_id);
+    let other_order = ed25519_instruction(&f.initializer, &cheaper);
+    // signed for another deployment
+    let elsewhere = ed25519_instruction(&f.initializer, &order.message(&Pubkey::new_unique()));
+    // the message is read from another instruction
+    let mut pointing_away = ed25519_instruction(&f.initializer, &message);
+    pointing_away.data[14..16].copy_from_slice(&0u16.to_le_bytes());
+    for signature in [by_taker, other_order, elsewhere, pointing_away].iter() {
+        assert_eq!(
+            test.process_at(&[signature.clone(), fill.clone()], 1),
+            Err(EscrowError::InvalidSignature.into())
+        );
+    }
+
+    // the right signature, but not right in front of the fill
+    let signature = ed25519_instruction(&f.initializer, &message);
+    let unrelated = ed25519_instruction(&f.taker, &[]);
+    assert_eq!(
+        test.process_at(&[signature.clone(), unrelated, fill.clone()], 2),
+        Err(EscrowError::InvalidSignature.into())
+    );
+    // and the sysvar has to be the real one
+    let mut fake_sysvar = fill.clone();
+    fake_sysvar.accounts[11].pubkey = f.taker_y;
+    assert_eq!(
+        test.process_at(&[signature.clone(), fake_sysvar], 1),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    test.process_at(&[signature, fill], 1).unwrap();
+}
+
+#[test]
+fn expired_orders_cant_be_filled() {
+    let mut test = ProgramTest::new();
+    let f = maker(&mut test, 1_000);
+    let order = f.order(600, 300, EXPIRY, 7);
+
+    test.set_clock(Clock {
+        unix_timestamp: EXPIRY,
+        ..Clock::default()
+    });
+    assert_eq!(
+        test.process_at(&signed(&test, &f, &order), 1),
+        Err(EscrowError::OrderExpired.into())
+    );
+}
+
+#[test]
+fn cancelled_nonces_cant_be_filled() {
+    let mut test = ProgramTest::new();
+    let f = maker(&mut test, 1_000);
+
+    let maker_lamports = test.lamports(&f.initializer);
+    test.process(&f.cancel_order_nonce(&test, 3)).unwrap();
+    let rent = Rent::default().minimum_balance(NonceBitmap::LEN);
+    assert_eq!(test.lamports(&f.initializer), maker_lamports - rent);
+    assert_eq!(
+        test.process_at(&signed(&test, &f, &f.order(600, 300, EXPIRY, 3)), 1),
+        Err(EscrowError::NonceAlreadyUsed.into())
+    );
+    assert_eq!(
+        test.process(&f.cancel_order_nonce(&test, 3)),
+        Err(EscrowError::NonceAlreadyUsed.into())
+    );
+
+    // nonces past the first window live in an account of their own
+    let nonce = NonceBitmap::NONCES + 3;
+    test.process_at(&signed(&test, &f, &f.order(600, 300, EXPIRY, nonce)), 1)
+        .unwrap();
+    let window =
+        NonceBitmap::unpack(&test.account(&bitmap(&test, &f.initializer, 1)).data).unwrap();
+    assert_eq!(window.window, 1);
+    assert!(window.is_used(nonce));
+
+    // only the maker can cancel their nonces
+    let mut ix = f.cancel_order_nonce(&test, 4);
+    ix.accounts[0].pubkey = f.taker;
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+}
+
+#[test]
+fn maker_accounts_and_approval() {
+    let mut test = ProgramTest::new();
+    let f = maker(&mut test, 500);
+    let order = f.order(600, 300, EXPIRY, 7);
+
+    // the delegate may only move 500 of the maker's X
+    assert_eq!(
+        test.process_at(&signed(&test, &f, &order), 1),
+        Err(EscrowError::OrderNotApproved.into())
+    );
+    assert!(!test
+        .accounts
+        .contains_key(&bitmap(&test, &f.initializer, 0)));
+
+    let order = f.order(500, 300, EXPIRY, 7);
+    let mut transaction = signed(&test, &f, &order);
+    // the taker can't pay themselves
+    transaction[1].accounts[4].pubkey = f.taker_y;
+    assert_eq!(
+        test.process_at(&transaction, 1),
+        Err(ProgramError::InvalidAccountData)
+    );
+    // nor use up another nonce
+    let mut transaction = signed(&test, &f, &order);
+    transaction[1].accounts[5].pubkey = bitmap(&test, &f.initializer, 1);
+    assert_eq!(
+        test.process_at(&transaction, 1),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    // nor sign with another delegate
+    let mut transaction = signed(&test, &f, &order);
+    transaction[1].accounts[10].pubkey = test.pda().0;
+    assert_eq!(
+        test.process_at(&transaction, 1),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    test.process_at(&signed(&test, &f, &order), 1).unwrap();
+    assert_eq!(test.token_balance(&f.taker_x), 500);
+}
+
+#[test]
+fn orders_cant_spend_an_approve_escrows_allowance() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 5_000);
+    test.process(&f.init_approve_escrow(&test, 500, 400))
+        .unwrap();
+
+    // the maker's X is approved to the PDA for the listing, an old order of theirs can't dip into it
+    assert_eq!(
+        test.process_at(&signed(&test, &f, &f.order(400, 1, EXPIRY, 7)), 1),
+        Err(EscrowError::OrderNotApproved.into())
+    );
+    assert_eq!(test.token_account(&f.temp_x).unwrap().delegated_amount, 400);
+
+    test.process(&f.exchange(&test, 400)).unwrap();
+    assert_eq!(test.token_balance(&f.taker_x), 400);
+}
+
+#[test]
+fn bitmap_address_sent_lamports_beforehand() {
+    let mut test = ProgramTest::new();
+    let f = maker(&mut test, 1_000);
+    // someone tries to block the maker's nonces by funding the address first
+    let bitmap = bitmap(&test, &f.initializer, 0);
+    test.add_account(
+        bitmap,
+        Account {
+            owner: system_program::id(),
+            lamports: 1_000,
+            ..Account::default()
+        },
+    );
+
+    test.process_at(&signed(&test, &f, &f.order(600, 300, EXPIRY, 7)), 1)
+        .unwrap();
+    assert_eq!(test.account(&bitmap).owner, test.program_id);
+    assert_eq!(
+        test.lamports(&bitmap),
+        Rent::default().minimum_balance(NonceBitmap::LEN)
+    );
+    assert!(NonceBitmap::unpack(&test.account(&bitmap).data)
+        .unwrap()
+        .is_used(7));
+}
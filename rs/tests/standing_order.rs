@@ -0,0 +1,280 @@
+mod common;
+
+use solana_program::{
+    clock::Clock, hash::hash, instruction::Instruction, program_error::ProgramError,
+    program_option::COption, program_pack::Pack, rent::Rent,
+};
+use spl_token::state::Account as TokenAccount;
+
+use bpf_program_template::{
+    error::EscrowError,
+    order::{delegate_address, Order},
+    state::{EscrowMode, NonceBitmap, StandingOrder},
+};
+
+use common::{ed25519_instruction, EscrowFixture, ProgramTest, StandingOrderAccounts};
+
+const INTERVAL: i64 = 100;
+
+fn at(test: &ProgramTest, unix_timestamp: i64) {
+    test.set_clock(Clock {
+        unix_timestamp,
+        ..Clock::default()
+    });
+}
+
+fn accounts(test: &mut ProgramTest, f: &EscrowFixture, budget: u64) -> StandingOrderAccounts {
+    let owner = test.add_wallet(1_000_000_000);
+    let keeper = test.add_wallet(1_000_000_000);
+    StandingOrderAccounts {
+        standing_order: test.add_program_account(StandingOrder::LEN),
+        owner,
+        vault: test.add_token_account(&f.mint_y, &owner, budget),
+        owner_x: test.add_token_account(&f.mint_x, &owner, 0),
+        owner_y: test.add_token_account(&f.mint_y, &owner, 0),
+        keeper,
+        keeper_y: test.add_token_account(&f.mint_y, &keeper, 0),
+    }
+}
+
+// the owner puts up `budget` Y to spend at most 300 a tranche, every 100 seconds, for no less than 500 X per 300 Y.
+// The keeper gets 10 Y a tranche
+fn standing(test: &mut ProgramTest, budget: u64) -> (EscrowFixture, StandingOrderAccounts) {
+    at(test, 1_000);
+    let f = EscrowFixture::new(test, 500, 0);
+    let so = accounts(test, &f, budget);
+    test.process(&f.init_standing_order(test, &so, 300, 500, INTERVAL, 10))
+        .unwrap();
+    assert_eq!(test.token_account(&so.vault).unwrap().owner, test.pda().0);
+    (f, so)
+}
+
+// another seller of the same X, listing `offered` for `price`
+fn listing(test: &mut ProgramTest, f: &EscrowFixture, offered: u64, price: u64) -> EscrowFixture {
+    let listing = EscrowFixture::for_pair(test, f.mint_x, f.mint_y, offered, 0);
+    test.process(&listing.init_escrow(test, price)).unwrap();
+    listing
+}
+
+fn standing_order(test: &ProgramTest, so: &StandingOrderAccounts) -> StandingOrder {
+    StandingOrder::unpack(&test.account(&so.standing_order).data).unwrap()
+}
+
+#[test]
+fn keepers_buy_tranche_by_tranche() {
+    let mut test = ProgramTest::new();
+    let (f, so) = standing(&mut test, 1_000);
+    test.process(&f.init_escrow(&test, 300)).unwrap();
+
+    test.process(&f.execute_tranche(&test, &so)).unwrap();
+    assert_eq!(test.token_balance(&so.owner_x), 500);
+    assert_eq!(test.token_balance(&f.initializer_y), 300);
+    assert_eq!(test.token_balance(&so.keeper_y), 10);
+    assert_eq!(test.token_balance(&so.vault), 690);
+    assert_eq!(test.lamports(&f.escrow), 0);
+    assert!(test.token_account(&f.temp_x).is_none());
+    let info = standing_order(&test, &so);
+    assert_eq!(
+        (info.last_execution, info.remaining, info.tranches),
+        (1_000, 690, 1)
+    );
+
+    // one tranche per interval
+    let cheap = listing(&mut test, &f, 450, 250);
+    at(&test, 1_099);
+    assert_eq!(
+        test.process(&cheap.execute_tranche(&test, &so)),
+        Err(EscrowError::TrancheNotDue.into())
+    );
+    at(&test, 1_100);
+    test.process(&cheap.execute_tranche(&test, &so)).unwrap();
+    assert_eq!(test.token_balance(&so.owner_x), 950);
+    assert_eq!(test.token_balance(&cheap.initializer_y), 250);
+    assert_eq!(test.token_balance(&so.keeper_y), 20);
+    assert_eq!(standing_order(&test, &so).remaining, 430);
+
+    // the owner calls it off and gets the rest of the budget and the rent
+    let rent = test.lamports(&so.standing_order) + test.lamports(&so.vault);
+    let owner_lamports = test.lamports(&so.owner);
+    test.process(&f.cancel_standing_order(&test, &so)).unwrap();
+    assert_eq!(test.token_balance(&so.owner_y), 430);
+    assert!(test.token_account(&so.vault).is_none());
+    assert_eq!(test.lamports(&so.standing_order), 0);
+    assert_eq!(test.lamports(&so.owner), owner_lamports + rent);
+}
+
+#[test]
+fn tranches_stay_within_the_limits() {
+    let mut test = ProgramTest::new();
+    let (f, so) = standing(&mut test, 600);
+
+    // more than a tranche spends, or less X than the price limit allows
+    for (offered, price, error) in [
+        (1_000, 301, EscrowError::TrancheTooLarge),
+        (400, 250, EscrowError::PriceAboveMaximum),
+    ]
+    .iter()
+    {
+        let listing = listing(&mut test, &f, *offered, *price);
+        assert_eq!(
+            test.process(&listing.execute_tranche(&test, &so)),
+            Err((*error).into())
+        );
+        test.process(&listing.cancel(&test)).unwrap();
+    }
+
+    // 300 + 10 for the keeper, twice, is more than the 600 there is
+    let first = listing(&mut test, &f, 500, 300);
+    test.process(&first.execute_tranche(&test, &so)).unwrap();
+    at(&test, 1_100);
+    let last = listing(&mut test, &f, 500, 300);
+    assert_eq!(
+        test.process(&last.execute_tranche(&test, &so)),
+        Err(EscrowError::BudgetExhausted.into())
+    );
+    let smaller = listing(&mut test, &f, 480, 280);
+    test.process(&smaller.execute_tranche(&test, &so)).unwrap();
+    assert_eq!(standing_order(&test, &so).remaining, 0);
+    assert_eq!(test.token_balance(&so.vault), 0);
+
+    // only plain fixed price listings
+    at(&test, 1_200);
+    let htlc = EscrowFixture::for_pair(&mut test, f.mint_x, f.mint_y, 500, 0)
+        .sized_for(&mut test, EscrowMode::Htlc);
+    test.process(&htlc.init_htlc(&test, hash(&[42; 32]).to_bytes(), 2_000))
+        .unwrap();
+    assert_eq!(
+        test.process(&htlc.execute_tranche(&test, &so)),
+        Err(EscrowError::WrongEscrowMode.into())
+    );
+}
+
+// the maker sells out of temp_x with their order delegate as its delegate
+fn maker(test: &mut ProgramTest, f: &EscrowFixture) -> EscrowFixture {
+    let maker = EscrowFixture::for_pair(test, f.mint_x, f.mint_y, 1_000, 0);
+    let mut token = test.token_account(&maker.temp_x).unwrap();
+    token.delegate = COption::Some(delegate_address(&maker.initializer, &test.program_id).0);
+    token.delegated_amount = 1_000;
+    TokenAccount::pack(
+        token,
+        &mut test.accounts.get_mut(&maker.temp_x).unwrap().data,
+    )
+    .unwrap();
+    maker
+}
+
+fn signed(
+    test: &ProgramTest,
+    maker: &EscrowFixture,
+    so: &StandingOrderAccounts,
+    order: &Order,
+) -> Vec<Instruction> {
+    vec![
+        ed25519_instruction(&order.maker, &order.message(&test.program_id)),
+        maker.execute_tranche_with_order(test, so, order),
+    ]
+}
+
+#[test]
+fn tranches_fill_signed_orders() {
+    let mut test = ProgramTest::new();
+    let (f, so) = standing(&mut test, 1_000);
+    let maker = maker(&mut test, &f);
+
+    let order = maker.order(500, 300, 2_000, 7);
+    let keeper_lamports = test.lamports(&so.keeper);
+    test.process_at(&signed(&test, &maker, &so, &order), 1)
+        .unwrap();
+    assert_eq!(test.token_balance(&so.owner_x), 500);
+    assert_eq!(test.token_balance(&maker.temp_x), 500);
+    assert_eq!(test.token_balance(&maker.initializer_y), 300);
+    assert_eq!(test.token_balance(&so.keeper_y), 10);
+    // the keeper paid for the maker's nonce bitmap
+    let rent = Rent::default().minimum_balance(NonceBitmap::LEN);
+    assert_eq!(test.lamports(&so.keeper), keeper_lamports - rent);
+
+    at(&test, 1_100);
+    assert_eq!(
+        test.process_at(&signed(&test, &maker, &so, &order), 1),
+        Err(EscrowError::NonceAlreadyUsed.into())
+    );
+    let pricey = maker.order(400, 300, 2_000, 8);
+    assert_eq!(
+        test.process_at(&signed(&test, &maker, &so, &pricey), 1),
+        Err(EscrowError::PriceAboveMaximum.into())
+    );
+    // the signature has to be there
+    assert_eq!(
+        test.process(&maker.execute_tranche_with_order(
+            &test,
+            &so,
+            &maker.order(500, 300, 2_000, 9)
+        )),
+        Err(EscrowError::InvalidSignature.into())
+    );
+    test.process_at(
+        &signed(&test, &maker, &so, &maker.order(500, 200, 2_000, 10)),
+        1,
+    )
+    .unwrap();
+    assert_eq!(test.token_balance(&so.owner_x), 1_000);
+    assert_eq!(standing_order(&test, &so).remaining, 480);
+}
+
+#[test]
+fn standing_orders_have_to_make_sense() {
+    let mut test = ProgramTest::new();
+    at(&test, 1_000);
+    let f = EscrowFixture::new(&mut test, 500, 0);
+    let so = accounts(&mut test, &f, 1_000);
+    for (amount_per_interval, min_amount_x, interval, error) in [
+        (0, 500, INTERVAL, EscrowError::InvalidSchedule),
+        (300, 500, 0, EscrowError::InvalidSchedule),
+        (300, 0, INTERVAL, EscrowError::InvalidInstruction),
+    ]
+    .iter()
+    {
+        assert_eq!(
+            test.process(&f.init_standing_order(
+                &test,
+                &so,
+                *amount_per_interval,
+                *min_amount_x,
+                *interval,
+                10
+            )),
+            Err((*error).into())
+        );
+    }
+    test.process(&f.init_standing_order(&test, &so, 300, 500, INTERVAL, 10))
+        .unwrap();
+    let again = StandingOrderAccounts {
+        vault: test.add_token_account(&f.mint_y, &so.owner, 1_000),
+        ..so
+    };
+    assert_eq!(
+        test.process(&f.init_standing_order(&test, &again, 300, 500, INTERVAL, 10)),
+        Err(ProgramError::AccountAlreadyInitialized)
+    );
+
+    // only the owner stops it
+    let stranger = StandingOrderAccounts {
+        owner: f.taker,
+        owner_y: f.taker_y,
+        ..so
+    };
+    assert_eq!(
+        test.process(&f.cancel_standing_order(&test, &stranger)),
+        Err(ProgramError::InvalidAccountData)
+    );
+    // and the X only goes where the owner said
+    test.process(&f.init_escrow(&test, 300)).unwrap();
+    let elsewhere = StandingOrderAccounts {
+        owner_x: f.taker_x,
+        ..so
+    };
+    assert_eq!(
+        test.process(&f.execute_tranche(&test, &elsewhere)),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
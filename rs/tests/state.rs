@@ -0,0 +1,789 @@
+use proptest::prelude::*;
+use std::convert::TryInto;
+
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+
+use bpf_program_template::state::{
+    AccountType, AuctionTerms, ClaimedBitmap, Contribution, Distribution, DutchTerms, EnglishTerms,
+    Escrow, EscrowMode, HtlcTerms, LegacyEscrow, Loan, MilestoneEscrow, Multisig, NonceBitmap,
+    OptionTerms, Pool, Request, SealedBid, SealedTerms, StandingOrder, Stream, Terms, Vesting,
+};
+
+fn pubkey() -> impl Strategy<Value = Pubkey> {
+    any::<[u8; 32]>().prop_map(Pubkey::new_from_array)
+}
+
+fn htlc_terms() -> impl Strategy<Value = HtlcTerms> {
+    (any::<[u8; 32]>(), any::<i64>())
+        .prop_map(|(hashlock, timelock)| HtlcTerms { hashlock, timelock })
+}
+
+fn option_terms() -> impl Strategy<Value = OptionTerms> {
+    (any::<i64>(), any::<u64>(), pubkey()).prop_map(|(expiry, premium, holder)| OptionTerms {
+        expiry,
+        premium,
+        holder_pubkey: holder,
+    })
+}
+
+fn dutch_terms() -> impl Strategy<Value = DutchTerms> {
+    (any::<u64>(), any::<i64>(), any::<i64>(), any::<u8>()).prop_map(
+        |(start_price, auction_start, auction_end, decay)| DutchTerms {
+            start_price,
+            auction_start,
+            auction_end,
+            decay,
+        },
+    )
+}
+
+fn auction_terms() -> impl Strategy<Value = AuctionTerms> {
+    (any::<i64>(), pubkey(), any::<u64>(), pubkey()).prop_map(
+        |(end, bid_vault, highest_bid, bidder)| AuctionTerms {
+            end,
+            bid_vault_pubkey: bid_vault,
+            highest_bid,
+            bidder_pubkey: bidder,
+        },
+    )
+}
+
+fn english_terms() -> impl Strategy<Value = EnglishTerms> {
+    (auction_terms(), any::<u64>(), any::<i64>()).prop_map(|(auction, min_increment, extension)| {
+        EnglishTerms {
+            auction,
+            min_increment,
+            extension,
+        }
+    })
+}
+
+fn sealed_terms() -> impl Strategy<Value = SealedTerms> {
+    (
+        auction_terms(),
+        any::<i64>(),
+        any::<u64>(),
+        any::<u64>(),
+        any::<bool>(),
+    )
+        .prop_map(
+            |(auction, commit_end, bond, second_bid, second_price)| SealedTerms {
+                auction,
+                commit_end,
+                bond,
+                second_bid,
+                second_price,
+            },
+        )
+}
+
+fn escrow() -> impl Strategy<Value = Escrow> {
+    // tuples only go up to 12
+    (
+        (
+            any::<bool>(),
+            pubkey(),
+            pubkey(),
+            pubkey(),
+            any::<u64>(),
+            prop_oneof![
+                Just(EscrowMode::Vault),
+                Just(EscrowMode::Approve),
+                Just(EscrowMode::Htlc),
+                Just(EscrowMode::CoveredCall),
+                Just(EscrowMode::DutchAuction),
+                Just(EscrowMode::EnglishAuction),
+                Just(EscrowMode::SealedAuction),
+                Just(EscrowMode::FlashSettling)
+            ],
+            any::<u64>(),
+            pubkey(),
+            pubkey(),
+        ),
+        (
+            prop_oneof![
+                Just(Request::Tokens),
+                Just(Request::Mint),
+                Just(Request::Collection)
+            ],
+            pubkey(),
+            any::<bool>(),
+            pubkey(),
+        ),
+        (
+            htlc_terms(),
+            option_terms(),
+            dutch_terms(),
+            english_terms(),
+            sealed_terms(),
+        ),
+    )
+        .prop_map(
+            |(
+                (
+                    is_initialized,
+                    initializer,
+                    temp,
+                    receive,
+                    expected_amount,
+                    mode,
+                    offered_amount,
+                    rent_payer,
+                    recipient,
+                ),
+                (request, request_key, royalties, authority),
+                (htlc, option, dutch, english, sealed),
+            )| {
+                // an uninitialized escrow doesn't have terms yet, so only one in a mode without them packs
+                let (mode, terms) = match (is_initialized, mode) {
+                    (false, EscrowMode::Approve) | (false, EscrowMode::FlashSettling) => {
+                        (mode, Terms::None)
+                    }
+                    (false, _) => (EscrowMode::Vault, Terms::None),
+                    (true, EscrowMode::Htlc) => (mode, Terms::Htlc(htlc)),
+                    (true, EscrowMode::CoveredCall) => (mode, Terms::CoveredCall(option)),
+                    (true, EscrowMode::DutchAuction) => (mode, Terms::DutchAuction(dutch)),
+                    (true, EscrowMode::EnglishAuction) => (mode, Terms::EnglishAuction(english)),
+                    (true, EscrowMode::SealedAuction) => (mode, Terms::SealedAuction(sealed)),
+                    (true, _) => (mode, Terms::None),
+                };
+                Escrow {
+                    is_initialized,
+                    initializer_pubkey: initializer,
+                    temp_token_account_pubkey: temp,
+                    initializer_token_to_receive_account_pubkey: receive,
+                    expected_amount,
+                    mode,
+                    offered_amount,
+                    rent_payer,
+                    recipient_token_account_pubkey: recipient,
+                    request,
+                    request_key,
+                    royalties,
+                    authority_pubkey: authority,
+                    terms,
+                }
+            },
+        )
+}
+
+fn legacy_escrow() -> impl Strategy<Value = LegacyEscrow> {
+    (any::<bool>(), pubkey(), pubkey(), pubkey(), any::<u64>()).prop_map(
+        |(is_initialized, initializer, temp, receive, expected_amount)| LegacyEscrow {
+            is_initialized,
+            initializer_pubkey: initializer,
+            temp_token_account_pubkey: temp,
+            initializer_token_to_receive_account_pubkey: receive,
+            expected_amount,
+        },
+    )
+}
+
+// packs an escrow into an account of the right size for its mode
+fn pack_escrow(escrow: Escrow, fill: u8) -> Vec<u8> {
+    let mut packed = vec![fill; Escrow::len(escrow.mode)];
+    Escrow::pack(escrow, &mut packed).unwrap();
+    packed
+}
+
+fn loan() -> impl Strategy<Value = Loan> {
+    (
+        any::<bool>(),
+        pubkey(),
+        pubkey(),
+        pubkey(),
+        (any::<u64>(), any::<u64>(), any::<i64>()),
+        pubkey(),
+        pubkey(),
+        pubkey(),
+        any::<i64>(),
+    )
+        .prop_map(
+            |(
+                is_initialized,
+                borrower,
+                collateral,
+                borrower_principal,
+                (principal, interest, duration),
+                rent_payer,
+                lender,
+                lender_principal,
+                deadline,
+            )| Loan {
+                is_initialized,
+                borrower_pubkey: borrower,
+                collateral_account_pubkey: collateral,
+                borrower_principal_account_pubkey: borrower_principal,
+                principal,
+                interest,
+                duration,
+                rent_payer,
+                lender_pubkey: lender,
+                lender_principal_account_pubkey: lender_principal,
+                deadline,
+            },
+        )
+}
+
+fn sealed_bid() -> impl Strategy<Value = SealedBid> {
+    (
+        any::<bool>(),
+        pubkey(),
+        pubkey(),
+        any::<[u8; 32]>(),
+        any::<u64>(),
+        any::<i64>(),
+    )
+        .prop_map(
+            |(is_initialized, escrow, bidder, commitment, bond, reveal_end)| SealedBid {
+                is_initialized,
+                escrow_pubkey: escrow,
+                bidder_pubkey: bidder,
+                commitment,
+                bond,
+                reveal_end,
+            },
+        )
+}
+
+fn nonce_bitmap() -> impl Strategy<Value = NonceBitmap> {
+    (
+        any::<bool>(),
+        pubkey(),
+        any::<u64>(),
+        prop::collection::vec(any::<u8>(), 256),
+    )
+        .prop_map(|(is_initialized, maker, window, bits)| NonceBitmap {
+            is_initialized,
+            maker_pubkey: maker,
+            window,
+            bits: bits.try_into().unwrap(),
+        })
+}
+
+fn distribution() -> impl Strategy<Value = Distribution> {
+    (
+        any::<bool>(),
+        pubkey(),
+        pubkey(),
+        pubkey(),
+        any::<[u8; 32]>(),
+        any::<u64>(),
+        any::<i64>(),
+    )
+        .prop_map(
+            |(
+                is_initialized,
+                initializer,
+                vault,
+                bitmap,
+                merkle_root,
+                num_recipients,
+                deadline,
+            )| {
+                Distribution {
+                    is_initialized,
+                    initializer_pubkey: initializer,
+                    vault_pubkey: vault,
+                    claimed_bitmap_pubkey: bitmap,
+                    merkle_root,
+                    num_recipients,
+                    deadline,
+                }
+            },
+        )
+}
+
+fn vesting() -> impl Strategy<Value = Vesting> {
+    (
+        any::<bool>(),
+        (pubkey(), pubkey(), pubkey(), pubkey()),
+        (any::<i64>(), any::<i64>(), any::<i64>()),
+        any::<u64>(),
+        any::<u64>(),
+    )
+        .prop_map(
+            |(
+                is_initialized,
+                (initializer, beneficiary, vault, revoker),
+                (start, cliff, end),
+                total_amount,
+                withdrawn,
+            )| Vesting {
+                is_initialized,
+                initializer_pubkey: initializer,
+                beneficiary_pubkey: beneficiary,
+                vault_pubkey: vault,
+                revoker_pubkey: revoker,
+                start,
+                cliff,
+                end,
+                total_amount,
+                withdrawn,
+            },
+        )
+}
+
+fn stream() -> impl Strategy<Value = Stream> {
+    (
+        any::<bool>(),
+        (pubkey(), pubkey(), pubkey()),
+        any::<u64>(),
+        (any::<i64>(), any::<i64>()),
+        any::<u64>(),
+    )
+        .prop_map(
+            |(is_initialized, (payer, recipient, vault), rate, (start, end), withdrawn)| Stream {
+                is_initialized,
+                payer_pubkey: payer,
+                recipient_pubkey: recipient,
+                vault_pubkey: vault,
+                rate,
+                start,
+                end,
+                withdrawn,
+            },
+        )
+}
+
+fn milestone_escrow() -> impl Strategy<Value = MilestoneEscrow> {
+    (
+        any::<bool>(),
+        (pubkey(), pubkey(), pubkey(), pubkey()),
+        (0..=MilestoneEscrow::MAX_MILESTONES as u8).prop_flat_map(|num| (Just(num), 0..=num)),
+        any::<bool>(),
+        any::<[u64; MilestoneEscrow::MAX_MILESTONES]>(),
+    )
+        .prop_map(
+            |(
+                is_initialized,
+                (payer, payee, arbiter, vault),
+                (num_milestones, current),
+                disputed,
+                amounts,
+            )| MilestoneEscrow {
+                is_initialized,
+                payer_pubkey: payer,
+                payee_pubkey: payee,
+                arbiter_pubkey: arbiter,
+                vault_pubkey: vault,
+                num_milestones,
+                current,
+                disputed,
+                amounts,
+            },
+        )
+}
+
+fn multisig() -> impl Strategy<Value = Multisig> {
+    (
+        any::<bool>(),
+        (0..=Multisig::MAX_SIGNERS as u8).prop_flat_map(|n| (0..=n, Just(n))),
+        prop::collection::vec(pubkey(), Multisig::MAX_SIGNERS),
+    )
+        .prop_map(|(is_initialized, (m, n), signers)| Multisig {
+            is_initialized,
+            m,
+            n,
+            signers: signers.try_into().unwrap(),
+        })
+}
+
+fn pool() -> impl Strategy<Value = Pool> {
+    (
+        any::<bool>(),
+        (pubkey(), pubkey(), pubkey(), pubkey()),
+        (any::<u64>(), any::<u64>(), any::<i64>(), any::<u64>()),
+        any::<bool>(),
+        any::<u64>(),
+    )
+        .prop_map(
+            |(
+                is_initialized,
+                (escrow, creator, y_vault, x_vault),
+                (target, raised, deadline, contributors),
+                executed,
+                received_x,
+            )| Pool {
+                is_initialized,
+                escrow_pubkey: escrow,
+                creator_pubkey: creator,
+                y_vault_pubkey: y_vault,
+                x_vault_pubkey: x_vault,
+                target,
+                raised,
+                deadline,
+                contributors,
+                executed,
+                received_x,
+            },
+        )
+}
+
+fn contribution() -> impl Strategy<Value = Contribution> {
+    (any::<bool>(), pubkey(), pubkey(), any::<u64>()).prop_map(
+        |(is_initialized, pool, contributor, amount)| Contribution {
+            is_initialized,
+            pool_pubkey: pool,
+            contributor_pubkey: contributor,
+            amount,
+        },
+    )
+}
+
+fn standing_order() -> impl Strategy<Value = StandingOrder> {
+    (
+        any::<bool>(),
+        (pubkey(), pubkey(), pubkey()),
+        (any::<u64>(), any::<u64>(), any::<i64>(), any::<u64>()),
+        (any::<i64>(), any::<u64>(), any::<u64>()),
+    )
+        .prop_map(
+            |(
+                is_initialized,
+                (owner, vault, x_account),
+                (amount_per_interval, min_amount_x, interval, keeper_reward),
+                (last_execution, remaining, tranches),
+            )| StandingOrder {
+                is_initialized,
+                owner_pubkey: owner,
+                vault_pubkey: vault,
+                x_account_pubkey: x_account,
+                amount_per_interval,
+                min_amount_x,
+                interval,
+                keeper_reward,
+                last_execution,
+                remaining,
+                tranches,
+            },
+        )
+}
+
+proptest! {
+    #[test]
+    fn escrow_round_trips(escrow in escrow(), junk in any::<u8>()) {
+        // start from junk so a field that isn't written would show up
+        let packed = pack_escrow(escrow, junk);
+        prop_assert_eq!(Escrow::unpack_unchecked(&packed).unwrap(), escrow);
+    }
+
+    #[test]
+    fn escrow_rejects_other_account_types(escrow in escrow(), account_type in 1u8..=255) {
+        prop_assume!(account_type != AccountType::Escrow as u8);
+        let mut packed = pack_escrow(escrow, 0);
+        packed[0] = account_type;
+        prop_assert_eq!(Escrow::unpack_unchecked(&packed), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn escrow_rejects_invalid_mode(escrow in escrow(), mode in 8u8..=255) {
+        let mut packed = pack_escrow(escrow, 0);
+        packed[105] = mode;
+        prop_assert_eq!(Escrow::unpack_unchecked(&packed), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn loan_round_trips(loan in loan(), junk in any::<u8>()) {
+        let mut packed = vec![junk; Loan::LEN];
+        loan.pack_into_slice(&mut packed);
+        prop_assert_eq!(Loan::unpack_from_slice(&packed).unwrap(), loan);
+    }
+
+    #[test]
+    fn loan_rejects_other_account_types(loan in loan(), account_type in 1u8..=255) {
+        prop_assume!(account_type != AccountType::Loan as u8);
+        let mut packed = vec![0; Loan::LEN];
+        loan.pack_into_slice(&mut packed);
+        packed[0] = account_type;
+        prop_assert_eq!(Loan::unpack_from_slice(&packed), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn sealed_terms_reject_invalid_second_price(terms in sealed_terms(), flag in 2u8..=255) {
+        let mut packed = vec![0; SealedTerms::LEN];
+        terms.pack_into_slice(&mut packed);
+        packed[SealedTerms::LEN - 1] = flag;
+        prop_assert_eq!(SealedTerms::unpack_from_slice(&packed), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn escrow_rejects_invalid_request(escrow in escrow(), request in 3u8..=255) {
+        let mut packed = pack_escrow(escrow, 0);
+        packed[178] = request;
+        prop_assert_eq!(Escrow::unpack_unchecked(&packed), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn escrow_rejects_invalid_royalties(escrow in escrow(), flag in 2u8..=255) {
+        let mut packed = pack_escrow(escrow, 0);
+        packed[211] = flag;
+        prop_assert_eq!(Escrow::unpack_unchecked(&packed), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn terms_round_trip(escrow in escrow()) {
+        let packed = pack_escrow(escrow, 0);
+        prop_assert_eq!(packed.len(), Escrow::HEADER_LEN + Terms::len(escrow.mode));
+        prop_assert_eq!(Escrow::unpack_unchecked(&packed).unwrap().terms, escrow.terms);
+    }
+
+    #[test]
+    fn escrow_rejects_another_modes_terms(escrow in escrow(), mode in 0u8..8) {
+        prop_assume!(escrow.is_initialized && escrow.terms != Terms::None);
+        let mut packed = pack_escrow(escrow, 0);
+        packed[105] = mode;
+        let other = Escrow::unpack_unchecked(&packed);
+        if mode == escrow.mode as u8 {
+            prop_assert_eq!(other.unwrap(), escrow);
+        } else {
+            // the terms are a different length in every mode that has them
+            prop_assert_eq!(other, Err(ProgramError::InvalidAccountData));
+        }
+    }
+
+    #[test]
+    fn legacy_escrow_round_trips(escrow in legacy_escrow(), junk in any::<u8>()) {
+        let mut packed = vec![junk; LegacyEscrow::LEN];
+        escrow.pack_into_slice(&mut packed);
+        prop_assert_eq!(LegacyEscrow::unpack_from_slice(&packed).unwrap(), escrow);
+        // too short for any escrow now
+        prop_assert_eq!(Escrow::unpack_unchecked(&packed), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn sealed_bid_round_trips(bid in sealed_bid(), junk in any::<u8>()) {
+        let mut packed = vec![junk; SealedBid::LEN];
+        bid.pack_into_slice(&mut packed);
+        prop_assert_eq!(SealedBid::unpack_from_slice(&packed).unwrap(), bid);
+    }
+
+    #[test]
+    fn sealed_bid_rejects_other_account_types(bid in sealed_bid(), account_type in 1u8..=255) {
+        prop_assume!(account_type != AccountType::SealedBid as u8);
+        let mut packed = vec![0; SealedBid::LEN];
+        bid.pack_into_slice(&mut packed);
+        packed[0] = account_type;
+        prop_assert_eq!(SealedBid::unpack_from_slice(&packed), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn nonce_bitmap_round_trips(bitmap in nonce_bitmap(), junk in any::<u8>()) {
+        let mut packed = vec![junk; NonceBitmap::LEN];
+        bitmap.pack_into_slice(&mut packed);
+        prop_assert_eq!(NonceBitmap::unpack_from_slice(&packed).unwrap(), bitmap);
+    }
+
+    #[test]
+    fn using_a_nonce_leaves_the_others(bitmap in nonce_bitmap(), nonce in any::<u64>(), other in any::<u64>()) {
+        prop_assume!(nonce % NonceBitmap::NONCES != other % NonceBitmap::NONCES);
+        let mut used = bitmap;
+        used.set_used(nonce);
+        prop_assert!(used.is_used(nonce));
+        prop_assert_eq!(used.is_used(other), bitmap.is_used(other));
+    }
+
+    #[test]
+    fn distribution_round_trips(distribution in distribution(), junk in any::<u8>()) {
+        let mut packed = vec![junk; Distribution::LEN];
+        distribution.pack_into_slice(&mut packed);
+        prop_assert_eq!(Distribution::unpack_from_slice(&packed).unwrap(), distribution);
+    }
+
+    #[test]
+    fn vesting_round_trips(vesting in vesting(), junk in any::<u8>()) {
+        let mut packed = vec![junk; Vesting::LEN];
+        vesting.pack_into_slice(&mut packed);
+        prop_assert_eq!(Vesting::unpack_from_slice(&packed).unwrap(), vesting);
+    }
+
+    #[test]
+    fn stream_round_trips(stream in stream(), junk in any::<u8>()) {
+        let mut packed = vec![junk; Stream::LEN];
+        stream.pack_into_slice(&mut packed);
+        prop_assert_eq!(Stream::unpack_from_slice(&packed).unwrap(), stream);
+    }
+
+    #[test]
+    fn stream_accrues_up_to_its_total(rate in 0u64..1 << 32, duration in 1i64..1 << 31, start in any::<i32>(), now in any::<i64>()) {
+        let stream = Stream { rate, start: start as i64, end: start as i64 + duration, ..Stream::default() };
+        let accrued = stream.accrued(now);
+        prop_assert!(accrued <= stream.total());
+        prop_assert!(accrued <= stream.accrued(now.saturating_add(1)));
+        prop_assert_eq!(stream.accrued(stream.start), 0);
+    }
+
+    #[test]
+    fn milestone_escrow_round_trips(escrow in milestone_escrow(), junk in any::<u8>()) {
+        let mut packed = vec![junk; MilestoneEscrow::LEN];
+        escrow.pack_into_slice(&mut packed);
+        prop_assert_eq!(MilestoneEscrow::unpack_from_slice(&packed).unwrap(), escrow);
+    }
+
+    #[test]
+    fn milestone_escrow_rejects_counters_out_of_range(escrow in milestone_escrow(), past in 1u8..=100) {
+        // num_milestones, then current right after the four pubkeys
+        let mut packed = vec![0; MilestoneEscrow::LEN];
+        escrow.pack_into_slice(&mut packed);
+        packed[130] = escrow.num_milestones + past;
+        prop_assert_eq!(MilestoneEscrow::unpack_from_slice(&packed), Err(ProgramError::InvalidAccountData));
+        escrow.pack_into_slice(&mut packed);
+        packed[129] = MilestoneEscrow::MAX_MILESTONES as u8 + past;
+        prop_assert_eq!(MilestoneEscrow::unpack_from_slice(&packed), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn multisig_round_trips(multisig in multisig(), junk in any::<u8>()) {
+        let mut packed = vec![junk; Multisig::LEN];
+        multisig.pack_into_slice(&mut packed);
+        prop_assert_eq!(Multisig::unpack_from_slice(&packed).unwrap(), multisig);
+    }
+
+    #[test]
+    fn multisig_rejects_thresholds_out_of_range(multisig in multisig(), past in 1u8..=100) {
+        // m, then n right after is_initialized
+        let mut packed = vec![0; Multisig::LEN];
+        multisig.pack_into_slice(&mut packed);
+        packed[1] = multisig.n + past;
+        prop_assert_eq!(Multisig::unpack_from_slice(&packed), Err(ProgramError::InvalidAccountData));
+        multisig.pack_into_slice(&mut packed);
+        packed[2] = Multisig::MAX_SIGNERS as u8 + past;
+        prop_assert_eq!(Multisig::unpack_from_slice(&packed), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn pool_round_trips(pool in pool(), junk in any::<u8>()) {
+        let mut packed = vec![junk; Pool::LEN];
+        pool.pack_into_slice(&mut packed);
+        prop_assert_eq!(Pool::unpack_from_slice(&packed).unwrap(), pool);
+    }
+
+    #[test]
+    fn pool_rejects_invalid_executed(pool in pool(), flag in 2u8..=255) {
+        let mut packed = vec![0; Pool::LEN];
+        pool.pack_into_slice(&mut packed);
+        packed[Pool::LEN - 9] = flag;
+        prop_assert_eq!(Pool::unpack_from_slice(&packed), Err(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn contribution_round_trips(contribution in contribution(), junk in any::<u8>()) {
+        let mut packed = vec![junk; Contribution::LEN];
+        contribution.pack_into_slice(&mut packed);
+        prop_assert_eq!(Contribution::unpack_from_slice(&packed).unwrap(), contribution);
+    }
+
+    #[test]
+    fn standing_order_round_trips(standing_order in standing_order(), junk in any::<u8>()) {
+        let mut packed = vec![junk; StandingOrder::LEN];
+        standing_order.pack_into_slice(&mut packed);
+        prop_assert_eq!(StandingOrder::unpack_from_slice(&packed).unwrap(), standing_order);
+    }
+
+    #[test]
+    fn claiming_a_leaf_leaves_the_others(num_recipients in 1u64..10_000, index in any::<u64>(), other in any::<u64>()) {
+        let (index, other) = (index % num_recipients, other % num_recipients);
+        prop_assume!(index != other);
+        let mut data = vec![0; ClaimedBitmap::len(num_recipients)];
+        ClaimedBitmap::init(&mut data, &Pubkey::new_unique());
+        ClaimedBitmap::set_claimed(&mut data, index);
+        prop_assert!(ClaimedBitmap::is_claimed(&data, index));
+        prop_assert!(!ClaimedBitmap::is_claimed(&data, other));
+    }
+
+    #[test]
+    fn claimed_bitmap_fits_every_recipient(num_recipients in 0u64..100_000) {
+        let len = ClaimedBitmap::len(num_recipients);
+        prop_assert!((len - ClaimedBitmap::HEADER_LEN) * 8 >= num_recipients as usize);
+        let mut data = vec![0; len];
+        ClaimedBitmap::init(&mut data, &Pubkey::new_unique());
+        prop_assert_eq!(AccountType::of(&data), Some(AccountType::ClaimedBitmap));
+    }
+
+    #[test]
+    fn escrow_rejects_wrong_length(escrow in escrow(), len in 0usize..2 * Escrow::len(EscrowMode::SealedAuction)) {
+        // an uninitialized one only has to fit the header, its mode isn't final yet
+        prop_assume!(len != Escrow::len(escrow.mode) && (escrow.is_initialized || len < Escrow::HEADER_LEN));
+        let mut packed = pack_escrow(escrow, 0);
+        packed.resize(len, 0);
+        prop_assert_eq!(Escrow::unpack_unchecked(&packed), Err(ProgramError::InvalidAccountData));
+        prop_assert_eq!(Escrow::pack(escrow, &mut packed), Err(ProgramError::InvalidAccountData));
+    }
+}
+
+#[test]
+fn unpack_checks_is_initialized() {
+    // an uninitialized escrow can be created for any mode
+    for mode in [
+        EscrowMode::Vault,
+        EscrowMode::Htlc,
+        EscrowMode::SealedAuction,
+    ]
+    .iter()
+    {
+        let packed = vec![0; Escrow::len(*mode)];
+        assert_eq!(
+            Escrow::unpack(&packed),
+            Err(ProgramError::UninitializedAccount)
+        );
+        assert_eq!(
+            Escrow::unpack_unchecked(&packed).unwrap(),
+            Escrow::default()
+        );
+    }
+}
+
+#[test]
+fn escrow_wont_pack_terms_that_arent_its_modes() {
+    let escrow = Escrow {
+        is_initialized: true,
+        mode: EscrowMode::Htlc,
+        terms: Terms::new(EscrowMode::CoveredCall),
+        ..Escrow::default()
+    };
+    let mut packed = vec![0; Escrow::len(EscrowMode::Htlc)];
+    assert_eq!(
+        Escrow::pack(escrow, &mut packed),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn legacy_escrow_becomes_a_vault_escrow() {
+    let initializer = Pubkey::new_unique();
+    let escrow: Escrow = LegacyEscrow {
+        is_initialized: true,
+        initializer_pubkey: initializer,
+        temp_token_account_pubkey: Pubkey::new_unique(),
+        initializer_token_to_receive_account_pubkey: Pubkey::new_unique(),
+        expected_amount: 500,
+    }
+    .into();
+    assert_eq!(escrow.mode, EscrowMode::Vault);
+    assert_eq!(escrow.rent_payer, initializer);
+    assert_eq!(escrow.authority_pubkey, Pubkey::default());
+    assert_eq!(escrow.terms, Terms::None);
+}
+
+// both are 145 bytes long, only the account type tells them apart
+#[test]
+fn claimed_bitmap_isnt_a_distribution() {
+    let distribution = Pubkey::new_unique();
+    let mut data = vec![0; ClaimedBitmap::len(896)];
+    assert_eq!(data.len(), Distribution::LEN);
+    ClaimedBitmap::init(&mut data, &distribution);
+    assert_eq!(
+        Distribution::unpack_unchecked(&data),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    let mut data = vec![0; Distribution::LEN];
+    Distribution {
+        is_initialized: true,
+        initializer_pubkey: distribution,
+        num_recipients: 896,
+        ..Distribution::default()
+    }
+    .pack_into_slice(&mut data);
+    assert!(!ClaimedBitmap::belongs_to(&data, &distribution, 896));
+}
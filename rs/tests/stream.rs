@@ -0,0 +1,177 @@
+mod common;
+
+use solana_program::{
+    clock::Clock, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+};
+
+use bpf_program_template::{error::EscrowError, state::Stream};
+
+use common::{EscrowFixture, ProgramTest};
+
+// alice streams 1000 X to the taker, 10 a second for 100 seconds from 0
+struct Payment {
+    f: EscrowFixture,
+    stream: Pubkey,
+}
+
+impl Payment {
+    fn new(test: &mut ProgramTest) -> Self {
+        let f = EscrowFixture::new(test, 1_000, 0);
+        let stream = test.add_program_account(Stream::LEN);
+        test.process(&f.init_stream(test, stream, f.taker, 10, 100))
+            .unwrap();
+        Self { f, stream }
+    }
+
+    fn withdraw(&self, test: &mut ProgramTest, now: i64) -> Result<u64, ProgramError> {
+        at(test, now);
+        let before = test.token_balance(&self.f.taker_x);
+        let ix = self
+            .f
+            .withdraw(test, self.stream, self.f.taker, self.f.taker_x);
+        test.process(&ix)?;
+        Ok(test.token_balance(&self.f.taker_x) - before)
+    }
+}
+
+fn at(test: &ProgramTest, unix_timestamp: i64) {
+    test.set_clock(Clock {
+        unix_timestamp,
+        ..Clock::default()
+    });
+}
+
+#[test]
+fn accrues_by_the_second() {
+    let mut test = ProgramTest::new();
+    let p = Payment::new(&mut test);
+    let stream = Stream::unpack(&test.account(&p.stream).data).unwrap();
+    assert_eq!((stream.start, stream.end, stream.total()), (0, 100, 1_000));
+    assert_eq!(test.token_account(&p.f.temp_x).unwrap().owner, test.pda().0);
+
+    assert_eq!(
+        p.withdraw(&mut test, 0),
+        Err(EscrowError::NothingToWithdraw.into())
+    );
+    assert_eq!(p.withdraw(&mut test, 25), Ok(250));
+    assert_eq!(p.withdraw(&mut test, 26), Ok(10));
+
+    let rent = test.lamports(&p.stream) + test.lamports(&p.f.temp_x);
+    let payer_lamports = test.lamports(&p.f.initializer);
+    assert_eq!(p.withdraw(&mut test, 1_000), Ok(740));
+    assert!(test.token_account(&p.f.temp_x).is_none());
+    assert_eq!(test.lamports(&p.stream), 0);
+    assert_eq!(test.lamports(&p.f.initializer), payer_lamports + rent);
+}
+
+#[test]
+fn only_the_recipient_withdraws() {
+    let mut test = ProgramTest::new();
+    let p = Payment::new(&mut test);
+    at(&test, 50);
+
+    let ix =
+        p.f.withdraw(&test, p.stream, p.f.initializer, p.f.initializer_x);
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+    // the rent goes back to the payer and nobody else
+    let mut ix = p.f.withdraw(&test, p.stream, p.f.taker, p.f.taker_x);
+    ix.accounts[7].pubkey = p.f.taker;
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+    // neither a vesting nor a stream
+    let mut ix = p.f.withdraw(&test, p.stream, p.f.taker, p.f.taker_x);
+    ix.accounts[2].pubkey = test.add_program_account(Stream::LEN + 1);
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+
+    assert_eq!(p.withdraw(&mut test, 50), Ok(500));
+}
+
+#[test]
+fn cancelling_pays_out_what_has_accrued() {
+    let mut test = ProgramTest::new();
+    let p = Payment::new(&mut test);
+    assert_eq!(p.withdraw(&mut test, 10), Ok(100));
+    at(&test, 30);
+
+    // the accrued part only goes to the recipient
+    let ix = p.f.cancel_stream(&test, p.stream, p.f.initializer_x);
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+    let mut ix = p.f.cancel_stream(&test, p.stream, p.f.taker_x);
+    ix.accounts[0].pubkey = p.f.taker;
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+    // and it has to be our stream
+    let copy = test.add_foreign_copy(&p.stream);
+    let ix = p.f.cancel_stream(&test, copy, p.f.taker_x);
+    assert_eq!(test.process(&ix), Err(ProgramError::IncorrectProgramId));
+
+    let rent = test.lamports(&p.stream) + test.lamports(&p.f.temp_x);
+    let payer_lamports = test.lamports(&p.f.initializer);
+    test.process(&p.f.cancel_stream(&test, p.stream, p.f.taker_x))
+        .unwrap();
+    assert_eq!(test.token_balance(&p.f.taker_x), 300);
+    assert_eq!(test.token_balance(&p.f.initializer_x), 700);
+    assert!(test.token_account(&p.f.temp_x).is_none());
+    assert_eq!(test.lamports(&p.stream), 0);
+    assert_eq!(test.lamports(&p.f.initializer), payer_lamports + rent);
+}
+
+#[test]
+fn cancelling_after_the_end_leaves_the_payer_nothing() {
+    let mut test = ProgramTest::new();
+    let p = Payment::new(&mut test);
+    at(&test, 500);
+
+    test.process(&p.f.cancel_stream(&test, p.stream, p.f.taker_x))
+        .unwrap();
+    assert_eq!(test.token_balance(&p.f.taker_x), 1_000);
+    assert_eq!(test.token_balance(&p.f.initializer_x), 0);
+}
+
+#[test]
+fn deposit_has_to_cover_the_stream() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 0);
+    let stream = test.add_program_account(Stream::LEN);
+
+    for (rate, duration, error) in [
+        (10, 99, EscrowError::ExpectedAmountMismatch),
+        (10, 101, EscrowError::ExpectedAmountMismatch),
+        (0, 100, EscrowError::InvalidSchedule),
+        (10, 0, EscrowError::InvalidSchedule),
+        (10, -100, EscrowError::InvalidSchedule),
+        (u64::MAX, 2, EscrowError::AmountOverflow),
+    ]
+    .iter()
+    {
+        assert_eq!(
+            test.process(&f.init_stream(&test, stream, f.taker, *rate, *duration)),
+            Err((*error).into())
+        );
+    }
+
+    test.process(&f.init_stream(&test, stream, f.taker, 1, 1_000))
+        .unwrap();
+    let other = EscrowFixture::new(&mut test, 1_000, 0);
+    assert_eq!(
+        test.process(&other.init_stream(&test, stream, other.taker, 10, 100)),
+        Err(ProgramError::AccountAlreadyInitialized)
+    );
+}
+
+#[test]
+fn withdraw_only_takes_a_stream_or_a_vesting() {
+    let mut test = ProgramTest::new();
+    let p = Payment::new(&mut test);
+    at(&test, 50);
+
+    // a fresh account, and another kind of account of the program's, whatever its length
+    let fresh = test.add_program_account(Stream::LEN);
+    let listing = EscrowFixture::new(&mut test, 1_000, 0);
+    test.process(&listing.init_escrow(&test, 500)).unwrap();
+    for state in [fresh, listing.escrow].iter() {
+        assert_eq!(
+            test.process(&p.f.withdraw(&test, *state, p.f.taker, p.f.taker_x)),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+    assert_eq!(p.withdraw(&mut test, 50), Ok(500));
+}
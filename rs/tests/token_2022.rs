@@ -0,0 +1,214 @@
+mod common;
+
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+use bpf_program_template::error::EscrowError;
+
+use common::{token_2022, EscrowFixture, ProgramTest};
+
+// 1% fee, capped at 50
+fn fee_mint(test: &mut ProgramTest) -> Pubkey {
+    test.add_mint_2022(6, &[token_2022::transfer_fee_config(100, 50)])
+}
+
+// the fee withheld when the initializer funded the temp account
+fn set_withheld(test: &mut ProgramTest, account: &Pubkey, withheld: u64) {
+    let data = &mut test.accounts.get_mut(account).unwrap().data;
+    let range = token_2022::find(data, token_2022::TRANSFER_FEE_AMOUNT).unwrap();
+    data[range].copy_from_slice(&withheld.to_le_bytes());
+}
+
+#[test]
+fn exchange_with_fee_on_x_leg() {
+    let mut test = ProgramTest::new();
+    let mint_x = fee_mint(&mut test);
+    let mint_y = test.add_mint(0);
+    let f = EscrowFixture::for_pair(&mut test, mint_x, mint_y, 1_000, 5_000);
+    set_withheld(&mut test, &f.temp_x, 10);
+    test.process(&f.init_escrow(&test, 500)).unwrap();
+
+    // the taker gets 1000 minus the 1% fee, and has to say so
+    assert_eq!(
+        test.process(&f.exchange(&test, 1_000)),
+        Err(EscrowError::ExpectedAmountMismatch.into())
+    );
+    test.process(&f.exchange(&test, 990)).unwrap();
+
+    assert_eq!(test.token_balance(&f.taker_x), 990);
+    assert_eq!(test.token_balance(&f.initializer_y), 500);
+    // withheld fees were harvested so the temp account could be closed
+    assert!(test.token_account(&f.temp_x).is_none());
+}
+
+#[test]
+fn exchange_with_fee_on_y_leg_pays_initializer_in_full() {
+    let mut test = ProgramTest::new();
+    let mint_x = test.add_mint(0);
+    let mint_y = fee_mint(&mut test);
+    let f = EscrowFixture::for_pair(&mut test, mint_x, mint_y, 1_000, 5_000);
+    test.process(&f.init_escrow(&test, 990)).unwrap();
+    test.process(&f.exchange(&test, 1_000)).unwrap();
+
+    assert_eq!(test.token_balance(&f.initializer_y), 990);
+    assert_eq!(test.token_balance(&f.taker_y), 5_000 - 1_000);
+    assert_eq!(test.token_balance(&f.taker_x), 1_000);
+}
+
+#[test]
+fn fee_is_capped() {
+    let mut test = ProgramTest::new();
+    let mint_x = test.add_mint(0);
+    let mint_y = fee_mint(&mut test);
+    let f = EscrowFixture::for_pair(&mut test, mint_x, mint_y, 1_000, 1_000_000);
+    test.process(&f.init_escrow(&test, 100_000)).unwrap();
+    test.process(&f.exchange(&test, 1_000)).unwrap();
+
+    assert_eq!(test.token_balance(&f.initializer_y), 100_000);
+    assert_eq!(test.token_balance(&f.taker_y), 1_000_000 - 100_050);
+}
+
+#[test]
+fn cancel_with_token_2022() {
+    let mut test = ProgramTest::new();
+    let mint_x = fee_mint(&mut test);
+    let mint_y = test.add_mint(0);
+    let f = EscrowFixture::for_pair(&mut test, mint_x, mint_y, 1_000, 5_000);
+    test.process(&f.init_escrow(&test, 500)).unwrap();
+    test.process(&f.cancel(&test)).unwrap();
+
+    assert_eq!(test.token_balance(&f.initializer_x), 990);
+    assert!(test.token_account(&f.temp_x).is_none());
+}
+
+#[test]
+fn init_rejects_unsupported_mint_extensions() {
+    for extension in [
+        (token_2022::NON_TRANSFERABLE, vec![]),
+        (
+            token_2022::PERMANENT_DELEGATE,
+            Pubkey::new_unique().to_bytes().to_vec(),
+        ),
+    ]
+    .iter()
+    {
+        let mut test = ProgramTest::new();
+        let mint_x = test.add_mint(0);
+        let mint_y = test.add_mint_2022(0, std::slice::from_ref(extension));
+        let f = EscrowFixture::for_pair(&mut test, mint_x, mint_y, 1_000, 5_000);
+        assert_eq!(
+            test.process(&f.init_escrow(&test, 500)),
+            Err(EscrowError::UnsupportedMintExtension.into())
+        );
+    }
+}
+
+fn with_extension(test: &mut ProgramTest, account: &Pubkey, extension: (u16, Vec<u8>)) {
+    let data = &mut test.accounts.get_mut(account).unwrap().data;
+    token_2022::push_extension(data, extension);
+}
+
+#[test]
+fn init_rejects_accounts_locked_against_the_escrow() {
+    // the vault can't change owners, Y can't arrive without a memo
+    for (on_receive_account, extension) in [
+        (false, (token_2022::IMMUTABLE_OWNER, vec![])),
+        (false, (token_2022::CPI_GUARD, vec![1])),
+        (true, (token_2022::MEMO_TRANSFER, vec![1])),
+    ]
+    .iter()
+    {
+        let mut test = ProgramTest::new();
+        let mint_x = test.add_mint_2022(0, &[]);
+        let mint_y = test.add_mint_2022(0, &[]);
+        let f = EscrowFixture::for_pair(&mut test, mint_x, mint_y, 1_000, 5_000);
+        let account = if *on_receive_account {
+            f.initializer_y
+        } else {
+            f.temp_x
+        };
+        with_extension(&mut test, &account, extension.clone());
+        assert_eq!(
+            test.process(&f.init_escrow(&test, 500)),
+            Err(EscrowError::UnsupportedAccountExtension.into())
+        );
+    }
+
+    // a cpi guard stops the approve as well, an immutable owner doesn't matter when the account stays alice's
+    let mut test = ProgramTest::new();
+    let mint_x = test.add_mint_2022(0, &[]);
+    let mint_y = test.add_mint(0);
+    let f = EscrowFixture::for_pair(&mut test, mint_x, mint_y, 1_000, 5_000);
+    with_extension(&mut test, &f.temp_x, (token_2022::IMMUTABLE_OWNER, vec![]));
+    test.process(&f.init_approve_escrow(&test, 500, 400))
+        .unwrap();
+    let f = EscrowFixture::for_pair(&mut test, mint_x, mint_y, 1_000, 5_000);
+    with_extension(&mut test, &f.temp_x, (token_2022::CPI_GUARD, vec![1]));
+    assert_eq!(
+        test.process(&f.init_approve_escrow(&test, 500, 400)),
+        Err(EscrowError::UnsupportedAccountExtension.into())
+    );
+}
+
+#[test]
+fn switched_off_extensions_dont_count() {
+    let mut test = ProgramTest::new();
+    let mint_x = test.add_mint_2022(0, &[]);
+    let mint_y = test.add_mint_2022(0, &[]);
+    let f = EscrowFixture::for_pair(&mut test, mint_x, mint_y, 1_000, 5_000);
+    with_extension(&mut test, &f.temp_x, (token_2022::CPI_GUARD, vec![0]));
+    with_extension(
+        &mut test,
+        &f.initializer_y,
+        (token_2022::MEMO_TRANSFER, vec![0]),
+    );
+    test.process(&f.init_escrow(&test, 500)).unwrap();
+    test.process(&f.exchange(&test, 1_000)).unwrap();
+    assert_eq!(test.token_balance(&f.initializer_y), 500);
+}
+
+#[test]
+fn init_rejects_wrong_token_program() {
+    let mut test = ProgramTest::new();
+    let mint_x = test.add_mint_2022(0, &[]);
+    let mint_y = test.add_mint(0);
+    let mut f = EscrowFixture::for_pair(&mut test, mint_x, mint_y, 1_000, 5_000);
+    f.token_program_x = spl_token::id();
+    assert_eq!(
+        test.process(&f.init_escrow(&test, 500)),
+        Err(ProgramError::IncorrectProgramId)
+    );
+}
+
+#[test]
+fn init_rejects_mint_of_another_token() {
+    let mut test = ProgramTest::new();
+    let mut f = EscrowFixture::new(&mut test, 1_000, 5_000);
+    f.mint_y = test.add_mint(0);
+    assert_eq!(
+        test.process(&f.init_escrow(&test, 500)),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+mod fee_math {
+    use bpf_program_template::token::TransferFee;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn pre_fee_amount_is_the_smallest_that_delivers(
+            received in 0u64..u64::MAX / 2,
+            transfer_fee_basis_points in 0u16..=10_000,
+            maximum_fee in any::<u64>(),
+        ) {
+            let fee = TransferFee { epoch: 0, maximum_fee, transfer_fee_basis_points };
+            if let Some(sent) = fee.calculate_pre_fee_amount(received) {
+                prop_assert!(sent - fee.calculate_fee(sent).unwrap() >= received);
+                if sent > received {
+                    let less = sent - 1;
+                    prop_assert!(less - fee.calculate_fee(less).unwrap() < received);
+                }
+            }
+        }
+    }
+}
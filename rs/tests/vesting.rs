@@ -0,0 +1,202 @@
+mod common;
+
+use solana_program::{
+    clock::Clock, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+};
+
+use bpf_program_template::{error::EscrowError, state::Vesting};
+
+use common::{EscrowFixture, ProgramTest};
+
+const START: i64 = 1_000;
+const CLIFF: i64 = 1_250;
+const END: i64 = 2_000;
+
+// 1000 X vesting for the taker from START to END, revocable by the initializer if `revocable`
+struct Grant {
+    f: EscrowFixture,
+    vesting: Pubkey,
+}
+
+impl Grant {
+    fn new(test: &mut ProgramTest, revocable: bool) -> Self {
+        let f = EscrowFixture::new(test, 1_000, 0);
+        let vesting = test.add_program_account(Vesting::LEN);
+        let revoker = Some(f.initializer).filter(|_| revocable);
+        test.process(&f.init_vesting(test, vesting, f.taker, revoker, START, CLIFF, END))
+            .unwrap();
+        Self { f, vesting }
+    }
+
+    fn withdraw(&self, test: &mut ProgramTest, now: i64) -> Result<u64, ProgramError> {
+        at(test, now);
+        let before = test.token_balance(&self.f.taker_x);
+        test.process(
+            &self
+                .f
+                .withdraw(test, self.vesting, self.f.taker, self.f.taker_x),
+        )?;
+        Ok(test.token_balance(&self.f.taker_x) - before)
+    }
+
+    fn revoke(&self, test: &mut ProgramTest, now: i64) -> Result<u64, ProgramError> {
+        at(test, now);
+        let before = test.token_balance(&self.f.initializer_x);
+        test.process(&self.f.revoke_vesting(
+            test,
+            self.vesting,
+            self.f.initializer,
+            self.f.initializer_x,
+        ))?;
+        Ok(test.token_balance(&self.f.initializer_x) - before)
+    }
+}
+
+fn at(test: &ProgramTest, unix_timestamp: i64) {
+    test.set_clock(Clock {
+        unix_timestamp,
+        ..Clock::default()
+    });
+}
+
+#[test]
+fn nothing_before_the_cliff_then_linear() {
+    let mut test = ProgramTest::new();
+    let g = Grant::new(&mut test, false);
+    let vesting = Vesting::unpack(&test.account(&g.vesting).data).unwrap();
+    assert_eq!(
+        (vesting.beneficiary_pubkey, vesting.total_amount),
+        (g.f.taker, 1_000)
+    );
+    assert_eq!(vesting.revoker_pubkey, Pubkey::default());
+    assert_eq!(test.token_account(&g.f.temp_x).unwrap().owner, test.pda().0);
+
+    assert_eq!(
+        g.withdraw(&mut test, CLIFF - 1),
+        Err(EscrowError::NothingToWithdraw.into())
+    );
+    // the cliff releases everything since the start at once
+    assert_eq!(g.withdraw(&mut test, CLIFF), Ok(250));
+    assert_eq!(g.withdraw(&mut test, 1_501), Ok(251));
+    assert_eq!(
+        g.withdraw(&mut test, 1_501),
+        Err(EscrowError::NothingToWithdraw.into())
+    );
+
+    let rent = test.lamports(&g.vesting) + test.lamports(&g.f.temp_x);
+    let initializer_lamports = test.lamports(&g.f.initializer);
+    assert_eq!(g.withdraw(&mut test, END + 100), Ok(499));
+    assert!(test.token_account(&g.f.temp_x).is_none());
+    assert_eq!(test.lamports(&g.vesting), 0);
+    assert_eq!(test.lamports(&g.f.initializer), initializer_lamports + rent);
+}
+
+#[test]
+fn only_the_beneficiary_withdraws() {
+    let mut test = ProgramTest::new();
+    let g = Grant::new(&mut test, true);
+    at(&test, END);
+
+    let ix =
+        g.f.withdraw(&test, g.vesting, g.f.initializer, g.f.initializer_x);
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+    // out of another vault, or with the rent going elsewhere
+    let mut ix = g.f.withdraw(&test, g.vesting, g.f.taker, g.f.taker_x);
+    ix.accounts[3].pubkey = g.f.initializer_x;
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+    let mut ix = g.f.withdraw(&test, g.vesting, g.f.taker, g.f.taker_x);
+    ix.accounts[7].pubkey = g.f.taker;
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+    // or from a copy of the vesting that isn't ours
+    let copy = test.add_foreign_copy(&g.vesting);
+    let ix = g.f.withdraw(&test, copy, g.f.taker, g.f.taker_x);
+    assert_eq!(test.process(&ix), Err(ProgramError::IncorrectProgramId));
+
+    assert_eq!(g.withdraw(&mut test, END), Ok(1_000));
+}
+
+#[test]
+fn revoking_keeps_what_has_vested() {
+    let mut test = ProgramTest::new();
+    let g = Grant::new(&mut test, true);
+    assert_eq!(g.withdraw(&mut test, 1_300), Ok(300));
+
+    assert_eq!(g.revoke(&mut test, 1_600), Ok(400));
+    let vesting = Vesting::unpack(&test.account(&g.vesting).data).unwrap();
+    assert_eq!((vesting.total_amount, vesting.withdrawn), (600, 300));
+    assert_eq!(
+        g.revoke(&mut test, 1_700),
+        Err(EscrowError::NotRevocable.into())
+    );
+
+    // the rest of it vested at the revocation
+    assert_eq!(g.withdraw(&mut test, 1_600), Ok(300));
+    assert!(test.token_account(&g.f.temp_x).is_none());
+    assert_eq!(test.lamports(&g.vesting), 0);
+}
+
+#[test]
+fn revoking_before_the_cliff_takes_it_all() {
+    let mut test = ProgramTest::new();
+    let g = Grant::new(&mut test, true);
+
+    let initializer_lamports = test.lamports(&g.f.initializer);
+    let rent = test.lamports(&g.vesting) + test.lamports(&g.f.temp_x);
+    assert_eq!(g.revoke(&mut test, CLIFF - 1), Ok(1_000));
+    assert!(test.token_account(&g.f.temp_x).is_none());
+    assert_eq!(test.lamports(&g.vesting), 0);
+    assert_eq!(test.lamports(&g.f.initializer), initializer_lamports + rent);
+}
+
+#[test]
+fn only_the_revocation_authority_revokes() {
+    let mut test = ProgramTest::new();
+    let irrevocable = Grant::new(&mut test, false);
+    assert_eq!(
+        irrevocable.revoke(&mut test, START),
+        Err(EscrowError::NotRevocable.into())
+    );
+
+    let g = Grant::new(&mut test, true);
+    let ix = g.f.revoke_vesting(&test, g.vesting, g.f.taker, g.f.taker_x);
+    assert_eq!(test.process(&ix), Err(ProgramError::InvalidAccountData));
+    let copy = test.add_foreign_copy(&g.vesting);
+    let ix =
+        g.f.revoke_vesting(&test, copy, g.f.initializer, g.f.initializer_x);
+    assert_eq!(test.process(&ix), Err(ProgramError::IncorrectProgramId));
+    // after the end there's nothing left to claw back
+    assert_eq!(g.revoke(&mut test, END), Ok(0));
+    assert_eq!(g.withdraw(&mut test, END), Ok(1_000));
+}
+
+#[test]
+fn schedule_has_to_make_sense() {
+    let mut test = ProgramTest::new();
+    let f = EscrowFixture::new(&mut test, 1_000, 0);
+    let vesting = test.add_program_account(Vesting::LEN);
+    for (start, cliff, end) in [
+        (START, START, START),
+        (END, CLIFF, START),
+        (START, START - 1, END),
+        (START, END + 1, END),
+    ]
+    .iter()
+    {
+        assert_eq!(
+            test.process(&f.init_vesting(&test, vesting, f.taker, None, *start, *cliff, *end)),
+            Err(EscrowError::InvalidSchedule.into())
+        );
+    }
+
+    // no cliff at all
+    test.process(&f.init_vesting(&test, vesting, f.taker, None, START, START, END))
+        .unwrap();
+    let g = Grant { f, vesting };
+    assert_eq!(g.withdraw(&mut test, START + 1), Ok(1));
+
+    let other = EscrowFixture::new(&mut test, 1_000, 0);
+    assert_eq!(
+        test.process(&other.init_vesting(&test, vesting, other.taker, None, START, CLIFF, END)),
+        Err(ProgramError::AccountAlreadyInitialized)
+    );
+}